@@ -3,19 +3,22 @@ import wirefunc as wf;
 
 // TODO use `if (json.field != null) json.field = defaultVal;` for defaults!
 // TODO do an example of renames with fka, like the above.
-// TODO attempt packing. Do stuff like storing structs as tuples on the wire:
+// Packing: structs and endpoints marked [ packed ] store structs in arrays as
+// tuples on the wire, and production builds write wf-packing.js (or
+// WfPacking.elm) to convert:
 //      json.users = json.users.map(function(packed) {
 //          return {
-//              username: packed["a"],
-//              email: packed["b"],
-//              name: packed["c"]
+//              username: packed[0],
+//              email: packed[1],
+//              name: packed[2]
 //          };
 //      });
 //
-// See how it benchmarks! Comparing serialization perf of tuples vs objects is
-// pretty easy. See how it goes with gzip. Try a GitHub API payload. Although I
-// guess you still kinda need to set up a gzipped server roundtrip to *really*
-// know. Maybe try it on the web perf bench to see it on a real low-end device?
+// TODO see how it benchmarks! `wf compare-sizes` shows the raw sizes.
+// Comparing serialization perf of tuples vs objects is pretty easy. See how it
+// goes with gzip. Try a GitHub API payload. Although I guess you still kinda
+// need to set up a gzipped server roundtrip to *really* know. Maybe try it on
+// the web perf bench to see it on a real low-end device?
 
 
 /**
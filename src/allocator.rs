@@ -0,0 +1,318 @@
+use history::{Entry, FormerName, History, Ledger, SchemaVersion};
+use types::{FieldId, Schema, TypeName};
+
+/// What changed when allocating IDs for a schema.
+///
+/// Names are qualified by their record or custom type, e.g. "User.email"
+#[derive(Debug, PartialEq)]
+pub struct Allocation {
+    /// The history's version after allocation. This only goes up if something
+    /// was added, renamed, or retired.
+    pub version: SchemaVersion,
+    pub added: Vec<String>,
+
+    /// Each rename's former name, followed by its new one.
+    pub renamed: Vec<(String, String)>,
+    pub retired: Vec<String>,
+
+    /// Fields and variants whose type changed, but which kept their IDs (see
+    /// `migration::apply`).
+    pub retyped: Vec<String>,
+}
+
+/// Assign a `field_id` to every field and a `variant_id` to every variant in
+/// the schema, recording any new assignments in the history.
+///
+/// * Fields and variants which already have an entry keep their IDs.
+/// * Ones marked `[ fka "oldName" ]` take over the entry for their old name,
+///   if there is one and nothing else is still using that name. The same goes
+///   for records and custom types, and a renamed record's inline records
+///   are renamed along with it.
+/// * New ones get the next ID which has never been used in their record or
+///   custom type.
+/// * Endpoint params and responses, and inline records, get IDs the same way,
+///   from ledgers named for where they are (see `RecordInfo::location`).
+/// * Entries whose field or variant is no longer in the schema get retired.
+///   Their IDs stay reserved forever, so a field added later can never be
+///   mistaken for the removed one by a client that's still running old code.
+pub fn allocate(history: &mut History, schema: &mut Schema) -> Allocation {
+    let version = history.version + 1;
+    let mut added: Vec<String> = vec![];
+    let mut renamed: Vec<(String, String)> = vec![];
+    let mut retired: Vec<String> = vec![];
+    let mut record_names: Vec<String> = schema
+        .records
+        .iter()
+        .filter_map(|record| record.name.as_ref().map(TypeName::to_string))
+        .collect();
+    let inline_records = schema.inline_field_names();
+    let type_names: Vec<String> = schema
+        .custom_types
+        .iter()
+        .map(|custom_type| custom_type.name.to_string())
+        .collect();
+
+    for record in schema.records.iter_mut() {
+        let record_name = match record.name {
+            Some(ref name) => name.to_string(),
+            None => continue,
+        };
+        let ledger = ledger_for(
+            &mut history.records,
+            record_name.as_str(),
+            &type_strings(&record.former_names),
+            &record_names,
+            version,
+            &mut renamed,
+        );
+        let field_names: Vec<String> = record
+            .fields
+            .iter()
+            .map(|field| field.name.clone())
+            .collect();
+
+        for field in record.fields.iter_mut() {
+            let assignment = Assignment {
+                name: field.name.as_str(),
+                former_names: &field.former_names,
+                names_in_use: &field_names,
+            };
+
+            field.field_id = assignment.assign(ledger, version, &mut added, &mut renamed);
+        }
+
+        retire_missing(ledger, &field_names, version, &mut retired);
+    }
+
+    // Inline records and endpoint bodies can't be renamed, since they have
+    // no names of their own, but their fields can.
+    schema.visit_inline_records_mut(&mut |location, fields| {
+        // e.g. a `get` endpoint with no params
+        if fields.is_empty() && history.record(location).is_none() {
+            return;
+        }
+
+        let ledger = ledger_for(
+            &mut history.records,
+            location,
+            &[],
+            &[],
+            version,
+            &mut renamed,
+        );
+        let field_names = inline_records
+            .iter()
+            .find(|(other, _)| other == location)
+            .map_or(&[][..], |(_, names)| names.as_slice());
+
+        for field in fields.iter_mut() {
+            let assignment = Assignment {
+                name: field.name.as_str(),
+                former_names: &field.former_names,
+                names_in_use: field_names,
+            };
+
+            field.field_id = assignment.assign(ledger, version, &mut added, &mut renamed);
+        }
+    });
+
+    for (location, field_names) in inline_records {
+        if let Some(ledger) = history
+            .records
+            .iter_mut()
+            .find(|ledger| ledger.name == location)
+        {
+            retire_missing(ledger, &field_names, version, &mut retired);
+        }
+
+        record_names.push(location);
+    }
+
+    for custom_type in schema.custom_types.iter_mut() {
+        let type_name = custom_type.name.to_string();
+        let ledger = ledger_for(
+            &mut history.custom_types,
+            type_name.as_str(),
+            &type_strings(&custom_type.former_names),
+            &type_names,
+            version,
+            &mut renamed,
+        );
+        let variant_names: Vec<String> = custom_type
+            .variants
+            .iter()
+            .map(|variant| variant.name.clone())
+            .collect();
+
+        for variant in custom_type.variants.iter_mut() {
+            let assignment = Assignment {
+                name: variant.name.as_str(),
+                former_names: &variant.former_names,
+                names_in_use: &variant_names,
+            };
+
+            variant.variant_id = assignment.assign(ledger, version, &mut added, &mut renamed);
+        }
+
+        retire_missing(ledger, &variant_names, version, &mut retired);
+    }
+
+    // Records and custom types which were removed entirely retire everything.
+    for ledger in history.records.iter_mut() {
+        if !record_names.contains(&ledger.name) {
+            retire_missing(ledger, &[], version, &mut retired);
+        }
+    }
+
+    for ledger in history.custom_types.iter_mut() {
+        if !type_names.contains(&ledger.name) {
+            retire_missing(ledger, &[], version, &mut retired);
+        }
+    }
+
+    if !added.is_empty() || !renamed.is_empty() || !retired.is_empty() {
+        history.version = version;
+    }
+
+    Allocation {
+        version: history.version,
+        added,
+        renamed,
+        retired,
+        retyped: vec![],
+    }
+}
+
+fn type_strings(type_names: &[TypeName]) -> Vec<String> {
+    type_names.iter().map(TypeName::to_string).collect()
+}
+
+/// The ledger for the given record or custom type. If there isn't one yet,
+/// this takes over the ledger for one of its former names, or else starts a
+/// new one.
+fn ledger_for<'a>(
+    ledgers: &'a mut Vec<Ledger>,
+    name: &str,
+    former_names: &[String],
+    names_in_use: &[String],
+    version: SchemaVersion,
+    renamed: &mut Vec<(String, String)>,
+) -> &'a mut Ledger {
+    if let Some(index) = ledgers.iter().position(|ledger| ledger.name == name) {
+        return &mut ledgers[index];
+    }
+
+    let former_index = ledgers.iter().position(|ledger| {
+        former_names.contains(&ledger.name) && !names_in_use.contains(&ledger.name)
+    });
+
+    match former_index {
+        Some(index) => {
+            let former_name = ledgers[index].name.clone();
+
+            // Inline records inside it are named for where they are, e.g.
+            // "User.profile", so they move along with it.
+            let prefix = format!("{}.", former_name);
+
+            for ledger in ledgers.iter_mut() {
+                let new_name = if ledger.name == former_name {
+                    name.to_string()
+                } else if ledger.name.starts_with(prefix.as_str()) {
+                    format!("{}.{}", name, &ledger.name[prefix.len()..])
+                } else {
+                    continue;
+                };
+
+                renamed.push((ledger.name.clone(), new_name.clone()));
+                ledger.former_names.push(FormerName {
+                    name: ledger.name.clone(),
+                    renamed: version,
+                });
+                ledger.name = new_name;
+            }
+
+            &mut ledgers[index]
+        }
+        None => {
+            ledgers.push(Ledger::new(name));
+
+            ledgers.last_mut().unwrap()
+        }
+    }
+}
+
+/// A field or variant in need of an ID.
+struct Assignment<'a> {
+    name: &'a str,
+    former_names: &'a [String],
+
+    /// The names of everything else in the same record or custom type, which
+    /// therefore can't be renamed away from.
+    names_in_use: &'a [String],
+}
+
+impl<'a> Assignment<'a> {
+    fn assign(
+        &self,
+        ledger: &mut Ledger,
+        version: SchemaVersion,
+        added: &mut Vec<String>,
+        renamed: &mut Vec<(String, String)>,
+    ) -> FieldId {
+        if let Some(entry) = ledger.entry(self.name) {
+            return entry.id;
+        }
+
+        let former_name = self.former_names.iter().find(|former_name| {
+            !self.names_in_use.contains(former_name) && ledger.entry(former_name).is_some()
+        });
+
+        if let Some(former_name) = former_name {
+            let entry = ledger
+                .entries
+                .iter_mut()
+                .find(|entry| entry.name == *former_name && entry.retired.is_none())
+                .unwrap();
+
+            entry.name = self.name.to_string();
+            entry.former_names.push(FormerName {
+                name: former_name.clone(),
+                renamed: version,
+            });
+            renamed.push((
+                format!("{}.{}", ledger.name, former_name),
+                format!("{}.{}", ledger.name, self.name),
+            ));
+
+            return entry.id;
+        }
+
+        let id = ledger.next_id();
+
+        ledger.entries.push(Entry {
+            name: self.name.to_string(),
+            id,
+            since: version,
+            former_names: vec![],
+            retyped: vec![],
+            retired: None,
+        });
+        added.push(format!("{}.{}", ledger.name, self.name));
+
+        id
+    }
+}
+
+fn retire_missing(
+    ledger: &mut Ledger,
+    names: &[String],
+    version: SchemaVersion,
+    retired: &mut Vec<String>,
+) {
+    for entry in ledger.entries.iter_mut() {
+        if entry.retired.is_none() && !names.contains(&entry.name) {
+            entry.retired = Some(version);
+            retired.push(format!("{}.{}", ledger.name, entry.name));
+        }
+    }
+}
@@ -1,40 +1,106 @@
 extern crate byteorder;
 
+use byteorder::{ByteOrder, LittleEndian};
 use pointer;
-use pointer::Pointer;
-use std::u16;
+use pointer::{Pointer, Units};
 
-pub fn encode_u64_array(word_index: u32, segment_id_offset: u16, arr: &[u64]) -> u64 {
-    // Since `length` will be cast into a u16, this function will work fine as
-    // long as usize is u16 or bigger. In other words, it works on 16-bit
-    // systems and higher.  Anyone trying to run WireFunc on an 8-bit system is
-    // (A) super hardcore and (B) out of luck.
-    let length: usize = arr.len();
-
-    if length == 0 {
+/// Write an Array UInt64 to the end of the segment, and return a pointer to
+/// it. `segment_id_offset` is how many segments after the pointer's own
+/// segment this one is.
+///
+/// Arrays longer than `pointer::MAX_LENGTH` are split into chunks, stitched
+/// together by a Composite Pointer.
+pub fn encode_u64_array(segment: &mut Vec<u64>, segment_id_offset: u16, arr: &[u64]) -> u64 {
+    if arr.is_empty() {
         // It's empty; bail out early. Decoders know to special-case this!
         return 0;
-    } else if length > (u16::max_value() as usize) {
-        // It's too long! Make a Composite Pointer
-        panic!("Composite Pointers aren't supported yet!");
-    } else {
-        // Make a regular Segment Pointer
-        pointer::encode(Pointer {
-            word_index: word_index,
-            segment_id_offset: segment_id_offset,
-            length: length as u16,
-            is_composite: false,
-        })
     }
+
+    let ptr = pointer::write_chunked(segment, arr.len(), |segment, start, end| {
+        segment.extend_from_slice(&arr[start..end])
+    });
+
+    pointer::encode(Pointer {
+        segment_id_offset,
+        ..ptr
+    })
 }
 
-pub fn decode_u64_array(raw_ptr: u64) -> Vec<u64> {
+/// Read an Array UInt64 from the message's segments. `segment` is the index
+/// of the segment the pointer was read from.
+pub fn decode_u64_array(
+    segments: &[Vec<u64>],
+    segment: usize,
+    raw_ptr: u64,
+) -> Result<Vec<u64>, pointer::Problem> {
     if raw_ptr == 0 {
         // If the whole pointer is zeroes, it's an empty vector. Bail out!
-        return vec![];
-    } else {
-        let _ptr: Pointer = pointer::decode(raw_ptr);
+        return Ok(vec![]);
+    }
+
+    let mut arr = vec![];
+
+    for ptr in pointer::resolve(segments, segment, pointer::decode(raw_ptr), Units::Words)? {
+        arr.extend_from_slice(pointer::words(
+            segments,
+            ptr.segment,
+            ptr.word_index,
+            ptr.length,
+        )?);
+    }
+
+    Ok(arr)
+}
+
+/// Write an Array UInt8 (which is what Bytes are) to the end of the segment,
+/// and return a pointer to it. The bytes are packed 8 to a Word, so the Data
+/// Length is in bytes, and the last Word is padded with zeroes.
+///
+/// Like `encode_u64_array`, long arrays get a Composite Pointer.
+pub fn encode_u8_array(segment: &mut Vec<u64>, segment_id_offset: u16, arr: &[u8]) -> u64 {
+    if arr.is_empty() {
+        return 0;
+    }
+
+    let ptr = pointer::write_chunked(segment, arr.len(), |segment, start, end| {
+        for chunk in arr[start..end].chunks(8) {
+            let mut word = [0u8; 8];
+
+            word[..chunk.len()].copy_from_slice(chunk);
+            segment.push(LittleEndian::read_u64(&word));
+        }
+    });
 
-        panic!("We don't know how to decode arrays yet!");
+    pointer::encode(Pointer {
+        segment_id_offset,
+        ..ptr
+    })
+}
+
+/// Read an Array UInt8 from the message's segments, like `decode_u64_array`.
+pub fn decode_u8_array(
+    segments: &[Vec<u64>],
+    segment: usize,
+    raw_ptr: u64,
+) -> Result<Vec<u8>, pointer::Problem> {
+    if raw_ptr == 0 {
+        return Ok(vec![]);
     }
+
+    let mut arr = vec![];
+
+    for ptr in pointer::resolve(segments, segment, pointer::decode(raw_ptr), Units::Bytes)? {
+        let words = pointer::words(
+            segments,
+            ptr.segment,
+            ptr.word_index,
+            Units::Bytes.words(ptr.length),
+        )?;
+        let mut bytes = vec![0u8; words.len() * 8];
+
+        LittleEndian::write_u64_into(words, &mut bytes);
+        arr.extend_from_slice(&bytes[..ptr.length]);
+    }
+
+    Ok(arr)
 }
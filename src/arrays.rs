@@ -153,7 +153,7 @@ pub fn write_nested_i32_array_to<W: io::Write>(
 
     // Write the elements
     for inner_arr in arr {
-        write_i32_array_to(&mut buffer, &inner_arr)?;
+        write_i32_array_to(&mut buffer, inner_arr)?;
     }
 
     writer.write_all(&buffer)
@@ -0,0 +1,457 @@
+use compat;
+use compat::ChangeKind;
+use history::{FormerName, History, Ledger, SchemaVersion};
+use std::fs;
+use std::io;
+use std::path::Path;
+use types::{Field, Schema};
+
+#[derive(Debug)]
+pub enum Problem {
+    /// `from` must come before `to`, and neither can be past `latest`.
+    InvalidRange {
+        from: SchemaVersion,
+        to: SchemaVersion,
+        latest: SchemaVersion,
+    },
+    Io(io::Error),
+}
+
+/// What changed in the schema between two versions, for release notes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Changelog {
+    pub from: SchemaVersion,
+    pub to: SchemaVersion,
+
+    /// One section per record, custom type, alias, or endpoint that changed,
+    /// with records and custom types first.
+    pub sections: Vec<Section>,
+
+    /// Whether type changes, deprecations, and endpoint changes are included.
+    /// Those need the schema as of both versions, not just the history.
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub kind: SectionKind,
+
+    /// The name as of the later version, e.g. "User" or "sendDM"
+    pub name: String,
+    pub notes: Vec<Note>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SectionKind {
+    Record,
+    CustomType,
+    Alias,
+    Endpoint,
+}
+
+/// A single line of the changelog. Names are relative to the section, e.g.
+/// "email" or "params.userId", except for the section's own name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Note {
+    Added(String),
+    Deprecated(String),
+    Renamed {
+        from: String,
+        to: String,
+    },
+    Retired(String),
+    Removed(String),
+
+    /// `widened` means the old type is listed in the new one's `formerly`, so
+    /// values of the old type are still accepted.
+    TypeChanged {
+        name: String,
+        from: String,
+        to: String,
+        widened: bool,
+    },
+
+    /// Anything else, described in a full sentence.
+    Other(String),
+}
+
+/// The changes after version `from`, up to and including version `to`.
+///
+/// Additions, renames, and retirements of fields and variants come from the
+/// history, so they can be listed between any two versions. Everything else
+/// needs `schemas`, the schema as of `from` and as of `to`.
+pub fn between(
+    history: &History,
+    from: SchemaVersion,
+    to: SchemaVersion,
+    schemas: Option<(&Schema, &Schema)>,
+) -> Changelog {
+    let mut sections: Vec<Section> = vec![];
+    // Changes to endpoint bodies and inline records are listed along with
+    // their endpoint or record, which needs the schemas.
+    let ledgers = history
+        .records
+        .iter()
+        .filter(|ledger| !ledger.is_inline())
+        .map(|ledger| (SectionKind::Record, ledger))
+        .chain(
+            history
+                .custom_types
+                .iter()
+                .map(|ledger| (SectionKind::CustomType, ledger)),
+        );
+
+    for (kind, ledger) in ledgers {
+        let notes = ledger_notes(ledger, from, to);
+
+        if !notes.is_empty() {
+            let name = name_as_of(&ledger.name, &ledger.former_names, to);
+
+            section(&mut sections, kind, name).notes.extend(notes);
+        }
+    }
+
+    if let Some((old, new)) = schemas {
+        for change in compat::compare(old, new) {
+            add_change(&mut sections, old, new, change);
+        }
+
+        for (kind, name, field) in deprecations(old, new) {
+            section(&mut sections, kind, name)
+                .notes
+                .push(Note::Deprecated(field));
+        }
+    }
+
+    sections.sort_by_key(|section| match section.kind {
+        SectionKind::Record | SectionKind::CustomType => 0,
+        SectionKind::Alias => 1,
+        SectionKind::Endpoint => 2,
+    });
+
+    Changelog {
+        from,
+        to,
+        sections,
+        complete: schemas.is_some(),
+    }
+}
+
+fn in_range(version: SchemaVersion, from: SchemaVersion, to: SchemaVersion) -> bool {
+    from < version && version <= to
+}
+
+/// Notes about the ledger's own renames, and its entries' additions, renames,
+/// and retirements. Something added and then renamed or retired within the
+/// range is only mentioned once, as it was at the end of the range.
+fn ledger_notes(ledger: &Ledger, from: SchemaVersion, to: SchemaVersion) -> Vec<Note> {
+    let mut notes: Vec<Note> = renames(&ledger.name, &ledger.former_names, from, to);
+
+    for entry in ledger.entries.iter() {
+        let added = in_range(entry.since, from, to);
+
+        match entry.retired {
+            Some(retired) if retired <= from || entry.since > to => continue,
+            Some(retired) if retired <= to => {
+                if !added {
+                    let name = name_as_of(&entry.name, &entry.former_names, retired - 1);
+
+                    notes.push(Note::Retired(name));
+                }
+
+                continue;
+            }
+            _ => (),
+        }
+
+        if added {
+            notes.push(Note::Added(name_as_of(
+                &entry.name,
+                &entry.former_names,
+                to,
+            )));
+        } else if entry.since <= from {
+            notes.extend(renames(&entry.name, &entry.former_names, from, to));
+        }
+    }
+
+    notes
+}
+
+fn renames(
+    name: &str,
+    former_names: &[FormerName],
+    from: SchemaVersion,
+    to: SchemaVersion,
+) -> Vec<Note> {
+    let mut notes: Vec<Note> = vec![];
+
+    for (index, former_name) in former_names.iter().enumerate() {
+        if in_range(former_name.renamed, from, to) {
+            let new_name = match former_names.get(index + 1) {
+                Some(next) => next.name.as_str(),
+                None => name,
+            };
+
+            notes.push(Note::Renamed {
+                from: former_name.name.clone(),
+                to: new_name.to_string(),
+            });
+        }
+    }
+
+    notes
+}
+
+/// What something was called as of the given version. Former names are listed
+/// in the order they were renamed from.
+fn name_as_of(name: &str, former_names: &[FormerName], version: SchemaVersion) -> String {
+    former_names
+        .iter()
+        .find(|former_name| former_name.renamed > version)
+        .map(|former_name| former_name.name.as_str())
+        .unwrap_or(name)
+        .to_string()
+}
+
+fn section(sections: &mut Vec<Section>, kind: SectionKind, name: String) -> &mut Section {
+    match sections
+        .iter()
+        .position(|section| section.kind == kind && section.name == name)
+    {
+        Some(index) => &mut sections[index],
+        None => {
+            sections.push(Section {
+                kind,
+                name,
+                notes: vec![],
+            });
+
+            sections.last_mut().unwrap()
+        }
+    }
+}
+
+/// Add the changes the history doesn't already cover.
+fn add_change(sections: &mut Vec<Section>, old: &Schema, new: &Schema, change: compat::Change) {
+    let mut segments = change.path.splitn(2, '.');
+    let name = segments.next().unwrap_or_default().to_string();
+    let rest = segments.next().map(String::from);
+    let kind = match kind_of(new, &name).or_else(|| kind_of(old, &name)) {
+        Some(kind) => kind,
+        None => return,
+    };
+    let is_endpoint = kind == SectionKind::Endpoint;
+    let description = compat::describe(&change);
+    let note = match (change.kind, rest) {
+        (ChangeKind::TypeChanged { from, to }, Some(rest)) => {
+            let widened =
+                field(new, &name, &rest).is_some_and(|field| field.tipe.formerly.contains(&from));
+
+            Note::TypeChanged {
+                name: rest,
+                from: from.to_string(),
+                to: to.to_string(),
+                widened,
+            }
+        }
+        (ChangeKind::DeclarationAdded, None) | (ChangeKind::EndpointAdded, None) => {
+            Note::Added(name.clone())
+        }
+        (ChangeKind::DeclarationRemoved, None) | (ChangeKind::EndpointRemoved, None) => {
+            Note::Removed(name.clone())
+        }
+
+        // The history has the whole story for fields and variants of records
+        // and custom types, but endpoints aren't in it.
+        (ChangeKind::FieldAdded(_), Some(rest)) if is_endpoint => Note::Added(rest),
+        (ChangeKind::FieldRemoved(_), Some(rest)) if is_endpoint => Note::Removed(rest),
+        (ChangeKind::Renamed(former_name), Some(rest)) if is_endpoint => Note::Renamed {
+            to: rest.rsplit('.').next().unwrap_or_default().to_string(),
+            from: former_name,
+        },
+        (ChangeKind::FieldAdded(_), _)
+        | (ChangeKind::FieldRemoved(_), _)
+        | (ChangeKind::Renamed(_), _)
+        | (ChangeKind::VariantAdded, _)
+        | (ChangeKind::VariantRemoved, _) => return,
+        _ => Note::Other(description),
+    };
+
+    let is_declaration = note == Note::Added(name.clone()) || note == Note::Removed(name.clone());
+    let notes = &mut section(sections, kind, name).notes;
+
+    // Adding or removing the whole thing goes above the details.
+    if is_declaration {
+        notes.insert(0, note);
+    } else {
+        notes.push(note);
+    }
+}
+
+fn kind_of(schema: &Schema, name: &str) -> Option<SectionKind> {
+    if schema.record(name).is_some() {
+        Some(SectionKind::Record)
+    } else if schema.custom_type(name).is_some() {
+        Some(SectionKind::CustomType)
+    } else if schema.alias(name).is_some() {
+        Some(SectionKind::Alias)
+    } else if schema.endpoint(name).is_some() {
+        Some(SectionKind::Endpoint)
+    } else {
+        None
+    }
+}
+
+/// The field at a path like "User" and "email", or "sendDM" and "params.userId"
+fn field<'a>(schema: &'a Schema, name: &str, rest: &str) -> Option<&'a Field> {
+    fields(schema, name, rest)
+        .and_then(|(fields, field_name)| fields.iter().find(|field| field.name == field_name))
+}
+
+fn fields<'a, 'b>(schema: &'a Schema, name: &str, rest: &'b str) -> Option<(&'a [Field], &'b str)> {
+    if let Some(record) = schema.record(name) {
+        return Some((&record.fields, rest));
+    }
+
+    let endpoint = schema.endpoint(name)?;
+
+    if let Some(field_name) = rest.strip_prefix("params.") {
+        Some((&endpoint.params, field_name))
+    } else {
+        rest.strip_prefix("response.")
+            .map(|field_name| (endpoint.response.as_slice(), field_name))
+    }
+}
+
+/// Fields which are deprecated in `new` but weren't in `old`, as the section
+/// they belong in and their name within it.
+fn deprecations(old: &Schema, new: &Schema) -> Vec<(SectionKind, String, String)> {
+    let mut deprecated: Vec<(SectionKind, String, String)> = vec![];
+
+    for record in new.records.iter() {
+        if let Some(ref name) = record.name {
+            let name = name.to_string();
+            let old_fields = old.record(name.as_str()).map(|record| &record.fields[..]);
+
+            for field_name in newly_deprecated(old_fields, &record.fields, "") {
+                deprecated.push((SectionKind::Record, name.clone(), field_name));
+            }
+        }
+    }
+
+    for endpoint in new.endpoints.iter() {
+        let old_endpoint = old.endpoint(endpoint.name.as_str());
+        let params = newly_deprecated(
+            old_endpoint.map(|endpoint| &endpoint.params[..]),
+            &endpoint.params,
+            "params.",
+        );
+        let response = newly_deprecated(
+            old_endpoint.map(|endpoint| &endpoint.response[..]),
+            &endpoint.response,
+            "response.",
+        );
+
+        for field_name in params.into_iter().chain(response) {
+            deprecated.push((SectionKind::Endpoint, endpoint.name.clone(), field_name));
+        }
+    }
+
+    deprecated
+}
+
+/// Something deprecated when it was added isn't worth mentioning twice.
+fn newly_deprecated(old: Option<&[Field]>, new: &[Field], prefix: &str) -> Vec<String> {
+    let old = old.unwrap_or_default();
+
+    new.iter()
+        .filter(|field| field.deprecated)
+        .filter(|field| {
+            old.iter().any(|old_field| {
+                !old_field.deprecated
+                    && (old_field.name == field.name
+                        || field.former_names.contains(&old_field.name))
+            })
+        })
+        .map(|field| format!("{}{}", prefix, field.name))
+        .collect()
+}
+
+pub fn write_to(path: &Path, changelog: &Changelog) -> Result<(), Problem> {
+    fs::write(path, to_markdown(changelog)).map_err(Problem::Io)
+}
+
+/// Render the changelog as Markdown, e.g.
+///
+/// ```text
+/// ## Schema changes from version 3 to 5
+///
+/// ### `User` record
+///
+/// - Added `nickname`
+/// - Renamed `id` to `userId`
+/// ```
+pub fn to_markdown(changelog: &Changelog) -> String {
+    let mut markdown = format!(
+        "## Schema changes from version {} to {}\n",
+        changelog.from, changelog.to
+    );
+
+    if changelog.sections.is_empty() {
+        markdown.push_str("\nNothing changed.\n");
+    }
+
+    for section in changelog.sections.iter() {
+        markdown.push_str(
+            format!(
+                "\n### `{}` {}\n\n",
+                section.name,
+                match section.kind {
+                    SectionKind::Record => "record",
+                    SectionKind::CustomType => "type",
+                    SectionKind::Alias => "alias",
+                    SectionKind::Endpoint => "endpoint",
+                }
+            )
+            .as_str(),
+        );
+
+        for note in section.notes.iter() {
+            markdown.push_str(format!("- {}\n", describe(note)).as_str());
+        }
+    }
+
+    if !changelog.complete {
+        markdown.push_str("\nThere's no snapshot of the schema as of both versions, so type changes, deprecations, and changes to endpoints aren't listed.\n");
+    }
+
+    markdown
+}
+
+fn describe(note: &Note) -> String {
+    match note {
+        Note::Added(name) => format!("Added `{}`", name),
+        Note::Deprecated(name) => format!("Deprecated `{}`", name),
+        Note::Renamed { from, to } => format!("Renamed `{}` to `{}`", from, to),
+        Note::Retired(name) => format!("Retired `{}`", name),
+        Note::Removed(name) => format!("Removed `{}`", name),
+        Note::TypeChanged {
+            name,
+            from,
+            to,
+            widened,
+        } => format!(
+            "Changed the type of `{}` from `{}` to `{}`{}",
+            name,
+            from,
+            to,
+            if *widened {
+                " (old values are still accepted)"
+            } else {
+                ""
+            }
+        ),
+        Note::Other(description) => description.clone(),
+    }
+}
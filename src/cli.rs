@@ -1,30 +1,144 @@
 extern crate clap;
 
-use self::clap::{App, Arg};
+use self::clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use code_gen::Mode;
+use codec::Names;
+use history;
+use history::SchemaVersion;
 use language;
 use language::Language;
+use migration::Decisions;
 use std::iter::FromIterator;
 use std::path::{Path, PathBuf};
 
 // Use the version number in Cargo.toml
-const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(PartialEq, Debug)]
 pub enum Problem {
     InvalidLanguage(String),
+    InvalidRenameGrace(String),
+    InvalidWindow(String),
+    InvalidRename(String),
+    InvalidVersion(String),
+
+    /// compare-sizes needs either --type or --endpoint.
+    MissingSizeTarget,
 }
 
 pub struct CliArgs {
     pub client: Option<Language>,
     pub server: Option<Language>,
     pub file_paths: Vec<PathBuf>,
+    pub history_path: PathBuf,
+
+    /// A git revision (e.g. `origin/main` or a release tag) whose `.wf` files
+    /// are the baseline, instead of the snapshots in the history.
+    pub since: Option<String>,
+
+    /// How many versions development builds keep accepting former names.
+    pub rename_grace: SchemaVersion,
+    pub mode: Mode,
+    pub command: Command,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Command {
+    Build,
+    CheckCompat(CheckCompatArgs),
+    Migrate(Decisions),
+    Changelog(ChangelogArgs),
+    CompareSizes(CompareSizesArgs),
+    ExportJsonSchema(ExportArgs),
+}
+
+#[derive(PartialEq, Debug)]
+pub struct CheckCompatArgs {
+    /// Compare against the `.wf` files in this directory instead of the
+    /// snapshot in the schema history.
+    pub baseline: Option<PathBuf>,
+
+    /// Breaking changes which should not cause a failure, e.g. "User.email"
+    pub acknowledged: Vec<String>,
+
+    /// If everything is compatible, record the current schema in the history
+    /// as the new baseline.
+    pub update_history: bool,
+
+    /// How many released versions to keep snapshots of, and check against.
+    pub window: usize,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct ChangelogArgs {
+    /// The version to list changes since. Defaults to the latest snapshot.
+    pub from: Option<SchemaVersion>,
+
+    /// The version to list changes up to. Defaults to the current schema.
+    pub to: Option<SchemaVersion>,
+
+    /// Write the Markdown here instead of printing it.
+    pub output: Option<PathBuf>,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct CompareSizesArgs {
+    /// A JSON file holding a sample value, with full names.
+    pub sample: PathBuf,
+    pub target: SizeTarget,
 }
 
-const ARG_CLIENT: &'static str = "client";
-const ARG_SERVER: &'static str = "server";
-const FILES_OR_DIRECTORIES: &'static str = "FILES_OR_DIRECTORIES";
+#[derive(PartialEq, Debug)]
+pub struct ExportArgs {
+    /// Full names, or the minified ones production sends.
+    pub names: Names,
+
+    /// Write the export here instead of printing it.
+    pub output: Option<PathBuf>,
+}
 
-pub fn parse_args<'a>() -> Result<CliArgs, Problem> {
+/// What the sample in compare-sizes is.
+#[derive(PartialEq, Debug)]
+pub enum SizeTarget {
+    Type(String),
+    Params(String),
+    Response(String),
+}
+
+const ARG_CLIENT: &str = "client";
+const ARG_SERVER: &str = "server";
+const ARG_HISTORY: &str = "history";
+const ARG_SINCE: &str = "since";
+const ARG_RENAME_GRACE: &str = "rename-grace";
+const ARG_PRODUCTION: &str = "production";
+const ARG_BASELINE: &str = "baseline";
+const ARG_ACKNOWLEDGE: &str = "acknowledge";
+const ARG_UPDATE_HISTORY: &str = "update-history";
+const ARG_WINDOW: &str = "window";
+const ARG_RENAME: &str = "rename";
+const ARG_RETIRE: &str = "retire";
+const ARG_NEW_ID: &str = "new-id";
+const ARG_KEEP_ID: &str = "keep-id";
+const ARG_FROM: &str = "from";
+const ARG_TO: &str = "to";
+const ARG_OUTPUT: &str = "output";
+const ARG_SAMPLE: &str = "sample";
+const ARG_TYPE: &str = "type";
+const ARG_ENDPOINT: &str = "endpoint";
+const ARG_PARAMS: &str = "params";
+const ARG_MINIFIED: &str = "minified";
+const FILES_OR_DIRECTORIES: &str = "FILES_OR_DIRECTORIES";
+const CMD_CHECK_COMPAT: &str = "check-compat";
+const CMD_MIGRATE: &str = "migrate";
+const CMD_CHANGELOG: &str = "changelog";
+const CMD_COMPARE_SIZES: &str = "compare-sizes";
+const CMD_EXPORT: &str = "export";
+const CMD_JSON_SCHEMA: &str = "json-schema";
+
+/// The schema history file `wf` uses if `--history` isn't specified.
+pub const DEFAULT_HISTORY_PATH: &str = "schema.wfhistory";
+
+pub fn parse_args() -> Result<CliArgs, Problem> {
     let matches = App::new("wf")
         .version(VERSION)
         .arg(
@@ -42,29 +156,315 @@ pub fn parse_args<'a>() -> Result<CliArgs, Problem> {
                 .takes_value(true),
         )
         .arg(
-            Arg::with_name(FILES_OR_DIRECTORIES)
-                .help("Run all tests found in these files and directories")
-                .multiple(true)
-                .index(1),
+            Arg::with_name(ARG_RENAME_GRACE)
+                .long("rename-grace")
+                .value_name("VERSIONS")
+                .help("How many schema versions development builds keep accepting the former names of things renamed with fka. Defaults to 3")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_PRODUCTION)
+                .long("production")
+                .help("Build for production, with minified names on the wire."),
+        )
+        .arg(history_arg())
+        .arg(since_arg())
+        .arg(files_arg())
+        .subcommand(
+            SubCommand::with_name(CMD_CHECK_COMPAT)
+                .about("Check whether the schema is compatible with the previous one.")
+                .arg(
+                    Arg::with_name(ARG_BASELINE)
+                        .long("baseline")
+                        .value_name("DIRECTORY")
+                        .help("Compare against the .wf files in this directory instead of the schema history.")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name(ARG_ACKNOWLEDGE)
+                        .long("acknowledge")
+                        .value_name("NAME")
+                        .help("Allow a breaking change to this field, variant, or endpoint, e.g. User.email")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name(ARG_UPDATE_HISTORY)
+                        .long("update-history")
+                        .help("If the schema is compatible, record it in the history as the new baseline."),
+                )
+                .arg(
+                    Arg::with_name(ARG_WINDOW)
+                        .long("window")
+                        .value_name("VERSIONS")
+                        .help("How many released versions clients and servers might still be running. Defaults to 5")
+                        .takes_value(true),
+                )
+                .arg(history_arg())
+                .arg(since_arg())
+                .arg(files_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_MIGRATE)
+                .about("Decide what ambiguous schema changes mean, and record them in the history.")
+                .arg(decision_arg(ARG_RENAME, "OLD=NEW", "It was renamed, e.g. User.id=userId"))
+                .arg(decision_arg(ARG_RETIRE, "NAME", "It was removed, e.g. User.id"))
+                .arg(decision_arg(ARG_NEW_ID, "NAME", "Its type changed, so give it a new ID, e.g. User.score"))
+                .arg(decision_arg(ARG_KEEP_ID, "NAME", "Its type changed, but it should keep its ID, e.g. User.score"))
+                .arg(history_arg())
+                .arg(since_arg())
+                .arg(files_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_CHANGELOG)
+                .about("Write a Markdown changelog of the schema between two versions.")
+                .arg(version_arg(ARG_FROM, "The version to list changes since. Defaults to the latest snapshot in the history."))
+                .arg(version_arg(ARG_TO, "The version to list changes up to. Defaults to the current schema."))
+                .arg(output_arg("Write the changelog to this file instead of printing it."))
+                .arg(history_arg())
+                .arg(files_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_COMPARE_SIZES)
+                .about("Compare how big a sample value is with full names, minified, and packed.")
+                .arg(
+                    Arg::with_name(ARG_SAMPLE)
+                        .long("sample")
+                        .value_name("FILE")
+                        .help("A JSON file holding the sample, with full names.")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(ARG_TYPE)
+                        .long("type")
+                        .value_name("NAME")
+                        .help("The type of the sample, e.g. User")
+                        .takes_value(true)
+                        .conflicts_with(ARG_ENDPOINT),
+                )
+                .arg(
+                    Arg::with_name(ARG_ENDPOINT)
+                        .long("endpoint")
+                        .value_name("NAME")
+                        .help("The endpoint whose response the sample is, e.g. sendDM")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name(ARG_PARAMS)
+                        .long("params")
+                        .help("The sample is the endpoint's params, instead of its response.")
+                        .requires(ARG_ENDPOINT),
+                )
+                .arg(history_arg())
+                .arg(files_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_EXPORT)
+                .about("Export the schema for other tools.")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name(CMD_JSON_SCHEMA)
+                        .about("Export every record, custom type, and endpoint body as JSON Schema.")
+                        .arg(
+                            Arg::with_name(ARG_MINIFIED)
+                                .long("minified")
+                                .help("Describe the minified JSON production builds send, instead of full names."),
+                        )
+                        .arg(output_arg("Write the JSON Schema to this file instead of printing it."))
+                        .arg(history_arg())
+                        .arg(files_arg()),
+                ),
         )
         .get_matches();
 
     let client = validate_language(matches.value_of(ARG_CLIENT))?;
     let server = validate_language(matches.value_of(ARG_SERVER))?;
+    let rename_grace = match matches.value_of(ARG_RENAME_GRACE) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| Problem::InvalidRenameGrace(value.to_string()))?,
+        None => history::DEFAULT_RENAME_GRACE,
+    };
+    let (command, sub_matches) = match matches.subcommand() {
+        (CMD_CHECK_COMPAT, Some(sub_matches)) => (
+            Command::CheckCompat(CheckCompatArgs {
+                baseline: sub_matches.value_of(ARG_BASELINE).map(PathBuf::from),
+                acknowledged: strings(sub_matches, ARG_ACKNOWLEDGE),
+                update_history: sub_matches.is_present(ARG_UPDATE_HISTORY),
+                window: match sub_matches.value_of(ARG_WINDOW) {
+                    Some(value) => match value.parse() {
+                        Ok(window) if window > 0 => window,
+                        _ => return Err(Problem::InvalidWindow(value.to_string())),
+                    },
+                    None => history::DEFAULT_SNAPSHOT_WINDOW,
+                },
+            }),
+            sub_matches,
+        ),
+        (CMD_MIGRATE, Some(sub_matches)) => (
+            Command::Migrate(Decisions {
+                renames: strings(sub_matches, ARG_RENAME)
+                    .into_iter()
+                    .map(parse_rename)
+                    .collect::<Result<Vec<(String, String)>, Problem>>()?,
+                retire: strings(sub_matches, ARG_RETIRE),
+                new_ids: strings(sub_matches, ARG_NEW_ID),
+                keep_ids: strings(sub_matches, ARG_KEEP_ID),
+            }),
+            sub_matches,
+        ),
+        (CMD_CHANGELOG, Some(sub_matches)) => (
+            Command::Changelog(ChangelogArgs {
+                from: version(sub_matches, ARG_FROM)?,
+                to: version(sub_matches, ARG_TO)?,
+                output: sub_matches.value_of(ARG_OUTPUT).map(PathBuf::from),
+            }),
+            sub_matches,
+        ),
+        (CMD_COMPARE_SIZES, Some(sub_matches)) => (
+            Command::CompareSizes(CompareSizesArgs {
+                sample: PathBuf::from(sub_matches.value_of(ARG_SAMPLE).unwrap_or_default()),
+                target: match (
+                    sub_matches.value_of(ARG_TYPE),
+                    sub_matches.value_of(ARG_ENDPOINT),
+                ) {
+                    (Some(name), _) => SizeTarget::Type(name.to_string()),
+                    (None, Some(name)) if sub_matches.is_present(ARG_PARAMS) => {
+                        SizeTarget::Params(name.to_string())
+                    }
+                    (None, Some(name)) => SizeTarget::Response(name.to_string()),
+                    (None, None) => return Err(Problem::MissingSizeTarget),
+                },
+            }),
+            sub_matches,
+        ),
+        (CMD_EXPORT, Some(export_matches)) => match export_matches.subcommand() {
+            (CMD_JSON_SCHEMA, Some(sub_matches)) => (
+                Command::ExportJsonSchema(ExportArgs {
+                    names: if sub_matches.is_present(ARG_MINIFIED) {
+                        Names::Packed
+                    } else {
+                        Names::Full
+                    },
+                    output: sub_matches.value_of(ARG_OUTPUT).map(PathBuf::from),
+                }),
+                sub_matches,
+            ),
+            // clap requires a subcommand, and json-schema is the only one.
+            _ => unreachable!(),
+        },
+        _ => (Command::Build, &matches),
+    };
     let file_paths: Vec<PathBuf> = Vec::from_iter(
-        matches
+        sub_matches
             .values_of(FILES_OR_DIRECTORIES)
-            .unwrap_or(Default::default())
-            .map(|value| (Path::new(value).to_path_buf())),
+            .unwrap_or_default()
+            .map(|value| Path::new(value).to_path_buf()),
     );
+    let history_path = PathBuf::from(
+        sub_matches
+            .value_of(ARG_HISTORY)
+            .unwrap_or(DEFAULT_HISTORY_PATH),
+    );
+
+    let since = sub_matches.value_of(ARG_SINCE).map(String::from);
 
     Ok(CliArgs {
-        client: client,
-        server: server,
-        file_paths: file_paths,
+        client,
+        server,
+        file_paths,
+        history_path,
+        since,
+        rename_grace,
+        mode: if matches.is_present(ARG_PRODUCTION) {
+            Mode::Production
+        } else {
+            Mode::Development
+        },
+        command,
     })
 }
 
+fn history_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(ARG_HISTORY)
+        .long("history")
+        .value_name("FILE")
+        .help("The schema history file. Defaults to schema.wfhistory")
+        .takes_value(true)
+}
+
+fn since_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(ARG_SINCE)
+        .long("since")
+        .value_name("REVISION")
+        .help("A git revision, e.g. origin/main or a release tag, whose .wf files are what was deployed.")
+        .takes_value(true)
+}
+
+fn files_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(FILES_OR_DIRECTORIES)
+        .help("The .wf files to use, and directories to search for them")
+        .multiple(true)
+}
+
+fn output_arg<'a, 'b>(help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name(ARG_OUTPUT)
+        .long("output")
+        .value_name("FILE")
+        .help(help)
+        .takes_value(true)
+}
+
+fn decision_arg<'a, 'b>(name: &'a str, value_name: &'a str, help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name(name)
+        .long(name)
+        .value_name(value_name)
+        .help(help)
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+}
+
+fn version_arg<'a, 'b>(name: &'a str, help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name(name)
+        .long(name)
+        .value_name("VERSION")
+        .help(help)
+        .takes_value(true)
+}
+
+fn version(matches: &ArgMatches, name: &str) -> Result<Option<SchemaVersion>, Problem> {
+    match matches.value_of(name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| Problem::InvalidVersion(value.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// Parses e.g. "User.id=userId" into ("User.id", "userId")
+fn parse_rename(value: String) -> Result<(String, String), Problem> {
+    let mut parts = value.splitn(2, '=');
+
+    match (parts.next(), parts.next()) {
+        (Some(former_name), Some(name)) if former_name.contains('.') && !name.is_empty() => {
+            Ok((former_name.to_string(), name.to_string()))
+        }
+        _ => Err(Problem::InvalidRename(value)),
+    }
+}
+
+fn strings(matches: &ArgMatches, name: &str) -> Vec<String> {
+    matches
+        .values_of(name)
+        .unwrap_or_default()
+        .map(String::from)
+        .collect()
+}
+
 fn validate_language(arg: Option<&str>) -> Result<Option<Language>, Problem> {
     match arg.map(String::from) {
         Some(string) => language::from_string(string.as_str())
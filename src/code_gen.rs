@@ -1,75 +1,2063 @@
+use codec::{bind, Bindings};
+use field_id;
+use history::{History, Ledger, SchemaVersion};
+use negotiation;
 use std::collections::HashSet;
 use std::io;
-use types::Field;
+use types::{CustomTypeInfo, Field, Optionality, RecordInfo, Schema, TypeParam, TypePrimitive};
 
 #[derive(Debug)]
-pub enum _Problem<'a> {
-    RetiredNamesUsed(HashSet<&'a str>),
+pub enum Problem {
+    /// Qualified names (e.g. "User.id") of fields and variants which reuse the
+    /// name of one that was retired.
+    RetiredNamesUsed(Vec<String>),
+
+    /// Qualified names of fields and variants which the history hasn't
+    /// assigned IDs to yet.
+    MissingIds(Vec<String>),
     WriteError(io::Error),
 }
 
-// pub fn _productionize<'a>(
-//     retired_names: HashSet<&str>,
-//     fields_by_name: Vec<(&'a str, Box<Field>)>,
-// ) -> Result<(), Problem<'a>> {
-//     let invalid_names: HashSet<&'a str> = check_for_retired_names(retired_names, &fields_by_name);
-
-//     if invalid_names.is_empty() {
-//         panic!("TODO");
-//     // Ok(minify(fields_by_name))
-//     } else {
-//         Err(Problem::RetiredNamesUsed(invalid_names))
-//     }
-// }
-
-fn _check_for_retired_names<'a>(
-    retired_names: HashSet<&str>,
-    fields_by_name: &Vec<(&'a str, Box<Field>)>,
-) -> HashSet<&'a str> {
-    let mut violations: HashSet<&'a str> = HashSet::new();
-
-    for (name, _) in fields_by_name.iter() {
-        if retired_names.contains(name) {
-            violations.insert(name);
-        }
-    }
-
-    violations
-    // TODO intersect the retiredNames set with names. Return a HashSet of any
-    // retired names that appear in the names set.
-    // We're starting with a HashMap<&str, Field>, so use whatever seems appropriate for names.
-}
-
-// fn minify<'a>(fields_by_name: Vec<(&'a str, Box<Field>)>) -> Vec<(&'a str, Box<Field>)> {
-//     let mut field_id = 0;
-
-//     for pair in fields_by_name {
-//         let (name, field) = pair;
-//     }
-// }
-
-// TODO:
-//
-//
-// {-| ALWAYS sort all fields alphabetically before doing anything else! Crucial for diffing, for history, for numbering, etc. This way reordering is harmless. -}
-// fun minifyNames(retiredNames: Set<String>, fields: Array<Field>): Array<Field, String>
-//
-// We need to add this as metadata rather than mutating the Array, so we can
-// generate the code that back-converts and applies defaults.
-//
-// So we take this Array<Field, String> and output:
-//
-// JS:
-//
-// var json = JSON.parse(body);
-//
-// return { userId /*: int*/: json.a, email /*: string*/: json.b, ... }
-//
-// [TODO: also generate .d.ts file]
-//
-// Elm:
-//
-// Json.Decode.succeed (...record constructor...)
-//     |> required "a" int
-//     |> required "b" string
-//
+/// Whether to generate code for development, which sends everything over the
+/// wire by its full name so it's easy to read in developer tools, or for
+/// production, which uses minified names.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Development,
+    Production,
+}
+
+/// The name every record field and custom type variant goes by on the wire in
+/// production. Every code generator uses this in production mode, so that all
+/// of them agree with each other (and with the history) on every name.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MinificationTable {
+    /// Named records, followed by inline records and endpoint bodies under
+    /// their locations (see `RecordInfo::ledger_name`).
+    pub records: Vec<MinifiedNames>,
+    pub custom_types: Vec<MinifiedNames>,
+}
+
+/// The minified names within a single record or custom type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinifiedNames {
+    pub name: String,
+
+    /// Each full name along with its minified one, sorted by full name.
+    pub names: Vec<(String, String)>,
+}
+
+impl MinificationTable {
+    pub fn field(&self, record: &str, field: &str) -> Option<&str> {
+        lookup(&self.records, record, field)
+    }
+
+    /// The minified name of one of the record's fields, whether the record is
+    /// named or inline.
+    pub fn record_field(&self, record: &RecordInfo, field: &str) -> Option<&str> {
+        record
+            .ledger_name()
+            .and_then(|name| self.field(name.as_str(), field))
+    }
+
+    pub fn variant(&self, custom_type: &str, variant: &str) -> Option<&str> {
+        lookup(&self.custom_types, custom_type, variant)
+    }
+}
+
+fn lookup<'a>(all_names: &'a [MinifiedNames], parent: &str, name: &str) -> Option<&'a str> {
+    all_names
+        .iter()
+        .find(|names| names.name == parent)
+        .and_then(|names| names.names.iter().find(|(full_name, _)| full_name == name))
+        .map(|(_, minified_name)| minified_name.as_str())
+}
+
+/// Build the minification table for a production build.
+///
+/// Each name is minified according to the ID the history assigned it (see
+/// `field_id::to_string`), so the history needs to have been brought up to
+/// date with `allocator::allocate` first. Fields and variants are sorted by
+/// name before anything else, so reordering them in the schema never changes
+/// the generated code.
+pub fn productionize(history: &History, schema: &Schema) -> Result<MinificationTable, Problem> {
+    check_for_retired_names(history, schema)?;
+
+    let mut table = MinificationTable::default();
+    let mut missing: Vec<String> = vec![];
+
+    for record in schema.records.iter() {
+        if let Some(ref name) = record.name {
+            let names: Vec<&str> = record
+                .fields
+                .iter()
+                .map(|field| field.name.as_str())
+                .collect();
+
+            table.records.push(minify(
+                name.to_string(),
+                history.record(name.to_string().as_str()),
+                names,
+                &mut missing,
+            ));
+        }
+    }
+
+    for (location, names) in schema.inline_field_names() {
+        let ledger = history.record(location.as_str());
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+        table
+            .records
+            .push(minify(location.clone(), ledger, names, &mut missing));
+    }
+
+    for custom_type in schema.custom_types.iter() {
+        let name = custom_type.name.to_string();
+        let names: Vec<&str> = custom_type
+            .variants
+            .iter()
+            .map(|variant| variant.name.as_str())
+            .collect();
+
+        table.custom_types.push(minify(
+            name.clone(),
+            history.custom_type(name.as_str()),
+            names,
+            &mut missing,
+        ));
+    }
+
+    if missing.is_empty() {
+        Ok(table)
+    } else {
+        Err(Problem::MissingIds(missing))
+    }
+}
+
+/// Pair up each name with its minified name, adding the qualified names of any
+/// which have no ID in the history to `missing`.
+fn minify(
+    parent: String,
+    ledger: Option<&Ledger>,
+    mut names: Vec<&str>,
+    missing: &mut Vec<String>,
+) -> MinifiedNames {
+    let mut minified_names: Vec<(String, String)> = vec![];
+
+    names.sort_unstable();
+
+    for name in names {
+        match ledger.and_then(|ledger| ledger.entry(name)) {
+            Some(entry) => minified_names.push((name.to_string(), field_id::to_string(entry.id))),
+            None => missing.push(format!("{}.{}", parent, name)),
+        }
+    }
+
+    MinifiedNames {
+        name: parent,
+        names: minified_names,
+    }
+}
+
+/// Make sure no field or variant in the schema has the same name as one the
+/// history says was retired, and which it doesn't know as a current one.
+///
+/// This has to run before `allocator::allocate`, which would otherwise hand
+/// the reintroduced name a fresh ID. Old clients would still use the name
+/// with its old meaning, and in development mode (where fields go over the
+/// wire by name) there would be no way to tell the two apart.
+pub fn check_for_retired_names(history: &History, schema: &Schema) -> Result<(), Problem> {
+    let mut violations: Vec<String> = vec![];
+
+    for record in schema.records.iter() {
+        let record_name = match record.name {
+            Some(ref name) => name.to_string(),
+            None => continue,
+        };
+
+        if let Some(ledger) = history.record(record_name.as_str()) {
+            let names: Vec<&str> = record
+                .fields
+                .iter()
+                .map(|field| field.name.as_str())
+                .collect();
+
+            for name in retired_names_used(ledger, &names) {
+                violations.push(format!("{}.{}", record_name, name));
+            }
+        }
+    }
+
+    for (location, names) in schema.inline_field_names() {
+        if let Some(ledger) = history.record(location.as_str()) {
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+            for name in retired_names_used(ledger, &names) {
+                violations.push(format!("{}.{}", location, name));
+            }
+        }
+    }
+
+    for custom_type in schema.custom_types.iter() {
+        let type_name = custom_type.name.to_string();
+
+        if let Some(ledger) = history.custom_type(type_name.as_str()) {
+            let names: Vec<&str> = custom_type
+                .variants
+                .iter()
+                .map(|variant| variant.name.as_str())
+                .collect();
+
+            for name in retired_names_used(ledger, &names) {
+                violations.push(format!("{}.{}", type_name, name));
+            }
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Problem::RetiredNamesUsed(violations))
+    }
+}
+
+/// The given names which appear in the ledger's retired names, in the order
+/// they were given.
+fn retired_names_used<'a>(ledger: &Ledger, names: &[&'a str]) -> Vec<&'a str> {
+    let retired_names: HashSet<&str> = ledger.retired_names();
+
+    names
+        .iter()
+        .filter(|name| retired_names.contains(*name))
+        .cloned()
+        .collect()
+}
+
+/// JavaScript which tells generated clients what to send in their `Accept`
+/// header, and generated servers which format to respond in and whether they
+/// can read a request body, following the rules in the `negotiation` module.
+/// JavaScript only speaks JSON, so binary is left out.
+pub fn formats_js(mode: Mode) -> String {
+    let media_type = negotiation::default_format(mode).media_type();
+
+    format!(
+        "// Generated by wf. Do not edit.
+
+var wf$format = \"{}\";
+
+// What clients send in their Accept header.
+var wf$accept = \"{}\";
+
+// The media type a server should respond with, or null if it should respond
+// 406 Not Acceptable.
+function wf$responseFormat(accept) {{
+  if (accept == null || accept.trim() === \"\") return wf$format;
+
+  var quality = 0;
+  var specificity = -1;
+
+  accept.split(\",\").forEach(function (range) {{
+    var params = range.split(\";\");
+    var type = params[0].trim().toLowerCase();
+    var rangeQuality = 1;
+    var rangeSpecificity =
+      type === wf$format ? 3 :
+      type === \"application/json\" ? 2 :
+      type === \"application/*\" ? 1 :
+      type === \"*/*\" ? 0 : -1;
+
+    params.slice(1).forEach(function (param) {{
+      var match = /^\\s*q=([0-9.]+)\\s*$/.exec(param);
+
+      if (match && rangeQuality === 1) rangeQuality = parseFloat(match[1]);
+    }});
+
+    if (rangeSpecificity > specificity) {{
+      specificity = rangeSpecificity;
+      quality = rangeQuality;
+    }}
+  }});
+
+  return quality > 0 ? wf$format : null;
+}}
+
+// Whether a server can read a request body with this Content-Type. If not, it
+// should respond 415 Unsupported Media Type.
+function wf$readsContentType(contentType) {{
+  if (contentType == null || contentType.trim() === \"\") return true;
+
+  var type = contentType.split(\";\")[0].trim().toLowerCase();
+
+  return type === wf$format || type === \"application/json\";
+}}
+",
+        media_type,
+        negotiation::accept_header(mode, false)
+    )
+}
+
+/// Whether any record or endpoint in the schema asks to be packed.
+pub fn uses_packing(schema: &Schema) -> bool {
+    schema.records.iter().any(|record| record.packed)
+        || schema.endpoints.iter().any(|endpoint| endpoint.packed)
+}
+
+/// JavaScript which converts production JSON between records as objects and
+/// records packed into tuples, following the same rules as the `codec`
+/// module. Each named record gets `wf$pack$Name(value, inArray, packAll)` and
+/// `wf$unpack$Name`, and each endpoint gets `wf$packParams$name(value)`,
+/// `wf$unpackParams$name`, `wf$packResponse$name` and `wf$unpackResponse$name`.
+///
+/// These work on JSON which already has minified names, so they sit between
+/// `JSON.parse`/`JSON.stringify` and the code that minifies names. The names
+/// come from the table `productionize` made for the schema.
+pub fn packing_js(schema: &Schema, table: &MinificationTable) -> Result<String, Problem> {
+    let mut packer = Packer::new(schema, table);
+    let mut js = String::from(PACKING_JS_HELPERS);
+
+    for record in schema.records.iter() {
+        let name = match record.name {
+            Some(ref name) => name.to_string(),
+            None => continue,
+        };
+        let slots: Vec<String> = packer
+            .slots(record)
+            .into_iter()
+            .map(|slot| slot.map_or_else(|| String::from("null"), |name| format!("\"{}\"", name)))
+            .collect();
+        let fields = packer.fields(record);
+        let condition = if record.packed {
+            "inArray"
+        } else {
+            "inArray && packAll"
+        };
+
+        js.push_str(&format!(
+            "\nvar wf$slots${} = [{}];\n",
+            name,
+            slots.join(", ")
+        ));
+        js.push_str(&format!(
+            "\nfunction wf$pack${}(value, inArray, packAll) {{\n  var fields = {};\n\n  return {} && fields != null ? wf$toTuple(fields, wf$slots${}) : fields;\n}}\n",
+            name,
+            js_update(&fields, "value", Verb::Pack),
+            condition,
+            name
+        ));
+        js.push_str(&format!(
+            "\nfunction wf$unpack${}(value, inArray, packAll) {{\n  return {};\n}}\n",
+            name,
+            js_update(
+                &fields,
+                format!(
+                    "{} ? wf$fromTuple(value, wf$slots${}) : value",
+                    condition, name
+                )
+                .as_str(),
+                Verb::Unpack
+            ),
+        ));
+    }
+
+    for endpoint in schema.endpoints.iter() {
+        for (body, record) in [
+            ("Params", endpoint.params_record()),
+            ("Response", endpoint.response_record()),
+        ] {
+            let fields = packer.fields(&record);
+
+            for verb in [Verb::Pack, Verb::Unpack] {
+                js.push_str(&format!(
+                    "\nfunction wf${}{}${}(value) {{\n  var packAll = {};\n\n  return {};\n}}\n",
+                    verb.as_str(),
+                    body,
+                    endpoint.name,
+                    endpoint.packed,
+                    js_update(&fields, "value", verb)
+                ));
+            }
+        }
+    }
+
+    packer.finish(js)
+}
+
+const PACKING_JS_HELPERS: &str = "// Generated by wf. Do not edit.
+
+function wf$mapArray(elems, convert) {
+  return elems == null ? elems : elems.map(convert);
+}
+
+function wf$update(value, update) {
+  if (value == null) return value;
+
+  var fields = {};
+
+  for (var key in value) {
+    if (Object.prototype.hasOwnProperty.call(value, key)) fields[key] = value[key];
+  }
+
+  update(fields);
+
+  return fields;
+}
+
+function wf$toTuple(fields, slots) {
+  var tuple = [];
+
+  for (var slot = 0; slot < slots.length; slot++) {
+    var value = slots[slot] == null ? null : fields[slots[slot]];
+
+    tuple.push(value === undefined ? null : value);
+  }
+
+  while (tuple.length > 0 && tuple[tuple.length - 1] === null) tuple.pop();
+
+  return tuple;
+}
+
+function wf$fromTuple(tuple, slots) {
+  var fields = {};
+
+  for (var slot = 0; slot < slots.length && slot < tuple.length; slot++) {
+    if (slots[slot] != null && tuple[slot] != null) fields[slots[slot]] = tuple[slot];
+  }
+
+  return fields;
+}
+";
+
+/// The Elm version of `packing_js`, as a `WfPacking` module. Each converter
+/// is a `Decoder Value` which turns one JSON value into another: named records
+/// get `packName : Bool -> Bool -> Decoder Value` (taking `inArray` and
+/// `packAll`) and `unpackName`, and each endpoint gets `packParams_name`,
+/// `unpackParams_name`, `packResponse_name` and `unpackResponse_name`.
+///
+/// Like the JavaScript, these work on JSON which already has minified names,
+/// e.g. `Decode.decodeValue WfPacking.unpackResponse_feed json` before
+/// decoding the response.
+pub fn packing_elm(schema: &Schema, table: &MinificationTable) -> Result<String, Problem> {
+    let mut packer = Packer::new(schema, table);
+    let mut elm = String::from(PACKING_ELM_HELPERS);
+
+    for record in schema.records.iter() {
+        let name = match record.name {
+            Some(ref name) => name.to_string(),
+            None => continue,
+        };
+        let slots: Vec<String> = packer
+            .slots(record)
+            .into_iter()
+            .map(|slot| {
+                slot.map_or_else(
+                    || String::from("Nothing"),
+                    |name| format!("Just \"{}\"", name),
+                )
+            })
+            .collect();
+        let fields = packer.fields(record);
+        let condition = if record.packed {
+            "inArray"
+        } else {
+            "inArray && packAll"
+        };
+
+        elm.push_str(&format!(
+            "\n\nslots{} : List (Maybe String)\nslots{} =\n    [{}]\n",
+            name,
+            name,
+            if slots.is_empty() {
+                String::new()
+            } else {
+                format!(" {} ", slots.join(", "))
+            }
+        ));
+        elm.push_str(&format!(
+            "\n\npack{} : Bool -> Bool -> Decoder Value\npack{} inArray packAll =\n    let\n        fields =\n            {}\n    in\n    if {} then\n        Decode.andThen (convertWith (toTuple slots{})) fields\n\n    else\n        fields\n",
+            name,
+            name,
+            elm_update(&fields, Verb::Pack),
+            condition,
+            name
+        ));
+        elm.push_str(&format!(
+            "\n\nunpack{} : Bool -> Bool -> Decoder Value\nunpack{} inArray packAll =\n    let\n        fields =\n            {}\n    in\n    if {} then\n        Decode.andThen (convertWith fields) (fromTuple slots{})\n\n    else\n        fields\n",
+            name,
+            name,
+            elm_update(&fields, Verb::Unpack),
+            condition,
+            name
+        ));
+    }
+
+    for endpoint in schema.endpoints.iter() {
+        for (body, record) in [
+            ("Params", endpoint.params_record()),
+            ("Response", endpoint.response_record()),
+        ] {
+            let fields = packer.fields(&record);
+
+            for verb in [Verb::Pack, Verb::Unpack] {
+                let function = format!("{}{}_{}", verb.as_str(), body, endpoint.name);
+
+                elm.push_str(&format!(
+                    "\n\n{} : Decoder Value\n{} =\n    let\n        packAll =\n            {}\n    in\n    {}\n",
+                    function,
+                    function,
+                    if endpoint.packed { "True" } else { "False" },
+                    elm_update(&fields, verb)
+                ));
+            }
+        }
+    }
+
+    packer.finish(elm)
+}
+
+const PACKING_ELM_HELPERS: &str = "module WfPacking exposing (..)
+
+-- Generated by wf. Do not edit.
+
+import Json.Decode as Decode exposing (Decoder, Value)
+import Json.Encode as Encode
+
+
+convertWith : Decoder Value -> Value -> Decoder Value
+convertWith convert value =
+    case Decode.decodeValue convert value of
+        Ok converted ->
+            Decode.succeed converted
+
+        Err err ->
+            Decode.fail (Decode.errorToString err)
+
+
+nullable : Decoder Value -> Decoder Value
+nullable convert =
+    Decode.oneOf [ Decode.null Encode.null, convert ]
+
+
+isNull : Value -> Bool
+isNull value =
+    case Decode.decodeValue (Decode.null ()) value of
+        Ok () ->
+            True
+
+        Err _ ->
+            False
+
+
+mapArray : Decoder Value -> Decoder Value
+mapArray convert =
+    nullable (Decode.map (Encode.list identity) (Decode.list convert))
+
+
+update : List ( String, Decoder Value ) -> Decoder Value
+update converters =
+    let
+        convertField ( key, value ) rest =
+            case List.filter (\\( name, _ ) -> name == key) converters of
+                ( _, convert ) :: _ ->
+                    Decode.map2 (::) (Decode.map (Tuple.pair key) (convertWith convert value)) rest
+
+                [] ->
+                    Decode.map ((::) ( key, value )) rest
+    in
+    nullable
+        (Decode.keyValuePairs Decode.value
+            |> Decode.andThen (List.foldr convertField (Decode.succeed []))
+            |> Decode.map Encode.object
+        )
+
+
+toTuple : List (Maybe String) -> Decoder Value
+toTuple slots =
+    let
+        slotValue fields slot =
+            case slot of
+                Just name ->
+                    List.filter (\\( key, _ ) -> key == name) fields
+                        |> List.head
+                        |> Maybe.map Tuple.second
+                        |> Maybe.withDefault Encode.null
+
+                Nothing ->
+                    Encode.null
+
+        dropNulls values =
+            case values of
+                value :: rest ->
+                    if isNull value then
+                        dropNulls rest
+
+                    else
+                        values
+
+                [] ->
+                    []
+    in
+    nullable
+        (Decode.keyValuePairs Decode.value
+            |> Decode.map
+                (\\fields ->
+                    List.map (slotValue fields) slots
+                        |> List.reverse
+                        |> dropNulls
+                        |> List.reverse
+                        |> Encode.list identity
+                )
+        )
+
+
+fromTuple : List (Maybe String) -> Decoder Value
+fromTuple slots =
+    let
+        field ( slot, value ) =
+            if isNull value then
+                Nothing
+
+            else
+                Maybe.map (\\name -> ( name, value )) slot
+    in
+    nullable
+        (Decode.list Decode.value
+            |> Decode.map (List.map2 Tuple.pair slots >> List.filterMap field >> Encode.object)
+        )
+";
+
+#[derive(Clone, Copy)]
+enum Verb {
+    Pack,
+    Unpack,
+}
+
+impl Verb {
+    fn as_str(self) -> &'static str {
+        match self {
+            Verb::Pack => "pack",
+            Verb::Unpack => "unpack",
+        }
+    }
+}
+
+/// What packing or unpacking does to a value, whichever language it's in.
+enum Conversion {
+    /// Convert each element of an array or set.
+    Elems(Box<Conversion>),
+
+    /// Convert some of an inline record's fields, by minified name.
+    Fields(Vec<(String, Conversion)>),
+
+    /// A named record, which is packed if it's in an array and marked (or in
+    /// an endpoint marked) `[ packed ]`.
+    Record { name: String, in_array: bool },
+}
+
+/// Works out the conversions for `packing_js` and `packing_elm`, keeping track
+/// of any fields the table has no minified name for.
+struct Packer<'a> {
+    schema: &'a Schema,
+    table: &'a MinificationTable,
+    missing: Vec<String>,
+}
+
+impl<'a> Packer<'a> {
+    fn new(schema: &'a Schema, table: &'a MinificationTable) -> Packer<'a> {
+        Packer {
+            schema,
+            table,
+            missing: vec![],
+        }
+    }
+
+    /// The generated code, unless some field had no minified name.
+    fn finish(self, code: String) -> Result<String, Problem> {
+        if self.missing.is_empty() {
+            Ok(code)
+        } else {
+            Err(Problem::MissingIds(self.missing))
+        }
+    }
+
+    fn field_name(&mut self, record: &RecordInfo, field: &Field) -> String {
+        minified_name(self.table, record, field, &mut self.missing)
+    }
+
+    /// The minified name of the field at each index of a packed record's tuple,
+    /// with `None` for IDs no current field has.
+    fn slots(&mut self, record: &RecordInfo) -> Vec<Option<String>> {
+        let mut slots: Vec<Option<String>> = vec![];
+
+        for field in record.fields.iter() {
+            let slot = field.field_id as usize;
+
+            if slots.len() <= slot {
+                slots.resize(slot + 1, None);
+            }
+
+            slots[slot] = Some(self.field_name(record, field));
+        }
+
+        slots
+    }
+
+    /// The record's fields which have something inside them to pack, by
+    /// minified name.
+    fn fields(&mut self, record: &RecordInfo) -> Vec<(String, Conversion)> {
+        record
+            .fields
+            .iter()
+            .filter_map(|field| {
+                let name = self.field_name(record, field);
+
+                self.convert(&field.tipe.current, false, &[])
+                    .map(|conversion| (name, conversion))
+            })
+            .collect()
+    }
+
+    /// How to pack or unpack a value of the given type, or `None` if there is
+    /// nothing in it to pack. Like the `codec` module, this doesn't look inside
+    /// dicts, custom type contents, or type params.
+    fn convert(
+        &mut self,
+        primitive: &TypePrimitive,
+        in_array: bool,
+        expanding: &[&str],
+    ) -> Option<Conversion> {
+        match primitive {
+            TypePrimitive::Array(elem) | TypePrimitive::Set(elem) => self
+                .convert(&elem.current, true, expanding)
+                .map(|elem| Conversion::Elems(Box::new(elem))),
+            TypePrimitive::Record(info) => Some(Conversion::Fields(self.fields(info))),
+            TypePrimitive::Named(name, _) => {
+                let name = name.to_string();
+
+                if self.schema.record(name.as_str()).is_some() {
+                    Some(Conversion::Record { name, in_array })
+                } else {
+                    match self.schema.alias(name.as_str()) {
+                        Some(alias)
+                            if alias.params.is_empty() && !expanding.contains(&name.as_str()) =>
+                        {
+                            let mut expanding = expanding.to_vec();
+
+                            expanding.push(name.as_str());
+
+                            self.convert(&alias.tipe.current, in_array, &expanding)
+                        }
+                        _ => None,
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A field's minified name, from the table, adding its qualified name to
+/// `missing` if the table doesn't have it.
+fn minified_name(
+    table: &MinificationTable,
+    record: &RecordInfo,
+    field: &Field,
+    missing: &mut Vec<String>,
+) -> String {
+    match table.record_field(record, field.name.as_str()) {
+        Some(name) => name.to_string(),
+        None => {
+            let qualified = format!(
+                "{}.{}",
+                record.ledger_name().unwrap_or_default(),
+                field.name
+            );
+
+            if !missing.contains(&qualified) {
+                missing.push(qualified);
+            }
+
+            field.name.clone()
+        }
+    }
+}
+
+/// A JS expression which copies the record, packing or unpacking whatever is
+/// inside its fields.
+fn js_update(fields: &[(String, Conversion)], value: &str, verb: Verb) -> String {
+    let statements: Vec<String> = fields
+        .iter()
+        .map(|(name, conversion)| {
+            let field_value = format!("fields.{}", name);
+
+            format!(
+                "if ({} != null) {} = {};",
+                field_value,
+                field_value,
+                js_convert(conversion, field_value.as_str(), verb)
+            )
+        })
+        .collect();
+
+    if statements.is_empty() {
+        format!("wf$update({}, function () {{}})", value)
+    } else {
+        format!(
+            "wf$update({}, function (fields) {{ {} }})",
+            value,
+            statements.join(" ")
+        )
+    }
+}
+
+/// A JS expression which packs or unpacks the given value.
+fn js_convert(conversion: &Conversion, value: &str, verb: Verb) -> String {
+    match conversion {
+        Conversion::Elems(elem) => format!(
+            "wf$mapArray({}, function (elem) {{ return {}; }})",
+            value,
+            js_convert(elem, "elem", verb)
+        ),
+        Conversion::Fields(fields) => js_update(fields, value, verb),
+        Conversion::Record { name, in_array } => format!(
+            "wf${}${}({}, {}, packAll)",
+            verb.as_str(),
+            name,
+            value,
+            in_array
+        ),
+    }
+}
+
+/// An Elm `Decoder Value` which copies the record, packing or unpacking
+/// whatever is inside its fields.
+fn elm_update(fields: &[(String, Conversion)], verb: Verb) -> String {
+    let converters: Vec<String> = fields
+        .iter()
+        .map(|(name, conversion)| format!("( \"{}\", {} )", name, elm_convert(conversion, verb)))
+        .collect();
+
+    if converters.is_empty() {
+        String::from("update []")
+    } else {
+        format!("update [ {} ]", converters.join(", "))
+    }
+}
+
+/// An Elm `Decoder Value` which packs or unpacks a value. Records are lazy,
+/// since they may contain themselves.
+fn elm_convert(conversion: &Conversion, verb: Verb) -> String {
+    match conversion {
+        Conversion::Elems(elem) => format!("mapArray ({})", elm_convert(elem, verb)),
+        Conversion::Fields(fields) => elm_update(fields, verb),
+        Conversion::Record { name, in_array } => format!(
+            "Decode.lazy (\\_ -> {}{} {} packAll)",
+            verb.as_str(),
+            name,
+            if *in_array { "True" } else { "False" }
+        ),
+    }
+}
+
+/// JavaScript which converts between the full-name JSON application code works
+/// with and the JSON that goes over the wire in the given mode. Development
+/// JSON has full names and `$type` annotations, and production JSON has
+/// minified names, custom types as `[id, ...contents]`, and records packed
+/// wherever the schema asks for it (see the `codec` module). Since generated
+/// code only talks to the wire through these, `wf build --production` is the
+/// one switch between the two formats.
+///
+/// Each record and custom type gets `wf$encode$Name(value)` and
+/// `wf$decode$Name(value)`, which take a converter for each of its type
+/// params, and each endpoint gets `wf$encodeParams$name`,
+/// `wf$decodeParams$name`, `wf$encodeResponse$name` and
+/// `wf$decodeResponse$name`. Packing applies to whole endpoint bodies, so in
+/// production these call the converters from `packing_js`.
+///
+/// Decoders throw if the JSON is in the other mode's format, so a client and
+/// server built in different modes fail loudly instead of quietly misreading
+/// each other. They leave checking the values' types to application code.
+///
+/// `former_names` is the history and rename grace, if development decoders
+/// should also accept the names fields, variants and records had before being
+/// renamed, the way `Codec::accepting_former_names` does.
+pub fn wire_js(
+    schema: &Schema,
+    table: &MinificationTable,
+    mode: Mode,
+    former_names: Option<(&History, SchemaVersion)>,
+) -> Result<String, Problem> {
+    let mut wirer = Wirer::new(schema, table, mode, former_names);
+    let mut js = format!(
+        "// Generated by wf. Do not edit.\n\n// Whether this build sends and reads production JSON, rather than\n// development JSON. Building with --production sets this.\nvar wf$production = {};\n{}",
+        mode == Mode::Production,
+        WIRE_JS_HELPERS
+    );
+
+    for record in schema.records.iter() {
+        let name = match record.name {
+            Some(ref name) => name.to_string(),
+            None => continue,
+        };
+        let fields = wirer.fields(record, &vec![]);
+        let type_names = wirer.type_names(name.as_str(), History::record);
+
+        for direction in [Direction::Encode, Direction::Decode] {
+            js.push_str(&format!(
+                "\nfunction wf${}${}({}) {{\n  return {};\n}}\n",
+                direction.as_str(),
+                name,
+                js_params(&record.params),
+                js_record(Some(&type_names), &fields, "value", direction)
+            ));
+        }
+    }
+
+    for custom_type in schema.custom_types.iter() {
+        let name = custom_type.name.to_string();
+        let variants = wirer.variants(custom_type, &vec![]);
+        let type_names = wirer.type_names(name.as_str(), History::custom_type);
+
+        for direction in [Direction::Encode, Direction::Decode] {
+            js.push_str(&format!(
+                "\nfunction wf${}${}({}) {{\n  return {};\n}}\n",
+                direction.as_str(),
+                name,
+                js_params(&custom_type.params),
+                js_variants(&type_names, &variants, "value", direction)
+            ));
+        }
+    }
+
+    for endpoint in schema.endpoints.iter() {
+        for (body, record) in [
+            ("Params", endpoint.params_record()),
+            ("Response", endpoint.response_record()),
+        ] {
+            let fields = wirer.fields(&record, &vec![]);
+
+            for direction in [Direction::Encode, Direction::Decode] {
+                let converted = match (wirer.packs(), direction) {
+                    (false, _) => js_record(None, &fields, "value", direction),
+                    (true, Direction::Encode) => format!(
+                        "wf$pack{}${}({})",
+                        body,
+                        endpoint.name,
+                        js_record(None, &fields, "value", direction)
+                    ),
+                    (true, Direction::Decode) => js_record(
+                        None,
+                        &fields,
+                        format!("wf$unpack{}${}(value)", body, endpoint.name).as_str(),
+                        direction,
+                    ),
+                };
+
+                js.push_str(&format!(
+                    "\nfunction wf${}{}${}(value) {{\n  return {};\n}}\n",
+                    direction.as_str(),
+                    body,
+                    endpoint.name,
+                    converted
+                ));
+            }
+        }
+    }
+
+    wirer.finish(js)
+}
+
+const WIRE_JS_HELPERS: &str = "
+function wf$convert(value, convert) {
+  return convert == null || value == null ? value : convert(value);
+}
+
+function wf$convertElems(elems, convert) {
+  return elems == null ? elems : elems.map(function (elem) { return wf$convert(elem, convert); });
+}
+
+// Dicts with String keys are objects, and other dicts are arrays of pairs.
+function wf$convertDict(dict, convertKey, convertVal) {
+  if (dict == null) return dict;
+
+  if (Array.isArray(dict)) {
+    return dict.map(function (pair) {
+      return [wf$convert(pair[0], convertKey), wf$convert(pair[1], convertVal)];
+    });
+  }
+
+  var converted = {};
+
+  for (var key in dict) {
+    if (Object.prototype.hasOwnProperty.call(dict, key)) converted[key] = wf$convert(dict[key], convertVal);
+  }
+
+  return converted;
+}
+
+function wf$mixed(type) {
+  return new Error(
+    wf$production
+      ? \"This build reads production JSON, but got development JSON for \" + type + \".\"
+      : \"This build reads development JSON, but got production JSON for \" + type + \".\"
+  );
+}
+
+// Development JSON may still be annotated with one of the type's former
+// names, if it was renamed within the grace period.
+function wf$checkAnnotation(json, type, formerTypes) {
+  if (json.$type === undefined) throw wf$mixed(type);
+  if (json.$type !== type && (formerTypes || []).indexOf(json.$type) < 0) {
+    throw new Error(\"Expected a \" + type + \", but got a \" + json.$type + \".\");
+  }
+}
+
+// Each field is [name, minified name, convert, required], followed by the
+// former names decoders still accept, if it has any. Inline records have no
+// type.
+function wf$recordToWire(value, type, fields) {
+  if (value == null) return value;
+
+  var json = {};
+
+  if (!wf$production && type != null) json.$type = type;
+
+  fields.forEach(function (field) {
+    if (value[field[0]] !== undefined) json[wf$wireName(field)] = wf$convert(value[field[0]], field[2]);
+  });
+
+  return json;
+}
+
+function wf$recordFromWire(json, type, fields, formerTypes) {
+  if (json == null) return json;
+
+  if (wf$production) {
+    if (json.$type !== undefined) throw wf$mixed(type || \"a record\");
+  } else if (type != null) {
+    if (Array.isArray(json)) throw wf$mixed(type);
+
+    wf$checkAnnotation(json, type, formerTypes);
+  }
+
+  var value = {};
+
+  fields.forEach(function (field) {
+    var names = wf$acceptedNames(field).filter(function (name) { return json[name] !== undefined; });
+    var wireValue = names.length > 0 ? json[names[0]] : undefined;
+    var otherName = wf$production ? field[0] : field[1];
+
+    // A required field which is only there under the name the other mode
+    // gives it means the JSON is in the wrong format.
+    if (field[3] && wireValue == null && json[otherName] != null) throw wf$mixed(type || \"a record\");
+    if (wireValue !== undefined) value[field[0]] = wf$convert(wireValue, field[2]);
+  });
+
+  return value;
+}
+
+// The names a decoder reads a field by, in order.
+function wf$acceptedNames(field) {
+  return wf$production ? [field[1]] : [field[0]].concat(field[4] || []);
+}
+
+function wf$wireName(field) {
+  return wf$production ? field[1] : field[0];
+}
+
+// Each variant is [name, ID, [convert for each of its contents]], followed by
+// the former names decoders still accept, if it has any.
+function wf$variantToWire(value, type, variants) {
+  if (value == null) return value;
+
+  var variant = wf$variant(variants, type, value.variant, function (variant) {
+    return variant[0] === value.variant;
+  });
+  var contents = wf$contents(variant, value.contents || []);
+
+  if (wf$production) return [variant[1]].concat(contents);
+
+  var json = { $type: type, variant: variant[0] };
+
+  if (contents.length > 0) json.contents = contents;
+
+  return json;
+}
+
+function wf$variantFromWire(json, type, variants, formerTypes) {
+  if (json == null) return json;
+  if (Array.isArray(json) !== wf$production) throw wf$mixed(type);
+  if (!wf$production) wf$checkAnnotation(json, type, formerTypes);
+
+  var found = wf$production ? json[0] : json.variant;
+  var variant = wf$variant(variants, type, found, function (variant) {
+    return wf$production ? variant[1] === found : [variant[0]].concat(variant[3] || []).indexOf(found) >= 0;
+  });
+  var contents = wf$contents(variant, wf$production ? json.slice(1) : json.contents || []);
+  var value = { variant: variant[0] };
+
+  if (contents.length > 0) value.contents = contents;
+
+  return value;
+}
+
+// The variant which matches what was found on the wire.
+function wf$variant(variants, type, found, matches) {
+  var variant = variants.filter(matches)[0];
+
+  if (variant == null) throw new Error(\"Unknown \" + type + \" variant \" + found + \".\");
+
+  return variant;
+}
+
+function wf$contents(variant, contents) {
+  if (contents.length !== variant[2].length) {
+    throw new Error(\"Expected \" + variant[2].length + \" value(s) in \" + variant[0] + \".\");
+  }
+
+  return contents.map(function (content, index) { return wf$convert(content, variant[2][index]); });
+}
+";
+
+/// The Elm version of `wire_js`, as a `WfWire` module. Each converter is a
+/// `Decoder Value` which turns one JSON value into another: records and
+/// custom types get `encodeName` and `decodeName`, which take a converter for
+/// each of their type params, and each endpoint gets `encodeParams_name`,
+/// `decodeParams_name`, `encodeResponse_name` and `decodeResponse_name`.
+///
+/// In production, the endpoint converters use `WfPacking` (see
+/// `packing_elm`) if the schema packs anything.
+pub fn wire_elm(
+    schema: &Schema,
+    table: &MinificationTable,
+    mode: Mode,
+    former_names: Option<(&History, SchemaVersion)>,
+) -> Result<String, Problem> {
+    let mut wirer = Wirer::new(schema, table, mode, former_names);
+    let mut elm = format!(
+        "module WfWire exposing (..)\n\n-- Generated by wf. Do not edit.\n\nimport Json.Decode as Decode exposing (Decoder, Value)\nimport Json.Encode as Encode\n{}\n\n{{-| Whether this build sends and reads production JSON, rather than\ndevelopment JSON. Building with --production sets this.\n-}}\nproduction : Bool\nproduction =\n    {}\n{}",
+        if wirer.packs() {
+            "import WfPacking\n"
+        } else {
+            ""
+        },
+        if mode == Mode::Production {
+            "True"
+        } else {
+            "False"
+        },
+        WIRE_ELM_HELPERS
+    );
+
+    for record in schema.records.iter() {
+        let name = match record.name {
+            Some(ref name) => name.to_string(),
+            None => continue,
+        };
+        let fields = wirer.fields(record, &vec![]);
+        let type_names = wirer.type_names(name.as_str(), History::record);
+
+        for direction in [Direction::Encode, Direction::Decode] {
+            elm.push_str(&elm_declaration(
+                format!("{}{}", direction.as_str(), name).as_str(),
+                &record.params,
+                elm_record(Some(&type_names), &fields, direction).as_str(),
+            ));
+        }
+    }
+
+    for custom_type in schema.custom_types.iter() {
+        let name = custom_type.name.to_string();
+        let variants = wirer.variants(custom_type, &vec![]);
+        let type_names = wirer.type_names(name.as_str(), History::custom_type);
+
+        for direction in [Direction::Encode, Direction::Decode] {
+            elm.push_str(&elm_declaration(
+                format!("{}{}", direction.as_str(), name).as_str(),
+                &custom_type.params,
+                elm_variants(&type_names, &variants, direction).as_str(),
+            ));
+        }
+    }
+
+    for endpoint in schema.endpoints.iter() {
+        for (body, record) in [
+            ("Params", endpoint.params_record()),
+            ("Response", endpoint.response_record()),
+        ] {
+            let fields = wirer.fields(&record, &vec![]);
+
+            for direction in [Direction::Encode, Direction::Decode] {
+                let converted = elm_record(None, &fields, direction);
+                let converted = match (wirer.packs(), direction) {
+                    (false, _) => converted,
+                    (true, Direction::Encode) => format!(
+                        "Decode.andThen (convertWith WfPacking.pack{}_{}) ({})",
+                        body, endpoint.name, converted
+                    ),
+                    (true, Direction::Decode) => format!(
+                        "Decode.andThen (convertWith ({})) WfPacking.unpack{}_{}",
+                        converted, body, endpoint.name
+                    ),
+                };
+
+                elm.push_str(&elm_declaration(
+                    format!("{}{}_{}", direction.as_str(), body, endpoint.name).as_str(),
+                    &[],
+                    converted.as_str(),
+                ));
+            }
+        }
+    }
+
+    wirer.finish(elm)
+}
+
+const WIRE_ELM_HELPERS: &str = "
+
+{-| `formerNames` are the names development decoders still accept, since it was
+renamed within the grace period.
+-}
+type alias Field =
+    { name : String
+    , minifiedName : String
+    , formerNames : List String
+    , convert : Decoder Value
+    , required : Bool
+    }
+
+
+type alias Variant =
+    { name : String
+    , id : Int
+    , formerNames : List String
+    , contents : List (Decoder Value)
+    }
+
+
+convertWith : Decoder a -> Value -> Decoder a
+convertWith convert value =
+    case Decode.decodeValue convert value of
+        Ok converted ->
+            Decode.succeed converted
+
+        Err err ->
+            Decode.fail (Decode.errorToString err)
+
+
+nullable : Decoder Value -> Decoder Value
+nullable convert =
+    Decode.oneOf [ Decode.null Encode.null, convert ]
+
+
+isArray : Decoder Bool
+isArray =
+    Decode.oneOf [ Decode.map (always True) (Decode.list Decode.value), Decode.succeed False ]
+
+
+lookup : List ( String, Value ) -> String -> Maybe Value
+lookup entries name =
+    List.filter (\\( key, _ ) -> key == name) entries
+        |> List.head
+        |> Maybe.map Tuple.second
+
+
+has : List ( String, Value ) -> String -> Bool
+has entries name =
+    List.any (\\( key, _ ) -> key == name) entries
+
+
+wireName : Field -> String
+wireName field =
+    if production then
+        field.minifiedName
+
+    else
+        field.name
+
+
+{-| The names a decoder reads a field by, in order.
+-}
+acceptedNames : Field -> List String
+acceptedNames field =
+    if production then
+        [ field.minifiedName ]
+
+    else
+        field.name :: field.formerNames
+
+
+otherName : Field -> String
+otherName field =
+    if production then
+        field.name
+
+    else
+        field.minifiedName
+
+
+mixed : String -> Decoder a
+mixed typeName =
+    if production then
+        Decode.fail (\"This build reads production JSON, but got development JSON for \" ++ typeName ++ \".\")
+
+    else
+        Decode.fail (\"This build reads development JSON, but got production JSON for \" ++ typeName ++ \".\")
+
+
+{-| Development JSON may still be annotated with one of the type's former
+names, if it was renamed within the grace period.
+-}
+checkAnnotation : String -> List String -> List ( String, Value ) -> Decoder (List ( String, Value ))
+checkAnnotation typeName formerTypes entries =
+    case Maybe.map (Decode.decodeValue Decode.string) (lookup entries \"$type\") of
+        Just (Ok annotation) ->
+            if annotation == typeName || List.member annotation formerTypes then
+                Decode.succeed entries
+
+            else
+                Decode.fail (\"Expected a \" ++ typeName ++ \", but got a \" ++ annotation ++ \".\")
+
+        Just (Err _) ->
+            Decode.fail (\"Expected $type to be \" ++ typeName ++ \".\")
+
+        Nothing ->
+            mixed typeName
+
+
+{-| A required field which is only there under the name the other mode gives
+it means the JSON is in the wrong format.
+-}
+checkNames : String -> List Field -> List ( String, Value ) -> Decoder (List ( String, Value ))
+checkNames typeName fields entries =
+    let
+        misnamed field =
+            field.required && not (List.any (has entries) (acceptedNames field)) && has entries (otherName field)
+    in
+    if List.any misnamed fields then
+        mixed typeName
+
+    else
+        Decode.succeed entries
+
+
+convertElems : Decoder Value -> Decoder Value
+convertElems convert =
+    nullable (Decode.map (Encode.list identity) (Decode.list convert))
+
+
+{-| Dicts with String keys are objects, and other dicts are arrays of pairs.
+-}
+convertDict : Decoder Value -> Decoder Value -> Decoder Value
+convertDict convertKey convertVal =
+    let
+        pair =
+            Decode.map2 (\\key val -> Encode.list identity [ key, val ])
+                (Decode.index 0 convertKey)
+                (Decode.index 1 convertVal)
+    in
+    nullable
+        (Decode.oneOf
+            [ Decode.map (Encode.list identity) (Decode.list pair)
+            , Decode.map Encode.object (Decode.keyValuePairs convertVal)
+            ]
+        )
+
+
+{-| `from` gives the names to read each field by, in order.
+-}
+convertFields : (Field -> List String) -> (Field -> String) -> List Field -> List ( String, Value ) -> Decoder (List ( String, Value ))
+convertFields from to fields entries =
+    let
+        convertField field rest =
+            case List.head (List.filterMap (lookup entries) (from field)) of
+                Just value ->
+                    Decode.map2 (::) (Decode.map (Tuple.pair (to field)) (convertWith field.convert value)) rest
+
+                Nothing ->
+                    rest
+    in
+    List.foldr convertField (Decode.succeed []) fields
+
+
+{-| Inline records have no type name.
+-}
+recordToWire : Maybe String -> List Field -> Decoder Value
+recordToWire typeName fields =
+    let
+        annotation =
+            case ( production, typeName ) of
+                ( False, Just name ) ->
+                    [ ( \"$type\", Encode.string name ) ]
+
+                _ ->
+                    []
+    in
+    nullable
+        (Decode.keyValuePairs Decode.value
+            |> Decode.andThen (convertFields (\\field -> [ field.name ]) wireName fields)
+            |> Decode.map (\\entries -> Encode.object (annotation ++ entries))
+        )
+
+
+recordFromWire : Maybe String -> List String -> List Field -> Decoder Value
+recordFromWire typeName formerTypes fields =
+    let
+        check entries =
+            case ( production, typeName, lookup entries \"$type\" ) of
+                ( True, _, Just _ ) ->
+                    mixed (Maybe.withDefault \"a record\" typeName)
+
+                ( False, Just name, _ ) ->
+                    checkAnnotation name formerTypes entries
+
+                _ ->
+                    Decode.succeed entries
+
+        fromObject =
+            Decode.keyValuePairs Decode.value
+                |> Decode.andThen check
+                |> Decode.andThen (checkNames (Maybe.withDefault \"a record\" typeName) fields)
+                |> Decode.andThen (convertFields acceptedNames .name fields)
+                |> Decode.map Encode.object
+    in
+    nullable
+        (isArray
+            |> Decode.andThen
+                (\\array ->
+                    case ( array && not production, typeName ) of
+                        ( True, Just name ) ->
+                            mixed name
+
+                        _ ->
+                            fromObject
+                )
+        )
+
+
+variantParts : Decoder ( String, List Value )
+variantParts =
+    Decode.map2 Tuple.pair
+        (Decode.field \"variant\" Decode.string)
+        (Decode.oneOf [ Decode.field \"contents\" (Decode.list Decode.value), Decode.succeed [] ])
+
+
+contentsEntry : List Value -> List ( String, Value )
+contentsEntry contents =
+    if List.isEmpty contents then
+        []
+
+    else
+        [ ( \"contents\", Encode.list identity contents ) ]
+
+
+convertContents : Variant -> List Value -> Decoder (List Value)
+convertContents variant contents =
+    if List.length contents /= List.length variant.contents then
+        Decode.fail (\"Expected \" ++ String.fromInt (List.length variant.contents) ++ \" value(s) in \" ++ variant.name ++ \".\")
+
+    else
+        List.foldr (Decode.map2 (::)) (Decode.succeed []) (List.map2 convertWith variant.contents contents)
+
+
+findVariant : String -> (Variant -> Bool) -> String -> List Variant -> Decoder Variant
+findVariant typeName matches found variants =
+    case List.filter matches variants of
+        variant :: _ ->
+            Decode.succeed variant
+
+        [] ->
+            Decode.fail (\"Unknown \" ++ typeName ++ \" variant \" ++ found ++ \".\")
+
+
+variantToWire : String -> List Variant -> Decoder Value
+variantToWire typeName variants =
+    let
+        toWire variant contents =
+            if production then
+                Encode.list identity (Encode.int variant.id :: contents)
+
+            else
+                Encode.object (( \"$type\", Encode.string typeName ) :: ( \"variant\", Encode.string variant.name ) :: contentsEntry contents)
+
+        convert ( name, contents ) =
+            findVariant typeName (\\variant -> variant.name == name) name variants
+                |> Decode.andThen (\\variant -> Decode.map (toWire variant) (convertContents variant contents))
+    in
+    nullable (Decode.andThen convert variantParts)
+
+
+variantFromWire : String -> List String -> List Variant -> Decoder Value
+variantFromWire typeName formerTypes variants =
+    let
+        convert variant contents =
+            Decode.map (\\converted -> Encode.object (( \"variant\", Encode.string variant.name ) :: contentsEntry converted))
+                (convertContents variant contents)
+
+        fromArray elems =
+            case elems of
+                id :: contents ->
+                    case Decode.decodeValue Decode.int id of
+                        Ok variantId ->
+                            findVariant typeName (\\variant -> variant.id == variantId) (String.fromInt variantId) variants
+                                |> Decode.andThen (\\variant -> convert variant contents)
+
+                        Err _ ->
+                            Decode.fail (\"Expected a \" ++ typeName ++ \" variant, like [0].\")
+
+                [] ->
+                    Decode.fail (\"Expected a \" ++ typeName ++ \" variant, like [0].\")
+
+        fromObject ( name, contents ) =
+            findVariant typeName (\\variant -> variant.name == name || List.member name variant.formerNames) name variants
+                |> Decode.andThen (\\variant -> convert variant contents)
+    in
+    nullable
+        (isArray
+            |> Decode.andThen
+                (\\array ->
+                    if array /= production then
+                        mixed typeName
+
+                    else if production then
+                        Decode.andThen fromArray (Decode.list Decode.value)
+
+                    else
+                        Decode.keyValuePairs Decode.value
+                            |> Decode.andThen (checkAnnotation typeName formerTypes)
+                            |> Decode.andThen (\\_ -> Decode.andThen fromObject variantParts)
+                )
+        )
+";
+
+#[derive(Clone, Copy)]
+enum Direction {
+    /// From full-name JSON to the wire.
+    Encode,
+
+    /// From the wire to full-name JSON.
+    Decode,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Encode => "encode",
+            Direction::Decode => "decode",
+        }
+    }
+
+    /// The end of the names of the generated helpers which go this way.
+    fn wire_suffix(self) -> &'static str {
+        match self {
+            Direction::Encode => "ToWire",
+            Direction::Decode => "FromWire",
+        }
+    }
+}
+
+/// What converting a value between full-name JSON and the wire does, whichever
+/// language it's in.
+enum Wire {
+    /// Nothing to convert, e.g. a `String`.
+    Same,
+
+    /// Convert each element of an array or set.
+    Elems(Box<Wire>),
+
+    /// Convert each key and value of a dict.
+    Dict(Box<Wire>, Box<Wire>),
+
+    /// An inline record.
+    Fields(Vec<WireField>),
+
+    /// An inline custom type, with its names.
+    Variants(Vec<String>, Vec<WireVariant>),
+
+    /// A record or custom type declaration, with how to convert each of its
+    /// type args.
+    Named(String, Vec<Wire>),
+
+    /// One of the declaration's type params, whose converter is passed in.
+    Param(String),
+}
+
+struct WireField {
+    name: String,
+    minified_name: String,
+
+    /// Names development decoders still accept for it, since it was renamed
+    /// within the grace period.
+    former_names: Vec<String>,
+    conversion: Wire,
+
+    /// Whether decoding it would fail if it were missing, since it's
+    /// required and has no default.
+    required: bool,
+}
+
+struct WireVariant {
+    name: String,
+    id: u64,
+    former_names: Vec<String>,
+    contents: Vec<Wire>,
+}
+
+/// Works out the conversions for `wire_js` and `wire_elm`, keeping track of
+/// any fields the table has no minified name for.
+struct Wirer<'a> {
+    schema: &'a Schema,
+    table: &'a MinificationTable,
+    mode: Mode,
+    former_names: Option<(&'a History, SchemaVersion)>,
+    missing: Vec<String>,
+}
+
+impl<'a> Wirer<'a> {
+    fn new(
+        schema: &'a Schema,
+        table: &'a MinificationTable,
+        mode: Mode,
+        former_names: Option<(&'a History, SchemaVersion)>,
+    ) -> Wirer<'a> {
+        Wirer {
+            schema,
+            table,
+            mode,
+            former_names,
+            missing: vec![],
+        }
+    }
+
+    /// The ledger for a record or custom type, if development decoders
+    /// should accept former names from it. Production JSON has no names to
+    /// rename.
+    fn ledger<F>(&self, name: Option<String>, find: F) -> Option<&'a Ledger>
+    where
+        F: Fn(&'a History, &str) -> Option<&'a Ledger>,
+    {
+        match (self.mode, self.former_names, name) {
+            (Mode::Development, Some((history, _)), Some(name)) => find(history, name.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The names of an entry which decoders still accept, besides its
+    /// current one.
+    fn former_names(&self, ledger: Option<&Ledger>, name: &str) -> Vec<String> {
+        match (
+            self.former_names,
+            ledger.and_then(|ledger| ledger.entry(name)),
+        ) {
+            (Some((history, grace)), Some(entry)) => entry.accepted_names(history.version, grace)
+                [1..]
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// A record or custom type's name, followed by any former names which
+    /// decoders still accept in its `$type` annotation.
+    fn type_names<F>(&self, name: &str, find: F) -> Vec<String>
+    where
+        F: Fn(&'a History, &str) -> Option<&'a Ledger>,
+    {
+        match (self.former_names, self.ledger(Some(name.to_string()), find)) {
+            (Some((history, grace)), Some(ledger)) => ledger
+                .accepted_names(history.version, grace)
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            _ => vec![name.to_string()],
+        }
+    }
+
+    /// The generated code, unless some field had no minified name.
+    fn finish(self, code: String) -> Result<String, Problem> {
+        if self.missing.is_empty() {
+            Ok(code)
+        } else {
+            Err(Problem::MissingIds(self.missing))
+        }
+    }
+
+    /// Whether endpoint bodies need packing and unpacking too.
+    fn packs(&self) -> bool {
+        self.mode == Mode::Production && uses_packing(self.schema)
+    }
+
+    fn fields(&mut self, record: &RecordInfo, bindings: &Bindings) -> Vec<WireField> {
+        let ledger = self.ledger(record.ledger_name(), History::record);
+
+        record
+            .fields
+            .iter()
+            .map(|field| WireField {
+                name: field.name.clone(),
+                minified_name: minified_name(self.table, record, field, &mut self.missing),
+                former_names: self.former_names(ledger, field.name.as_str()),
+                conversion: self.convert(&field.tipe.current, bindings, &[]),
+                required: field.optionality == Optionality::Required && field.default.is_none(),
+            })
+            .collect()
+    }
+
+    fn variants(&mut self, custom_type: &CustomTypeInfo, bindings: &Bindings) -> Vec<WireVariant> {
+        let ledger = self.ledger(Some(custom_type.name.to_string()), History::custom_type);
+
+        custom_type
+            .variants
+            .iter()
+            .map(|variant| WireVariant {
+                name: variant.name.clone(),
+                id: variant.variant_id,
+                former_names: self.former_names(ledger, variant.name.as_str()),
+                contents: variant
+                    .contents
+                    .iter()
+                    .map(|tipe| self.convert(&tipe.current, bindings, &[]))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// How to convert a value of the given type. `bindings` are the params of
+    /// any generic aliases being expanded, whose args are filled in in place.
+    fn convert(
+        &mut self,
+        primitive: &TypePrimitive,
+        bindings: &Bindings,
+        expanding: &[&str],
+    ) -> Wire {
+        match primitive {
+            TypePrimitive::Array(elem) | TypePrimitive::Set(elem) => {
+                match self.convert(&elem.current, bindings, expanding) {
+                    Wire::Same => Wire::Same,
+                    elem => Wire::Elems(Box::new(elem)),
+                }
+            }
+            TypePrimitive::Dict(key, val) => match (
+                self.convert(&key.current, bindings, expanding),
+                self.convert(&val.current, bindings, expanding),
+            ) {
+                (Wire::Same, Wire::Same) => Wire::Same,
+                (key, val) => Wire::Dict(Box::new(key), Box::new(val)),
+            },
+            TypePrimitive::Record(info) => Wire::Fields(self.fields(info, bindings)),
+            TypePrimitive::Custom(info) => Wire::Variants(
+                self.type_names(info.name.to_string().as_str(), History::custom_type),
+                self.variants(info, bindings),
+            ),
+            TypePrimitive::Named(name, args) => {
+                let name = name.to_string();
+                let schema = self.schema;
+
+                if schema.record(name.as_str()).is_some()
+                    || schema.custom_type(name.as_str()).is_some()
+                {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.convert(&arg.current, bindings, expanding))
+                        .collect();
+
+                    return Wire::Named(name, args);
+                }
+
+                match schema.alias(name.as_str()) {
+                    Some(alias) if !expanding.contains(&name.as_str()) => {
+                        let bindings = bind(&alias.params, args, bindings);
+                        let mut expanding = expanding.to_vec();
+
+                        expanding.push(name.as_str());
+
+                        self.convert(&alias.tipe.current, &bindings, &expanding)
+                    }
+                    _ => Wire::Same,
+                }
+            }
+            TypePrimitive::Param(TypeParam::TypeParam(param)) => {
+                match bindings.iter().find(|(name, _)| name == param) {
+                    // `bind` already filled in the params from further out.
+                    Some((_, tipe)) => self.convert(&tipe.current, &vec![], expanding),
+                    None => Wire::Param(param.clone()),
+                }
+            }
+            _ => Wire::Same,
+        }
+    }
+}
+
+/// The params of a JS converter for a declaration with the given type params.
+fn js_params(params: &[TypeParam]) -> String {
+    let mut names = vec![String::from("value")];
+
+    names.extend(
+        params
+            .iter()
+            .map(|TypeParam::TypeParam(param)| format!("param${}", param)),
+    );
+
+    names.join(", ")
+}
+
+/// A JS expression which converts a record to or from the wire. `type_names`
+/// are the record's name followed by its former names, if it has a name.
+fn js_record(
+    type_names: Option<&[String]>,
+    fields: &[WireField],
+    value: &str,
+    direction: Direction,
+) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            format!(
+                "[\"{}\", \"{}\", {}, {}{}]",
+                field.name,
+                field.minified_name,
+                js_wire(&field.conversion, direction),
+                field.required,
+                js_former_names(&field.former_names, direction)
+            )
+        })
+        .collect();
+
+    format!(
+        "wf$record{}({}, {}, [{}]{})",
+        direction.wire_suffix(),
+        value,
+        type_names.map_or_else(|| String::from("null"), |names| format!("\"{}\"", names[0])),
+        fields.join(", "),
+        type_names.map_or_else(String::new, |names| js_former_names(&names[1..], direction))
+    )
+}
+
+/// The optional last argument or array element with the former names a
+/// decoder accepts. Encoders always use the current names.
+fn js_former_names(former_names: &[String], direction: Direction) -> String {
+    match direction {
+        Direction::Decode if !former_names.is_empty() => {
+            let names: Vec<String> = former_names
+                .iter()
+                .map(|name| format!("\"{}\"", name))
+                .collect();
+
+            format!(", [{}]", names.join(", "))
+        }
+        _ => String::new(),
+    }
+}
+
+/// A JS expression which converts a custom type value to or from the wire.
+fn js_variants(
+    type_names: &[String],
+    variants: &[WireVariant],
+    value: &str,
+    direction: Direction,
+) -> String {
+    let variants: Vec<String> = variants
+        .iter()
+        .map(|variant| {
+            let contents: Vec<String> = variant
+                .contents
+                .iter()
+                .map(|content| js_wire(content, direction))
+                .collect();
+
+            format!(
+                "[\"{}\", {}, [{}]{}]",
+                variant.name,
+                variant.id,
+                contents.join(", "),
+                js_former_names(&variant.former_names, direction)
+            )
+        })
+        .collect();
+
+    format!(
+        "wf$variant{}({}, \"{}\", [{}]{})",
+        direction.wire_suffix(),
+        value,
+        type_names[0],
+        variants.join(", "),
+        js_former_names(&type_names[1..], direction)
+    )
+}
+
+/// A JS function which converts a value, or `null` if there's nothing to do.
+fn js_wire(wire: &Wire, direction: Direction) -> String {
+    match wire {
+        Wire::Same => String::from("null"),
+        Wire::Elems(elem) => format!(
+            "function (value) {{ return wf$convertElems(value, {}); }}",
+            js_wire(elem, direction)
+        ),
+        Wire::Dict(key, val) => format!(
+            "function (value) {{ return wf$convertDict(value, {}, {}); }}",
+            js_wire(key, direction),
+            js_wire(val, direction)
+        ),
+        Wire::Fields(fields) => format!(
+            "function (value) {{ return {}; }}",
+            js_record(None, fields, "value", direction)
+        ),
+        Wire::Variants(names, variants) => format!(
+            "function (value) {{ return {}; }}",
+            js_variants(names, variants, "value", direction)
+        ),
+        Wire::Named(name, args) if args.is_empty() => {
+            format!("wf${}${}", direction.as_str(), name)
+        }
+        Wire::Named(name, args) => {
+            let args: Vec<String> = args.iter().map(|arg| js_wire(arg, direction)).collect();
+
+            format!(
+                "function (value) {{ return wf${}${}(value, {}); }}",
+                direction.as_str(),
+                name,
+                args.join(", ")
+            )
+        }
+        Wire::Param(param) => format!("param${}", param),
+    }
+}
+
+/// A top-level Elm converter, taking a converter for each type param.
+fn elm_declaration(name: &str, params: &[TypeParam], body: &str) -> String {
+    let mut annotation: Vec<&str> = params.iter().map(|_| "Decoder Value").collect();
+    let mut names = vec![name.to_string()];
+
+    annotation.push("Decoder Value");
+    names.extend(
+        params
+            .iter()
+            .map(|TypeParam::TypeParam(param)| format!("param_{}", param)),
+    );
+
+    format!(
+        "\n\n{} : {}\n{} =\n    {}\n",
+        name,
+        annotation.join(" -> "),
+        names.join(" "),
+        body
+    )
+}
+
+/// An Elm `Decoder Value` which converts a record to or from the wire.
+/// `type_names` are the record's name followed by its former names, if it has
+/// a name.
+fn elm_record(type_names: Option<&[String]>, fields: &[WireField], direction: Direction) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            format!(
+                "Field \"{}\" \"{}\" {} {} {}",
+                field.name,
+                field.minified_name,
+                elm_strings(&field.former_names),
+                elm_arg(elm_wire(&field.conversion, direction)),
+                if field.required { "True" } else { "False" }
+            )
+        })
+        .collect();
+
+    format!(
+        "record{} {}{} {}",
+        direction.wire_suffix(),
+        type_names.map_or_else(
+            || String::from("Nothing"),
+            |names| format!("(Just \"{}\")", names[0])
+        ),
+        elm_former_types(type_names.map_or(&[], |names| &names[1..]), direction),
+        elm_list(&fields)
+    )
+}
+
+/// An Elm `Decoder Value` which converts a custom type value to or from the
+/// wire.
+fn elm_variants(type_names: &[String], variants: &[WireVariant], direction: Direction) -> String {
+    let variants: Vec<String> = variants
+        .iter()
+        .map(|variant| {
+            let contents: Vec<String> = variant
+                .contents
+                .iter()
+                .map(|content| elm_wire(content, direction))
+                .collect();
+
+            format!(
+                "Variant \"{}\" {} {} {}",
+                variant.name,
+                variant.id,
+                elm_strings(&variant.former_names),
+                elm_list(&contents)
+            )
+        })
+        .collect();
+
+    format!(
+        "variant{} \"{}\"{} {}",
+        direction.wire_suffix(),
+        type_names[0],
+        elm_former_types(&type_names[1..], direction),
+        elm_list(&variants)
+    )
+}
+
+/// The former type names a decoder accepts in `$type` annotations, as its
+/// second argument. Encoders always annotate with the current name.
+fn elm_former_types(former_names: &[String], direction: Direction) -> String {
+    match direction {
+        Direction::Encode => String::new(),
+        Direction::Decode => format!(" {}", elm_strings(former_names)),
+    }
+}
+
+/// An Elm `Decoder Value` which converts a value. Declarations are lazy,
+/// since they may contain themselves.
+fn elm_wire(wire: &Wire, direction: Direction) -> String {
+    match wire {
+        Wire::Same => String::from("Decode.value"),
+        Wire::Elems(elem) => format!("convertElems {}", elm_arg(elm_wire(elem, direction))),
+        Wire::Dict(key, val) => format!(
+            "convertDict {} {}",
+            elm_arg(elm_wire(key, direction)),
+            elm_arg(elm_wire(val, direction))
+        ),
+        Wire::Fields(fields) => elm_record(None, fields, direction),
+        Wire::Variants(names, variants) => elm_variants(names, variants, direction),
+        Wire::Named(name, args) => {
+            let mut call = vec![format!("{}{}", direction.as_str(), name)];
+
+            call.extend(args.iter().map(|arg| elm_arg(elm_wire(arg, direction))));
+
+            format!("Decode.lazy (\\_ -> {})", call.join(" "))
+        }
+        Wire::Param(param) => format!("param_{}", param),
+    }
+}
+
+/// Parenthesize an Elm expression, if it needs it to be an argument.
+fn elm_arg(expression: String) -> String {
+    if expression.contains(' ') {
+        format!("({})", expression)
+    } else {
+        expression
+    }
+}
+
+fn elm_strings(strings: &[String]) -> String {
+    let strings: Vec<String> = strings.iter().map(|s| format!("\"{}\"", s)).collect();
+
+    elm_list(&strings)
+}
+
+fn elm_list(elems: &[String]) -> String {
+    if elems.is_empty() {
+        String::from("[]")
+    } else {
+        format!("[ {} ]", elems.join(", "))
+    }
+}
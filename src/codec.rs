@@ -0,0 +1,1136 @@
+use code_gen::{MinificationTable, Mode};
+use history::{History, Ledger, SchemaVersion};
+use json;
+use json::Value;
+use std::convert::TryFrom;
+use std::fmt;
+use types::{
+    CustomTypeInfo, Endpoint, Field, Optionality, RecordInfo, Schema, Type, TypeName, TypeParam,
+    TypePrimitive,
+};
+
+/// Converting JSON values between the names the schema gives fields, and the
+/// minified names production uses on the wire. For example, given
+///
+/// ```text
+/// struct User
+///     * userId : Int
+///     ~ email : String [ default "" ]
+/// ```
+///
+/// and a history which gave `userId` the ID 0 and `email` the ID 1, this
+/// decodes `{"a":5}` to `{"userId":5,"email":""}`, and encodes it back.
+///
+/// Every value is checked against its type along the way, so a decoded value
+/// is safe to hand to application code. Problems say exactly where they are,
+/// e.g. `user.profile.email`.
+///
+/// Minified names come from the `MinificationTable` (see
+/// `code_gen::productionize`), which covers the fields of inline records and
+/// endpoint bodies too. Packed slots are field IDs from the schema, so the
+/// history's IDs need to have been pinned with `History::pin_ids` first.
+///
+/// Custom type values go over the wire as `[id, ...contents]`, and become
+/// `{"variant":"Email","contents":[...]}` in full-name JSON, with `contents`
+/// left out if the variant has none. Dicts with `String` keys are objects,
+/// and other dicts are arrays of `[key, value]` pairs.
+///
+/// Records marked `[ packed ]` go over the wire as tuples when they're
+/// elements of an array or set, with each field at the index of its ID, e.g.
+/// `[5,""]` instead of `{"a":5,"b":""}`. Missing optional fields are `null`,
+/// and trailing `null`s are left off. Endpoints marked `[ packed ]` do this
+/// for every record in an array in their params and response. Packing
+/// doesn't reach inside dicts, custom type contents, or type params.
+///
+/// Development builds send something easier to read instead: full names, with
+/// each record and custom type value annotated with its type, e.g.
+/// `{"$type":"User","userId":5}`. Which of the two goes over the wire depends
+/// only on the `Mode` the codec was made with, and decoding refuses JSON in
+/// the other format, so a client and server built in different modes fail
+/// loudly instead of quietly misreading each other.
+pub struct Codec<'a> {
+    schema: &'a Schema,
+    table: &'a MinificationTable,
+    mode: Mode,
+
+    /// The history and rename grace, if development JSON should be accepted
+    /// with the former names of renamed fields and variants.
+    history: Option<(&'a History, SchemaVersion)>,
+}
+
+/// Which names a JSON value uses for its fields and variants.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Names {
+    Full,
+
+    /// Minified names, with every record as an object.
+    Minified,
+
+    /// What production actually sends: minified names, with records packed
+    /// into tuples wherever the schema asks for it.
+    Packed,
+
+    /// What development builds send: full names, with `$type` annotations.
+    Development,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// Where the problem is, e.g. "user.profile.email" or "users[3].name"
+    pub path: String,
+    pub kind: ProblemKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProblemKind {
+    /// A required field with no default was missing (or null).
+    Missing,
+
+    /// The value was the wrong kind of thing, e.g. a string where an `Int32`
+    /// should be, or an integer too big for one.
+    Expected {
+        expected: String,
+        found: &'static str,
+    },
+
+    /// A field the schema doesn't have. Minified JSON may have fields from a
+    /// newer schema, so this only applies to full-name JSON.
+    UnknownField(String),
+    UnknownVariant(String),
+
+    /// A type the schema doesn't declare.
+    UnknownType(String),
+
+    /// JSON in the other mode's format, e.g. development JSON sent to a
+    /// production build.
+    MixedFormats {
+        expected: Mode,
+    },
+
+    /// The minification table has no name for a field, so it wasn't made
+    /// from the codec's schema.
+    NotMinified,
+
+    /// Only an Array or Set can be streamed, and only through fields of
+    /// records without type params.
+    NotStreamable,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(formatter, "{}: ", self.path)?;
+        }
+
+        match self.kind {
+            ProblemKind::Missing => write!(formatter, "this required field is missing"),
+            ProblemKind::Expected {
+                ref expected,
+                found,
+            } => write!(formatter, "expected {}, but found {}", expected, found),
+            ProblemKind::UnknownField(ref name) => write!(formatter, "unknown field {}", name),
+            ProblemKind::UnknownVariant(ref name) => {
+                write!(formatter, "unknown variant {}", name)
+            }
+            ProblemKind::UnknownType(ref name) => write!(formatter, "unknown type {}", name),
+            ProblemKind::NotStreamable => write!(
+                formatter,
+                "only Arrays and Sets in the fields of records can be decoded one element at a time"
+            ),
+            ProblemKind::NotMinified => write!(
+                formatter,
+                "this field has no minified name. Was the minification table made from this schema?"
+            ),
+            ProblemKind::MixedFormats { expected } => {
+                let (expected, found) = match expected {
+                    Mode::Development => ("development", "production"),
+                    Mode::Production => ("production", "development"),
+                };
+
+                write!(
+                    formatter,
+                    "expected {} JSON, but this is {} JSON. Were the client and server built in different modes?",
+                    expected, found
+                )
+            }
+        }
+    }
+}
+
+/// The key development JSON uses to annotate records and custom type values
+/// with their type.
+pub const TYPE_KEY: &str = "$type";
+
+/// The key used for the variant's name in full-name JSON
+pub const VARIANT_KEY: &str = "variant";
+
+/// The key used for the variant's contents in full-name JSON
+pub const CONTENTS_KEY: &str = "contents";
+
+/// A reference to one of the schema's declarations, e.g. `User`
+pub fn named(name: &str) -> Type {
+    Type::new(TypePrimitive::Named(
+        TypeName::TypeName(name.to_string()),
+        vec![],
+    ))
+}
+
+/// An endpoint's params, as an inline record.
+pub fn params(endpoint: &Endpoint) -> Type {
+    Type::new(TypePrimitive::Record(endpoint.params_record()))
+}
+
+/// An endpoint's response, as an inline record.
+pub fn response(endpoint: &Endpoint) -> Type {
+    Type::new(TypePrimitive::Record(endpoint.response_record()))
+}
+
+/// An Array or Set in an endpoint's response, whose elements can be decoded
+/// one at a time (see `json_stream::read_response_field_from`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseArray {
+    /// The full names of the fields leading to it, e.g. `["page", "items"]`
+    pub path: Vec<String>,
+
+    /// The same fields' names on the wire.
+    pub wire_path: Vec<String>,
+
+    /// Whether each of the fields is optional, so that its being missing (or
+    /// null) means there are no elements.
+    pub optional: Vec<bool>,
+    pub elem: Type,
+
+    /// Whether the endpoint is marked `[ packed ]`.
+    pub pack_all: bool,
+}
+
+/// Where we are in the value, built up on the stack as we go, and only turned
+/// into a string if there's a problem.
+#[derive(Clone, Copy)]
+enum Path<'a> {
+    Root,
+    Field(&'a Path<'a>, &'a str),
+    Index(&'a Path<'a>, usize),
+    Key(&'a Path<'a>, &'a str),
+}
+
+impl<'a> fmt::Display for Path<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Path::Root => Ok(()),
+            Path::Field(Path::Root, name) => write!(formatter, "{}", name),
+            Path::Field(parent, name) => write!(formatter, "{}.{}", parent, name),
+            Path::Index(parent, index) => write!(formatter, "{}[{}]", parent, index),
+            Path::Key(parent, key) => write!(formatter, "{}[{:?}]", parent, key),
+        }
+    }
+}
+
+/// Type params, along with the types they stand for.
+pub type Bindings = Vec<(String, Type)>;
+
+#[derive(Clone, Copy)]
+struct Context {
+    from: Names,
+    to: Names,
+    packing: Packing,
+}
+
+impl Context {
+    fn nested(self, in_array: bool) -> Context {
+        Context {
+            packing: self.packing.nested(in_array),
+            ..self
+        }
+    }
+
+    fn unpackable(self) -> Context {
+        Context {
+            packing: self.packing.unpackable(),
+            ..self
+        }
+    }
+}
+
+/// Where a value is, which decides whether its records are packed. The
+/// `json_schema` module describes packed records by the same rules.
+#[derive(Clone, Copy)]
+pub(crate) struct Packing {
+    /// Whether the value is an element of an array (or set).
+    pub(crate) in_array: bool,
+
+    /// Whether we're in the body of an endpoint marked `[ packed ]`.
+    pub(crate) pack_all: bool,
+
+    /// Packing doesn't reach inside dicts, custom type contents, or type
+    /// params, which keeps the generated code simple.
+    pub(crate) can_pack: bool,
+}
+
+impl Packing {
+    /// A value which isn't inside anything.
+    pub(crate) const TOP: Packing = Packing {
+        in_array: false,
+        pack_all: false,
+        can_pack: true,
+    };
+
+    pub(crate) fn nested(self, in_array: bool) -> Packing {
+        Packing { in_array, ..self }
+    }
+
+    pub(crate) fn unpackable(self) -> Packing {
+        Packing {
+            in_array: false,
+            can_pack: false,
+            ..self
+        }
+    }
+
+    /// Whether a record here is packed into a tuple, in production.
+    pub(crate) fn is_packed(self, info: &RecordInfo) -> bool {
+        info.name.is_some() && self.in_array && self.can_pack && (info.packed || self.pack_all)
+    }
+
+    /// Where the record's fields are.
+    pub(crate) fn fields(self, info: &RecordInfo) -> Packing {
+        Packing {
+            in_array: false,
+            // Endpoint bodies are inline records, which are marked packed if
+            // the endpoint is.
+            pack_all: self.pack_all || (info.name.is_none() && info.packed),
+            ..self
+        }
+    }
+}
+
+impl<'a> Codec<'a> {
+    /// A codec for production builds.
+    pub fn new(schema: &'a Schema, table: &'a MinificationTable) -> Codec<'a> {
+        Codec::with_mode(schema, table, Mode::Production)
+    }
+
+    pub fn with_mode(schema: &'a Schema, table: &'a MinificationTable, mode: Mode) -> Codec<'a> {
+        Codec {
+            schema,
+            table,
+            mode,
+            history: None,
+        }
+    }
+
+    /// Accept the former names the history has for renamed fields, variants
+    /// and records, for `grace` versions after the rename (see
+    /// `FormerName::is_accepted`). Production JSON has no names to rename, so
+    /// this only matters in development.
+    pub fn accepting_former_names(self, history: &'a History, grace: SchemaVersion) -> Codec<'a> {
+        Codec {
+            history: Some((history, grace)),
+            ..self
+        }
+    }
+
+    /// The names this codec's mode uses on the wire.
+    pub fn wire_names(&self) -> Names {
+        match self.mode {
+            Mode::Development => Names::Development,
+            Mode::Production => Names::Packed,
+        }
+    }
+
+    /// JSON from the wire to full-name JSON, applying defaults.
+    pub fn decode(&self, tipe: &Type, wire: &Value) -> Result<Value, Problem> {
+        self.convert(tipe, wire, self.wire_names(), Names::Full)
+    }
+
+    /// One element of an array from the wire to full-name JSON, for when the
+    /// elements are decoded one at a time (see `json_stream`). `index` is
+    /// where the element is in the array, for reporting problems.
+    pub fn decode_element(
+        &self,
+        elem: &Type,
+        index: usize,
+        wire: &Value,
+    ) -> Result<Value, Problem> {
+        self.value(
+            elem,
+            wire,
+            &Path::Index(&Path::Root, index),
+            &vec![],
+            Context {
+                from: self.wire_names(),
+                to: Names::Full,
+                packing: Packing::TOP.nested(true),
+            },
+        )
+    }
+
+    /// Find the Array or Set at the given path of fields in the endpoint's
+    /// response, e.g. "users" or "page.items". Every field on the way there
+    /// must be a record without type params.
+    pub fn response_array(
+        &self,
+        endpoint: &Endpoint,
+        field_path: &str,
+    ) -> Result<ResponseArray, Problem> {
+        let names: Vec<&str> = field_path.split('.').collect();
+        let mut info = endpoint.response_record();
+        let mut path: Vec<String> = vec![];
+        let mut wire_path: Vec<String> = vec![];
+        let mut optional: Vec<bool> = vec![];
+
+        for (step, name) in names.iter().enumerate() {
+            let here = Problem {
+                path: path.join("."),
+                kind: ProblemKind::UnknownField(name.to_string()),
+            };
+            let field = info
+                .fields
+                .iter()
+                .find(|field| field.name == *name)
+                .ok_or(here)?
+                .clone();
+
+            path.push(field.name.clone());
+            wire_path.push(
+                self.field_name(&info, &field, self.wire_names(), &Path::Root)
+                    .map_err(|problem| Problem {
+                        path: path.join("."),
+                        ..problem
+                    })?
+                    .to_string(),
+            );
+
+            optional.push(field.optionality == Optionality::Optional);
+
+            let not_streamable = || Problem {
+                path: path.join("."),
+                kind: ProblemKind::NotStreamable,
+            };
+
+            match self.schema.expand_aliases(&field.tipe).current {
+                TypePrimitive::Array(elem) | TypePrimitive::Set(elem)
+                    if step == names.len() - 1 =>
+                {
+                    return Ok(ResponseArray {
+                        path,
+                        wire_path,
+                        optional,
+                        elem: *elem,
+                        pack_all: endpoint.packed,
+                    });
+                }
+                _ if step == names.len() - 1 => return Err(not_streamable()),
+                TypePrimitive::Record(inner) => info = inner,
+                TypePrimitive::Named(ref name, ref args) if args.is_empty() => {
+                    info = self
+                        .schema
+                        .record(name.to_string().as_str())
+                        .filter(|record| record.params.is_empty())
+                        .ok_or_else(not_streamable)?
+                        .clone();
+                }
+                _ => return Err(not_streamable()),
+            }
+        }
+
+        // Splitting always yields at least one name.
+        unreachable!()
+    }
+
+    /// Like `decode_element`, but for an element of an Array or Set in an
+    /// endpoint's response, so problems are reported with the path to it,
+    /// e.g. "users[3].name", and records are packed if the endpoint is.
+    pub fn decode_response_element(
+        &self,
+        array: &ResponseArray,
+        index: usize,
+        wire: &Value,
+    ) -> Result<Value, Problem> {
+        within(&Path::Root, &array.path, &mut |path| {
+            self.value(
+                &array.elem,
+                wire,
+                &Path::Index(path, index),
+                &vec![],
+                Context {
+                    from: self.wire_names(),
+                    to: Names::Full,
+                    packing: Packing {
+                        in_array: true,
+                        pack_all: array.pack_all,
+                        can_pack: true,
+                    },
+                },
+            )
+        })
+    }
+
+    /// Full-name JSON to JSON for the wire.
+    pub fn encode(&self, tipe: &Type, full: &Value) -> Result<Value, Problem> {
+        self.convert(tipe, full, Names::Full, self.wire_names())
+    }
+
+    /// Check a value against its type, converting between the given names.
+    /// Defaults are applied when converting to full names, since that's what
+    /// application code sees.
+    pub fn convert(
+        &self,
+        tipe: &Type,
+        value: &Value,
+        from: Names,
+        to: Names,
+    ) -> Result<Value, Problem> {
+        self.value(
+            tipe,
+            value,
+            &Path::Root,
+            &vec![],
+            Context {
+                from,
+                to,
+                packing: Packing::TOP,
+            },
+        )
+    }
+
+    fn value(
+        &self,
+        tipe: &Type,
+        value: &Value,
+        path: &Path,
+        bindings: &Bindings,
+        context: Context,
+    ) -> Result<Value, Problem> {
+        let result = self.primitive(&tipe.current, value, path, bindings, context);
+
+        // Old senders may still be using one of the field's former types.
+        if result.is_err() {
+            for former in tipe.formerly.iter() {
+                if let Ok(converted) = self.primitive(former, value, path, bindings, context) {
+                    return Ok(converted);
+                }
+            }
+        }
+
+        result
+    }
+
+    fn primitive(
+        &self,
+        primitive: &TypePrimitive,
+        value: &Value,
+        path: &Path,
+        bindings: &Bindings,
+        context: Context,
+    ) -> Result<Value, Problem> {
+        match (primitive, value) {
+            (TypePrimitive::Bool, Value::Bool(_)) | (TypePrimitive::String, Value::String(_)) => {
+                Ok(value.clone())
+            }
+            (TypePrimitive::Int, Value::Int(_)) | (TypePrimitive::Int64, Value::Int(_)) => {
+                Ok(value.clone())
+            }
+            (TypePrimitive::Int8, Value::Int(int)) if i8::try_from(*int).is_ok() => {
+                Ok(value.clone())
+            }
+            (TypePrimitive::Int16, Value::Int(int)) if i16::try_from(*int).is_ok() => {
+                Ok(value.clone())
+            }
+            (TypePrimitive::Int32, Value::Int(int)) if i32::try_from(*int).is_ok() => {
+                Ok(value.clone())
+            }
+            (TypePrimitive::Float, Value::Int(_))
+            | (TypePrimitive::Float, Value::Float(_))
+            | (TypePrimitive::Float32, Value::Int(_))
+            | (TypePrimitive::Float32, Value::Float(_))
+            | (TypePrimitive::Float64, Value::Int(_))
+            | (TypePrimitive::Float64, Value::Float(_)) => Ok(value.clone()),
+            (TypePrimitive::Array(elem), Value::Array(elems))
+            | (TypePrimitive::Set(elem), Value::Array(elems)) => elems
+                .iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    self.value(
+                        elem,
+                        value,
+                        &Path::Index(path, index),
+                        bindings,
+                        context.nested(true),
+                    )
+                })
+                .collect::<Result<Vec<Value>, Problem>>()
+                .map(Value::Array),
+            (TypePrimitive::Dict(key, val), _) => {
+                self.dict(key, val, value, path, bindings, context.unpackable())
+            }
+            (TypePrimitive::Record(info), _) => self.record(info, value, path, bindings, context),
+            (TypePrimitive::Custom(info), _) => {
+                self.custom_type(info, value, path, bindings, context)
+            }
+            (TypePrimitive::Named(name, args), _) => {
+                self.named(name, args, value, path, bindings, context)
+            }
+            (TypePrimitive::Param(TypeParam::TypeParam(param)), _) => {
+                match bindings.iter().find(|(name, _)| name == param) {
+                    Some((_, tipe)) => {
+                        self.value(tipe, value, path, bindings, context.unpackable())
+                    }
+                    None => Err(problem(path, ProblemKind::UnknownType(param.clone()))),
+                }
+            }
+            (primitive, value) => Err(expected(path, primitive, value)),
+        }
+    }
+
+    fn named(
+        &self,
+        name: &TypeName,
+        args: &[Type],
+        value: &Value,
+        path: &Path,
+        bindings: &Bindings,
+        context: Context,
+    ) -> Result<Value, Problem> {
+        let name_string = name.to_string();
+        let name = name_string.as_str();
+
+        if let Some(record) = self.schema.record(name) {
+            let bindings = bind(&record.params, args, bindings);
+
+            self.record(record, value, path, &bindings, context)
+        } else if let Some(custom_type) = self.schema.custom_type(name) {
+            let bindings = bind(&custom_type.params, args, bindings);
+
+            self.custom_type(custom_type, value, path, &bindings, context)
+        } else if let Some(alias) = self.schema.alias(name) {
+            let bindings = bind(&alias.params, args, bindings);
+            let context = if alias.params.is_empty() {
+                context
+            } else {
+                context.unpackable()
+            };
+
+            self.value(&alias.tipe, value, path, &bindings, context)
+        } else {
+            Err(problem(path, ProblemKind::UnknownType(name_string)))
+        }
+    }
+
+    fn dict(
+        &self,
+        key: &Type,
+        val: &Type,
+        value: &Value,
+        path: &Path,
+        bindings: &Bindings,
+        context: Context,
+    ) -> Result<Value, Problem> {
+        match value {
+            Value::Object(entries) if self.is_string(key, bindings) => entries
+                .iter()
+                .map(|(entry_key, entry_value)| {
+                    self.value(
+                        val,
+                        entry_value,
+                        &Path::Key(path, entry_key),
+                        bindings,
+                        context,
+                    )
+                    .map(|converted| (entry_key.clone(), converted))
+                })
+                .collect::<Result<Vec<(String, Value)>, Problem>>()
+                .map(Value::Object),
+            Value::Array(pairs) if !self.is_string(key, bindings) => pairs
+                .iter()
+                .enumerate()
+                .map(|(index, pair)| {
+                    let path = Path::Index(path, index);
+
+                    match pair {
+                        Value::Array(pair) if pair.len() == 2 => Ok(Value::Array(vec![
+                            self.value(key, &pair[0], &Path::Index(&path, 0), bindings, context)?,
+                            self.value(val, &pair[1], &Path::Index(&path, 1), bindings, context)?,
+                        ])),
+                        _ => Err(problem(
+                            &path,
+                            ProblemKind::Expected {
+                                expected: String::from("a [key, value] pair"),
+                                found: pair.kind(),
+                            },
+                        )),
+                    }
+                })
+                .collect::<Result<Vec<Value>, Problem>>()
+                .map(Value::Array),
+            _ => Err(problem(
+                path,
+                ProblemKind::Expected {
+                    expected: if self.is_string(key, bindings) {
+                        String::from("an object")
+                    } else {
+                        String::from("an array of [key, value] pairs")
+                    },
+                    found: value.kind(),
+                },
+            )),
+        }
+    }
+
+    /// Whether the type is `String`, or an alias for it.
+    fn is_string(&self, tipe: &Type, bindings: &Bindings) -> bool {
+        match tipe.current {
+            TypePrimitive::String => true,
+            TypePrimitive::Param(TypeParam::TypeParam(ref param)) => bindings
+                .iter()
+                .find(|(name, _)| name == param)
+                .is_some_and(|(_, tipe)| self.is_string(tipe, bindings)),
+            _ => matches!(
+                self.schema.expand_aliases(tipe).current,
+                TypePrimitive::String
+            ),
+        }
+    }
+
+    fn record(
+        &self,
+        info: &RecordInfo,
+        value: &Value,
+        path: &Path,
+        bindings: &Bindings,
+        context: Context,
+    ) -> Result<Value, Problem> {
+        let type_name = info.name.as_ref().map(|name| name.to_string());
+        let ledger = type_name.as_ref().and_then(|name| {
+            self.history
+                .and_then(|(history, _)| history.record(name.as_str()))
+        });
+        let is_packed = context.packing.is_packed(info);
+        let reads_tuple = is_packed && context.from == Names::Packed;
+        let writes_tuple = is_packed && context.to == Names::Packed;
+        let field_context = Context {
+            packing: context.packing.fields(info),
+            ..context
+        };
+        let (entries, slots): (&[(String, Value)], &[Value]) = match value {
+            Value::Object(entries) if !reads_tuple => (entries, &[]),
+            Value::Array(slots) if reads_tuple => (&[], slots),
+            _ => {
+                return Err(problem(
+                    path,
+                    ProblemKind::Expected {
+                        expected: if reads_tuple {
+                            String::from("a packed record, like [1,\"a\"]")
+                        } else {
+                            String::from("an object")
+                        },
+                        found: value.kind(),
+                    },
+                ))
+            }
+        };
+        let mut converted: Vec<(String, Value)> = vec![];
+        let mut tuple: Vec<Value> = vec![];
+
+        self.check_annotation(type_name.as_ref(), ledger, value, path, context.from)?;
+
+        if context.to == Names::Development {
+            if let Some(ref type_name) = type_name {
+                converted.push((String::from(TYPE_KEY), Value::String(type_name.clone())));
+            }
+        }
+
+        for field in info.fields.iter() {
+            let field_path = Path::Field(path, field.name.as_str());
+            let field_value = if reads_tuple {
+                slots.get(field.field_id as usize)
+            } else if context.from == Names::Development {
+                self.accepted_names(ledger, field.name.as_str())
+                    .into_iter()
+                    .find_map(|name| value.get(name))
+            } else {
+                value.get(self.field_name(info, field, context.from, &field_path)?)
+            };
+            let field_value = match field_value {
+                Some(Value::Null) | None => {
+                    let default = field
+                        .default
+                        .as_ref()
+                        .filter(|_| context.to == Names::Full)
+                        .and_then(|default| json::parse(default).ok());
+
+                    match (default, &field.optionality) {
+                        (Some(default), _) => default,
+                        (None, Optionality::Required) => {
+                            let kind =
+                                self.missing(info, field, value, context.from, &field_path)?;
+
+                            return Err(problem(&field_path, kind));
+                        }
+                        (None, Optionality::Optional) => continue,
+                    }
+                }
+                Some(field_value) => self.value(
+                    &field.tipe,
+                    field_value,
+                    &field_path,
+                    bindings,
+                    field_context,
+                )?,
+            };
+
+            if writes_tuple {
+                let slot = field.field_id as usize;
+
+                if tuple.len() <= slot {
+                    tuple.resize(slot + 1, Value::Null);
+                }
+
+                tuple[slot] = field_value;
+            } else {
+                let name = self.field_name(info, field, context.to, &field_path)?;
+
+                converted.push((name.to_string(), field_value));
+            }
+        }
+
+        // Minified JSON may come from someone running a newer schema, so
+        // fields we don't know about are fine. Full-name JSON comes from
+        // application code, where an unknown field is probably a typo.
+        if context.from == Names::Full {
+            for (key, _) in entries.iter() {
+                if !info.fields.iter().any(|field| field.name == *key) {
+                    return Err(problem(path, ProblemKind::UnknownField(key.clone())));
+                }
+            }
+        }
+
+        if writes_tuple {
+            Ok(Value::Array(tuple))
+        } else {
+            Ok(Value::Object(converted))
+        }
+    }
+
+    fn custom_type(
+        &self,
+        info: &CustomTypeInfo,
+        value: &Value,
+        path: &Path,
+        bindings: &Bindings,
+        context: Context,
+    ) -> Result<Value, Problem> {
+        let type_name = info.name.to_string();
+        let ledger = self
+            .history
+            .and_then(|(history, _)| history.custom_type(type_name.as_str()));
+        let (variant, contents) = match (context.from, value) {
+            (Names::Minified, Value::Array(elems)) | (Names::Packed, Value::Array(elems))
+                if !elems.is_empty() =>
+            {
+                let variant = match elems[0] {
+                    Value::Int(id) => info
+                        .variants
+                        .iter()
+                        .find(|variant| variant.variant_id as i64 == id),
+                    _ => None,
+                };
+
+                match variant {
+                    Some(variant) => (variant, &elems[1..]),
+                    None => {
+                        return Err(problem(
+                            path,
+                            ProblemKind::UnknownVariant(elems[0].to_string()),
+                        ))
+                    }
+                }
+            }
+            (Names::Full, Value::Object(_)) | (Names::Development, Value::Object(_)) => {
+                self.check_annotation(Some(&type_name), ledger, value, path, context.from)?;
+
+                let name = match value.get(VARIANT_KEY) {
+                    Some(Value::String(name)) => name,
+                    _ => {
+                        return Err(problem(
+                            &Path::Field(path, VARIANT_KEY),
+                            ProblemKind::Missing,
+                        ))
+                    }
+                };
+                let contents = match value.get(CONTENTS_KEY) {
+                    Some(Value::Array(contents)) => contents.as_slice(),
+                    None => &[],
+                    Some(other) => {
+                        return Err(problem(
+                            &Path::Field(path, CONTENTS_KEY),
+                            ProblemKind::Expected {
+                                expected: String::from("an array"),
+                                found: other.kind(),
+                            },
+                        ))
+                    }
+                };
+
+                let variant = info.variants.iter().find(|variant| {
+                    if context.from == Names::Development {
+                        self.accepted_names(ledger, variant.name.as_str())
+                            .contains(&name.as_str())
+                    } else {
+                        variant.name == *name
+                    }
+                });
+
+                match variant {
+                    Some(variant) => (variant, contents),
+                    None => return Err(problem(path, ProblemKind::UnknownVariant(name.clone()))),
+                }
+            }
+            (Names::Minified, Value::Object(_)) | (Names::Packed, Value::Object(_)) => {
+                return Err(mixed(path, Mode::Production))
+            }
+            (Names::Development, Value::Array(_)) => return Err(mixed(path, Mode::Development)),
+            _ => {
+                return Err(problem(
+                    path,
+                    ProblemKind::Expected {
+                        expected: match context.from {
+                            Names::Minified | Names::Packed => {
+                                format!("a {} variant, like [0]", info.name)
+                            }
+                            Names::Full | Names::Development => {
+                                format!("a {} variant, like {{\"variant\":\"...\"}}", info.name)
+                            }
+                        },
+                        found: value.kind(),
+                    },
+                ))
+            }
+        };
+        let variant_path = Path::Field(path, variant.name.as_str());
+
+        if contents.len() != variant.contents.len() {
+            return Err(problem(
+                &variant_path,
+                ProblemKind::Expected {
+                    expected: format!("{} value(s)", variant.contents.len()),
+                    found: "a different number",
+                },
+            ));
+        }
+
+        let contents = variant
+            .contents
+            .iter()
+            .zip(contents.iter())
+            .enumerate()
+            .map(|(index, (tipe, value))| {
+                self.value(
+                    tipe,
+                    value,
+                    &Path::Index(&variant_path, index),
+                    bindings,
+                    context.unpackable(),
+                )
+            })
+            .collect::<Result<Vec<Value>, Problem>>()?;
+
+        Ok(match context.to {
+            Names::Minified | Names::Packed => {
+                let mut elems = vec![Value::Int(variant.variant_id as i64)];
+
+                elems.extend(contents);
+
+                Value::Array(elems)
+            }
+            Names::Full | Names::Development => {
+                let mut entries = vec![];
+
+                if context.to == Names::Development {
+                    entries.push((String::from(TYPE_KEY), Value::String(type_name)));
+                }
+
+                entries.push((
+                    String::from(VARIANT_KEY),
+                    Value::String(variant.name.clone()),
+                ));
+
+                if !contents.is_empty() {
+                    entries.push((String::from(CONTENTS_KEY), Value::Array(contents)));
+                }
+
+                Value::Object(entries)
+            }
+        })
+    }
+
+    /// Make sure an object is annotated with its type if, and only if, it's
+    /// development JSON. Inline records have no type to annotate them with.
+    fn check_annotation(
+        &self,
+        type_name: Option<&String>,
+        ledger: Option<&Ledger>,
+        value: &Value,
+        path: &Path,
+        from: Names,
+    ) -> Result<(), Problem> {
+        match (from, value.get(TYPE_KEY), type_name) {
+            (Names::Minified, Some(_), _) | (Names::Packed, Some(_), _) => {
+                Err(mixed(path, Mode::Production))
+            }
+            (Names::Development, None, Some(_)) => Err(mixed(path, Mode::Development)),
+            (Names::Development, Some(annotation), Some(type_name)) => {
+                let accepted = match (self.history, ledger) {
+                    (Some((history, grace)), Some(ledger)) => {
+                        ledger.accepted_names(history.version, grace)
+                    }
+                    _ => vec![type_name.as_str()],
+                };
+
+                match annotation {
+                    Value::String(annotation) if accepted.contains(&annotation.as_str()) => Ok(()),
+                    _ => Err(problem(
+                        &Path::Field(path, TYPE_KEY),
+                        ProblemKind::Expected {
+                            expected: format!("\"{}\"", type_name),
+                            found: match annotation {
+                                Value::String(_) => "another type",
+                                other => other.kind(),
+                            },
+                        },
+                    )),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The name a field goes by on the wire. `path` is only for the problem,
+    /// if the table has no name for it.
+    fn field_name<'b>(
+        &'b self,
+        info: &RecordInfo,
+        field: &'b Field,
+        names: Names,
+        path: &Path,
+    ) -> Result<&'b str, Problem> {
+        match names {
+            Names::Full | Names::Development => Ok(field.name.as_str()),
+            Names::Minified | Names::Packed => self
+                .table
+                .record_field(info, field.name.as_str())
+                .ok_or_else(|| problem(path, ProblemKind::NotMinified)),
+        }
+    }
+
+    /// A missing field is usually just missing, but if it's there under the
+    /// name the other mode would give it, the JSON is in the wrong format.
+    fn missing(
+        &self,
+        info: &RecordInfo,
+        field: &Field,
+        value: &Value,
+        from: Names,
+        path: &Path,
+    ) -> Result<ProblemKind, Problem> {
+        let (other_name, expected) = match from {
+            Names::Minified | Names::Packed => (field.name.as_str(), Mode::Production),
+            Names::Development => (
+                self.field_name(info, field, Names::Minified, path)?,
+                Mode::Development,
+            ),
+            Names::Full => return Ok(ProblemKind::Missing),
+        };
+
+        Ok(match value.get(other_name) {
+            Some(Value::Null) | None => ProblemKind::Missing,
+            Some(_) => ProblemKind::MixedFormats { expected },
+        })
+    }
+
+    /// The names a field or variant may go by in development JSON: its current
+    /// name, followed by any former names still within the rename grace
+    /// period.
+    fn accepted_names<'b>(&self, ledger: Option<&'b Ledger>, name: &'b str) -> Vec<&'b str> {
+        match (self.history, ledger.and_then(|ledger| ledger.entry(name))) {
+            (Some((history, grace)), Some(entry)) => entry.accepted_names(history.version, grace),
+            _ => vec![name],
+        }
+    }
+}
+
+/// Call `f` with the path of the given fields, within `parent`.
+fn within<T, F: FnMut(&Path) -> T>(parent: &Path, names: &[String], f: &mut F) -> T {
+    match names.split_first() {
+        Some((name, rest)) => within(&Path::Field(parent, name.as_str()), rest, f),
+        None => f(parent),
+    }
+}
+
+fn mixed(path: &Path, expected: Mode) -> Problem {
+    problem(path, ProblemKind::MixedFormats { expected })
+}
+
+/// Bind a declaration's params to the args it was given, which may refer to
+/// params bound further out.
+pub fn bind(params: &[TypeParam], args: &[Type], outer: &Bindings) -> Bindings {
+    params
+        .iter()
+        .zip(args.iter())
+        .map(|(TypeParam::TypeParam(param), arg)| (param.clone(), substitute(arg, outer)))
+        .collect()
+}
+
+fn substitute(tipe: &Type, bindings: &Bindings) -> Type {
+    Type {
+        current: substitute_primitive(&tipe.current, bindings),
+        formerly: tipe
+            .formerly
+            .iter()
+            .map(|former| substitute_primitive(former, bindings))
+            .collect(),
+    }
+}
+
+fn substitute_primitive(primitive: &TypePrimitive, bindings: &Bindings) -> TypePrimitive {
+    match primitive {
+        TypePrimitive::Param(TypeParam::TypeParam(param)) => bindings
+            .iter()
+            .find(|(name, _)| name == param)
+            .map(|(_, tipe)| tipe.current.clone())
+            .unwrap_or_else(|| primitive.clone()),
+        TypePrimitive::Array(elem) => TypePrimitive::Array(Box::new(substitute(elem, bindings))),
+        TypePrimitive::Set(elem) => TypePrimitive::Set(Box::new(substitute(elem, bindings))),
+        TypePrimitive::Dict(key, val) => TypePrimitive::Dict(
+            Box::new(substitute(key, bindings)),
+            Box::new(substitute(val, bindings)),
+        ),
+        TypePrimitive::Named(name, args) => TypePrimitive::Named(
+            name.clone(),
+            args.iter().map(|arg| substitute(arg, bindings)).collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn problem(path: &Path, kind: ProblemKind) -> Problem {
+    Problem {
+        path: path.to_string(),
+        kind,
+    }
+}
+
+fn expected(path: &Path, primitive: &TypePrimitive, value: &Value) -> Problem {
+    let article = match primitive.to_string().chars().next() {
+        Some('A') | Some('E') | Some('I') | Some('O') | Some('U') => "an",
+        _ => "a",
+    };
+
+    problem(
+        path,
+        ProblemKind::Expected {
+            expected: format!("{} {}", article, primitive),
+            found: value.kind(),
+        },
+    )
+}
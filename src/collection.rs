@@ -0,0 +1,249 @@
+//! Dicts and Sets on the wire.
+//!
+//! A Set is laid out like an Array: one Word per element (see `Slot`), with
+//! the Data Length counting elements. A Dict has two Words per entry, the key
+//! and then the value, with the Data Length counting entries. Either way,
+//! elements and keys are in ascending order, so the same collection always
+//! encodes to the same bytes, and each one appears only once. Anything they
+//! point to (e.g. the bytes of a String) comes before them in the segment.
+//!
+//! Large collections are split into chunks by a Composite Pointer. Each
+//! chunk must be in ascending order by itself, and decoding merges the
+//! chunks together, so a key appearing in two chunks is a duplicate too.
+use array;
+use pointer;
+use pointer::{Resolved, Units};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use string;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    Pointer(pointer::Problem),
+    String(string::Problem),
+
+    /// A chunk's elements or keys aren't in ascending order.
+    Unsorted,
+
+    /// The same element or key appears more than once.
+    Duplicate,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Pointer(problem) => write!(formatter, "{}", problem),
+            Problem::String(problem) => write!(formatter, "{}", problem),
+            Problem::Unsorted => write!(formatter, "a Dict or Set is not in ascending order"),
+            Problem::Duplicate => write!(formatter, "a Dict or Set has a duplicate key"),
+        }
+    }
+}
+
+impl From<pointer::Problem> for Problem {
+    fn from(problem: pointer::Problem) -> Problem {
+        Problem::Pointer(problem)
+    }
+}
+
+impl From<string::Problem> for Problem {
+    fn from(problem: string::Problem) -> Problem {
+        Problem::String(problem)
+    }
+}
+
+/// A value which takes up one Word in a Dict or Set: either the value itself,
+/// or a pointer to its data.
+pub trait Slot: Sized {
+    /// Write whatever the value points to (if anything) to the end of the
+    /// segment, and return its Word.
+    fn encode(&self, segment: &mut Vec<u64>) -> u64;
+
+    /// Read the value, given its Word and the index of the segment the Word
+    /// is in.
+    fn decode(segments: &[Vec<u64>], segment: usize, word: u64) -> Result<Self, Problem>;
+}
+
+impl Slot for u64 {
+    fn encode(&self, _: &mut Vec<u64>) -> u64 {
+        *self
+    }
+
+    fn decode(_: &[Vec<u64>], _: usize, word: u64) -> Result<u64, Problem> {
+        Ok(word)
+    }
+}
+
+impl Slot for i64 {
+    fn encode(&self, _: &mut Vec<u64>) -> u64 {
+        *self as u64
+    }
+
+    fn decode(_: &[Vec<u64>], _: usize, word: u64) -> Result<i64, Problem> {
+        Ok(word as i64)
+    }
+}
+
+impl Slot for f64 {
+    fn encode(&self, _: &mut Vec<u64>) -> u64 {
+        self.to_bits()
+    }
+
+    fn decode(_: &[Vec<u64>], _: usize, word: u64) -> Result<f64, Problem> {
+        Ok(f64::from_bits(word))
+    }
+}
+
+/// True is all ones, but any Word other than zero decodes as true.
+impl Slot for bool {
+    fn encode(&self, _: &mut Vec<u64>) -> u64 {
+        if *self {
+            u64::MAX
+        } else {
+            0
+        }
+    }
+
+    fn decode(_: &[Vec<u64>], _: usize, word: u64) -> Result<bool, Problem> {
+        Ok(word != 0)
+    }
+}
+
+impl Slot for String {
+    fn encode(&self, segment: &mut Vec<u64>) -> u64 {
+        string::encode_string(segment, 0, self)
+    }
+
+    fn decode(segments: &[Vec<u64>], segment: usize, word: u64) -> Result<String, Problem> {
+        Ok(string::decode_string(segments, segment, word)?)
+    }
+}
+
+/// Write a Set to the end of the segment, and return a pointer to it.
+/// `segment_id_offset` is as in `array::encode_u64_array`.
+pub fn encode_set<T: Slot + Ord>(
+    segment: &mut Vec<u64>,
+    segment_id_offset: u16,
+    set: &BTreeSet<T>,
+) -> u64 {
+    let words: Vec<u64> = set.iter().map(|elem| elem.encode(segment)).collect();
+
+    array::encode_u64_array(segment, segment_id_offset, &words)
+}
+
+/// Read a Set from the message's segments. `segment` is the index of the
+/// segment the pointer was read from.
+pub fn decode_set<T: Slot + Ord + Clone>(
+    segments: &[Vec<u64>],
+    segment: usize,
+    raw_ptr: u64,
+) -> Result<BTreeSet<T>, Problem> {
+    let mut set = BTreeSet::new();
+
+    for chunk in chunks(segments, segment, raw_ptr, Units::Words)? {
+        let mut previous: Option<T> = None;
+
+        for word in pointer::words(segments, chunk.segment, chunk.word_index, chunk.length)? {
+            let elem = T::decode(segments, chunk.segment, *word)?;
+
+            check_order(previous.as_ref(), &elem)?;
+
+            if !set.insert(elem.clone()) {
+                return Err(Problem::Duplicate);
+            }
+
+            previous = Some(elem);
+        }
+    }
+
+    Ok(set)
+}
+
+/// Write a Dict to the end of the segment, and return a pointer to it.
+/// `segment_id_offset` is as in `array::encode_u64_array`.
+pub fn encode_dict<K: Slot + Ord, V: Slot>(
+    segment: &mut Vec<u64>,
+    segment_id_offset: u16,
+    dict: &BTreeMap<K, V>,
+) -> u64 {
+    if dict.is_empty() {
+        return 0;
+    }
+
+    let words: Vec<u64> = dict
+        .iter()
+        .flat_map(|(key, val)| vec![key.encode(segment), val.encode(segment)])
+        .collect();
+    let ptr = pointer::write_chunked(segment, dict.len(), |segment, start, end| {
+        segment.extend_from_slice(&words[start * 2..end * 2])
+    });
+
+    pointer::encode(pointer::Pointer {
+        segment_id_offset,
+        ..ptr
+    })
+}
+
+/// Read a Dict from the message's segments. `segment` is the index of the
+/// segment the pointer was read from.
+pub fn decode_dict<K: Slot + Ord + Clone, V: Slot>(
+    segments: &[Vec<u64>],
+    segment: usize,
+    raw_ptr: u64,
+) -> Result<BTreeMap<K, V>, Problem> {
+    let mut dict = BTreeMap::new();
+
+    for chunk in chunks(segments, segment, raw_ptr, Units::Entries)? {
+        let words = pointer::words(
+            segments,
+            chunk.segment,
+            chunk.word_index,
+            Units::Entries.words(chunk.length),
+        )?;
+        let mut previous: Option<K> = None;
+
+        for pair in words.chunks(2) {
+            let key = K::decode(segments, chunk.segment, pair[0])?;
+
+            check_order(previous.as_ref(), &key)?;
+
+            if dict.contains_key(&key) {
+                return Err(Problem::Duplicate);
+            }
+
+            dict.insert(key.clone(), V::decode(segments, chunk.segment, pair[1])?);
+            previous = Some(key);
+        }
+    }
+
+    Ok(dict)
+}
+
+/// The Segment Pointers for each chunk of a Dict or Set.
+fn chunks(
+    segments: &[Vec<u64>],
+    segment: usize,
+    raw_ptr: u64,
+    units: Units,
+) -> Result<Vec<Resolved>, Problem> {
+    if raw_ptr == 0 {
+        // An empty collection
+        return Ok(vec![]);
+    }
+
+    Ok(pointer::resolve(
+        segments,
+        segment,
+        pointer::decode(raw_ptr),
+        units,
+    )?)
+}
+
+/// Within a chunk, each element or key must be greater than the last.
+fn check_order<T: Ord>(previous: Option<&T>, next: &T) -> Result<(), Problem> {
+    match previous {
+        Some(previous) if previous == next => Err(Problem::Duplicate),
+        Some(previous) if previous > next => Err(Problem::Unsorted),
+        _ => Ok(()),
+    }
+}
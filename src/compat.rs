@@ -0,0 +1,676 @@
+use history::SchemaVersion;
+use std::ptr;
+use types::{
+    CustomTypeInfo, Endpoint, Field, Optionality, RecordInfo, Schema, Type, TypeName,
+    TypePrimitive, Variant,
+};
+use widening;
+
+/// Who a change would break, if they were still running code built against
+/// the old schema.
+///
+/// "Senders" and "receivers" rather than "clients" and "servers," because
+/// records get sent in both directions. For endpoint params the client is the
+/// sender, and for responses it's the server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Impact {
+    Safe,
+    BreaksOldSenders,
+    BreaksOldReceivers,
+    BreaksBoth,
+}
+
+impl Impact {
+    pub fn is_breaking(self) -> bool {
+        self != Impact::Safe
+    }
+
+    pub fn breaks_old_senders(self) -> bool {
+        self == Impact::BreaksOldSenders || self == Impact::BreaksBoth
+    }
+
+    pub fn breaks_old_receivers(self) -> bool {
+        self == Impact::BreaksOldReceivers || self == Impact::BreaksBoth
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeKind {
+    FieldAdded(Optionality),
+    FieldRemoved(Optionality),
+    OptionalityChanged {
+        from: Optionality,
+        to: Optionality,
+    },
+    TypeChanged {
+        from: Box<TypePrimitive>,
+        to: Box<TypePrimitive>,
+    },
+    /// Renamed with `[ fka "..." ]` from the given former name
+    Renamed(String),
+    VariantAdded,
+    VariantRemoved,
+    VariantContentsChanged,
+    DeclarationAdded,
+    DeclarationRemoved,
+    EndpointAdded,
+    EndpointRemoved,
+    VerbChanged,
+}
+
+/// A single difference between two schemas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// What changed, e.g. "User.email" or "sendDM.params.userId"
+    pub path: String,
+    pub kind: ChangeKind,
+
+    /// Which way the changed thing travels.
+    pub flow: Flow,
+    pub impact: Impact,
+}
+
+/// Which way something travels between clients and servers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+    /// Endpoint params, and the endpoints themselves
+    ClientToServer,
+
+    /// Endpoint responses
+    ServerToClient,
+
+    /// Records and custom types, which could be used in either direction
+    Both,
+}
+
+/// Which side of a client and server pair is still running an old version of
+/// the schema. The other side is running the current one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Client,
+    Server,
+}
+
+impl Change {
+    /// Whether this change breaks communication between a client and server
+    /// where the given side is still running the old schema.
+    pub fn breaks(&self, old_side: Side) -> bool {
+        let old_sender = self.impact.breaks_old_senders();
+        let old_receiver = self.impact.breaks_old_receivers();
+
+        match (self.flow, old_side) {
+            (Flow::ClientToServer, Side::Client) | (Flow::ServerToClient, Side::Server) => {
+                old_sender
+            }
+            (Flow::ClientToServer, Side::Server) | (Flow::ServerToClient, Side::Client) => {
+                old_receiver
+            }
+            (Flow::Both, _) => old_sender || old_receiver,
+        }
+    }
+}
+
+/// Compare the schema we're about to deploy to the one that's currently out
+/// there, and classify each difference by who it would break.
+///
+/// * Adding a required field breaks old senders, which don't know to send it.
+///   Adding an optional field is safe.
+/// * Removing a required field breaks old receivers, which still expect it.
+///   Removing an optional field is safe.
+/// * Making a field required breaks old senders; making it optional breaks
+///   old receivers.
+/// * Changing a field's type breaks everyone, unless the new type lists the
+///   old one as `[ formerly ... ]` and it's a safe widening. In that case new
+///   receivers accept what old senders send, but old receivers may still get
+///   values too big for them.
+/// * Changes inside an inline record (even one in an array, set or dict) are
+///   classified field by field, e.g. "sendDM.response.user.email", rather
+///   than as a change to the whole type.
+/// * Adding a variant breaks old receivers; removing one breaks old senders.
+/// * Adding an endpoint breaks old servers, which a new client may call
+///   before they have it. Removing one breaks old clients, which may still
+///   call it.
+/// * Renaming something with `[ fka "..." ]` is safe, since it keeps its ID.
+pub fn compare(old: &Schema, new: &Schema) -> Vec<Change> {
+    let mut changes: Vec<Change> = vec![];
+    let types = Types { old, new };
+    let (records, added_records) = pair_up(&old.records, &new.records);
+
+    for (old_record, new_record) in records {
+        match new_record {
+            Some(new_record) => {
+                let name = compare_names("", old_record, new_record, &mut changes);
+
+                types.compare_fields(
+                    name.as_str(),
+                    &old_record.fields,
+                    &new_record.fields,
+                    Flow::Both,
+                    &mut changes,
+                )
+            }
+            None => changes.push(declaration_removed(old_record.name())),
+        }
+    }
+
+    for new_record in added_records {
+        changes.push(declaration_added(new_record.name()));
+    }
+
+    let (custom_types, added_types) = pair_up(&old.custom_types, &new.custom_types);
+
+    for (old_type, new_type) in custom_types {
+        match new_type {
+            Some(new_type) => {
+                let name = compare_names("", old_type, new_type, &mut changes);
+
+                types.compare_variants(
+                    name.as_str(),
+                    &old_type.variants,
+                    &new_type.variants,
+                    &mut changes,
+                )
+            }
+            None => changes.push(declaration_removed(old_type.name())),
+        }
+    }
+
+    for new_type in added_types {
+        changes.push(declaration_added(new_type.name()));
+    }
+
+    for old_endpoint in old.endpoints.iter() {
+        match new.endpoint(old_endpoint.name.as_str()) {
+            Some(new_endpoint) => types.compare_endpoints(old_endpoint, new_endpoint, &mut changes),
+            None => changes.push(Change {
+                path: old_endpoint.name.clone(),
+                kind: ChangeKind::EndpointRemoved,
+                flow: Flow::ClientToServer,
+                impact: Impact::BreaksOldSenders,
+            }),
+        }
+    }
+
+    for new_endpoint in new.endpoints.iter() {
+        if old.endpoint(new_endpoint.name.as_str()).is_none() {
+            changes.push(Change {
+                path: new_endpoint.name.clone(),
+                kind: ChangeKind::EndpointAdded,
+                flow: Flow::ClientToServer,
+                impact: Impact::BreaksOldReceivers,
+            });
+        }
+    }
+
+    changes
+}
+
+/// A client and server which can't talk to each other, because one of them is
+/// still running an old version of the schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incompatibility {
+    pub old_side: Side,
+    pub old_version: SchemaVersion,
+    pub changes: Vec<Change>,
+}
+
+/// Check the current schema against each released version in the window, in
+/// both directions: clients running that version talking to a server running
+/// the current one, and the reverse. Acknowledged changes are ignored.
+///
+/// Checking only against the latest release isn't enough, since someone who
+/// left a tab open across several deploys is still running an older client.
+pub fn check_window(
+    released: &[(SchemaVersion, Schema)],
+    current: &Schema,
+    acknowledged: &[String],
+) -> Vec<Incompatibility> {
+    let mut incompatibilities: Vec<Incompatibility> = vec![];
+
+    for (version, schema) in released.iter() {
+        let changes: Vec<Change> = compare(schema, current)
+            .into_iter()
+            .filter(|change| !is_acknowledged(change, acknowledged))
+            .collect();
+
+        for old_side in [Side::Client, Side::Server].iter() {
+            let breaking: Vec<Change> = changes
+                .iter()
+                .filter(|change| change.breaks(*old_side))
+                .cloned()
+                .collect();
+
+            if !breaking.is_empty() {
+                incompatibilities.push(Incompatibility {
+                    old_side: *old_side,
+                    old_version: *version,
+                    changes: breaking,
+                });
+            }
+        }
+    }
+
+    incompatibilities
+}
+
+/// Whether a change has been acknowledged by the person deploying it, e.g.
+/// with `--acknowledge User.email`
+pub fn is_acknowledged(change: &Change, acknowledged: &[String]) -> bool {
+    acknowledged.contains(&change.path)
+}
+
+pub fn describe(change: &Change) -> String {
+    match change.kind {
+        ChangeKind::FieldAdded(ref optionality) => {
+            format!(
+                "{} was added as {} field.",
+                change.path,
+                a_field(optionality)
+            )
+        }
+        ChangeKind::FieldRemoved(ref optionality) => {
+            format!(
+                "{} was removed. It was {} field.",
+                change.path,
+                a_field(optionality)
+            )
+        }
+        ChangeKind::OptionalityChanged { ref to, .. } => format!(
+            "{} became {}.",
+            change.path,
+            match to {
+                Optionality::Required => "required",
+                Optionality::Optional => "optional",
+            }
+        ),
+        ChangeKind::TypeChanged { ref from, ref to } => {
+            format!("{} changed from {} to {}.", change.path, from, to)
+        }
+        ChangeKind::Renamed(ref former_name) => {
+            format!("{} was renamed from {}.", change.path, former_name)
+        }
+        ChangeKind::VariantAdded => format!("{} was added as a new variant.", change.path),
+        ChangeKind::VariantRemoved => format!("{} was removed.", change.path),
+        ChangeKind::VariantContentsChanged => {
+            format!("The contents of {} changed.", change.path)
+        }
+        ChangeKind::DeclarationAdded => format!("{} was added.", change.path),
+        ChangeKind::DeclarationRemoved => format!("{} was removed.", change.path),
+        ChangeKind::EndpointAdded => format!("The {} endpoint was added.", change.path),
+        ChangeKind::EndpointRemoved => format!("The {} endpoint was removed.", change.path),
+        ChangeKind::VerbChanged => format!("The {} endpoint's HTTP verb changed.", change.path),
+    }
+}
+
+pub fn describe_impact(impact: Impact) -> &'static str {
+    match impact {
+        Impact::Safe => "safe",
+        Impact::BreaksOldSenders => "breaks old senders",
+        Impact::BreaksOldReceivers => "breaks old receivers",
+        Impact::BreaksBoth => "breaks old senders and receivers",
+    }
+}
+
+fn a_field(optionality: &Optionality) -> &'static str {
+    match optionality {
+        Optionality::Required => "a required",
+        Optionality::Optional => "an optional",
+    }
+}
+
+/// Something which can be renamed with `[ fka "..." ]`
+trait Named {
+    fn name(&self) -> String;
+    fn former_names(&self) -> Vec<String>;
+}
+
+impl Named for RecordInfo {
+    fn name(&self) -> String {
+        self.name
+            .as_ref()
+            .map(TypeName::to_string)
+            .unwrap_or_default()
+    }
+
+    fn former_names(&self) -> Vec<String> {
+        self.former_names.iter().map(TypeName::to_string).collect()
+    }
+}
+
+impl Named for CustomTypeInfo {
+    fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn former_names(&self) -> Vec<String> {
+        self.former_names.iter().map(TypeName::to_string).collect()
+    }
+}
+
+impl Named for Field {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn former_names(&self) -> Vec<String> {
+        self.former_names.clone()
+    }
+}
+
+impl Named for Variant {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn former_names(&self) -> Vec<String> {
+        self.former_names.clone()
+    }
+}
+
+/// Pair up each old item with its new counterpart, which either has the same
+/// name or was renamed from it (and doesn't share a name with some other old
+/// item). Also returns the new items which have no old counterpart.
+#[allow(clippy::type_complexity)]
+fn pair_up<'b, T: Named>(old: &'b [T], new: &'b [T]) -> (Vec<(&'b T, Option<&'b T>)>, Vec<&'b T>) {
+    let old_names: Vec<String> = old.iter().map(Named::name).collect();
+    let pairs: Vec<(&T, Option<&T>)> = old
+        .iter()
+        .map(|old_item| {
+            let name = old_item.name();
+            let counterpart = new
+                .iter()
+                .find(|new_item| new_item.name() == name)
+                .or_else(|| {
+                    new.iter().find(|new_item| {
+                        !old_names.contains(&new_item.name())
+                            && new_item.former_names().contains(&name)
+                    })
+                });
+
+            (old_item, counterpart)
+        })
+        .collect();
+    let added: Vec<&T> = new
+        .iter()
+        .filter(|new_item| {
+            !pairs.iter().any(|(_, counterpart)| {
+                counterpart.is_some_and(|counterpart| ptr::eq(counterpart, *new_item))
+            })
+        })
+        .collect();
+
+    (pairs, added)
+}
+
+/// Record a change if the new item was renamed from the old one, and return
+/// the new item's path. The rename keeps its ID, so it's safe.
+fn compare_names<T: Named>(prefix: &str, old: &T, new: &T, changes: &mut Vec<Change>) -> String {
+    let path = qualify(prefix, new.name());
+
+    if old.name() != new.name() {
+        changes.push(Change {
+            path: path.clone(),
+            kind: ChangeKind::Renamed(qualify(prefix, old.name())),
+            flow: Flow::Both,
+            impact: Impact::Safe,
+        });
+    }
+
+    path
+}
+
+fn qualify(prefix: &str, name: String) -> String {
+    if prefix.is_empty() {
+        name
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+fn declaration_added(name: String) -> Change {
+    Change {
+        path: name,
+        kind: ChangeKind::DeclarationAdded,
+        flow: Flow::Both,
+        impact: Impact::Safe,
+    }
+}
+
+/// Removing a type is only a problem if something still refers to it, and
+/// that shows up as a type change wherever it was referred to.
+fn declaration_removed(name: String) -> Change {
+    Change {
+        path: name,
+        kind: ChangeKind::DeclarationRemoved,
+        flow: Flow::Both,
+        impact: Impact::Safe,
+    }
+}
+
+/// The two schemas being compared, so that types can be compared after
+/// expanding each side's aliases.
+struct Types<'a> {
+    old: &'a Schema,
+    new: &'a Schema,
+}
+
+impl<'a> Types<'a> {
+    fn compare_endpoints(&self, old: &Endpoint, new: &Endpoint, changes: &mut Vec<Change>) {
+        if old.verb != new.verb {
+            changes.push(Change {
+                path: old.name.clone(),
+                kind: ChangeKind::VerbChanged,
+                flow: Flow::Both,
+                impact: Impact::BreaksBoth,
+            });
+        }
+
+        self.compare_fields(
+            format!("{}.params", old.name).as_str(),
+            &old.params,
+            &new.params,
+            Flow::ClientToServer,
+            changes,
+        );
+        self.compare_fields(
+            format!("{}.response", old.name).as_str(),
+            &old.response,
+            &new.response,
+            Flow::ServerToClient,
+            changes,
+        );
+    }
+
+    fn compare_fields(
+        &self,
+        prefix: &str,
+        old: &[Field],
+        new: &[Field],
+        flow: Flow,
+        changes: &mut Vec<Change>,
+    ) {
+        let (fields, added) = pair_up(old, new);
+
+        for (old_field, new_field) in fields {
+            match new_field {
+                Some(new_field) => {
+                    let path = compare_names(prefix, old_field, new_field, changes);
+
+                    if old_field.optionality != new_field.optionality {
+                        changes.push(Change {
+                            path: path.clone(),
+                            kind: ChangeKind::OptionalityChanged {
+                                from: old_field.optionality.clone(),
+                                to: new_field.optionality.clone(),
+                            },
+                            flow,
+                            impact: match new_field.optionality {
+                                Optionality::Required => Impact::BreaksOldSenders,
+                                Optionality::Optional => Impact::BreaksOldReceivers,
+                            },
+                        });
+                    }
+
+                    self.compare_types(path, &old_field.tipe, &new_field.tipe, flow, changes);
+                }
+                None => changes.push(Change {
+                    path: format!("{}.{}", prefix, old_field.name),
+                    kind: ChangeKind::FieldRemoved(old_field.optionality.clone()),
+                    flow,
+                    impact: match old_field.optionality {
+                        Optionality::Required => Impact::BreaksOldReceivers,
+                        Optionality::Optional => Impact::Safe,
+                    },
+                }),
+            }
+        }
+
+        for new_field in added {
+            changes.push(Change {
+                path: format!("{}.{}", prefix, new_field.name),
+                kind: ChangeKind::FieldAdded(new_field.optionality.clone()),
+                flow,
+                impact: match new_field.optionality {
+                    Optionality::Required => Impact::BreaksOldSenders,
+                    Optionality::Optional => Impact::Safe,
+                },
+            });
+        }
+    }
+
+    fn compare_types(
+        &self,
+        path: String,
+        old: &Type,
+        new: &Type,
+        flow: Flow,
+        changes: &mut Vec<Change>,
+    ) {
+        let old = self.old.expand_aliases(old);
+        let new = self.new.expand_aliases(new);
+
+        if old.current == new.current
+            || self.compare_nested(path.as_str(), &old.current, &new.current, flow, changes)
+        {
+            return;
+        }
+
+        let is_declared_widening = new.formerly.contains(&old.current)
+            && widening::is_safe_widening(&old.current, &new.current);
+
+        changes.push(Change {
+            path,
+            kind: ChangeKind::TypeChanged {
+                from: Box::new(old.current),
+                to: Box::new(new.current),
+            },
+            flow,
+            impact: if is_declared_widening {
+                Impact::BreaksOldReceivers
+            } else {
+                Impact::BreaksBoth
+            },
+        });
+    }
+
+    /// If the only differences between the two types are inside inline
+    /// records (including ones in arrays, sets and dict values), compare the
+    /// records' fields under the same path, and return true. Otherwise the
+    /// type as a whole changed.
+    fn compare_nested(
+        &self,
+        path: &str,
+        old: &TypePrimitive,
+        new: &TypePrimitive,
+        flow: Flow,
+        changes: &mut Vec<Change>,
+    ) -> bool {
+        match (old, new) {
+            (TypePrimitive::Record(old), TypePrimitive::Record(new)) => {
+                self.compare_fields(path, &old.fields, &new.fields, flow, changes);
+
+                true
+            }
+            (TypePrimitive::Array(old), TypePrimitive::Array(new))
+            | (TypePrimitive::Set(old), TypePrimitive::Set(new)) => {
+                self.compare_elems(path, old, new, flow, changes)
+            }
+            (TypePrimitive::Dict(old_key, old_value), TypePrimitive::Dict(new_key, new_value)) => {
+                self.old.expand_aliases(old_key).current == self.new.expand_aliases(new_key).current
+                    && self.compare_elems(path, old_value, new_value, flow, changes)
+            }
+            _ => false,
+        }
+    }
+
+    fn compare_elems(
+        &self,
+        path: &str,
+        old: &Type,
+        new: &Type,
+        flow: Flow,
+        changes: &mut Vec<Change>,
+    ) -> bool {
+        let old = self.old.expand_aliases(old);
+        let new = self.new.expand_aliases(new);
+
+        old.current == new.current
+            || self.compare_nested(path, &old.current, &new.current, flow, changes)
+    }
+
+    fn compare_variants(
+        &self,
+        prefix: &str,
+        old: &[Variant],
+        new: &[Variant],
+        changes: &mut Vec<Change>,
+    ) {
+        let (variants, added) = pair_up(old, new);
+
+        for (old_variant, new_variant) in variants {
+            match new_variant {
+                Some(new_variant) => {
+                    let path = compare_names(prefix, old_variant, new_variant, changes);
+                    let old_contents: Vec<Type> = old_variant
+                        .contents
+                        .iter()
+                        .map(|tipe| self.old.expand_aliases(tipe))
+                        .collect();
+                    let new_contents: Vec<Type> = new_variant
+                        .contents
+                        .iter()
+                        .map(|tipe| self.new.expand_aliases(tipe))
+                        .collect();
+
+                    if old_contents.len() != new_contents.len()
+                        || old_contents.iter().zip(new_contents.iter()).any(
+                            |(old_content, new_content)| old_content.current != new_content.current,
+                        )
+                    {
+                        changes.push(Change {
+                            path,
+                            kind: ChangeKind::VariantContentsChanged,
+                            flow: Flow::Both,
+                            impact: Impact::BreaksBoth,
+                        });
+                    }
+                }
+                None => changes.push(Change {
+                    path: format!("{}.{}", prefix, old_variant.name),
+                    kind: ChangeKind::VariantRemoved,
+                    flow: Flow::Both,
+                    impact: Impact::BreaksOldSenders,
+                }),
+            }
+        }
+
+        for new_variant in added {
+            changes.push(Change {
+                path: format!("{}.{}", prefix, new_variant.name),
+                kind: ChangeKind::VariantAdded,
+                flow: Flow::Both,
+                impact: Impact::BreaksOldReceivers,
+            });
+        }
+    }
+}
@@ -1,5 +1,13 @@
+use changelog;
 use cli;
+use code_gen;
+use compat;
+use git;
+use history;
+use loader;
+use migration;
 use problems::Problem;
+use widening;
 
 pub fn report(problem: Problem) -> String {
     match problem {
@@ -7,5 +15,210 @@ pub fn report(problem: Problem) -> String {
             "{} is not a supported language. Supported languages include JavaScript and Elm.",
             lang
         ),
+        Problem::Cli(cli::Problem::InvalidRenameGrace(value)) => format!(
+            "--rename-grace must be a whole number of schema versions, but it was {}.",
+            value
+        ),
+        Problem::Cli(cli::Problem::InvalidWindow(value)) => format!(
+            "--window must be a whole number of versions, and at least 1, but it was {}.",
+            value
+        ),
+        Problem::Cli(cli::Problem::InvalidRename(value)) => format!(
+            "--rename expects the old name and the new one, like --rename User.id=userId, but it was {}.",
+            value
+        ),
+        Problem::Cli(cli::Problem::InvalidVersion(value)) => format!(
+            "Schema versions are whole numbers, like --from 3, but one was {}.",
+            value
+        ),
+        Problem::Cli(cli::Problem::MissingSizeTarget) => String::from(
+            "compare-sizes needs to know what the sample is. Use --type to name its type, e.g. --type User, or --endpoint to name the endpoint whose response (or, with --params, params) it is.",
+        ),
+        Problem::Loader(problem) => report_loader(problem),
+        Problem::History(problem) => report_history(problem),
+        Problem::Git(problem) => report_git(problem),
+        Problem::Changelog(changelog::Problem::InvalidRange { from, to, latest }) => format!(
+            "I can't list the changes from version {} to version {}. The first version has to come before the second, and the latest version is {}.",
+            from, to, latest
+        ),
+        Problem::Changelog(changelog::Problem::Io(err)) => {
+            format!("I could not write the changelog: {}", err)
+        }
+        Problem::Json(path, problem) => format!("{} is not valid JSON: {}", path.display(), problem),
+        Problem::Codec(problem) => format!(
+            "The sample does not match the schema:\n\n    {}",
+            problem
+        ),
+        Problem::CodeGen(problem) => report_code_gen(problem),
+        Problem::TypeChanges(problems) => problems
+            .into_iter()
+            .map(report_type_change)
+            .collect::<Vec<String>>()
+            .join("\n\n"),
+        Problem::BreakingChanges(changes) => {
+            let mut message = String::from(
+                "These changes could break clients or servers that are still running an earlier version:\n",
+            );
+
+            for change in changes.iter() {
+                message.push_str(
+                    format!(
+                        "\n    {} ({})",
+                        compat::describe(change),
+                        compat::describe_impact(change.impact)
+                    )
+                    .as_str(),
+                );
+            }
+
+            message.push_str("\n\nIf you're sure, you can allow each one with --acknowledge, e.g.\n\n    wf check-compat");
+
+            for change in changes.iter() {
+                message.push_str(format!(" --acknowledge {}", change.path).as_str());
+            }
+
+            message
+        }
+        Problem::UndecidedMigrations(questions) => {
+            let mut message = String::from(
+                "I can't tell what these changes mean, and the schema history depends on it:\n",
+            );
+
+            for question in questions.iter() {
+                message.push_str(format!("\n    {}", migration::describe(question)).as_str());
+            }
+
+            message.push_str("\n\nRun wf migrate to answer these questions, or answer them up front, e.g.\n\n    wf migrate");
+
+            for question in questions.iter() {
+                let flag = match question {
+                    migration::Question::Removed { candidates, .. } => {
+                        format!("--rename {}={}", question.path(), candidates[0])
+                    }
+                    migration::Question::Retyped { .. } => format!("--new-id {}", question.path()),
+                };
+
+                message.push_str(format!(" {}", flag).as_str());
+            }
+
+            message
+        }
+        Problem::Prompt(err) => format!("I could not ask about the schema changes: {}", err),
+        Problem::DivergedHistories {
+            revision,
+            path,
+            missing,
+        } => {
+            let mut message = format!(
+                "{} at {} and the one here have each recorded IDs the other doesn't have, so I can't tell which one to use. The older one has these, which the newer one doesn't:\n",
+                path.display(),
+                revision
+            );
+
+            for name in missing.iter() {
+                message.push_str(format!("\n    {}", name).as_str());
+            }
+
+            message.push_str("\n\nMerge the history at that revision into this one by hand, giving any IDs only it has to the same fields and variants here, then run wf again.");
+
+            message
+        }
+    }
+}
+
+fn report_loader(problem: loader::Problem) -> String {
+    match problem {
+        loader::Problem::Io(path, err) => format!("I could not read {}: {}", path.display(), err),
+        loader::Problem::Parse(path, problem) => format!(
+            "I ran into a problem on line {} of {}:\n\n    {}\n\nI was expecting {}.",
+            problem.line_number,
+            path.display(),
+            problem.line.trim(),
+            problem.expected
+        ),
+        loader::Problem::DuplicateDeclaration(name) => format!(
+            "{} is declared more than once. Every record, type, alias, and endpoint needs its own name.",
+            name
+        ),
+    }
+}
+
+fn report_code_gen(problem: code_gen::Problem) -> String {
+    match problem {
+        code_gen::Problem::RetiredNamesUsed(names) => {
+            let mut message = String::from(
+                "The schema history says these were removed or renamed in an earlier version, so their names are retired:\n",
+            );
+
+            for name in names.iter() {
+                message.push_str(format!("\n    {}", name).as_str());
+            }
+
+            message.push_str("\n\nClients and servers still running an older version may send them with their old meaning, and in development builds there would be no way to tell the old meaning from the new one. Please give them different names!");
+
+            message
+        }
+        code_gen::Problem::MissingIds(names) => format!(
+            "The schema history has no IDs for these yet, so I can't minify their names:\n\n    {}\n\nRunning wf build will assign them.",
+            names.join("\n    ")
+        ),
+        code_gen::Problem::WriteError(err) => {
+            format!("I could not write the generated code: {}", err)
+        }
+    }
+}
+
+fn report_type_change(problem: widening::Problem) -> String {
+    match problem {
+        widening::Problem::Narrowing { field, from, to } => format!(
+            "{} was changed from {} to {}, which is a narrowing. Anyone still sending a {} might send a value that doesn't fit in a {}! If you need a smaller type, add a new field instead.",
+            field, from, to, from, to
+        ),
+        widening::Problem::Incompatible { field, from, to } => format!(
+            "{} was changed from {} to {}, but a {} cannot be safely converted into a {}. Anyone still sending the old type would break! If you need a different type, add a new field instead.",
+            field, from, to, from, to
+        ),
+    }
+}
+
+fn report_git(problem: git::Problem) -> String {
+    match problem {
+        git::Problem::Unavailable(err) => format!(
+            "I could not run git, which I need to read the schema as of an earlier revision: {}",
+            err
+        ),
+        git::Problem::Failed { args, stderr } => format!(
+            "I ran \"git {}\", but it failed:\n\n    {}\n\nIs this a git repository, and does that revision exist?",
+            args.join(" "),
+            stderr
+        ),
+    }
+}
+
+fn report_history(problem: history::Problem) -> String {
+    match problem {
+        history::Problem::MissingHeader => format!(
+            "The schema history file must begin with the line \"{}\". Was it edited by hand?",
+            history::FORMAT_HEADER
+        ),
+        history::Problem::UnsupportedFormat(header) => format!(
+            "The schema history file begins with \"{}\", but this version of wf only understands \"{}\". Try upgrading wf!",
+            header,
+            history::FORMAT_HEADER
+        ),
+        history::Problem::InvalidLine { line_number, line } => format!(
+            "Line {} of the schema history file is not valid:\n\n    {}",
+            line_number,
+            line.trim()
+        ),
+        history::Problem::InvalidId { line_number, id } => format!(
+            "Line {} of the schema history file has the ID \"{}\", but IDs must be made of lowercase letters.",
+            line_number, id
+        ),
+        history::Problem::DuplicateEntry { line_number, name } => format!(
+            "Line {} of the schema history file has a second entry for {}, or reuses another entry's ID. Every name and ID must appear only once per record or type.",
+            line_number, name
+        ),
+        history::Problem::Io(err) => format!("I could not read or write the schema history file: {}", err),
     }
 }
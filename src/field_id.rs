@@ -25,7 +25,6 @@ use types::FieldId;
 /// and more error-prone it is for others to make third-party tooling around it.
 ///
 /// All in all, this seems like the best trade-off of compactness to simplicity!
-
 #[allow(dead_code)]
 pub fn from_string(str: &str) -> Option<FieldId> {
     if str.is_empty() {
@@ -33,10 +32,10 @@ pub fn from_string(str: &str) -> Option<FieldId> {
     }
 
     let mut result: u64 = 0;
-    let mut index: u32 = 0;
     let last_index: u32 = str.len() as u32 - 1;
 
-    for char in str.chars() {
+    for (index, char) in str.chars().enumerate() {
+        let index = index as u32;
         let num: u64 = char as u64;
 
         if num > 122 {
@@ -49,8 +48,6 @@ pub fn from_string(str: &str) -> Option<FieldId> {
         } else {
             return None;
         }
-
-        index += 1;
     }
 
     // We've been 1-indexed up to this point, but we want to be 0-indexed.
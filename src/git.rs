@@ -0,0 +1,106 @@
+use loader::Source;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Reading files as they were at a particular revision, e.g. `origin/main` or
+/// a release tag, by shelling out to `git`. This lets the baseline for
+/// compatibility checks be whatever was actually deployed, rather than a
+/// snapshot which someone had to remember to update.
+#[derive(Debug)]
+pub enum Problem {
+    /// `git` couldn't be run at all, e.g. because it isn't installed.
+    Unavailable(io::Error),
+    Failed {
+        args: Vec<String>,
+        stderr: String,
+    },
+}
+
+/// Find every `.wf` file under the given paths (relative to `directory`) as of
+/// the given revision, and read them in. Like `loader::read_sources`, they're
+/// sorted by path.
+pub fn read_sources(
+    directory: &Path,
+    revision: &str,
+    paths: &[PathBuf],
+) -> Result<Vec<Source>, Problem> {
+    let mut args: Vec<String> = vec![
+        String::from("ls-tree"),
+        String::from("-r"),
+        String::from("--name-only"),
+        revision.to_string(),
+        String::from("--"),
+    ];
+
+    args.extend(paths.iter().map(|path| path.to_string_lossy().into_owned()));
+
+    let listing = run(directory, args)?;
+    let mut file_paths: Vec<PathBuf> = listing
+        .lines()
+        .map(PathBuf::from)
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wf") || paths.contains(path))
+        .collect();
+
+    file_paths.sort();
+    file_paths.dedup();
+
+    file_paths
+        .into_iter()
+        .map(|path| {
+            let contents = show(directory, revision, &path)?;
+
+            Ok((path, contents))
+        })
+        .collect()
+}
+
+/// The contents of a single file as of the given revision, or `None` if it
+/// didn't exist then.
+pub fn read_file(directory: &Path, revision: &str, path: &Path) -> Result<Option<String>, Problem> {
+    let listing = run(
+        directory,
+        vec![
+            String::from("ls-tree"),
+            String::from("--name-only"),
+            revision.to_string(),
+            String::from("--"),
+            path.to_string_lossy().into_owned(),
+        ],
+    )?;
+
+    if listing.trim().is_empty() {
+        Ok(None)
+    } else {
+        show(directory, revision, path).map(Some)
+    }
+}
+
+fn show(directory: &Path, revision: &str, path: &Path) -> Result<String, Problem> {
+    // The ./ makes the path relative to the directory rather than to the root
+    // of the repository.
+    run(
+        directory,
+        vec![
+            String::from("show"),
+            format!("{}:./{}", revision, path.to_string_lossy()),
+        ],
+    )
+}
+
+fn run(directory: &Path, args: Vec<String>) -> Result<String, Problem> {
+    let output = Command::new("git")
+        .current_dir(directory)
+        .args(&args)
+        .output()
+        .map_err(Problem::Unavailable)?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(Problem::Failed {
+            args,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
@@ -0,0 +1,680 @@
+use field_id;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use types::{FieldId, Schema};
+
+/// Schema versions start at 1, and go up by 1 every time the history is
+/// updated. Version 0 means nothing has been recorded yet.
+pub type SchemaVersion = u32;
+
+pub const FORMAT_HEADER: &str = "wirefunc history 1";
+
+/// How many schema versions development builds keep accepting a renamed
+/// field, variant, record, or type under its former name, if `--rename-grace`
+/// isn't specified.
+pub const DEFAULT_RENAME_GRACE: SchemaVersion = 3;
+
+/// How many of the most recently released schemas the history keeps
+/// snapshots of, if `--window` isn't specified.
+pub const DEFAULT_SNAPSHOT_WINDOW: usize = 5;
+
+const INDENT: &str = "    ";
+
+/// The schema history is a file that gets committed alongside the `.wf` files.
+/// It pins every record field and custom type variant to the ID it was
+/// assigned the first time `wf` saw it. That way, IDs (and therefore minified
+/// JSON field names) stay the same no matter how fields get reordered.
+///
+/// It looks like this:
+///
+/// ```text
+/// wirefunc history 1
+/// version 3
+///
+/// record User fka Person 2
+///     a userId since 1 fka id 3
+///     b profile since 1 retyped 3
+///     c aliases since 2
+///     d id since 1 retired 3
+///
+/// type Result
+///     a ok since 1
+///     b err since 1
+///
+/// snapshot 2
+///     struct Person
+///         * id : Int
+///         ...
+///
+/// snapshot 3
+///     struct User
+///         * userId : Int
+///         ...
+/// ```
+///
+/// * The first line identifies the file format, so we can change it later.
+/// * `version` is the most recent schema version recorded in this history.
+/// * Each `record` and `type` section lists one entry per field or variant.
+///   An entry begins with its ID, written as its production minified name
+///   (see `field_id::to_string`), followed by its name in the schema and the
+///   schema version in which it first appeared.
+/// * Endpoint params and responses, and inline records, get `record` sections
+///   too. They have no names, so their sections are named for where they are
+///   in the schema instead, e.g. `record sendDM.params` or
+///   `record User.profile` (see `RecordInfo::location`).
+/// * Entries for fields or variants which have been removed from the schema
+///   end with the version in which they were retired. Their IDs are never
+///   handed out again, because some client somewhere might still be sending
+///   them with the old meaning! For the same reason, `wf` refuses to build a
+///   schema which reuses a retired name (see
+///   `code_gen::check_for_retired_names`).
+/// * `fka` (formerly known as) records a rename, along with the version in
+///   which it happened. The entry keeps its ID, so production builds are
+///   unaffected, and development builds accept the former name for a grace
+///   period (see `FormerName::is_accepted`). Former names are reserved just
+///   like retired ones.
+/// * `retyped` records a version in which the field or variant's type changed
+///   incompatibly, and `wf migrate` was told to keep its ID anyway (see
+///   `migration::Decision::KeepId`), so it isn't asked about again.
+/// * Each `snapshot` holds the `.wf` source of the schema as of the given
+///   version, indented by four spaces. Only the most recent few are kept.
+///   They're what `wf check-compat` compares against, since clients and
+///   servers running any of those versions may still be out there.
+/// * Lines beginning with `#` are comments, and blank lines are ignored.
+///
+/// Sections are written sorted by name, and entries sorted by ID, so that
+/// diffs of this file in code review are as small and readable as possible.
+#[derive(Debug, Clone, PartialEq)]
+pub struct History {
+    pub version: SchemaVersion,
+    pub records: Vec<Ledger>,
+    pub custom_types: Vec<Ledger>,
+    /// Oldest first
+    pub snapshots: Vec<Snapshot>,
+}
+
+/// The `.wf` source of the schema as of a particular version, which
+/// `wf check-compat` compares against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub version: SchemaVersion,
+    pub source: String,
+}
+
+/// All the IDs ever handed out within a single record or custom type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ledger {
+    pub name: String,
+    pub former_names: Vec<FormerName>,
+    pub entries: Vec<Entry>,
+}
+
+/// The ID assigned to a single record field or custom type variant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub id: FieldId,
+    pub since: SchemaVersion,
+    pub former_names: Vec<FormerName>,
+
+    /// Versions in which its type changed, but it kept its ID.
+    pub retyped: Vec<SchemaVersion>,
+    pub retired: Option<SchemaVersion>,
+}
+
+/// A name something had before it was renamed with `[ fka "..." ]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormerName {
+    pub name: String,
+
+    /// The first version in which it had its new name.
+    pub renamed: SchemaVersion,
+}
+
+#[derive(Debug)]
+pub enum Problem {
+    MissingHeader,
+    UnsupportedFormat(String),
+    InvalidLine { line_number: usize, line: String },
+    InvalidId { line_number: usize, id: String },
+    DuplicateEntry { line_number: usize, name: String },
+    Io(io::Error),
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            version: 0,
+            records: vec![],
+            custom_types: vec![],
+            snapshots: vec![],
+        }
+    }
+
+    pub fn record(&self, name: &str) -> Option<&Ledger> {
+        self.records.iter().find(|ledger| ledger.name == name)
+    }
+
+    pub fn custom_type(&self, name: &str) -> Option<&Ledger> {
+        self.custom_types.iter().find(|ledger| ledger.name == name)
+    }
+
+    pub fn latest_snapshot(&self) -> Option<&Snapshot> {
+        self.snapshots.last()
+    }
+
+    /// Record the given `.wf` source as the latest snapshot, bumping the version
+    /// if anything changed since the previous snapshot, and then drop all but
+    /// the most recent `window` snapshots. Returns the version of the snapshot.
+    pub fn record_snapshot(&mut self, source: String, window: usize) -> SchemaVersion {
+        let source = normalize(source.as_str());
+        let version = match self.snapshots.last() {
+            Some(snapshot) if snapshot.source == source => snapshot.version,
+            previous => {
+                if previous.is_some_and(|snapshot| snapshot.version == self.version)
+                    || self.version == 0
+                {
+                    self.version += 1;
+                }
+
+                self.snapshots.push(Snapshot {
+                    version: self.version,
+                    source,
+                });
+
+                self.version
+            }
+        };
+
+        if self.snapshots.len() > window {
+            let excess = self.snapshots.len() - window;
+
+            self.snapshots.drain(..excess);
+        }
+
+        version
+    }
+
+    /// Set the `field_id` of every field and the `variant_id` of every variant
+    /// which already has an entry in this history.
+    ///
+    /// Returns the qualified names (e.g. "User.email") of the fields and
+    /// variants which have no entry yet, and therefore still need IDs.
+    pub fn pin_ids(&self, schema: &mut Schema) -> Vec<String> {
+        let mut unpinned: Vec<String> = vec![];
+
+        for record in schema.records.iter_mut() {
+            let record_name = match record.name {
+                Some(ref name) => name.to_string(),
+                None => continue,
+            };
+            let ledger = self.record(record_name.as_str());
+
+            for field in record.fields.iter_mut() {
+                match ledger.and_then(|ledger| ledger.entry(field.name.as_str())) {
+                    Some(entry) => field.field_id = entry.id,
+                    None => unpinned.push(format!("{}.{}", record_name, field.name)),
+                }
+            }
+        }
+
+        schema.visit_inline_records_mut(&mut |location, fields| {
+            let ledger = self.record(location);
+
+            for field in fields.iter_mut() {
+                match ledger.and_then(|ledger| ledger.entry(field.name.as_str())) {
+                    Some(entry) => field.field_id = entry.id,
+                    None => unpinned.push(format!("{}.{}", location, field.name)),
+                }
+            }
+        });
+
+        for custom_type in schema.custom_types.iter_mut() {
+            let type_name = custom_type.name.to_string();
+            let ledger = self.custom_type(type_name.as_str());
+
+            for variant in custom_type.variants.iter_mut() {
+                match ledger.and_then(|ledger| ledger.entry(variant.name.as_str())) {
+                    Some(entry) => variant.variant_id = entry.id,
+                    None => unpinned.push(format!("{}.{}", type_name, variant.name)),
+                }
+            }
+        }
+
+        unpinned
+    }
+
+    /// The qualified names (e.g. "User.email") of the entries in this history
+    /// which `newer` doesn't have, or has given a different ID. If there are
+    /// none, `newer` carries on from this history, and can replace it.
+    pub fn missing_from(&self, newer: &History) -> Vec<String> {
+        let mut missing: Vec<String> = vec![];
+
+        for (ledgers, newer_ledgers) in [
+            (&self.records, &newer.records),
+            (&self.custom_types, &newer.custom_types),
+        ]
+        .iter()
+        {
+            for ledger in ledgers.iter() {
+                let newer_ledger = newer_ledgers
+                    .iter()
+                    .find(|newer_ledger| newer_ledger.is_known_as(&ledger.name));
+
+                for entry in ledger.entries.iter() {
+                    let kept = newer_ledger.is_some_and(|newer_ledger| {
+                        newer_ledger.entries.iter().any(|newer_entry| {
+                            newer_entry.id == entry.id && newer_entry.is_known_as(&entry.name)
+                        })
+                    });
+
+                    if !kept {
+                        missing.push(format!("{}.{}", ledger.name, entry.name));
+                    }
+                }
+            }
+        }
+
+        missing
+    }
+}
+
+impl Default for History {
+    fn default() -> History {
+        History::new()
+    }
+}
+
+impl Ledger {
+    pub fn new(name: &str) -> Ledger {
+        Ledger {
+            name: name.to_string(),
+            former_names: vec![],
+            entries: vec![],
+        }
+    }
+
+    /// The entry currently in use for the given name, if any. Retired entries
+    /// are ignored.
+    pub fn entry(&self, name: &str) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name == name && entry.retired.is_none())
+    }
+
+    /// Names which only belong to retired entries, or which entries had before
+    /// being renamed. A name which was retired and later reintroduced (before
+    /// `wf` enforced this) is not included, since it has an entry in use.
+    pub fn retired_names(&self) -> HashSet<&str> {
+        let mut names: HashSet<&str> = HashSet::new();
+
+        for entry in self.entries.iter() {
+            if entry.retired.is_some() {
+                names.insert(entry.name.as_str());
+            }
+
+            for former_name in entry.former_names.iter() {
+                names.insert(former_name.name.as_str());
+            }
+        }
+
+        names.retain(|name| self.entry(name).is_none());
+
+        names
+    }
+
+    /// Whether this is the ledger's name, or one it had before being renamed.
+    pub fn is_known_as(&self, name: &str) -> bool {
+        self.name == name
+            || self
+                .former_names
+                .iter()
+                .any(|former_name| former_name.name == name)
+    }
+
+    /// Whether this ledger is for an endpoint body or inline records, rather
+    /// than a named record. Those are named for their location, which always
+    /// has a dot in it, e.g. "sendDM.params".
+    pub fn is_inline(&self) -> bool {
+        self.name.contains('.')
+    }
+
+    /// Like `Entry::accepted_names`, but for the record or custom type itself.
+    pub fn accepted_names(&self, version: SchemaVersion, grace: SchemaVersion) -> Vec<&str> {
+        accepted_names(&self.name, &self.former_names, version, grace)
+    }
+
+    /// The lowest ID which has never been handed out in this ledger, including
+    /// to entries which have since been retired.
+    pub fn next_id(&self) -> FieldId {
+        self.entries
+            .iter()
+            .map(|entry| entry.id + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl Entry {
+    /// Whether this is the entry's name, or one it had before being renamed.
+    pub fn is_known_as(&self, name: &str) -> bool {
+        self.name == name
+            || self
+                .former_names
+                .iter()
+                .any(|former_name| former_name.name == name)
+    }
+
+    /// The names development builds accept for this entry as of the given
+    /// version: its current name, followed by any former names which are still
+    /// within the grace period.
+    pub fn accepted_names(&self, version: SchemaVersion, grace: SchemaVersion) -> Vec<&str> {
+        accepted_names(&self.name, &self.former_names, version, grace)
+    }
+}
+
+impl FormerName {
+    /// Whether development builds should still accept this name as of the
+    /// given version. A grace of 3 means the name is accepted in the version it
+    /// was renamed in, plus the two after that. A grace of 0 means it's never
+    /// accepted.
+    pub fn is_accepted(&self, version: SchemaVersion, grace: SchemaVersion) -> bool {
+        version < self.renamed.saturating_add(grace)
+    }
+}
+
+fn accepted_names<'a>(
+    name: &'a str,
+    former_names: &'a [FormerName],
+    version: SchemaVersion,
+    grace: SchemaVersion,
+) -> Vec<&'a str> {
+    let mut names = vec![name];
+
+    for former_name in former_names.iter().rev() {
+        if former_name.is_accepted(version, grace) {
+            names.push(former_name.name.as_str());
+        }
+    }
+
+    names
+}
+
+/// Snapshot sources always end in exactly one newline, so that reading them
+/// back in from the file gives the same source that was written.
+fn normalize(source: &str) -> String {
+    let mut normalized = source.trim_end().to_string();
+
+    normalized.push('\n');
+
+    normalized
+}
+
+pub fn read_from(path: &Path) -> Result<History, Problem> {
+    let source = fs::read_to_string(path).map_err(Problem::Io)?;
+
+    parse(source.as_str())
+}
+
+pub fn write_to(path: &Path, history: &History) -> Result<(), Problem> {
+    fs::write(path, to_string(history)).map_err(Problem::Io)
+}
+
+enum Section {
+    Record,
+    CustomType,
+    Snapshot,
+}
+
+pub fn parse(source: &str) -> Result<History, Problem> {
+    let mut history = History::new();
+    let mut has_header = false;
+    let mut section: Option<Section> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            // Blank lines are part of the snapshot's source.
+            if let (Some(Section::Snapshot), Some(snapshot)) =
+                (&section, history.snapshots.last_mut())
+            {
+                snapshot.source.push('\n');
+            }
+
+            continue;
+        } else if trimmed.starts_with('#') {
+            continue;
+        }
+
+        if !has_header {
+            if trimmed == FORMAT_HEADER {
+                has_header = true;
+                continue;
+            } else if trimmed.starts_with("wirefunc history") {
+                return Err(Problem::UnsupportedFormat(trimmed.to_string()));
+            } else {
+                return Err(Problem::MissingHeader);
+            }
+        }
+
+        let invalid_line = || Problem::InvalidLine {
+            line_number,
+            line: line.to_string(),
+        };
+        let words: Vec<&str> = trimmed.split_whitespace().collect();
+        let is_indented = line.starts_with(' ') || line.starts_with('\t');
+
+        if let (true, Some(Section::Snapshot), Some(snapshot)) =
+            (is_indented, &section, history.snapshots.last_mut())
+        {
+            let unindented = match line.strip_prefix('\t') {
+                Some(rest) => rest,
+                None => line.get(INDENT.len()..).unwrap_or(""),
+            };
+
+            snapshot.source.push_str(unindented);
+            snapshot.source.push('\n');
+
+            continue;
+        }
+
+        if !is_indented {
+            match words.as_slice() {
+                ["version", version] => {
+                    history.version = version.parse().map_err(|_| invalid_line())?;
+                }
+                ["record", name, rest @ ..] => {
+                    history.records.push(Ledger {
+                        former_names: parse_former_names(rest).ok_or_else(invalid_line)?,
+                        ..Ledger::new(name)
+                    });
+                    section = Some(Section::Record);
+                }
+                ["type", name, rest @ ..] => {
+                    history.custom_types.push(Ledger {
+                        former_names: parse_former_names(rest).ok_or_else(invalid_line)?,
+                        ..Ledger::new(name)
+                    });
+                    section = Some(Section::CustomType);
+                }
+                ["snapshot", version] => {
+                    history.snapshots.push(Snapshot {
+                        version: version.parse().map_err(|_| invalid_line())?,
+                        source: String::new(),
+                    });
+                    section = Some(Section::Snapshot);
+                }
+                _ => return Err(invalid_line()),
+            }
+
+            continue;
+        }
+
+        let ledger = match section {
+            Some(Section::Record) => history.records.last_mut(),
+            Some(Section::CustomType) => history.custom_types.last_mut(),
+            Some(Section::Snapshot) | None => None,
+        }
+        .ok_or_else(invalid_line)?;
+
+        let (id, name, since, rest) = match words.as_slice() {
+            [id, name, "since", since, rest @ ..] => (id, name, since, rest),
+            _ => return Err(invalid_line()),
+        };
+        let (changes, retired) = match rest {
+            [changes @ .., "retired", retired] => (changes, Some(retired)),
+            changes => (changes, None),
+        };
+        let (former_names, retyped) = parse_changes(changes).ok_or_else(invalid_line)?;
+        let entry = Entry {
+            name: name.to_string(),
+            id: field_id::from_string(id).ok_or_else(|| Problem::InvalidId {
+                line_number,
+                id: id.to_string(),
+            })?,
+            since: since.parse().map_err(|_| invalid_line())?,
+            former_names,
+            retyped,
+            retired: match retired {
+                Some(retired) => Some(retired.parse().map_err(|_| invalid_line())?),
+                None => None,
+            },
+        };
+
+        if ledger.entries.iter().any(|existing| {
+            existing.id == entry.id
+                || (existing.name == entry.name
+                    && existing.retired.is_none()
+                    && entry.retired.is_none())
+        }) {
+            return Err(Problem::DuplicateEntry {
+                line_number,
+                name: entry.name,
+            });
+        }
+
+        ledger.entries.push(entry);
+    }
+
+    for snapshot in history.snapshots.iter_mut() {
+        snapshot.source = normalize(snapshot.source.as_str());
+    }
+
+    history.snapshots.sort_by_key(|snapshot| snapshot.version);
+
+    if has_header {
+        Ok(history)
+    } else {
+        Err(Problem::MissingHeader)
+    }
+}
+
+/// Parses any number of `fka oldName 3`
+fn parse_former_names(words: &[&str]) -> Option<Vec<FormerName>> {
+    words
+        .chunks(3)
+        .map(|chunk| match chunk {
+            ["fka", name, renamed] => Some(FormerName {
+                name: name.to_string(),
+                renamed: renamed.parse().ok()?,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parses an entry's `fka oldName 3` and `retyped 4` words, fkas first.
+fn parse_changes(words: &[&str]) -> Option<(Vec<FormerName>, Vec<SchemaVersion>)> {
+    let renames = words
+        .iter()
+        .position(|word| *word == "retyped")
+        .unwrap_or(words.len());
+    let (renames, retypes) = words.split_at(renames);
+    let retyped = retypes
+        .chunks(2)
+        .map(|chunk| match chunk {
+            ["retyped", version] => version.parse().ok(),
+            _ => None,
+        })
+        .collect::<Option<Vec<SchemaVersion>>>()?;
+
+    Some((parse_former_names(renames)?, retyped))
+}
+
+pub fn to_string(history: &History) -> String {
+    let mut output = String::new();
+
+    output.push_str("# This file is generated by wf. Commit it to version control!\n");
+    output.push_str(FORMAT_HEADER);
+    output.push('\n');
+    output.push_str(format!("version {}\n", history.version).as_str());
+
+    write_ledgers(&mut output, "record", &history.records);
+    write_ledgers(&mut output, "type", &history.custom_types);
+
+    for snapshot in history.snapshots.iter() {
+        output.push_str(format!("\nsnapshot {}\n", snapshot.version).as_str());
+
+        for line in snapshot.source.trim_end().lines() {
+            if line.trim().is_empty() {
+                output.push('\n');
+            } else {
+                output.push_str(INDENT);
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+
+    output
+}
+
+fn write_ledgers(output: &mut String, keyword: &str, ledgers: &[Ledger]) {
+    let mut sorted: Vec<&Ledger> = ledgers.iter().collect();
+
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for ledger in sorted {
+        let mut entries: Vec<&Entry> = ledger.entries.iter().collect();
+
+        entries.sort_by_key(|entry| entry.id);
+
+        output.push_str(format!("\n{} {}", keyword, ledger.name).as_str());
+        write_former_names(output, &ledger.former_names);
+        output.push('\n');
+
+        for entry in entries {
+            output.push_str(
+                format!(
+                    "    {} {} since {}",
+                    field_id::to_string(entry.id),
+                    entry.name,
+                    entry.since
+                )
+                .as_str(),
+            );
+
+            write_former_names(output, &entry.former_names);
+
+            for retyped in entry.retyped.iter() {
+                output.push_str(format!(" retyped {}", retyped).as_str());
+            }
+
+            if let Some(retired) = entry.retired {
+                output.push_str(format!(" retired {}", retired).as_str());
+            }
+
+            output.push('\n');
+        }
+    }
+}
+
+fn write_former_names(output: &mut String, former_names: &[FormerName]) {
+    for former_name in former_names.iter() {
+        output.push_str(format!(" fka {} {}", former_name.name, former_name.renamed).as_str());
+    }
+}
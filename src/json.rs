@@ -0,0 +1,486 @@
+use std::fmt;
+
+/// A JSON value. Objects keep their keys in order, so that encoding a value
+/// we decoded gives back the same JSON.
+///
+/// Integers are kept separate from floats, so that an `Int64` doesn't lose
+/// precision on its way through.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The value of the given key, if this is an object which has it.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// What kind of value this is, for error messages, e.g. "an array"
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "a boolean",
+            Value::Int(_) => "an integer",
+            Value::Float(_) => "a number",
+            Value::String(_) => "a string",
+            Value::Array(_) => "an array",
+            Value::Object(_) => "an object",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(formatter, "null"),
+            Value::Bool(bool) => write!(formatter, "{}", bool),
+            Value::Int(int) => write!(formatter, "{}", int),
+            // JSON has no NaN or Infinity.
+            Value::Float(float) if !float.is_finite() => write!(formatter, "null"),
+            Value::Float(float) if float.fract() == 0.0 && float.abs() < 1e15 => {
+                write!(formatter, "{:.1}", float)
+            }
+            Value::Float(float) => write!(formatter, "{}", float),
+            Value::String(string) => write_string(formatter, string),
+            Value::Array(elems) => {
+                write!(formatter, "[")?;
+
+                for (index, elem) in elems.iter().enumerate() {
+                    if index > 0 {
+                        write!(formatter, ",")?;
+                    }
+
+                    write!(formatter, "{}", elem)?;
+                }
+
+                write!(formatter, "]")
+            }
+            Value::Object(entries) => {
+                write!(formatter, "{{")?;
+
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(formatter, ",")?;
+                    }
+
+                    write_string(formatter, key)?;
+                    write!(formatter, ":{}", value)?;
+                }
+
+                write!(formatter, "}}")
+            }
+        }
+    }
+}
+
+/// The value as JSON indented by two spaces, for files people will read.
+/// Empty arrays and objects stay on one line.
+pub fn to_pretty_string(value: &Value) -> String {
+    let mut pretty = String::new();
+
+    write_pretty(&mut pretty, value, 0);
+    pretty.push('\n');
+
+    pretty
+}
+
+fn write_pretty(pretty: &mut String, value: &Value, depth: usize) {
+    let indent = |pretty: &mut String, depth: usize| {
+        pretty.push('\n');
+        pretty.push_str(&"  ".repeat(depth));
+    };
+
+    match value {
+        Value::Array(elems) if !elems.is_empty() => {
+            pretty.push('[');
+
+            for (index, elem) in elems.iter().enumerate() {
+                if index > 0 {
+                    pretty.push(',');
+                }
+
+                indent(pretty, depth + 1);
+                write_pretty(pretty, elem, depth + 1);
+            }
+
+            indent(pretty, depth);
+            pretty.push(']');
+        }
+        Value::Object(entries) if !entries.is_empty() => {
+            pretty.push('{');
+
+            for (index, (key, value)) in entries.iter().enumerate() {
+                if index > 0 {
+                    pretty.push(',');
+                }
+
+                indent(pretty, depth + 1);
+                pretty.push_str(&format!("{}: ", Value::String(key.clone())));
+                write_pretty(pretty, value, depth + 1);
+            }
+
+            indent(pretty, depth);
+            pretty.push('}');
+        }
+        other => pretty.push_str(&other.to_string()),
+    }
+}
+
+fn write_string(formatter: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    write!(formatter, "\"")?;
+
+    for char in string.chars() {
+        match char {
+            '"' => write!(formatter, "\\\"")?,
+            '\\' => write!(formatter, "\\\\")?,
+            '\n' => write!(formatter, "\\n")?,
+            '\r' => write!(formatter, "\\r")?,
+            '\t' => write!(formatter, "\\t")?,
+            char if (char as u32) < 0x20 => write!(formatter, "\\u{:04x}", char as u32)?,
+            char => write!(formatter, "{}", char)?,
+        }
+    }
+
+    write!(formatter, "\"")
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+    /// How many bytes into the input the problem is.
+    pub offset: usize,
+
+    /// What we were expecting to find instead, e.g. "a `:` after the key"
+    pub expected: &'static str,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "invalid JSON at byte {}: expected {}",
+            self.offset, self.expected
+        )
+    }
+}
+
+/// Parse a complete JSON document. Anything but whitespace after the value is
+/// an error.
+pub fn parse(source: &str) -> Result<Value, Problem> {
+    let mut parser = Parser {
+        bytes: source.as_bytes(),
+        offset: 0,
+    };
+    let value = parser.value()?;
+
+    parser.skip_whitespace();
+
+    if parser.offset < parser.bytes.len() {
+        Err(parser.problem("the end of the input"))
+    } else {
+        Ok(value)
+    }
+}
+
+/// The maximum depth of nested arrays and objects, so that hostile input like
+/// `[[[[[[...` can't overflow the stack.
+pub const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn problem(&self, expected: &'static str) -> Problem {
+        Problem {
+            offset: self.offset,
+            expected,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.offset).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\n') | Some(b'\r') | Some(b'\t') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8, expected: &'static str) -> Result<(), Problem> {
+        self.skip_whitespace();
+
+        if self.peek() == Some(byte) {
+            self.offset += 1;
+
+            Ok(())
+        } else {
+            Err(self.problem(expected))
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: Value) -> Result<Value, Problem> {
+        if self.bytes[self.offset..].starts_with(literal.as_bytes()) {
+            self.offset += literal.len();
+
+            Ok(value)
+        } else {
+            Err(self.problem("a value"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Problem> {
+        self.nested_value(0)
+    }
+
+    fn nested_value(&mut self, depth: usize) -> Result<Value, Problem> {
+        if depth > MAX_DEPTH {
+            return Err(self.problem("less deeply nested arrays and objects"));
+        }
+
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'[') => {
+                let mut elems: Vec<Value> = vec![];
+
+                self.offset += 1;
+                self.skip_whitespace();
+
+                if self.peek() == Some(b']') {
+                    self.offset += 1;
+
+                    return Ok(Value::Array(elems));
+                }
+
+                loop {
+                    elems.push(self.nested_value(depth + 1)?);
+                    self.skip_whitespace();
+
+                    match self.peek() {
+                        Some(b',') => self.offset += 1,
+                        Some(b']') => {
+                            self.offset += 1;
+
+                            return Ok(Value::Array(elems));
+                        }
+                        _ => return Err(self.problem("a `,` or `]`")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                let mut entries: Vec<(String, Value)> = vec![];
+
+                self.offset += 1;
+                self.skip_whitespace();
+
+                if self.peek() == Some(b'}') {
+                    self.offset += 1;
+
+                    return Ok(Value::Object(entries));
+                }
+
+                loop {
+                    self.skip_whitespace();
+
+                    if self.peek() != Some(b'"') {
+                        return Err(self.problem("a string key"));
+                    }
+
+                    let key = self.string()?;
+
+                    self.expect(b':', "a `:` after the key")?;
+                    entries.push((key, self.nested_value(depth + 1)?));
+                    self.skip_whitespace();
+
+                    match self.peek() {
+                        Some(b',') => self.offset += 1,
+                        Some(b'}') => {
+                            self.offset += 1;
+
+                            return Ok(Value::Object(entries));
+                        }
+                        _ => return Err(self.problem("a `,` or `}`")),
+                    }
+                }
+            }
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            _ => Err(self.problem("a value")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, Problem> {
+        let start = self.offset;
+        let mut is_float = false;
+
+        if self.peek() == Some(b'-') {
+            self.offset += 1;
+        }
+
+        if !self.digits() {
+            return Err(self.problem("a digit"));
+        }
+
+        if self.peek() == Some(b'.') {
+            is_float = true;
+            self.offset += 1;
+
+            if !self.digits() {
+                return Err(self.problem("a digit after the `.`"));
+            }
+        }
+
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            is_float = true;
+            self.offset += 1;
+
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.offset += 1;
+            }
+
+            if !self.digits() {
+                return Err(self.problem("a digit in the exponent"));
+            }
+        }
+
+        // Only ASCII digits and signs were consumed, so this can't fail.
+        let text = ::std::str::from_utf8(&self.bytes[start..self.offset]).unwrap();
+
+        match text.parse::<i64>() {
+            Ok(int) if !is_float => Ok(Value::Int(int)),
+            _ => text
+                .parse::<f64>()
+                .map(Value::Float)
+                .map_err(|_| self.problem("a number")),
+        }
+    }
+
+    /// Consume a run of digits, returning whether there were any.
+    fn digits(&mut self) -> bool {
+        let start = self.offset;
+
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.offset += 1;
+        }
+
+        self.offset > start
+    }
+
+    fn string(&mut self) -> Result<String, Problem> {
+        // Skip the opening quote
+        self.offset += 1;
+
+        let mut string = String::new();
+
+        loop {
+            let start = self.offset;
+
+            while let Some(byte) = self.peek() {
+                if byte == b'"' || byte == b'\\' || byte < 0x20 {
+                    break;
+                }
+
+                self.offset += 1;
+            }
+
+            // The input came from a &str, and we only stopped on ASCII bytes,
+            // so this is always on a char boundary.
+            string.push_str(::std::str::from_utf8(&self.bytes[start..self.offset]).unwrap());
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.offset += 1;
+
+                    return Ok(string);
+                }
+                Some(b'\\') => {
+                    self.offset += 1;
+
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.offset += 1;
+
+                            let char = self.unicode_escape()?;
+
+                            string.push(char);
+
+                            continue;
+                        }
+                        _ => return Err(self.problem("a valid escape sequence")),
+                    };
+
+                    self.offset += 1;
+                    string.push(escaped);
+                }
+                Some(_) => return Err(self.problem("control characters to be escaped")),
+                None => return Err(self.problem("a closing `\"`")),
+            }
+        }
+    }
+
+    /// Parses the XXXX of a \uXXXX escape, along with the low half of a
+    /// surrogate pair if it's the high half of one.
+    fn unicode_escape(&mut self) -> Result<char, Problem> {
+        let high = self.hex4()?;
+
+        let code_point = if (0xD800..0xDC00).contains(&high) {
+            if !self.bytes[self.offset..].starts_with(b"\\u") {
+                return Err(self.problem("the low half of a surrogate pair"));
+            }
+
+            self.offset += 2;
+
+            let low = self.hex4()?;
+
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.problem("the low half of a surrogate pair"));
+            }
+
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        ::std::char::from_u32(code_point).ok_or_else(|| self.problem("a valid code point"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, Problem> {
+        let digits = self
+            .bytes
+            .get(self.offset..self.offset + 4)
+            .filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|digits| ::std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.problem("four hex digits"))?;
+
+        self.offset += 4;
+
+        Ok(digits)
+    }
+}
@@ -0,0 +1,540 @@
+use code_gen::{MinificationTable, Problem};
+use codec::{bind, Bindings, Names, Packing, CONTENTS_KEY, TYPE_KEY, VARIANT_KEY};
+use json;
+use json::Value;
+use types::{
+    CustomTypeInfo, Field, Optionality, RecordInfo, Schema, Type, TypeParam, TypePrimitive,
+};
+
+/// The JSON Schema dialect exported documents use.
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Describe the JSON the `codec` module reads and writes with the given names
+/// as a JSON Schema document, for tools which don't understand `.wf` files.
+///
+/// Every record, custom type and endpoint body is in `$defs`, e.g.
+/// `#/$defs/User` or `#/$defs/sendDM.params`. A record can look different
+/// depending on where it is when it's packed (see the `codec` module), so
+/// production names can add defs like `User.packed`, for a record packed into
+/// a tuple, or `User.inPackedEndpoint`, for one inside an endpoint marked
+/// `[ packed ]`.
+///
+/// Declarations with type params have no def of their own. Each use of them
+/// is described in place, with its type args filled in.
+///
+/// Fields with defaults aren't `required`, since decoders fill them in, and
+/// deprecated fields are marked `deprecated`. Unknown fields are refused
+/// with full and development names, as the codec refuses them in application
+/// code, but allowed in production JSON, since it may come from someone
+/// running a newer schema.
+///
+/// Minified names come from the table `code_gen::productionize` made for the
+/// schema.
+pub fn export(schema: &Schema, table: &MinificationTable, names: Names) -> Result<Value, Problem> {
+    let mut exporter = Exporter {
+        schema,
+        table,
+        names,
+        defs: vec![],
+        missing: vec![],
+        expanding: vec![],
+    };
+    let context = Packing::TOP;
+
+    for record in schema.records.iter() {
+        if let Some(ref name) = record.name {
+            if record.params.is_empty() {
+                exporter.record_ref(record, name.to_string().as_str(), context);
+            }
+        }
+    }
+
+    for custom_type in schema.custom_types.iter() {
+        if custom_type.params.is_empty() {
+            exporter.custom_type_ref(custom_type);
+        }
+    }
+
+    for endpoint in schema.endpoints.iter() {
+        for record in [endpoint.params_record(), endpoint.response_record()] {
+            let def = exporter.record(&record, &vec![], context);
+
+            exporter
+                .defs
+                .push((record.location.unwrap_or_default(), def));
+        }
+    }
+
+    if !exporter.missing.is_empty() {
+        return Err(Problem::MissingIds(exporter.missing));
+    }
+
+    Ok(object(vec![
+        ("$schema", string(DIALECT)),
+        ("$defs", Value::Object(exporter.defs)),
+    ]))
+}
+
+struct Exporter<'a> {
+    schema: &'a Schema,
+    table: &'a MinificationTable,
+    names: Names,
+    defs: Vec<(String, Value)>,
+
+    /// Qualified names of fields the table has no minified name for.
+    missing: Vec<String>,
+
+    /// Declarations with type params which are being described in place, so
+    /// that a recursive one doesn't go on forever.
+    expanding: Vec<String>,
+}
+
+impl<'a> Exporter<'a> {
+    fn tipe(&mut self, tipe: &Type, bindings: &Bindings, context: Packing) -> Value {
+        let current = self.primitive(&tipe.current, bindings, context);
+
+        if tipe.formerly.is_empty() {
+            return current;
+        }
+
+        // Decoders still accept the field's former types.
+        let mut any_of = vec![current];
+
+        for former in tipe.formerly.iter() {
+            any_of.push(self.primitive(former, bindings, context));
+        }
+
+        object(vec![("anyOf", Value::Array(any_of))])
+    }
+
+    fn primitive(
+        &mut self,
+        primitive: &TypePrimitive,
+        bindings: &Bindings,
+        context: Packing,
+    ) -> Value {
+        match primitive {
+            TypePrimitive::Bool => schema_type("boolean"),
+            TypePrimitive::String => schema_type("string"),
+            TypePrimitive::Int | TypePrimitive::Int64 => schema_type("integer"),
+            TypePrimitive::Int8 => integer(i8::MIN as i64, i8::MAX as i64),
+            TypePrimitive::Int16 => integer(i16::MIN as i64, i16::MAX as i64),
+            TypePrimitive::Int32 => integer(i32::MIN as i64, i32::MAX as i64),
+            TypePrimitive::Float | TypePrimitive::Float32 | TypePrimitive::Float64 => {
+                schema_type("number")
+            }
+            TypePrimitive::Array(elem) => object(vec![
+                ("type", string("array")),
+                ("items", self.tipe(elem, bindings, context.nested(true))),
+            ]),
+            TypePrimitive::Set(elem) => object(vec![
+                ("type", string("array")),
+                ("items", self.tipe(elem, bindings, context.nested(true))),
+                ("uniqueItems", Value::Bool(true)),
+            ]),
+            TypePrimitive::Dict(key, val) => {
+                let context = context.unpackable();
+
+                if self.is_string(key, bindings) {
+                    object(vec![
+                        ("type", string("object")),
+                        ("additionalProperties", self.tipe(val, bindings, context)),
+                    ])
+                } else {
+                    let pair = vec![
+                        self.tipe(key, bindings, context),
+                        self.tipe(val, bindings, context),
+                    ];
+
+                    object(vec![
+                        ("type", string("array")),
+                        ("items", fixed_array(pair)),
+                    ])
+                }
+            }
+            TypePrimitive::Record(info) => self.record(info, bindings, context),
+            TypePrimitive::Custom(info) => self.custom_type(info, bindings),
+            TypePrimitive::Named(name, args) => {
+                let name = name.to_string();
+
+                self.named(name.as_str(), args, bindings, context)
+            }
+            TypePrimitive::Param(TypeParam::TypeParam(param)) => {
+                match bindings.iter().find(|(name, _)| name == param) {
+                    Some((_, tipe)) => {
+                        let tipe = tipe.clone();
+
+                        self.tipe(&tipe, bindings, context.unpackable())
+                    }
+                    None => object(vec![]),
+                }
+            }
+        }
+    }
+
+    fn named(&mut self, name: &str, args: &[Type], bindings: &Bindings, context: Packing) -> Value {
+        let schema = self.schema;
+
+        if let Some(record) = schema.record(name) {
+            if record.params.is_empty() {
+                return self.record_ref(record, name, context);
+            }
+
+            let bindings = bind(&record.params, args, bindings);
+
+            self.in_place(name, |exporter| exporter.record(record, &bindings, context))
+        } else if let Some(custom_type) = schema.custom_type(name) {
+            if custom_type.params.is_empty() {
+                return self.custom_type_ref(custom_type);
+            }
+
+            let bindings = bind(&custom_type.params, args, bindings);
+
+            self.in_place(name, |exporter| {
+                exporter.custom_type(custom_type, &bindings)
+            })
+        } else if let Some(alias) = schema.alias(name) {
+            let bindings = bind(&alias.params, args, bindings);
+            let context = if alias.params.is_empty() {
+                context
+            } else {
+                context.unpackable()
+            };
+
+            self.in_place(name, |exporter| {
+                exporter.tipe(&alias.tipe, &bindings, context)
+            })
+        } else {
+            object(vec![])
+        }
+    }
+
+    /// Describe a declaration in place, or allow anything if we're already in
+    /// the middle of describing it.
+    fn in_place<F: FnOnce(&mut Exporter<'a>) -> Value>(
+        &mut self,
+        name: &str,
+        describe: F,
+    ) -> Value {
+        if self.expanding.iter().any(|expanding| expanding == name) {
+            return object(vec![]);
+        }
+
+        self.expanding.push(name.to_string());
+
+        let described = describe(self);
+
+        self.expanding.pop();
+
+        described
+    }
+
+    /// A `$ref` to the def for a record without type params, adding the def if
+    /// it isn't there yet.
+    fn record_ref(&mut self, record: &RecordInfo, name: &str, context: Packing) -> Value {
+        let mut key = name.to_string();
+
+        if self.is_packed(record, context) {
+            key.push_str(".packed");
+        }
+
+        if self.names == Names::Packed && context.can_pack && context.pack_all {
+            key.push_str(".inPackedEndpoint");
+        }
+
+        if !self.defs.iter().any(|(def_key, _)| *def_key == key) {
+            // A placeholder, in case the record refers to itself.
+            self.defs.push((key.clone(), Value::Null));
+
+            let def = self.record(record, &vec![], context);
+
+            if let Some(entry) = self.defs.iter_mut().find(|(def_key, _)| *def_key == key) {
+                entry.1 = def;
+            }
+        }
+
+        reference(key.as_str())
+    }
+
+    /// Like `record_ref`, but custom types have only the one def, since
+    /// packing doesn't reach inside their contents.
+    fn custom_type_ref(&mut self, custom_type: &CustomTypeInfo) -> Value {
+        let key = custom_type.name.to_string();
+
+        if !self.defs.iter().any(|(def_key, _)| *def_key == key) {
+            self.defs.push((key.clone(), Value::Null));
+
+            let def = self.custom_type(custom_type, &vec![]);
+
+            if let Some(entry) = self.defs.iter_mut().find(|(def_key, _)| *def_key == key) {
+                entry.1 = def;
+            }
+        }
+
+        reference(key.as_str())
+    }
+
+    fn is_packed(&self, record: &RecordInfo, context: Packing) -> bool {
+        self.names == Names::Packed && context.is_packed(record)
+    }
+
+    fn record(&mut self, info: &RecordInfo, bindings: &Bindings, context: Packing) -> Value {
+        let is_packed = self.is_packed(info, context);
+        let field_context = context.fields(info);
+        let title = info
+            .name
+            .as_ref()
+            .map(|name| string(name.to_string().as_str()));
+
+        if is_packed {
+            let mut slots: Vec<Value> = vec![];
+            let mut min_items: u64 = 0;
+
+            for field in info.fields.iter() {
+                let slot = field.field_id as usize;
+                let mut field_schema = self.field(field, bindings, field_context);
+
+                if is_required(field) {
+                    min_items = min_items.max(field.field_id + 1);
+                } else {
+                    field_schema = object(vec![(
+                        "anyOf",
+                        Value::Array(vec![field_schema, schema_type("null")]),
+                    )]);
+                }
+
+                // Slots no current field has may hold a retired field's value.
+                if slots.len() <= slot {
+                    slots.resize(slot + 1, object(vec![]));
+                }
+
+                slots[slot] = field_schema;
+            }
+
+            let mut entries = vec![("type", string("array"))];
+
+            entries.extend(title.map(|title| ("title", title)));
+            entries.push(("prefixItems", Value::Array(slots)));
+            entries.push(("minItems", Value::Int(min_items as i64)));
+
+            return object(entries);
+        }
+
+        let mut properties: Vec<(String, Value)> = vec![];
+        let mut required: Vec<Value> = vec![];
+
+        if let (Names::Development, Some(ref title)) = (self.names, &title) {
+            properties.push((
+                String::from(TYPE_KEY),
+                object(vec![("const", title.clone())]),
+            ));
+            required.push(string(TYPE_KEY));
+        }
+
+        for field in info.fields.iter() {
+            let name = match self.names {
+                Names::Full | Names::Development => field.name.clone(),
+                Names::Minified | Names::Packed => {
+                    match self.table.record_field(info, field.name.as_str()) {
+                        Some(name) => name.to_string(),
+                        None => {
+                            let qualified = format!(
+                                "{}.{}",
+                                info.ledger_name().unwrap_or_default(),
+                                field.name
+                            );
+
+                            if !self.missing.contains(&qualified) {
+                                self.missing.push(qualified);
+                            }
+
+                            field.name.clone()
+                        }
+                    }
+                }
+            };
+
+            if is_required(field) {
+                required.push(string(name.as_str()));
+            }
+
+            properties.push((name, self.field(field, bindings, field_context)));
+        }
+
+        let mut entries = vec![("type", string("object"))];
+
+        entries.extend(title.map(|title| ("title", title)));
+        entries.push(("properties", Value::Object(properties)));
+
+        if !required.is_empty() {
+            entries.push(("required", Value::Array(required)));
+        }
+
+        if let Names::Full | Names::Development = self.names {
+            entries.push(("additionalProperties", Value::Bool(false)));
+        }
+
+        object(entries)
+    }
+
+    fn field(&mut self, field: &Field, bindings: &Bindings, context: Packing) -> Value {
+        let mut field_schema = self.tipe(&field.tipe, bindings, context);
+        let mut annotations: Vec<(String, Value)> = vec![];
+
+        // Minified names mean nothing to a reader, so say what the field is.
+        if let Names::Minified | Names::Packed = self.names {
+            annotations.push((String::from("title"), string(field.name.as_str())));
+        }
+
+        // Defaults which aren't JSON (e.g. constants) can't be described.
+        if let Some(default) = field
+            .default
+            .as_ref()
+            .and_then(|default| json::parse(default).ok())
+        {
+            annotations.push((String::from("default"), default));
+        }
+
+        if field.deprecated {
+            annotations.push((String::from("deprecated"), Value::Bool(true)));
+        }
+
+        if let Value::Object(ref mut entries) = field_schema {
+            entries.extend(annotations);
+        }
+
+        field_schema
+    }
+
+    fn custom_type(&mut self, info: &CustomTypeInfo, bindings: &Bindings) -> Value {
+        let name = info.name.to_string();
+        let context = Packing::TOP.unpackable();
+        let variants: Vec<Value> = info
+            .variants
+            .iter()
+            .map(|variant| {
+                let contents: Vec<Value> = variant
+                    .contents
+                    .iter()
+                    .map(|tipe| self.tipe(tipe, bindings, context))
+                    .collect();
+
+                match self.names {
+                    Names::Minified | Names::Packed => {
+                        let mut elems = vec![object(vec![(
+                            "const",
+                            Value::Int(variant.variant_id as i64),
+                        )])];
+
+                        elems.extend(contents);
+
+                        let mut variant_schema = fixed_array(elems);
+
+                        if let Value::Object(ref mut entries) = variant_schema {
+                            entries
+                                .insert(1, (String::from("title"), string(variant.name.as_str())));
+                        }
+
+                        variant_schema
+                    }
+                    Names::Full | Names::Development => {
+                        let mut properties: Vec<(String, Value)> = vec![];
+                        let mut required: Vec<Value> = vec![];
+
+                        if self.names == Names::Development {
+                            properties.push((
+                                String::from(TYPE_KEY),
+                                object(vec![("const", string(name.as_str()))]),
+                            ));
+                            required.push(string(TYPE_KEY));
+                        }
+
+                        properties.push((
+                            String::from(VARIANT_KEY),
+                            object(vec![("const", string(variant.name.as_str()))]),
+                        ));
+                        required.push(string(VARIANT_KEY));
+
+                        if !contents.is_empty() {
+                            properties.push((String::from(CONTENTS_KEY), fixed_array(contents)));
+                            required.push(string(CONTENTS_KEY));
+                        }
+
+                        object(vec![
+                            ("type", string("object")),
+                            ("properties", Value::Object(properties)),
+                            ("required", Value::Array(required)),
+                        ])
+                    }
+                }
+            })
+            .collect();
+
+        object(vec![
+            ("title", string(name.as_str())),
+            ("oneOf", Value::Array(variants)),
+        ])
+    }
+
+    /// Whether the type is `String`, or an alias for it.
+    fn is_string(&self, tipe: &Type, bindings: &Bindings) -> bool {
+        match tipe.current {
+            TypePrimitive::String => true,
+            TypePrimitive::Param(TypeParam::TypeParam(ref param)) => bindings
+                .iter()
+                .find(|(name, _)| name == param)
+                .is_some_and(|(_, tipe)| self.is_string(tipe, bindings)),
+            _ => matches!(
+                self.schema.expand_aliases(tipe).current,
+                TypePrimitive::String
+            ),
+        }
+    }
+}
+
+/// Whether a decoder insists on the field. Fields with defaults get them
+/// filled in when they're missing.
+fn is_required(field: &Field) -> bool {
+    field.optionality == Optionality::Required && field.default.is_none()
+}
+
+fn object(entries: Vec<(&str, Value)>) -> Value {
+    Value::Object(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    )
+}
+
+fn string(string: &str) -> Value {
+    Value::String(string.to_string())
+}
+
+fn schema_type(name: &str) -> Value {
+    object(vec![("type", string(name))])
+}
+
+fn integer(minimum: i64, maximum: i64) -> Value {
+    object(vec![
+        ("type", string("integer")),
+        ("minimum", Value::Int(minimum)),
+        ("maximum", Value::Int(maximum)),
+    ])
+}
+
+/// An array of exactly these elements, e.g. a `[key, value]` pair.
+fn fixed_array(elems: Vec<Value>) -> Value {
+    let len = elems.len() as i64;
+
+    object(vec![
+        ("type", string("array")),
+        ("prefixItems", Value::Array(elems)),
+        ("minItems", Value::Int(len)),
+        ("maxItems", Value::Int(len)),
+    ])
+}
+
+fn reference(key: &str) -> Value {
+    object(vec![("$ref", Value::String(format!("#/$defs/{}", key)))])
+}
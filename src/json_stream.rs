@@ -0,0 +1,399 @@
+//! Decoding big JSON arrays one element at a time, straight from a reader.
+//!
+//! Only one element is held in memory at once: we scan the input just far
+//! enough to find where the next element ends, then parse it, and check it
+//! against the schema with the `codec`. So memory is bounded by the biggest
+//! element rather than the whole array, which matters for endpoints that
+//! return tens of thousands of records.
+use codec;
+use codec::{Codec, ProblemKind, ResponseArray};
+use json;
+use json::Value;
+use std::fmt;
+use std::io;
+use std::io::BufRead;
+use std::str;
+use types::{Endpoint, Type};
+
+#[derive(Debug)]
+pub enum Problem {
+    Io(io::Error),
+
+    /// The input isn't valid JSON. The offset is from the start of the input,
+    /// not the element.
+    Json(json::Problem),
+
+    /// An element was valid JSON, but didn't match the schema.
+    Codec(codec::Problem),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Io(error) => write!(formatter, "{}", error),
+            Problem::Json(problem) => write!(formatter, "{}", problem),
+            Problem::Codec(problem) => write!(formatter, "{}", problem),
+        }
+    }
+}
+
+/// Read an Array of `elem` from the wire, in whichever JSON format the
+/// codec's mode speaks. Each element is decoded to full-name JSON as it's
+/// reached, with problems reported by index, e.g. "[3].name".
+///
+/// A problem with an element's contents doesn't stop the elements after it
+/// from being read, but once the JSON itself is broken (or the reader
+/// fails), there's nothing more to read.
+pub fn read_array_from<'a, R: io::Read>(
+    reader: R,
+    codec: &'a Codec<'a>,
+    elem: &'a Type,
+) -> Elements<'a, R> {
+    Elements {
+        reader: io::BufReader::new(reader),
+        codec,
+        target: Target::Array(elem),
+        offset: 0,
+        index: 0,
+        state: State::Start,
+        buffer: vec![],
+    }
+}
+
+/// Read the Array or Set at `field_path` in the endpoint's response, e.g.
+/// "users" or "page.items", from a whole response on the wire. Its records
+/// are packed into tuples if the endpoint is `[ packed ]`, and problems are
+/// reported by their path in the response, e.g. "users[3].name".
+///
+/// The other fields on the way are skipped, so memory is bounded by the
+/// biggest of them too. Nothing after the array is read: once it ends, so
+/// do the elements.
+pub fn read_response_field_from<'a, R: io::Read>(
+    reader: R,
+    codec: &'a Codec<'a>,
+    endpoint: &Endpoint,
+    field_path: &str,
+) -> Result<Elements<'a, R>, Problem> {
+    let array = codec
+        .response_array(endpoint, field_path)
+        .map_err(Problem::Codec)?;
+
+    Ok(Elements {
+        reader: io::BufReader::new(reader),
+        codec,
+        target: Target::Field(Box::new(array)),
+        offset: 0,
+        index: 0,
+        state: State::Start,
+        buffer: vec![],
+    })
+}
+
+pub struct Elements<'a, R> {
+    reader: io::BufReader<R>,
+    codec: &'a Codec<'a>,
+    target: Target<'a>,
+
+    /// How many bytes we've read, for reporting problems.
+    offset: usize,
+
+    /// The index of the next element.
+    index: usize,
+    state: State,
+
+    /// The bytes of the current element, kept around to reuse the allocation.
+    buffer: Vec<u8>,
+}
+
+enum Target<'a> {
+    /// The whole input is an array of these.
+    Array(&'a Type),
+
+    /// The input is an endpoint's response, with the array in this field.
+    Field(Box<ResponseArray>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Before the `[`.
+    Start,
+
+    /// After an element, expecting a `,` or `]`.
+    Between,
+    Done,
+}
+
+impl<'a, R: io::Read> Iterator for Elements<'a, R> {
+    type Item = Result<Value, Problem>;
+
+    fn next(&mut self) -> Option<Result<Value, Problem>> {
+        match self.advance() {
+            Ok(Some(wire)) => {
+                let index = self.index;
+
+                self.index += 1;
+
+                let decoded = match self.target {
+                    Target::Array(elem) => self.codec.decode_element(elem, index, &wire),
+                    Target::Field(ref array) => {
+                        self.codec.decode_response_element(array, index, &wire)
+                    }
+                };
+
+                Some(decoded.map_err(Problem::Codec))
+            }
+            Ok(None) => None,
+            Err(problem) => {
+                self.state = State::Done;
+
+                Some(Err(problem))
+            }
+        }
+    }
+}
+
+impl<'a, R: io::Read> Elements<'a, R> {
+    /// The next element as it is on the wire, if there is one.
+    fn advance(&mut self) -> Result<Option<Value>, Problem> {
+        match self.state {
+            State::Done => Ok(None),
+            State::Start => {
+                if !self.find_field()? {
+                    self.state = State::Done;
+
+                    return Ok(None);
+                }
+
+                self.skip_whitespace()?;
+
+                if self.peek()? != Some(b'[') {
+                    return Err(self.problem("an array"));
+                }
+
+                self.consume();
+                self.skip_whitespace()?;
+
+                if self.peek()? == Some(b']') {
+                    self.consume();
+                    self.finish()
+                } else {
+                    self.state = State::Between;
+                    self.element().map(Some)
+                }
+            }
+            State::Between => {
+                self.skip_whitespace()?;
+
+                match self.peek()? {
+                    Some(b',') => {
+                        self.consume();
+                        self.element().map(Some)
+                    }
+                    Some(b']') => {
+                        self.consume();
+                        self.finish()
+                    }
+                    _ => Err(self.problem("a `,` or `]`")),
+                }
+            }
+        }
+    }
+
+    /// After the `]`, only whitespace is allowed, unless the array was in a
+    /// response field.
+    fn finish(&mut self) -> Result<Option<Value>, Problem> {
+        if let Target::Array(_) = self.target {
+            self.skip_whitespace()?;
+
+            if self.peek()?.is_some() {
+                return Err(self.problem("the end of the input"));
+            }
+        }
+
+        self.state = State::Done;
+
+        Ok(None)
+    }
+
+    /// Read into the response's objects, up to the value of the array's
+    /// field. False if an optional field on the way is missing or null, so
+    /// there are no elements.
+    fn find_field(&mut self) -> Result<bool, Problem> {
+        let array = match self.target {
+            Target::Array(_) => return Ok(true),
+            Target::Field(ref array) => array.clone(),
+        };
+
+        for (step, wire_name) in array.wire_path.iter().enumerate() {
+            self.skip_whitespace()?;
+
+            if self.peek()? != Some(b'{') {
+                return Err(self.problem("an object"));
+            }
+
+            self.consume();
+
+            let mut first = true;
+
+            loop {
+                self.skip_whitespace()?;
+
+                match self.peek()? {
+                    Some(b'}') => {
+                        self.consume();
+
+                        return missing(&array, step);
+                    }
+                    Some(b',') if !first => self.consume(),
+                    _ if first => {}
+                    _ => return Err(self.problem("a `,` or `}`")),
+                }
+
+                first = false;
+                self.skip_whitespace()?;
+
+                let start = self.offset;
+                let key = match self.element()? {
+                    Value::String(key) => key,
+                    _ => {
+                        return Err(Problem::Json(json::Problem {
+                            offset: start,
+                            expected: "a string",
+                        }))
+                    }
+                };
+
+                self.skip_whitespace()?;
+
+                if self.peek()? != Some(b':') {
+                    return Err(self.problem("a `:`"));
+                }
+
+                self.consume();
+
+                if key != *wire_name {
+                    self.element()?;
+                    continue;
+                }
+
+                self.skip_whitespace()?;
+
+                if self.peek()? == Some(b'n') {
+                    self.element()?;
+
+                    return missing(&array, step);
+                }
+
+                break;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Scan to the end of the next element, and parse it. Only enough of the
+    /// grammar to find the end is checked here; `json::parse` does the rest.
+    fn element(&mut self) -> Result<Value, Problem> {
+        self.skip_whitespace()?;
+        self.buffer.clear();
+
+        let start = self.offset;
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        while let Some(byte) = self.peek()? {
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if byte == b'\\' {
+                    escaped = true;
+                } else if byte == b'"' {
+                    in_string = false;
+                }
+            } else {
+                match byte {
+                    b'"' => in_string = true,
+                    b'[' | b'{' => {
+                        depth += 1;
+
+                        if depth > json::MAX_DEPTH {
+                            return Err(self.problem("less deeply nested arrays and objects"));
+                        }
+                    }
+                    b']' | b'}' if depth == 0 => break,
+                    b']' | b'}' => depth -= 1,
+                    b',' | b' ' | b'\n' | b'\r' | b'\t' if depth == 0 => break,
+                    _ => {}
+                }
+            }
+
+            self.buffer.push(byte);
+            self.consume();
+
+            let closed = matches!(byte, b']' | b'}' | b'"');
+
+            if depth == 0 && !in_string && closed {
+                break;
+            }
+        }
+
+        let source = str::from_utf8(&self.buffer).map_err(|error| {
+            Problem::Json(json::Problem {
+                offset: start + error.valid_up_to(),
+                expected: "valid UTF-8",
+            })
+        })?;
+
+        json::parse(source).map_err(|problem| {
+            Problem::Json(json::Problem {
+                offset: start + problem.offset,
+                expected: problem.expected,
+            })
+        })
+    }
+
+    fn problem(&self, expected: &'static str) -> Problem {
+        Problem::Json(json::Problem {
+            offset: self.offset,
+            expected,
+        })
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, Problem> {
+        loop {
+            match self.reader.fill_buf() {
+                Ok(bytes) => return Ok(bytes.first().cloned()),
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(Problem::Io(error)),
+            }
+        }
+    }
+
+    /// Move past the byte we just peeked at.
+    fn consume(&mut self) {
+        self.reader.consume(1);
+        self.offset += 1;
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), Problem> {
+        while let Some(b' ') | Some(b'\n') | Some(b'\r') | Some(b'\t') = self.peek()? {
+            self.consume();
+        }
+
+        Ok(())
+    }
+}
+
+/// A field on the way to the array is missing (or null), which is only a
+/// problem if it's required.
+fn missing(array: &ResponseArray, step: usize) -> Result<bool, Problem> {
+    if array.optional[step] {
+        return Ok(false);
+    }
+
+    Err(Problem::Codec(codec::Problem {
+        path: array.path[..=step].join("."),
+        kind: ProblemKind::Missing,
+    }))
+}
@@ -1,21 +1,52 @@
 extern crate byteorder;
 
+pub mod allocator;
 pub mod array;
 pub mod arrays;
+pub mod changelog;
 pub mod cli;
 pub mod code_gen;
+pub mod codec;
+pub mod collection;
+pub mod compat;
 pub mod error_messages;
 pub mod field_id;
+pub mod git;
+pub mod history;
+pub mod json;
+pub mod json_schema;
+pub mod json_stream;
 pub mod language;
+pub mod loader;
+pub mod migration;
+pub mod negotiation;
+pub mod parse;
 pub mod pointer;
 pub mod problems;
+pub mod reader;
 pub mod record;
+pub mod segments;
+pub mod string;
 pub mod types;
+pub mod widening;
 
+use allocator::Allocation;
+use cli::{
+    ChangelogArgs, CheckCompatArgs, CliArgs, Command, CompareSizesArgs, ExportArgs, SizeTarget,
+};
+use code_gen::{MinificationTable, Mode};
+use codec::{Codec, Names};
+use compat::{Change, Incompatibility, Side};
+use history::{History, SchemaVersion};
+use language::Language;
+use loader::Source;
+use migration::{Decision, Decisions, Question};
 use problems::Problem;
-use std::collections::HashSet;
-use std::iter::FromIterator;
-use std::path::PathBuf;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::slice;
+use types::Schema;
 
 fn report_problem(problem: Problem) {
     // TODO color "Error:" in red.
@@ -24,24 +55,676 @@ fn report_problem(problem: Problem) {
     std::process::exit(1);
 }
 
-pub fn run() -> () {
+pub fn run() {
     // Parse and validate CLI arguments
     match cli::parse_args().map_err(Problem::Cli) {
         Err(problem) => {
             report_problem(problem);
         }
         Ok(args) => {
-            let _unique_file_paths: HashSet<PathBuf> = HashSet::from_iter(
-                args.file_paths
-                    .iter()
-                    .map(|file_path| file_path.to_path_buf()),
-            );
-
             // Print the headline. Something like:
             //
             // wirefunc 0.1.0
             // --------------
             cli::print_headline();
+
+            let result = match args.command {
+                Command::Build => build(&args),
+                Command::CheckCompat(ref compat_args) => check_compat(&args, compat_args),
+                Command::Migrate(ref decisions) => migrate(&args, decisions),
+                Command::Changelog(ref changelog_args) => changelog(&args, changelog_args),
+                Command::CompareSizes(ref sizes_args) => compare_sizes(&args, sizes_args),
+                Command::ExportJsonSchema(ref export_args) => {
+                    export_json_schema(&args, export_args)
+                }
+            };
+
+            if let Err(problem) = result {
+                report_problem(problem);
+            }
+        }
+    }
+}
+
+fn build(args: &CliArgs) -> Result<(), Problem> {
+    let (_, mut schema) = load_schema(&args.file_paths)?;
+    let mut history = load_history(args)?;
+    let previous_version = history.version;
+    let allocation = allocate_ids(&mut history, &mut schema)?;
+
+    write_allocation(args, &history, &allocation, previous_version)?;
+
+    write_formats(args)?;
+
+    // Development builds need the table too, to recognize production JSON.
+    let table = code_gen::productionize(&history, &schema).map_err(Problem::CodeGen)?;
+
+    if args.mode == Mode::Production {
+        let count: usize = table
+            .records
+            .iter()
+            .chain(table.custom_types.iter())
+            .map(|names| names.names.len())
+            .sum();
+
+        println!("Minified {} field and variant names for production.", count);
+
+        if code_gen::uses_packing(&schema) {
+            write_packing(args, &schema, &table)?;
+        }
+    }
+
+    write_wire(args, &schema, &table, &history)
+}
+
+/// Write the JavaScript which picks the media types to send and accept next to
+/// the history, since they differ between development and production.
+fn write_formats(args: &CliArgs) -> Result<(), Problem> {
+    if [&args.client, &args.server].contains(&&Some(Language::JavaScript)) {
+        let path = args.history_path.with_file_name(FORMATS_JS_FILE);
+
+        fs::write(&path, code_gen::formats_js(args.mode))
+            .map_err(|err| Problem::CodeGen(code_gen::Problem::WriteError(err)))?;
+    }
+
+    Ok(())
+}
+
+/// The file `write_formats` writes the generated JavaScript to.
+const FORMATS_JS_FILE: &str = "wf-formats.js";
+
+/// Write the code which packs and unpacks records next to the history, for
+/// each language in use.
+fn write_packing(
+    args: &CliArgs,
+    schema: &Schema,
+    table: &MinificationTable,
+) -> Result<(), Problem> {
+    let languages = [&args.client, &args.server];
+
+    if languages.contains(&&Some(Language::JavaScript)) {
+        let code = code_gen::packing_js(schema, table).map_err(Problem::CodeGen)?;
+
+        write_packing_file(&args.history_path.with_file_name(PACKING_JS_FILE), code)?;
+    }
+
+    if languages.contains(&&Some(Language::Elm)) {
+        let code = code_gen::packing_elm(schema, table).map_err(Problem::CodeGen)?;
+
+        write_packing_file(&args.history_path.with_file_name(PACKING_ELM_FILE), code)?;
+    }
+
+    Ok(())
+}
+
+fn write_packing_file(path: &Path, code: String) -> Result<(), Problem> {
+    fs::write(path, code).map_err(|err| Problem::CodeGen(code_gen::Problem::WriteError(err)))?;
+
+    println!("Wrote the code to pack records to {}", path.display());
+
+    Ok(())
+}
+
+/// The files `write_packing` writes the generated code to.
+const PACKING_JS_FILE: &str = "wf-packing.js";
+const PACKING_ELM_FILE: &str = "WfPacking.elm";
+
+/// Write the code which converts JSON to and from the wire format of the
+/// build's mode next to the history, for each language in use.
+/// Development decoders accept former names through the rename grace.
+fn write_wire(
+    args: &CliArgs,
+    schema: &Schema,
+    table: &MinificationTable,
+    history: &History,
+) -> Result<(), Problem> {
+    let languages = [&args.client, &args.server];
+    let former_names = Some((history, args.rename_grace));
+
+    if languages.contains(&&Some(Language::JavaScript)) {
+        let code =
+            code_gen::wire_js(schema, table, args.mode, former_names).map_err(Problem::CodeGen)?;
+
+        write_wire_file(&args.history_path.with_file_name(WIRE_JS_FILE), code)?;
+    }
+
+    if languages.contains(&&Some(Language::Elm)) {
+        let code =
+            code_gen::wire_elm(schema, table, args.mode, former_names).map_err(Problem::CodeGen)?;
+
+        write_wire_file(&args.history_path.with_file_name(WIRE_ELM_FILE), code)?;
+    }
+
+    Ok(())
+}
+
+fn write_wire_file(path: &Path, code: String) -> Result<(), Problem> {
+    fs::write(path, code).map_err(|err| Problem::CodeGen(code_gen::Problem::WriteError(err)))?;
+
+    println!(
+        "Wrote the code to convert JSON to and from the wire to {}",
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// The files `write_wire` writes the generated code to.
+const WIRE_JS_FILE: &str = "wf-wire.js";
+const WIRE_ELM_FILE: &str = "WfWire.elm";
+
+fn migrate(args: &CliArgs, decisions: &Decisions) -> Result<(), Problem> {
+    let (_, mut schema) = load_schema(&args.file_paths)?;
+    let mut history = load_history(args)?;
+    let previous_version = history.version;
+    let baseline = match args.since {
+        Some(ref revision) => Some((
+            history_at(revision, args)?.version,
+            load_schema_at(revision, &args.file_paths)?,
+        )),
+        None => load_snapshots(&args.history_path, &history)?.pop(),
+    };
+    let questions = migration::questions(
+        &history,
+        baseline
+            .as_ref()
+            .map(|(version, baseline)| (*version, baseline)),
+        &schema,
+    );
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut decided: Vec<(Question, Decision)> = vec![];
+    let mut undecided: Vec<Question> = vec![];
+
+    if questions.is_empty() {
+        println!("Nothing needs deciding. Every change to the schema is unambiguous.");
+    }
+
+    for question in questions {
+        let decision = match decisions.decide(&question) {
+            Some(decision) => Some(decision),
+            None => migration::ask(&question, &mut stdin.lock(), &mut stdout.lock())
+                .map_err(Problem::Prompt)?,
+        };
+
+        match decision {
+            Some(decision) => decided.push((question, decision)),
+            None => undecided.push(question),
+        }
+    }
+
+    if !undecided.is_empty() {
+        return Err(Problem::UndecidedMigrations(undecided));
+    }
+
+    if !decided.is_empty() {
+        println!();
+    }
+
+    code_gen::check_for_retired_names(&history, &schema).map_err(Problem::CodeGen)?;
+
+    let allocation = migration::apply(&mut history, &mut schema, &decided);
+
+    write_allocation(args, &history, &allocation, previous_version)
+}
+
+/// If allocation changed the history, write it out and summarize what changed.
+fn write_allocation(
+    args: &CliArgs,
+    history: &History,
+    allocation: &Allocation,
+    previous_version: SchemaVersion,
+) -> Result<(), Problem> {
+    if allocation.version == previous_version {
+        return Ok(());
+    }
+
+    history::write_to(&args.history_path, history).map_err(Problem::History)?;
+
+    for name in allocation.added.iter() {
+        println!("Added   {}", name);
+    }
+
+    for (former_name, name) in allocation.renamed.iter() {
+        println!("Renamed {} to {}", former_name, name);
+    }
+
+    for name in allocation.retired.iter() {
+        println!("Retired {}", name);
+    }
+
+    for name in allocation.retyped.iter() {
+        println!("Retyped {}, keeping its ID", name);
+    }
+
+    println!(
+        "\nRecorded version {} in {}",
+        allocation.version,
+        args.history_path.display()
+    );
+
+    if !allocation.renamed.is_empty() && args.rename_grace > 0 {
+        println!(
+            "Development builds will accept the former names through version {}.",
+            allocation.version + args.rename_grace - 1
+        );
+    }
+
+    Ok(())
+}
+
+fn check_compat(args: &CliArgs, compat_args: &CheckCompatArgs) -> Result<(), Problem> {
+    let (sources, mut schema) = load_schema(&args.file_paths)?;
+    let mut history = load_history(args)?;
+    let baseline = match (&compat_args.baseline, &args.since) {
+        (Some(directory), _) => Some(load_schema(slice::from_ref(directory))?.1),
+        (None, Some(revision)) => Some(load_schema_at(revision, &args.file_paths)?),
+        (None, None) => None,
+    };
+
+    match baseline {
+        Some(baseline) => {
+            let changes = compat::compare(&baseline, &schema);
+
+            print_changes(&changes);
+
+            let unacknowledged: Vec<Change> = changes
+                .into_iter()
+                .filter(|change| {
+                    change.impact.is_breaking()
+                        && !compat::is_acknowledged(change, &compat_args.acknowledged)
+                })
+                .collect();
+
+            if !unacknowledged.is_empty() {
+                return Err(Problem::BreakingChanges(unacknowledged));
+            }
+        }
+        None => {
+            let released = load_snapshots(&args.history_path, &history)?;
+            let window = &released[released.len().saturating_sub(compat_args.window)..];
+
+            match window.last() {
+                Some((_, latest)) => print_changes(&compat::compare(latest, &schema)),
+                None => println!(
+                    "There is no previous schema to compare against, so there is nothing to break."
+                ),
+            }
+
+            let incompatibilities =
+                compat::check_window(window, &schema, &compat_args.acknowledged);
+
+            if !incompatibilities.is_empty() {
+                print_incompatibilities(&incompatibilities);
+
+                let mut changes: Vec<Change> = vec![];
+
+                for change in incompatibilities
+                    .into_iter()
+                    .flat_map(|incompatibility| incompatibility.changes)
+                {
+                    if !changes.contains(&change) {
+                        changes.push(change);
+                    }
+                }
+
+                return Err(Problem::BreakingChanges(changes));
+            } else if let (Some((first, _)), Some((last, _))) = (window.first(), window.last()) {
+                let versions = if first == last {
+                    format!("version {}", last)
+                } else {
+                    format!("versions {} through {}", first, last)
+                };
+
+                println!(
+                    "\nClients and servers running {} can all talk to ones running this schema.",
+                    versions
+                );
+            }
+        }
+    }
+
+    if compat_args.update_history {
+        allocate_ids(&mut history, &mut schema)?;
+
+        let version = history.record_snapshot(loader::concat(&sources), compat_args.window);
+
+        history::write_to(&args.history_path, &history).map_err(Problem::History)?;
+
+        println!(
+            "\nRecorded version {} in {}",
+            version,
+            args.history_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn changelog(args: &CliArgs, changelog_args: &ChangelogArgs) -> Result<(), Problem> {
+    let (_, mut schema) = load_schema(&args.file_paths)?;
+    let mut history = load_history(args)?;
+    let released = load_snapshots(&args.history_path, &history)?;
+
+    // Allocating IDs records the current schema's changes as the next version,
+    // without writing them out.
+    allocate_ids(&mut history, &mut schema)?;
+
+    let latest = history.version;
+    let to = changelog_args.to.unwrap_or(latest);
+    let from = match changelog_args.from {
+        Some(from) => from,
+        None => released
+            .iter()
+            .rev()
+            .map(|(version, _)| *version)
+            .find(|version| *version < to)
+            .unwrap_or(0),
+    };
+
+    if from >= to || to > latest {
+        return Err(Problem::Changelog(changelog::Problem::InvalidRange {
+            from,
+            to,
+            latest,
+        }));
+    }
+
+    // There's nothing before version 1.
+    let empty = Schema::default();
+    let schema_as_of = |version: SchemaVersion| {
+        if version == 0 {
+            Some(&empty)
+        } else if version == latest {
+            Some(&schema)
+        } else {
+            released
+                .iter()
+                .find(|(released_version, _)| *released_version == version)
+                .map(|(_, schema)| schema)
+        }
+    };
+    let schemas = match (schema_as_of(from), schema_as_of(to)) {
+        (Some(old), Some(new)) => Some((old, new)),
+        _ => None,
+    };
+    let changelog = changelog::between(&history, from, to, schemas);
+
+    match changelog_args.output {
+        Some(ref path) => {
+            changelog::write_to(path, &changelog).map_err(Problem::Changelog)?;
+
+            println!(
+                "Wrote the changes from version {} to {} to {}",
+                from,
+                to,
+                path.display()
+            );
+        }
+        None => print!("{}", changelog::to_markdown(&changelog)),
+    }
+
+    Ok(())
+}
+
+fn compare_sizes(args: &CliArgs, sizes_args: &CompareSizesArgs) -> Result<(), Problem> {
+    let (_, mut schema) = load_schema(&args.file_paths)?;
+    let mut history = load_history(args)?;
+
+    // The codec needs every field to have an ID. Nothing is written out.
+    allocate_ids(&mut history, &mut schema)?;
+
+    let table = code_gen::productionize(&history, &schema).map_err(Problem::CodeGen)?;
+
+    let source = fs::read_to_string(&sizes_args.sample)
+        .map_err(|err| Problem::Loader(loader::Problem::Io(sizes_args.sample.clone(), err)))?;
+    let sample = json::parse(&source)
+        .map_err(|problem| Problem::Json(sizes_args.sample.clone(), problem))?;
+
+    // The same schema, as if every record were marked [ packed ].
+    let mut all_packed = schema.clone();
+
+    for record in all_packed.records.iter_mut() {
+        record.packed = true;
+    }
+
+    let size = |schema: &Schema, to: Names| -> Result<usize, Problem> {
+        let tipe = match sizes_args.target {
+            SizeTarget::Type(ref name) => codec::named(name),
+            SizeTarget::Params(ref name) | SizeTarget::Response(ref name) => {
+                let endpoint = schema.endpoint(name).ok_or_else(|| {
+                    Problem::Codec(codec::Problem {
+                        path: String::new(),
+                        kind: codec::ProblemKind::UnknownType(name.clone()),
+                    })
+                })?;
+
+                match sizes_args.target {
+                    SizeTarget::Params(_) => codec::params(endpoint),
+                    _ => codec::response(endpoint),
+                }
+            }
+        };
+
+        Codec::new(schema, &table)
+            .convert(&tipe, &sample, Names::Full, to)
+            .map(|value| value.to_string().len())
+            .map_err(Problem::Codec)
+    };
+    // Converting to full names would fill in defaults, so measure the sample
+    // as it is, once we know it matches the schema.
+    let minified = size(&schema, Names::Minified)?;
+    let full = sample.to_string().len();
+    let sizes = [
+        ("Full names", full),
+        ("Development", size(&schema, Names::Development)?),
+        ("Minified", minified),
+        ("Packed as marked", size(&schema, Names::Packed)?),
+        ("Everything packed", size(&all_packed, Names::Packed)?),
+    ];
+
+    for (label, bytes) in sizes.iter() {
+        println!(
+            "{:<20}{:>10} bytes {:>5.0}%",
+            label,
+            bytes,
+            *bytes as f64 * 100.0 / full.max(1) as f64
+        );
+    }
+
+    println!("\nSizes are of compact JSON, before compression.");
+
+    Ok(())
+}
+
+fn export_json_schema(args: &CliArgs, export_args: &ExportArgs) -> Result<(), Problem> {
+    let (_, mut schema) = load_schema(&args.file_paths)?;
+    let mut history = load_history(args)?;
+
+    // Minified names come from IDs. Nothing is written out.
+    allocate_ids(&mut history, &mut schema)?;
+
+    let table = code_gen::productionize(&history, &schema).map_err(Problem::CodeGen)?;
+    let exported =
+        json_schema::export(&schema, &table, export_args.names).map_err(Problem::CodeGen)?;
+    let exported = json::to_pretty_string(&exported);
+
+    match export_args.output {
+        Some(ref path) => {
+            fs::write(path, exported)
+                .map_err(|err| Problem::CodeGen(code_gen::Problem::WriteError(err)))?;
+
+            println!("Wrote the JSON Schema to {}", path.display());
+        }
+        None => print!("{}", exported),
+    }
+
+    Ok(())
+}
+
+fn print_changes(changes: &[Change]) {
+    if changes.is_empty() {
+        println!("The schema has not changed.");
+    }
+
+    for change in changes.iter() {
+        println!(
+            "{:<34} {}",
+            compat::describe_impact(change.impact),
+            compat::describe(change)
+        );
+    }
+}
+
+// prints something like this:
+//
+// Client    Server    Breaking changes
+// 3         current   User.email, sendDM.params.userId
+// current   3         fetchReplies
+fn print_incompatibilities(incompatibilities: &[Incompatibility]) {
+    println!("\n{:<10}{:<10}Breaking changes", "Client", "Server");
+
+    for incompatibility in incompatibilities.iter() {
+        let old_version = incompatibility.old_version.to_string();
+        let (client, server) = match incompatibility.old_side {
+            Side::Client => (old_version.as_str(), "current"),
+            Side::Server => ("current", old_version.as_str()),
+        };
+        let paths: Vec<&str> = incompatibility
+            .changes
+            .iter()
+            .map(|change| change.path.as_str())
+            .collect();
+
+        println!("{:<10}{:<10}{}", client, server, paths.join(", "));
+    }
+}
+
+/// The schema as of each snapshot in the history, oldest first.
+fn load_snapshots(
+    history_path: &Path,
+    history: &History,
+) -> Result<Vec<(SchemaVersion, Schema)>, Problem> {
+    history
+        .snapshots
+        .iter()
+        .map(|snapshot| {
+            loader::load(&[(history_path.to_path_buf(), snapshot.source.clone())])
+                .map(|schema| (snapshot.version, schema))
+                .map_err(Problem::Loader)
+        })
+        .collect()
+}
+
+/// Load the schema from the given `.wf` files and directories (or the current
+/// directory, if none were given), and make sure its type histories are valid.
+fn load_schema(file_paths: &[PathBuf]) -> Result<(Vec<Source>, Schema), Problem> {
+    let file_paths: Vec<PathBuf> = if file_paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        file_paths.to_vec()
+    };
+    let sources = loader::read_sources(&file_paths).map_err(Problem::Loader)?;
+    let schema = loader::load(&sources).map_err(Problem::Loader)?;
+    let type_problems: Vec<widening::Problem> = schema
+        .records
+        .iter()
+        .flat_map(widening::check_record)
+        .chain(schema.endpoints.iter().flat_map(widening::check_endpoint))
+        .collect();
+
+    if type_problems.is_empty() {
+        Ok((sources, schema))
+    } else {
+        Err(Problem::TypeChanges(type_problems))
+    }
+}
+
+/// Load the schema as it was at the given git revision, from the same files and
+/// directories `load_schema` would use now.
+fn load_schema_at(revision: &str, file_paths: &[PathBuf]) -> Result<Schema, Problem> {
+    let file_paths: Vec<PathBuf> = if file_paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        file_paths.to_vec()
+    };
+    let sources = git::read_sources(Path::new("."), revision, &file_paths).map_err(Problem::Git)?;
+
+    loader::load(&sources).map_err(Problem::Loader)
+}
+
+/// Assign IDs to everything in the schema, refusing to reintroduce any names
+/// the history has retired, or to guess whether something was renamed.
+fn allocate_ids(history: &mut History, schema: &mut Schema) -> Result<Allocation, Problem> {
+    let questions = migration::removal_questions(history, schema);
+
+    if !questions.is_empty() {
+        return Err(Problem::UndecidedMigrations(questions));
+    }
+
+    code_gen::check_for_retired_names(history, schema).map_err(Problem::CodeGen)?;
+
+    Ok(allocator::allocate(history, schema))
+}
+
+/// Read the history, or with `--since`, whichever is further along of the
+/// history file and the history as of that revision. That way a stale local
+/// file never wins over what was deployed, but a branch's own migrations do.
+/// If the further along one is missing IDs the other handed out, they've
+/// diverged, and neither can be used.
+fn load_history(args: &CliArgs) -> Result<History, Problem> {
+    let history = read_history(&args.history_path)?;
+
+    match args.since {
+        Some(ref revision) => {
+            let deployed = history_at(revision, args)?;
+            let (older, newer) = if deployed.version > history.version {
+                (history, deployed)
+            } else {
+                (deployed, history)
+            };
+            let missing = older.missing_from(&newer);
+
+            if !missing.is_empty() {
+                return Err(Problem::DivergedHistories {
+                    revision: revision.clone(),
+                    path: args.history_path.clone(),
+                    missing,
+                });
+            }
+
+            Ok(newer)
+        }
+        None => Ok(history),
+    }
+}
+
+/// The history file as it was committed at the given revision. If there wasn't
+/// one, the history is worked out from the `.wf` files at that revision, as if
+/// `wf build` had been run on them.
+fn history_at(revision: &str, args: &CliArgs) -> Result<History, Problem> {
+    let committed =
+        git::read_file(Path::new("."), revision, &args.history_path).map_err(Problem::Git)?;
+
+    match committed {
+        Some(source) => history::parse(&source).map_err(Problem::History),
+        None => {
+            let mut history = History::new();
+            let mut schema = load_schema_at(revision, &args.file_paths)?;
+
+            allocator::allocate(&mut history, &mut schema);
+
+            Ok(history)
+        }
+    }
+}
+
+/// A missing history file is fine; it just means nothing has been recorded yet.
+fn read_history(path: &Path) -> Result<History, Problem> {
+    match history::read_from(path) {
+        Err(history::Problem::Io(ref err)) if err.kind() == io::ErrorKind::NotFound => {
+            Ok(History::new())
         }
+        result => result.map_err(Problem::History),
     }
 }
@@ -0,0 +1,116 @@
+use parse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use types::Schema;
+
+#[derive(Debug)]
+pub enum Problem {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, parse::Problem),
+    DuplicateDeclaration(String),
+}
+
+/// A `.wf` file's path along with its contents.
+pub type Source = (PathBuf, String);
+
+/// Find every `.wf` file in the given files and directories (searching
+/// directories recursively) and read them in. They're returned sorted by path,
+/// so that loading the same files always produces the same schema.
+pub fn read_sources(paths: &[PathBuf]) -> Result<Vec<Source>, Problem> {
+    let mut file_paths: Vec<PathBuf> = vec![];
+
+    for path in paths {
+        find_wf_files(path, &mut file_paths)?;
+    }
+
+    file_paths.sort();
+    file_paths.dedup();
+
+    file_paths
+        .into_iter()
+        .map(|path| match fs::read_to_string(&path) {
+            Ok(contents) => Ok((path, contents)),
+            Err(err) => Err(Problem::Io(path, err)),
+        })
+        .collect()
+}
+
+fn find_wf_files(path: &Path, file_paths: &mut Vec<PathBuf>) -> Result<(), Problem> {
+    let io_problem = |err| Problem::Io(path.to_path_buf(), err);
+
+    if fs::metadata(path).map_err(io_problem)?.is_dir() {
+        for entry in fs::read_dir(path).map_err(io_problem)? {
+            let entry_path = entry.map_err(io_problem)?.path();
+
+            if entry_path.is_dir() || entry_path.extension().is_some_and(|ext| ext == "wf") {
+                find_wf_files(&entry_path, file_paths)?;
+            }
+        }
+    } else {
+        // Files given explicitly are loaded regardless of their extension.
+        file_paths.push(path.to_path_buf());
+    }
+
+    Ok(())
+}
+
+/// Parse each source and combine them into one schema.
+pub fn load(sources: &[Source]) -> Result<Schema, Problem> {
+    let mut schema = Schema::default();
+
+    for (path, contents) in sources {
+        let parsed =
+            parse::parse(contents).map_err(|problem| Problem::Parse(path.clone(), problem))?;
+
+        schema.records.extend(parsed.records);
+        schema.custom_types.extend(parsed.custom_types);
+        schema.aliases.extend(parsed.aliases);
+        schema.endpoints.extend(parsed.endpoints);
+    }
+
+    check_for_duplicates(&schema)?;
+
+    Ok(schema)
+}
+
+/// Concatenate the sources into a single `.wf` document, e.g. to store in the
+/// schema history.
+pub fn concat(sources: &[Source]) -> String {
+    sources
+        .iter()
+        .map(|(_, contents)| contents.trim())
+        .collect::<Vec<&str>>()
+        .join("\n\n")
+}
+
+fn check_for_duplicates(schema: &Schema) -> Result<(), Problem> {
+    // Records, custom types, and aliases share a namespace.
+    let mut type_names: Vec<String> = schema
+        .records
+        .iter()
+        .filter_map(|record| record.name.as_ref().map(|name| name.to_string()))
+        .chain(
+            schema
+                .custom_types
+                .iter()
+                .map(|custom_type| custom_type.name.to_string()),
+        )
+        .chain(schema.aliases.iter().map(|alias| alias.name.to_string()))
+        .collect();
+    let mut endpoint_names: Vec<String> = schema
+        .endpoints
+        .iter()
+        .map(|endpoint| endpoint.name.clone())
+        .collect();
+
+    for names in [&mut type_names, &mut endpoint_names].iter_mut() {
+        names.sort();
+
+        if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(Problem::DuplicateDeclaration(pair[0].clone()));
+        }
+    }
+
+    Ok(())
+}
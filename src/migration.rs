@@ -0,0 +1,438 @@
+use allocator;
+use allocator::Allocation;
+use compat;
+use compat::ChangeKind;
+use history::{Entry, History, Ledger, SchemaVersion};
+use std::io;
+use std::io::{BufRead, Write};
+use types::{Field, Schema, Variant};
+
+/// A change to the schema which `wf` can't interpret on its own, because the
+/// history would end up different depending on what was meant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Question {
+    /// A field or variant disappeared from a record or custom type, while
+    /// others appeared in it. Was it renamed to one of them, or removed?
+    Removed {
+        /// The record or custom type, e.g. "User"
+        parent: String,
+        name: String,
+        candidates: Vec<String>,
+    },
+
+    /// A field's type (or a variant's contents) changed, and it wasn't a
+    /// declared widening. Should it keep its ID, so old clients' values are
+    /// still decoded (and had better be compatible), or get a new one, so
+    /// they're ignored?
+    Retyped { parent: String, name: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    /// It was renamed to the given name, and should keep its ID.
+    Rename(String),
+
+    /// It was removed, and its ID should be retired.
+    Retire,
+
+    /// Retire its old ID and give it a new one.
+    NewId,
+
+    /// Keep its ID despite the new type.
+    KeepId,
+}
+
+/// Decisions given up front as command line flags, identified by qualified
+/// names like "User.email"
+#[derive(Debug, Default, PartialEq)]
+pub struct Decisions {
+    /// Former names paired with new names, e.g. ("User.id", "userId")
+    pub renames: Vec<(String, String)>,
+    pub retire: Vec<String>,
+    pub new_ids: Vec<String>,
+    pub keep_ids: Vec<String>,
+}
+
+impl Question {
+    /// The qualified name the question is about, e.g. "User.email"
+    pub fn path(&self) -> String {
+        match self {
+            Question::Removed { parent, name, .. } | Question::Retyped { parent, name } => {
+                format!("{}.{}", parent, name)
+            }
+        }
+    }
+
+    /// The decisions which would answer this question.
+    pub fn choices(&self) -> Vec<Decision> {
+        match self {
+            Question::Removed { candidates, .. } => candidates
+                .iter()
+                .map(|candidate| Decision::Rename(candidate.clone()))
+                .chain(Some(Decision::Retire))
+                .collect(),
+            Question::Retyped { .. } => vec![Decision::NewId, Decision::KeepId],
+        }
+    }
+}
+
+impl Decisions {
+    /// The decision for the given question, if one was given.
+    pub fn decide(&self, question: &Question) -> Option<Decision> {
+        let path = question.path();
+        let rename = self
+            .renames
+            .iter()
+            .find(|(former_name, _)| *former_name == path)
+            .map(|(_, name)| Decision::Rename(name.clone()));
+        let decision = match question {
+            Question::Removed { .. } => {
+                rename.or_else(|| flagged(&self.retire, &path, Decision::Retire))
+            }
+            Question::Retyped { .. } => flagged(&self.new_ids, &path, Decision::NewId)
+                .or_else(|| flagged(&self.keep_ids, &path, Decision::KeepId)),
+        };
+
+        decision.filter(|decision| question.choices().contains(decision))
+    }
+}
+
+fn flagged(paths: &[String], path: &str, decision: Decision) -> Option<Decision> {
+    if paths.iter().any(|flagged_path| flagged_path == path) {
+        Some(decision)
+    } else {
+        None
+    }
+}
+
+/// Find the changes between the history (and the latest snapshot, if there is
+/// one) and the schema which need a human to decide what they mean.
+///
+/// Fields and variants which were added or removed without anything else
+/// changing in the same record or custom type are unambiguous, as are renames
+/// marked with `[ fka "..." ]`, so they're left to `allocator::allocate`.
+pub fn questions(
+    history: &History,
+    baseline: Option<(SchemaVersion, &Schema)>,
+    schema: &Schema,
+) -> Vec<Question> {
+    let mut questions = removal_questions(history, schema);
+
+    if let Some((baseline_version, baseline)) = baseline {
+        for change in compat::compare(baseline, schema) {
+            let is_retyped = match change.kind {
+                ChangeKind::TypeChanged { .. } => change.impact == compat::Impact::BreaksBoth,
+                ChangeKind::VariantContentsChanged => true,
+                _ => false,
+            };
+            // The parent is everything before the last dot, so that endpoint
+            // bodies and inline records find their ledgers, e.g.
+            // "sendDM.params" for "sendDM.params.userId".
+            let mut segments = change.path.rsplitn(2, '.');
+
+            if let (true, Some(name), Some(parent)) = (is_retyped, segments.next(), segments.next())
+            {
+                let entry = history
+                    .record(parent)
+                    .or_else(|| history.custom_type(parent))
+                    .and_then(|ledger| ledger.entry(name));
+
+                // If it already got a new ID (or kept its ID) since the
+                // baseline, that settles it.
+                if entry.is_some_and(|entry| {
+                    entry.since <= baseline_version
+                        && entry
+                            .retyped
+                            .iter()
+                            .all(|retyped| *retyped <= baseline_version)
+                }) {
+                    questions.push(Question::Retyped {
+                        parent: parent.to_string(),
+                        name: name.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    questions
+}
+
+/// Only the questions about whether something was renamed or removed. These
+/// have to be answered before building, because allocating IDs would settle
+/// them (as removals) for good.
+pub fn removal_questions(history: &History, schema: &Schema) -> Vec<Question> {
+    let mut questions: Vec<Question> = vec![];
+
+    let mut records: Vec<(String, &[Field])> = schema
+        .records
+        .iter()
+        .filter_map(|record| {
+            record
+                .ledger_name()
+                .map(|parent| (parent, record.fields.as_slice()))
+        })
+        .collect();
+
+    // Endpoint bodies and inline records have ledgers too, named for where
+    // they are, e.g. "sendDM.params" or "User.profile".
+    schema.visit_inline_records(&mut |location, fields| {
+        if !records.iter().any(|(parent, _)| parent == location) {
+            records.push((location.to_string(), fields));
+        }
+    });
+
+    for (parent, fields) in records {
+        if let Some(ledger) = history.record(parent.as_str()) {
+            let names: Vec<(&str, &[String])> = fields.iter().map(field_names).collect();
+
+            ask_about_removals(parent, ledger, &names, &mut questions);
+        }
+    }
+
+    for custom_type in schema.custom_types.iter() {
+        let parent = custom_type.name.to_string();
+
+        if let Some(ledger) = history.custom_type(parent.as_str()) {
+            let names: Vec<(&str, &[String])> =
+                custom_type.variants.iter().map(variant_names).collect();
+
+            ask_about_removals(parent, ledger, &names, &mut questions);
+        }
+    }
+
+    questions
+}
+
+fn field_names(field: &Field) -> (&str, &[String]) {
+    (field.name.as_str(), &field.former_names)
+}
+
+fn variant_names(variant: &Variant) -> (&str, &[String]) {
+    (variant.name.as_str(), &variant.former_names)
+}
+
+/// `names` holds each field or variant's name, along with its former names.
+fn ask_about_removals(
+    parent: String,
+    ledger: &Ledger,
+    names: &[(&str, &[String])],
+    questions: &mut Vec<Question>,
+) {
+    let is_known = |name: &str| ledger.entry(name).is_some();
+    let renamed_from: Vec<&String> = names
+        .iter()
+        .filter(|(name, _)| !is_known(name))
+        .flat_map(|(_, former_names)| former_names.iter())
+        .filter(|former_name| is_known(former_name))
+        .collect();
+    let removed: Vec<&str> = ledger
+        .entries
+        .iter()
+        .filter(|entry| entry.retired.is_none())
+        .map(|entry| entry.name.as_str())
+        .filter(|name| {
+            !names.iter().any(|(current, _)| current == name)
+                && !renamed_from.iter().any(|former_name| former_name == name)
+        })
+        .collect();
+    let added: Vec<String> = names
+        .iter()
+        .filter(|(name, former_names)| {
+            !is_known(name) && !former_names.iter().any(|former_name| is_known(former_name))
+        })
+        .map(|(name, _)| name.to_string())
+        .collect();
+
+    if added.is_empty() {
+        return;
+    }
+
+    for name in removed {
+        questions.push(Question::Removed {
+            parent: parent.clone(),
+            name: name.to_string(),
+            candidates: added.clone(),
+        });
+    }
+}
+
+/// Ask about a question on the given input and output (normally stdin and
+/// stdout), returning `None` if the input ran out before it was answered.
+pub fn ask<R: BufRead, W: Write>(
+    question: &Question,
+    input: &mut R,
+    output: &mut W,
+) -> io::Result<Option<Decision>> {
+    let choices = question.choices();
+
+    writeln!(output, "\n{}", describe(question))?;
+
+    for (index, choice) in choices.iter().enumerate() {
+        writeln!(
+            output,
+            "    {}. {}",
+            index + 1,
+            describe_choice(question, choice)
+        )?;
+    }
+
+    loop {
+        write!(output, "Choose 1-{}: ", choices.len())?;
+        output.flush()?;
+
+        let mut line = String::new();
+
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        match line.trim().parse::<usize>() {
+            Ok(number) if (1..=choices.len()).contains(&number) => {
+                return Ok(Some(choices[number - 1].clone()));
+            }
+            _ => writeln!(output, "Please enter a number from the list.")?,
+        }
+    }
+}
+
+pub fn describe(question: &Question) -> String {
+    match question {
+        Question::Removed {
+            parent,
+            name,
+            candidates,
+        } => format!(
+            "{}.{} is gone, and {} {} added. Was it renamed?",
+            parent,
+            name,
+            candidates
+                .iter()
+                .map(|candidate| format!("{}.{}", parent, candidate))
+                .collect::<Vec<String>>()
+                .join(", "),
+            if candidates.len() == 1 { "was" } else { "were" }
+        ),
+        Question::Retyped { parent, name } => format!(
+            "{}.{} changed in a way old clients and servers won't understand. Should it get a new ID?",
+            parent, name
+        ),
+    }
+}
+
+fn describe_choice(question: &Question, decision: &Decision) -> String {
+    match decision {
+        Decision::Rename(name) => format!("It was renamed to {}", name),
+        Decision::Retire => String::from("It was removed"),
+        Decision::NewId => format!(
+            "Give {} a new ID, so values sent by old code are ignored",
+            question.path()
+        ),
+        Decision::KeepId => format!(
+            "Keep {}'s ID, so values sent by old code are decoded as the new type",
+            question.path()
+        ),
+    }
+}
+
+/// Record the decisions in the history, and allocate IDs for the rest of the
+/// schema as usual.
+///
+/// Renames are applied as if the new field or variant had been marked
+/// `[ fka "..." ]`, so the history gets a `fka` entry and the ID is kept.
+/// Retirements happen on their own, since the name is no longer in the schema.
+/// Retyped fields and variants either have their entries retired, so they get
+/// new ones, or get a `retyped` entry, so they aren't asked about again.
+pub fn apply(
+    history: &mut History,
+    schema: &mut Schema,
+    decisions: &[(Question, Decision)],
+) -> Allocation {
+    let version = history.version + 1;
+    let mut retired: Vec<String> = vec![];
+    let mut retyped: Vec<String> = vec![];
+
+    for (question, decision) in decisions.iter() {
+        match (question, decision) {
+            (Question::Removed { parent, name, .. }, Decision::Rename(new_name)) => {
+                add_former_name(schema, parent, new_name, name)
+            }
+            (Question::Retyped { parent, name }, Decision::NewId) => {
+                for entry in entries_in_use(history, parent, name) {
+                    entry.retired = Some(version);
+                    retired.push(question.path());
+                }
+            }
+            (Question::Retyped { parent, name }, Decision::KeepId) => {
+                for entry in entries_in_use(history, parent, name) {
+                    entry.retyped.push(version);
+                    retyped.push(question.path());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let mut allocation = allocator::allocate(history, schema);
+
+    allocation.retired.extend(retired);
+
+    // Keeping an ID allocates nothing, but it still needs recording.
+    if !retyped.is_empty() {
+        history.version = version;
+        allocation.version = version;
+        allocation.retyped = retyped;
+    }
+
+    allocation
+}
+
+/// The entries in use for the given name in any record or custom type with
+/// the given name.
+fn entries_in_use<'a>(
+    history: &'a mut History,
+    parent: &'a str,
+    name: &'a str,
+) -> impl Iterator<Item = &'a mut Entry> {
+    history
+        .records
+        .iter_mut()
+        .chain(history.custom_types.iter_mut())
+        .filter(move |ledger| ledger.name == parent)
+        .flat_map(|ledger| ledger.entries.iter_mut())
+        .filter(move |entry| entry.name == name && entry.retired.is_none())
+}
+
+fn add_former_name(schema: &mut Schema, parent: &str, name: &str, former_name: &str) {
+    for record in schema.records.iter_mut() {
+        if record
+            .name
+            .as_ref()
+            .is_some_and(|record_name| record_name.to_string() == parent)
+        {
+            for field in record.fields.iter_mut().filter(|field| field.name == name) {
+                field.former_names.push(former_name.to_string());
+            }
+        }
+    }
+
+    for custom_type in schema.custom_types.iter_mut() {
+        if custom_type.name.to_string() == parent {
+            for variant in custom_type
+                .variants
+                .iter_mut()
+                .filter(|variant| variant.name == name)
+            {
+                variant.former_names.push(former_name.to_string());
+            }
+        }
+    }
+
+    schema.visit_inline_records_mut(&mut |location, fields| {
+        if location == parent {
+            for field in fields.iter_mut().filter(|field| field.name == name) {
+                field.former_names.push(former_name.to_string());
+            }
+        }
+    });
+}
@@ -0,0 +1,236 @@
+use code_gen::Mode;
+use codec::Names;
+use std::fmt;
+
+/// The formats a payload can go over the wire in. Each has its own media type,
+/// so that servers can tell what a request body is from its `Content-Type`,
+/// and pick what to respond with from its `Accept` header.
+///
+/// Development builds only speak development JSON, and production builds
+/// speak minified JSON (for browsers) and binary (between servers), so
+/// a client and server built in different modes never agree on a format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Development,
+    Minified,
+    Binary,
+}
+
+pub const DEVELOPMENT_MEDIA_TYPE: &str = "application/vnd.wirefunc.dev+json";
+pub const MINIFIED_MEDIA_TYPE: &str = "application/vnd.wirefunc.min+json";
+pub const BINARY_MEDIA_TYPE: &str = "application/vnd.wirefunc.bin";
+
+/// What `Accept: application/json` and a missing `Content-Type` mean.
+const GENERIC_JSON_MEDIA_TYPE: &str = "application/json";
+
+impl Format {
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Format::Development => DEVELOPMENT_MEDIA_TYPE,
+            Format::Minified => MINIFIED_MEDIA_TYPE,
+            Format::Binary => BINARY_MEDIA_TYPE,
+        }
+    }
+
+    /// The format with the given media type. Parameters like `; charset=utf-8`
+    /// are ignored, and so is case.
+    pub fn from_media_type(media_type: &str) -> Option<Format> {
+        let essence = essence(media_type);
+
+        [Format::Development, Format::Minified, Format::Binary]
+            .iter()
+            .cloned()
+            .find(|format| format.media_type() == essence)
+    }
+
+    /// The mode whose builds speak this format.
+    pub fn mode(self) -> Mode {
+        match self {
+            Format::Development => Mode::Development,
+            Format::Minified | Format::Binary => Mode::Production,
+        }
+    }
+
+    /// The names the `codec` module uses for this format, if it's JSON.
+    pub fn names(self) -> Option<Names> {
+        match self {
+            Format::Development => Some(Names::Development),
+            Format::Minified => Some(Names::Packed),
+            Format::Binary => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// None of the formats this build speaks are in the `Accept` header.
+    NotAcceptable(String),
+
+    /// The `Content-Type` isn't one of our formats.
+    UnsupportedMediaType(String),
+
+    /// The `Content-Type` is the other mode's format.
+    MixedFormats { expected: Mode, found: Format },
+}
+
+impl Problem {
+    /// The HTTP status a server should respond with.
+    pub fn status(&self) -> u16 {
+        match self {
+            Problem::NotAcceptable(_) => 406,
+            Problem::UnsupportedMediaType(_) | Problem::MixedFormats { .. } => 415,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::NotAcceptable(accept) => write!(
+                formatter,
+                "none of the formats in \"Accept: {}\" are supported",
+                accept
+            ),
+            Problem::UnsupportedMediaType(content_type) => {
+                write!(formatter, "unsupported Content-Type {}", content_type)
+            }
+            Problem::MixedFormats { expected, found } => write!(
+                formatter,
+                "a {} build can't read {}. Were the client and server built in different modes?",
+                describe_mode(*expected),
+                found.media_type()
+            ),
+        }
+    }
+}
+
+fn describe_mode(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Development => "development",
+        Mode::Production => "production",
+    }
+}
+
+/// The formats builds in the given mode speak, most preferred first.
+pub fn supported(mode: Mode) -> &'static [Format] {
+    match mode {
+        Mode::Development => &[Format::Development],
+        Mode::Production => &[Format::Minified, Format::Binary],
+    }
+}
+
+/// The JSON format builds in the given mode speak, which is what they use when
+/// the other side doesn't say.
+pub fn default_format(mode: Mode) -> Format {
+    match mode {
+        Mode::Development => Format::Development,
+        Mode::Production => Format::Minified,
+    }
+}
+
+/// The `Accept` header a client should send. Browsers can't cheaply decode
+/// binary, so only clients which can should include it.
+pub fn accept_header(mode: Mode, accepts_binary: bool) -> String {
+    match mode {
+        Mode::Production if accepts_binary => {
+            format!("{}, {};q=0.9", BINARY_MEDIA_TYPE, MINIFIED_MEDIA_TYPE)
+        }
+        mode => default_format(mode).media_type().to_string(),
+    }
+}
+
+/// Pick the format to respond in, given the request's `Accept` header. As in
+/// HTTP, the most specific media range matching a format decides its quality,
+/// and ties go to whichever format comes first in `supported`.
+pub fn response_format(accept: Option<&str>, mode: Mode) -> Result<Format, Problem> {
+    let accept = match accept {
+        Some(accept) if !accept.trim().is_empty() => accept,
+        _ => return Ok(default_format(mode)),
+    };
+    let ranges: Vec<(String, f64)> = accept.split(',').map(media_range).collect();
+    let mut best: Option<(Format, f64)> = None;
+
+    for format in supported(mode).iter().cloned() {
+        let quality = ranges
+            .iter()
+            .filter_map(|(range, quality)| {
+                specificity(range, format, mode).map(|specificity| (specificity, *quality))
+            })
+            .fold(
+                None,
+                |best: Option<(u8, f64)>, (specificity, quality)| match best {
+                    Some((best_specificity, _)) if best_specificity >= specificity => best,
+                    _ => Some((specificity, quality)),
+                },
+            )
+            .map_or(0.0, |(_, quality)| quality);
+
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((format, quality));
+        }
+    }
+
+    best.map(|(format, _)| format)
+        .ok_or_else(|| Problem::NotAcceptable(accept.to_string()))
+}
+
+/// The format of a request body, given its `Content-Type` header.
+pub fn request_format(content_type: Option<&str>, mode: Mode) -> Result<Format, Problem> {
+    let content_type = match content_type {
+        Some(content_type) if !content_type.trim().is_empty() => content_type,
+        _ => return Ok(default_format(mode)),
+    };
+
+    if essence(content_type) == GENERIC_JSON_MEDIA_TYPE {
+        return Ok(default_format(mode));
+    }
+
+    match Format::from_media_type(content_type) {
+        Some(format) if format.mode() == mode => Ok(format),
+        Some(format) => Err(Problem::MixedFormats {
+            expected: mode,
+            found: format,
+        }),
+        None => Err(Problem::UnsupportedMediaType(content_type.to_string())),
+    }
+}
+
+/// The media type without its parameters, lowercased.
+fn essence(media_type: &str) -> String {
+    media_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Parses e.g. "application/json;q=0.5" into ("application/json", 0.5)
+fn media_range(range: &str) -> (String, f64) {
+    let quality = range
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.trim().strip_prefix("q="))
+        .filter_map(|quality| quality.trim().parse::<f64>().ok())
+        .next()
+        .unwrap_or(1.0);
+
+    (essence(range), quality)
+}
+
+/// How specifically the media range matches the format, if it does at all:
+/// `*/*` least, then `application/*`, then `application/json` (which means
+/// the mode's JSON format), then the format's own media type.
+fn specificity(range: &str, format: Format, mode: Mode) -> Option<u8> {
+    if range == format.media_type() {
+        Some(3)
+    } else if range == GENERIC_JSON_MEDIA_TYPE && format == default_format(mode) {
+        Some(2)
+    } else if range == "application/*" {
+        Some(1)
+    } else if range == "*/*" {
+        Some(0)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,624 @@
+use types::{
+    Alias, CustomTypeInfo, Endpoint, Field, Optionality, RecordInfo, Schema, Type, TypeName,
+    TypeParam, TypePrimitive, Variant, Verb,
+};
+
+/// Parse the contents of a `.wf` file. For example:
+///
+/// ```text
+/// post sendDM
+///     params:
+///         * userId : Int
+///         ~ contents : String [ default "" ]
+///
+///     response:
+///         * user : User Profile
+///
+/// struct User a
+///     * userId : Int [ fka "id" ]
+///     * profile : a
+///     ~ score : Float64 [ formerly Float32 ]
+///
+/// type Reply
+///     | Email EmailReply
+///     | TextMessage TextReply
+///
+/// alias Email = String
+/// ```
+///
+/// Declarations start at the beginning of a line, and their contents are
+/// indented beneath them.
+///
+/// * `*` marks a required field, `~` an optional one, and `!` a deprecated one.
+/// * Attributes go in square brackets after the field's type, or after the
+///   name of a record or endpoint, e.g. `struct User [ packed ]`
+/// * `record` is a synonym for `struct`.
+/// * Lines beginning with `--` are comments.
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+    pub line_number: usize,
+    pub line: String,
+
+    /// What we were expecting to find instead, e.g. "a field like `* userId : Int`"
+    pub expected: &'static str,
+}
+
+pub fn parse(source: &str) -> Result<Schema, Problem> {
+    let mut schema = Schema::default();
+    let mut context = Context::TopLevel;
+
+    for (index, line) in source.lines().enumerate() {
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with("--") {
+            continue;
+        }
+
+        let problem = |expected: &'static str| Problem {
+            line_number: index + 1,
+            line: line.to_string(),
+            expected,
+        };
+        let tokens = tokenize(line).ok_or_else(|| problem("a closing quotation mark"))?;
+        let is_indented = line.starts_with(' ') || line.starts_with('\t');
+
+        if !is_indented {
+            context = parse_declaration(&mut schema, line, &tokens).map_err(problem)?;
+
+            continue;
+        }
+
+        match context {
+            Context::TopLevel => {
+                return Err(problem(
+                    "a declaration like `struct User` at the start of the line",
+                ));
+            }
+            Context::Record => {
+                let field = parse_field(line, &tokens).map_err(problem)?;
+
+                schema.records.last_mut().unwrap().fields.push(field);
+            }
+            Context::CustomType => {
+                let variant = parse_variant(&schema, line, &tokens).map_err(problem)?;
+
+                schema
+                    .custom_types
+                    .last_mut()
+                    .unwrap()
+                    .variants
+                    .push(variant);
+            }
+            Context::Endpoint(section) => match tokens.as_slice() {
+                [Token::Word("params"), Token::Symbol(":")]
+                | [Token::Word("args"), Token::Symbol(":")] => {
+                    context = Context::Endpoint(Some(Section::Params));
+                }
+                [Token::Word("response"), Token::Symbol(":")] => {
+                    context = Context::Endpoint(Some(Section::Response));
+                }
+                _ => {
+                    let field = parse_field(line, &tokens).map_err(problem)?;
+                    let endpoint = schema.endpoints.last_mut().unwrap();
+
+                    match section {
+                        Some(Section::Params) => endpoint.params.push(field),
+                        Some(Section::Response) => endpoint.response.push(field),
+                        None => return Err(problem("`params:` or `response:`")),
+                    }
+                }
+            },
+            Context::Alias => {
+                return Err(problem(
+                    "a declaration like `struct User` at the start of the line",
+                ));
+            }
+        }
+    }
+
+    schema.locate_inline_records();
+
+    Ok(schema)
+}
+
+#[derive(Clone, Copy)]
+enum Context {
+    TopLevel,
+    Record,
+    CustomType,
+    Alias,
+    Endpoint(Option<Section>),
+}
+
+#[derive(Clone, Copy)]
+enum Section {
+    Params,
+    Response,
+}
+
+const EXPECTED_DECLARATION: &str =
+    "a declaration like `struct User`, `type Reply`, `alias Email = String`, or `post sendDM`";
+
+fn parse_declaration(
+    schema: &mut Schema,
+    line: &str,
+    tokens: &[Token],
+) -> Result<Context, &'static str> {
+    let (keyword, rest) = match tokens.split_first() {
+        Some((Token::Word(keyword), rest)) => (*keyword, rest),
+        _ => return Err(EXPECTED_DECLARATION),
+    };
+
+    match keyword {
+        "struct" | "record" => {
+            let (name, params, rest) = parse_type_header(rest)?;
+            let attributes = parse_attributes(line, rest)?;
+
+            schema.records.push(RecordInfo {
+                name: Some(name),
+                former_names: type_names(attributes.former_names),
+                fields: vec![],
+                params,
+                packed: attributes.packed,
+                location: None,
+            });
+
+            Ok(Context::Record)
+        }
+        "type" => {
+            let (name, params, rest) = parse_type_header(rest)?;
+            let attributes = parse_attributes(line, rest)?;
+
+            schema.custom_types.push(CustomTypeInfo {
+                name,
+                former_names: type_names(attributes.former_names),
+                variants: vec![],
+                params,
+            });
+
+            Ok(Context::CustomType)
+        }
+        "alias" => {
+            let (name, params, rest) = parse_type_header(rest)?;
+            let rest = match rest.split_first() {
+                Some((Token::Symbol("="), rest)) => rest,
+                _ => return Err("an `=` after the alias's name"),
+            };
+            let (tipe, rest) = parse_type(rest)?;
+
+            if !rest.is_empty() {
+                return Err("the end of the line after the alias's type");
+            }
+
+            schema.aliases.push(Alias {
+                name,
+                params,
+                tipe: Type::new(tipe),
+            });
+
+            Ok(Context::Alias)
+        }
+        _ => {
+            let verb = match keyword {
+                "get" => Verb::Get,
+                "post" => Verb::Post,
+                "put" => Verb::Put,
+                "patch" => Verb::Patch,
+                "delete" => Verb::Delete,
+                _ => return Err(EXPECTED_DECLARATION),
+            };
+            let (name, rest) = match rest {
+                [Token::Word(name), rest @ ..] => (name.to_string(), rest),
+                _ => return Err("an endpoint name like `post sendDM`"),
+            };
+            let attributes = parse_attributes(line, rest)?;
+
+            schema.endpoints.push(Endpoint {
+                verb,
+                name,
+                params: vec![],
+                response: vec![],
+                packed: attributes.packed,
+            });
+
+            Ok(Context::Endpoint(None))
+        }
+    }
+}
+
+/// Parses e.g. the `User a` in `struct User a`
+fn parse_type_header<'a, 'b>(
+    tokens: &'b [Token<'a>],
+) -> Result<(TypeName, Vec<TypeParam>, &'b [Token<'a>]), &'static str> {
+    let (name, mut rest) = match tokens.split_first() {
+        Some((Token::Word(name), rest)) if is_uppercase(name) => (*name, rest),
+        _ => return Err("a capitalized type name"),
+    };
+    let mut params = vec![];
+
+    while let Some((Token::Word(param), remaining)) = rest.split_first() {
+        if is_uppercase(param) {
+            return Err("lowercase type parameters");
+        }
+
+        params.push(TypeParam::TypeParam(param.to_string()));
+        rest = remaining;
+    }
+
+    Ok((TypeName::TypeName(name.to_string()), params, rest))
+}
+
+const EXPECTED_FIELD: &str = "a field like `* userId : Int`";
+
+fn parse_field(line: &str, tokens: &[Token]) -> Result<Field, &'static str> {
+    let (optionality, deprecated, rest) = match tokens.split_first() {
+        Some((Token::Symbol("*"), rest)) => (Optionality::Required, false, rest),
+        Some((Token::Symbol("~"), rest)) => (Optionality::Optional, false, rest),
+        Some((Token::Symbol("!"), rest)) => (Optionality::Optional, true, rest),
+        _ => return Err(EXPECTED_FIELD),
+    };
+    let (name, rest) = match rest {
+        [Token::Word(name), Token::Symbol(":"), rest @ ..] if !is_uppercase(name) => (*name, rest),
+        _ => return Err(EXPECTED_FIELD),
+    };
+    let (tipe, rest) = parse_type(rest)?;
+    let attributes = parse_attributes(line, rest)?;
+
+    Ok(Field {
+        optionality,
+        name: name.to_string(),
+        tipe: Type {
+            current: tipe,
+            formerly: attributes.formerly,
+        },
+        field_id: 0,
+        fallback: attributes.fallback,
+        former_names: attributes.former_names,
+        deprecated: deprecated || attributes.deprecated,
+        default: attributes.default,
+        header: attributes.header,
+    })
+}
+
+fn parse_variant(schema: &Schema, line: &str, tokens: &[Token]) -> Result<Variant, &'static str> {
+    let (name, mut rest) = match tokens {
+        [Token::Symbol("|"), Token::Word(name), rest @ ..] if is_uppercase(name) => (*name, rest),
+        _ => return Err("a variant like `| Email EmailReply`"),
+    };
+    let mut contents = vec![];
+
+    while !rest.is_empty() && rest[0] != Token::Symbol("[") {
+        let (content, remaining) = parse_atom(rest)?;
+
+        contents.push(Type::new(content));
+        rest = remaining;
+    }
+
+    let attributes = parse_attributes(line, rest)?;
+    let custom_type = schema.custom_types.last().unwrap();
+    let params = custom_type
+        .params
+        .iter()
+        .map(|param| Type::new(TypePrimitive::Param(param.clone())))
+        .collect();
+
+    Ok(Variant {
+        optionality: Optionality::Required,
+        name: name.to_string(),
+        tipe: Type::new(TypePrimitive::Named(custom_type.name.clone(), params)),
+        variant_id: 0,
+        former_names: attributes.former_names,
+        contents,
+    })
+}
+
+/// Parse a type, along with any arguments it takes, e.g. `Dict String (Array Int)`
+fn parse_type<'a, 'b>(
+    tokens: &'b [Token<'a>],
+) -> Result<(TypePrimitive, &'b [Token<'a>]), &'static str> {
+    match tokens.split_first() {
+        Some((Token::Word(name), rest)) if is_uppercase(name) => {
+            let mut args: Vec<Type> = vec![];
+            let mut rest = rest;
+
+            while starts_atom(rest) {
+                let (arg, remaining) = parse_atom(rest)?;
+
+                args.push(Type::new(arg));
+                rest = remaining;
+            }
+
+            Ok((apply(name, args)?, rest))
+        }
+        _ => parse_atom(tokens),
+    }
+}
+
+/// Parse a type that takes no arguments, or one that's wrapped in parens or
+/// braces, e.g. `Int` or `a` or `(Array Int)` or `{ ok : Int, err : String }`
+fn parse_atom<'a, 'b>(
+    tokens: &'b [Token<'a>],
+) -> Result<(TypePrimitive, &'b [Token<'a>]), &'static str> {
+    match tokens.split_first() {
+        Some((Token::Word(name), rest)) if is_uppercase(name) => Ok((apply(name, vec![])?, rest)),
+        Some((Token::Word(param), rest)) => Ok((
+            TypePrimitive::Param(TypeParam::TypeParam(param.to_string())),
+            rest,
+        )),
+        Some((Token::Symbol("("), rest)) => {
+            let (tipe, rest) = parse_type(rest)?;
+
+            match rest.split_first() {
+                Some((Token::Symbol(")"), rest)) => Ok((tipe, rest)),
+                _ => Err("a closing `)`"),
+            }
+        }
+        Some((Token::Symbol("{"), rest)) => parse_inline_record(rest),
+        _ => Err("a type like `Int` or `Array String`"),
+    }
+}
+
+fn starts_atom(tokens: &[Token]) -> bool {
+    matches!(
+        tokens.first(),
+        Some(Token::Word(_)) | Some(Token::Symbol("(")) | Some(Token::Symbol("{"))
+    )
+}
+
+/// Parses the fields of e.g. `{ ok : User Profile, err : Array String }`
+/// (after the opening brace). All of them are required.
+fn parse_inline_record<'a, 'b>(
+    tokens: &'b [Token<'a>],
+) -> Result<(TypePrimitive, &'b [Token<'a>]), &'static str> {
+    let mut fields = vec![];
+    let mut rest = tokens;
+
+    loop {
+        match rest {
+            [Token::Symbol("}"), remaining @ ..] => {
+                rest = remaining;
+                break;
+            }
+            [Token::Word(name), Token::Symbol(":"), remaining @ ..] => {
+                let (tipe, remaining) = parse_type(remaining)?;
+
+                fields.push(Field {
+                    optionality: Optionality::Required,
+                    name: name.to_string(),
+                    tipe: Type::new(tipe),
+                    field_id: 0,
+                    fallback: false,
+                    former_names: vec![],
+                    deprecated: false,
+                    default: None,
+                    header: None,
+                });
+
+                rest = match remaining.split_first() {
+                    Some((Token::Symbol(","), remaining)) => remaining,
+                    _ => remaining,
+                };
+            }
+            _ => return Err("a record field like `ok : Int` or a closing `}`"),
+        }
+    }
+
+    let record = RecordInfo {
+        name: None,
+        former_names: vec![],
+        fields,
+        params: vec![],
+        packed: false,
+        location: None,
+    };
+
+    Ok((TypePrimitive::Record(record), rest))
+}
+
+/// Apply a capitalized type name to its arguments, checking the arity of
+/// the built-in types.
+fn apply(name: &str, args: Vec<Type>) -> Result<TypePrimitive, &'static str> {
+    let scalar = match name {
+        "Bool" => Some(TypePrimitive::Bool),
+        "String" => Some(TypePrimitive::String),
+        "Int" => Some(TypePrimitive::Int),
+        "Int8" => Some(TypePrimitive::Int8),
+        "Int16" => Some(TypePrimitive::Int16),
+        "Int32" => Some(TypePrimitive::Int32),
+        "Int64" => Some(TypePrimitive::Int64),
+        "Float" => Some(TypePrimitive::Float),
+        "Float32" => Some(TypePrimitive::Float32),
+        "Float64" => Some(TypePrimitive::Float64),
+        _ => None,
+    };
+
+    if let Some(scalar) = scalar {
+        return if args.is_empty() {
+            Ok(scalar)
+        } else {
+            Err("no type arguments after a scalar type like `Int`")
+        };
+    }
+
+    let mut args = args;
+
+    match name {
+        "Array" | "Set" => {
+            if args.len() != 1 {
+                return Err("exactly one type argument, like `Array Int`");
+            }
+
+            let elem = Box::new(args.remove(0));
+
+            Ok(if name == "Array" {
+                TypePrimitive::Array(elem)
+            } else {
+                TypePrimitive::Set(elem)
+            })
+        }
+        "Dict" => {
+            if args.len() != 2 {
+                return Err("exactly two type arguments, like `Dict String Int`");
+            }
+
+            let key = Box::new(args.remove(0));
+            let val = Box::new(args.remove(0));
+
+            Ok(TypePrimitive::Dict(key, val))
+        }
+        _ => Ok(TypePrimitive::Named(
+            TypeName::TypeName(name.to_string()),
+            args,
+        )),
+    }
+}
+
+#[derive(Default)]
+struct Attributes {
+    former_names: Vec<String>,
+    formerly: Vec<TypePrimitive>,
+    default: Option<String>,
+    header: Option<String>,
+    deprecated: bool,
+    fallback: bool,
+    packed: bool,
+}
+
+/// Parses a series of attributes, e.g. `[ default "" ] [ fka "id" ]`
+fn parse_attributes(line: &str, tokens: &[Token]) -> Result<Attributes, &'static str> {
+    let mut attributes = Attributes::default();
+    let mut rest = tokens;
+
+    while let Some((first, remaining)) = rest.split_first() {
+        if *first != Token::Symbol("[") {
+            return Err("an attribute like `[ deprecated ]`");
+        }
+
+        // Find the matching `]`, skipping over any nested brackets like the
+        // ones in `[ default [] ]`
+        let mut depth = 1;
+        let mut end = 0;
+
+        while depth > 0 {
+            match remaining.get(end) {
+                Some(Token::Symbol("[")) => depth += 1,
+                Some(Token::Symbol("]")) => depth -= 1,
+                Some(_) => (),
+                None => return Err("a closing `]`"),
+            }
+
+            end += 1;
+        }
+
+        let contents = &remaining[0..end - 1];
+
+        rest = &remaining[end..];
+
+        match contents {
+            [Token::Word("deprecated")] => attributes.deprecated = true,
+            [Token::Word("fallback")] => attributes.fallback = true,
+            [Token::Word("packed")] => attributes.packed = true,
+            [Token::Word("fka"), Token::Str(name)] => attributes.former_names.push(name.to_string()),
+            [Token::Word("header"), Token::Str(name)] => attributes.header = Some(name.to_string()),
+            [Token::Word("formerly"), tipe @ ..] => {
+                let (former, leftover) = parse_type(tipe)?;
+
+                if !leftover.is_empty() {
+                    return Err("a single type after `formerly`");
+                }
+
+                attributes.formerly.push(former);
+            }
+            [Token::Word("default"), first_token, ..] => {
+                // Keep the default's source exactly as written. It gets
+                // interpreted by whoever needs to apply it.
+                let start = span_start(line, first_token);
+                let end = span_start(line, &remaining[end - 1]);
+
+                attributes.default = Some(line[start..end].trim().to_string());
+            }
+            _ => return Err("an attribute like `[ deprecated ]`, `[ fallback ]`, `[ fka \"oldName\" ]`, `[ default ... ]`, `[ formerly Int32 ]`, `[ header \"X-Name\" ]`, or `[ packed ]`"),
+        }
+    }
+
+    Ok(attributes)
+}
+
+fn type_names(names: Vec<String>) -> Vec<TypeName> {
+    names.into_iter().map(TypeName::TypeName).collect()
+}
+
+fn is_uppercase(word: &str) -> bool {
+    word.chars()
+        .next()
+        .is_some_and(|ch| ch.is_ascii_uppercase())
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    /// Identifiers and number literals, e.g. `userId` or `Int` or `1.5`
+    Word(&'a str),
+
+    /// The contents of a string literal, without the quotation marks
+    Str(&'a str),
+
+    /// Any other single character, e.g. `:` or `[`
+    Symbol(&'a str),
+}
+
+/// Tokens borrow from the line they came from, so we can recover their
+/// position in it from their pointers.
+fn span_start(line: &str, token: &Token) -> usize {
+    let offset = |text: &str| text.as_ptr() as usize - line.as_ptr() as usize;
+
+    match token {
+        Token::Word(text) | Token::Symbol(text) => offset(text),
+        // Include the opening quotation mark
+        Token::Str(text) => offset(text) - 1,
+    }
+}
+
+/// Returns None if there's an unterminated string literal.
+fn tokenize(line: &str) -> Option<Vec<Token<'_>>> {
+    let mut tokens = vec![];
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((start, ch)) = chars.next() {
+        if ch.is_whitespace() {
+            continue;
+        } else if ch == '"' {
+            let mut escaped = false;
+            let mut end = None;
+
+            for (index, ch) in chars.by_ref() {
+                if escaped {
+                    escaped = false;
+                } else if ch == '\\' {
+                    escaped = true;
+                } else if ch == '"' {
+                    end = Some(index);
+                    break;
+                }
+            }
+
+            tokens.push(Token::Str(&line[start + 1..end?]));
+        } else if ch.is_alphanumeric() || ch == '_' {
+            let mut end = start + ch.len_utf8();
+
+            while let Some(&(index, next)) = chars.peek() {
+                if next.is_alphanumeric() || next == '_' || next == '.' {
+                    end = index + next.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+
+            tokens.push(Token::Word(&line[start..end]));
+        } else {
+            tokens.push(Token::Symbol(&line[start..start + ch.len_utf8()]));
+        }
+    }
+
+    Some(tokens)
+}
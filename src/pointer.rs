@@ -1,6 +1,9 @@
 extern crate byteorder;
 
-/// There are three types of pointers:
+use std::cmp;
+use std::fmt;
+
+/// There are two types of pointers:
 ///
 /// 1. Segment Pointers
 /// 2. Composite Pointers
@@ -42,8 +45,8 @@ extern crate byteorder;
 ///   segments into the future. So this is extremely conservative, and if you
 ///   somehow run out, the only consequence is that the remainder of the message
 ///   gets stuck into the final segment, which could be uncomfortably large.
-/// * 15 bits of data length lets us store Arrays and Strings of up to 32,768 in
-///   length before we need a composite pointer. It's okay if this is not very
+/// * 15 bits of data length lets us store Arrays and Strings of up to 32,767 in
+///   length (`MAX_LENGTH`) before we need a composite pointer. It's okay if this is not very
 ///   long, because composite pointers give us theoretically infinite runway.
 ///   The only consequence to shortening this value is that values that don't
 ///   fit in this length need to use composite pointers, which are a bit slower.
@@ -69,12 +72,17 @@ extern crate byteorder;
 ///
 /// The difference is that for a Composite Pointer, the final bit will be 1
 /// instead of 0, and Data Length refers to the number of pointers in the array.
-
+///
+/// Encoders split a value into chunks of `MAX_LENGTH` (in the value's units),
+/// and point to each chunk with a Segment Pointer. If there are more than
+/// `MAX_LENGTH` of those, they're grouped under nested Composite Pointers
+/// until a single pointer is left. See `write_chunked` and `resolve`.
+///
 /// Layout on the wire:
 ///
 /// |--Index within Segment--|--Segment--|--Data Length--|--Composite?--|
 ///          32 bits            16 bits       15 bits          1 bit
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub struct Pointer {
     /// Index within the segment, in Words
     pub word_index: u32,
@@ -91,6 +99,10 @@ pub struct Pointer {
     pub is_composite: bool,
 }
 
+/// The longest Data Length a single pointer can hold. Longer values need a
+/// Composite Pointer.
+pub const MAX_LENGTH: usize = 0b0111_1111_1111_1111;
+
 #[inline]
 pub fn decode(word: u64) -> Pointer {
     Pointer {
@@ -107,14 +119,22 @@ pub fn decode(word: u64) -> Pointer {
         // Then zero out the first bit because that one's for segment_id_offset.
         length: ((word >> 1) as u16) & 0b0111_1111_1111_1111,
 
-        /// Check if the final bit in the Word is 1, indicating
-        /// that this is a Composite Pointer.
+        // Check if the final bit in the Word is 1, indicating
+        // that this is a Composite Pointer.
         is_composite: word & 0b1 != 0,
     }
 }
 
 #[inline]
 pub fn encode(ptr: Pointer) -> u64 {
+    // A longer length would spill into the segment offset, so the pointer
+    // would silently point somewhere else.
+    assert!(
+        ptr.length as usize <= MAX_LENGTH,
+        "Data Length only has 15 bits, but it was {}",
+        ptr.length
+    );
+
     // Convert the pointer's integer fields to u64s so we can do bitwise
     // operations on them.
     let word_index: u64 = ptr.word_index as u64;
@@ -134,3 +154,295 @@ pub fn encode(ptr: Pointer) -> u64 {
         result
     }
 }
+
+/// Write a value which is `length` units long to the end of the segment, and
+/// return a pointer to it. `write_chunk` appends units `start..end` of the
+/// value to the segment, and is called once per chunk of at most
+/// `MAX_LENGTH` units.
+///
+/// Empty values get the zero pointer, since decoders special-case it.
+pub fn write_chunked<F>(segment: &mut Vec<u64>, length: usize, mut write_chunk: F) -> Pointer
+where
+    F: FnMut(&mut Vec<u64>, usize, usize),
+{
+    let mut pointers: Vec<Pointer> = vec![];
+    let mut start = 0;
+
+    while start < length {
+        let end = cmp::min(start + MAX_LENGTH, length);
+        let word_index = word_index(segment);
+
+        write_chunk(segment, start, end);
+        pointers.push(Pointer {
+            word_index,
+            segment_id_offset: 0,
+            length: (end - start) as u16,
+            is_composite: false,
+        });
+
+        start = end;
+    }
+
+    // Stitch the chunks together with Composite Pointers, nesting them until
+    // there's only one pointer left.
+    while pointers.len() > 1 {
+        pointers = pointers
+            .chunks(MAX_LENGTH)
+            .map(|chunk| match chunk {
+                [ptr] => *ptr,
+                _ => {
+                    let word_index = word_index(segment);
+
+                    segment.extend(chunk.iter().map(|ptr| encode(*ptr)));
+
+                    Pointer {
+                        word_index,
+                        segment_id_offset: 0,
+                        length: chunk.len() as u16,
+                        is_composite: true,
+                    }
+                }
+            })
+            .collect();
+    }
+
+    pointers.pop().unwrap_or_else(|| decode(0))
+}
+
+/// The index of the next word to be written to the segment.
+fn word_index(segment: &[u64]) -> u32 {
+    if segment.len() > u32::MAX as usize {
+        panic!("Segments can't be longer than u32::MAX words!");
+    }
+
+    segment.len() as u32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// A pointer's `segment_id_offset` goes past the last segment.
+    MissingSegment(usize),
+
+    /// A pointer's data goes past the end of its segment.
+    OutOfBounds {
+        segment: usize,
+        word_index: usize,
+        words: usize,
+    },
+
+    /// Composite Pointers nested deeper than `MAX_COMPOSITE_DEPTH`.
+    TooDeep,
+
+    /// Composite Pointers which point to more Words of pointers than the
+    /// message has, so some of them must loop or overlap.
+    Overlapping,
+
+    /// Segment Pointers which point to more Words of data than the message
+    /// has, e.g. a Composite Pointer to the same long chunk over and over,
+    /// which would blow a small message up into a huge value.
+    TooMuchData,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::MissingSegment(segment) => {
+                write!(formatter, "a pointer refers to missing segment {}", segment)
+            }
+            Problem::OutOfBounds {
+                segment,
+                word_index,
+                words,
+            } => write!(
+                formatter,
+                "a pointer refers to Words {}..{} of segment {}, which is shorter than that",
+                word_index,
+                word_index + words,
+                segment
+            ),
+            Problem::TooDeep => write!(
+                formatter,
+                "Composite Pointers are nested more than {} deep",
+                MAX_COMPOSITE_DEPTH
+            ),
+            Problem::Overlapping => write!(formatter, "Composite Pointers loop or overlap"),
+            Problem::TooMuchData => write!(
+                formatter,
+                "pointers refer to more data than the message has, so some of it must be reused"
+            ),
+        }
+    }
+}
+
+/// How deeply Composite Pointers may be nested. Encoders only nest them once
+/// there are more than `MAX_LENGTH` chunks, so even 4 levels would cover
+/// values with over 10^18 units; this is just to stop hostile input from
+/// overflowing the stack.
+pub const MAX_COMPOSITE_DEPTH: usize = 8;
+
+/// What a pointer's Data Length counts, which depends on the type of value it
+/// points to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Units {
+    /// Arrays of 64-bit values, Sets, and Records
+    Words,
+
+    /// Bytes and Strings, packed 8 to a Word
+    Bytes,
+
+    /// Dict entries, each a key Word followed by a value Word
+    Entries,
+}
+
+impl Units {
+    /// How many Words the given Data Length takes up.
+    pub fn words(self, length: usize) -> usize {
+        match self {
+            Units::Words => length,
+            Units::Bytes => length.div_ceil(8),
+            Units::Entries => length * 2,
+        }
+    }
+}
+
+/// A Segment Pointer, along with the index of the segment it points into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resolved {
+    pub segment: usize,
+    pub word_index: usize,
+    pub length: usize,
+}
+
+/// Where a message's segments live, so that pointers can be resolved the
+/// same way whether the segments are being built or read.
+pub trait Segments {
+    /// How many segments there are.
+    fn segment_count(&self) -> usize;
+
+    /// How many Words the given segment has.
+    fn segment_words(&self, segment: usize) -> usize;
+
+    /// The given Word, which the caller has bounds-checked.
+    fn word(&self, segment: usize, word_index: usize) -> u64;
+
+    /// Check that the given Words of a segment are all there.
+    fn check_bounds(&self, segment: usize, word_index: usize, words: usize) -> Result<(), Problem> {
+        if segment >= self.segment_count() {
+            Err(Problem::MissingSegment(segment))
+        } else if word_index + words > self.segment_words(segment) {
+            Err(Problem::OutOfBounds {
+                segment,
+                word_index,
+                words,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Segments for [Vec<u64>] {
+    fn segment_count(&self) -> usize {
+        self.len()
+    }
+
+    fn segment_words(&self, segment: usize) -> usize {
+        self[segment].len()
+    }
+
+    fn word(&self, segment: usize, word_index: usize) -> u64 {
+        self[segment][word_index]
+    }
+}
+
+/// The Segment Pointers the given pointer stands for, in order, following
+/// Composite Pointers (including nested ones) through the message's segments.
+/// `segment` is the index of the segment the pointer itself is in, and
+/// `units` is what the value's Data Lengths count. Decoders concatenate what
+/// these point to in order to get the value.
+pub fn resolve<S: Segments + ?Sized>(
+    segments: &S,
+    segment: usize,
+    ptr: Pointer,
+    units: Units,
+) -> Result<Vec<Resolved>, Problem> {
+    let mut resolved = vec![];
+
+    // Encoders never point to the same Word twice, so there can't be more
+    // Words of pointers and data than there are Words in the message.
+    let mut budget: usize = (0..segments.segment_count())
+        .map(|segment| segments.segment_words(segment))
+        .sum();
+
+    resolve_into(segments, segment, ptr, units, 0, &mut budget, &mut resolved)?;
+
+    Ok(resolved)
+}
+
+fn resolve_into<S: Segments + ?Sized>(
+    segments: &S,
+    segment: usize,
+    ptr: Pointer,
+    units: Units,
+    depth: usize,
+    budget: &mut usize,
+    resolved: &mut Vec<Resolved>,
+) -> Result<(), Problem> {
+    let target = segment + ptr.segment_id_offset as usize;
+
+    if target >= segments.segment_count() {
+        return Err(Problem::MissingSegment(target));
+    }
+
+    if !ptr.is_composite {
+        if ptr.length > 0 {
+            let words = units.words(ptr.length as usize);
+
+            segments.check_bounds(target, ptr.word_index as usize, words)?;
+            *budget = budget.checked_sub(words).ok_or(Problem::TooMuchData)?;
+            resolved.push(Resolved {
+                segment: target,
+                word_index: ptr.word_index as usize,
+                length: ptr.length as usize,
+            });
+        }
+
+        return Ok(());
+    }
+
+    if depth >= MAX_COMPOSITE_DEPTH {
+        return Err(Problem::TooDeep);
+    }
+
+    let word_index = ptr.word_index as usize;
+    let length = ptr.length as usize;
+
+    *budget = budget.checked_sub(length).ok_or(Problem::Overlapping)?;
+    segments.check_bounds(target, word_index, length)?;
+
+    for index in word_index..word_index + length {
+        let inner = decode(segments.word(target, index));
+
+        resolve_into(segments, target, inner, units, depth + 1, budget, resolved)?;
+    }
+
+    Ok(())
+}
+
+/// The given Words of a segment, if they're all there.
+pub fn words(
+    segments: &[Vec<u64>],
+    segment: usize,
+    word_index: usize,
+    words: usize,
+) -> Result<&[u64], Problem> {
+    segments
+        .get(segment)
+        .ok_or(Problem::MissingSegment(segment))?
+        .get(word_index..word_index + words)
+        .ok_or(Problem::OutOfBounds {
+            segment,
+            word_index,
+            words,
+        })
+}
@@ -1,6 +1,39 @@
+use changelog;
 use cli;
+use code_gen;
+use codec;
+use compat;
+use git;
+use history;
+use json;
+use loader;
+use migration;
+use std::io;
+use std::path::PathBuf;
+use widening;
 
 #[derive(Debug)]
 pub enum Problem {
     Cli(cli::Problem),
+    Loader(loader::Problem),
+    CodeGen(code_gen::Problem),
+    History(history::Problem),
+    Git(git::Problem),
+    Changelog(changelog::Problem),
+    Json(PathBuf, json::Problem),
+    Codec(codec::Problem),
+    TypeChanges(Vec<widening::Problem>),
+    BreakingChanges(Vec<compat::Change>),
+    UndecidedMigrations(Vec<migration::Question>),
+
+    /// Asking a `wf migrate` question on the terminal failed.
+    Prompt(io::Error),
+
+    /// The history at the `--since` revision and the local one have each
+    /// recorded IDs the other doesn't have.
+    DivergedHistories {
+        revision: String,
+        path: PathBuf,
+        missing: Vec<String>,
+    },
 }
@@ -0,0 +1,430 @@
+extern crate byteorder;
+
+use byteorder::{ByteOrder, LittleEndian};
+use pointer;
+use pointer::{Resolved, Segments, Units};
+use record;
+use record::PresenceTable;
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str;
+use types::{Type, TypePrimitive};
+use widening;
+
+/// Reading a binary message (as laid out by `segments::MessageBuilder`)
+/// without copying it. The segment table is parsed once, up front, and after
+/// that every value is a view borrowing from the original bytes.
+///
+/// Every dereference is bounds-checked, so a hostile message gets a `Problem`
+/// rather than a panic. Values split up by Composite Pointers can't be
+/// borrowed as one slice, so strings and bytes are copied in that case only.
+pub struct Reader<'a> {
+    /// Each segment's Words, without the length at the beginning.
+    segments: Vec<&'a [u8]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The bytes ended partway through the given segment.
+    Truncated(usize),
+
+    /// The message has no segments, so there's no root record.
+    Empty,
+    Pointer(pointer::Problem),
+
+    /// A record has fewer slots than the one asked for.
+    MissingSlot(usize),
+
+    /// A record's presence table is cut short.
+    PresenceTable(record::Problem),
+
+    /// Records are read in place, so they can't be split up by a Composite
+    /// Pointer.
+    CompositeRecord,
+    InvalidUtf8,
+
+    /// The slot was sent as a type which isn't the field's current type or
+    /// one of its `formerly` types.
+    NotAccepted(usize),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Truncated(segment) => {
+                write!(
+                    formatter,
+                    "the message ends partway through segment {}",
+                    segment
+                )
+            }
+            Problem::Empty => write!(formatter, "the message is empty"),
+            Problem::Pointer(problem) => write!(formatter, "{}", problem),
+            Problem::MissingSlot(slot) => write!(formatter, "the record has no slot {}", slot),
+            Problem::PresenceTable(problem) => write!(formatter, "{}", problem),
+            Problem::CompositeRecord => {
+                write!(formatter, "a record is split up by a Composite Pointer")
+            }
+            Problem::InvalidUtf8 => write!(formatter, "a string is not valid UTF-8"),
+            Problem::NotAccepted(slot) => {
+                write!(
+                    formatter,
+                    "slot {} was sent as a type it can't widen from",
+                    slot
+                )
+            }
+        }
+    }
+}
+
+impl From<pointer::Problem> for Problem {
+    fn from(problem: pointer::Problem) -> Problem {
+        Problem::Pointer(problem)
+    }
+}
+
+impl From<record::Problem> for Problem {
+    fn from(problem: record::Problem) -> Problem {
+        Problem::PresenceTable(problem)
+    }
+}
+
+impl<'a> Segments for Reader<'a> {
+    fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    fn segment_words(&self, segment: usize) -> usize {
+        self.segments[segment].len() / 8
+    }
+
+    fn word(&self, segment: usize, word_index: usize) -> u64 {
+        LittleEndian::read_u64(&self.segments[segment][word_index * 8..])
+    }
+}
+
+impl<'a> Reader<'a> {
+    /// Parse the segment table.
+    pub fn new(bytes: &'a [u8]) -> Result<Reader<'a>, Problem> {
+        let mut segments = vec![];
+        let mut rest = bytes;
+
+        while !rest.is_empty() {
+            let segment = segments.len();
+
+            if rest.len() < 8 {
+                return Err(Problem::Truncated(segment));
+            }
+
+            let words = LittleEndian::read_u64(rest);
+            let length = words
+                .checked_mul(8)
+                .filter(|length| *length <= (rest.len() - 8) as u64)
+                .ok_or(Problem::Truncated(segment))? as usize;
+
+            segments.push(&rest[8..8 + length]);
+            rest = &rest[8 + length..];
+        }
+
+        if segments.is_empty() {
+            Err(Problem::Empty)
+        } else {
+            Ok(Reader { segments })
+        }
+    }
+
+    /// The root record, which is the whole first segment.
+    pub fn root(&self) -> Result<Record<'_, 'a>, Problem> {
+        Record::new(self, 0, self.segments[0])
+    }
+
+    /// The Words a Segment Pointer points to, borrowed from the message.
+    fn words(&self, ptr: Resolved, words: usize) -> Result<&'a [u8], Problem> {
+        self.check_bounds(ptr.segment, ptr.word_index, words)?;
+
+        let start = ptr.word_index * 8;
+
+        Ok(&self.segments[ptr.segment][start..start + words * 8])
+    }
+
+    /// The record the given pointer (stored in `segment`) points to.
+    pub fn record(&self, segment: usize, raw_ptr: u64) -> Result<Record<'_, 'a>, Problem> {
+        let ptr = pointer::decode(raw_ptr);
+
+        if ptr.is_composite {
+            return Err(Problem::CompositeRecord);
+        }
+
+        match pointer::resolve(self, segment, ptr, Units::Words)?.pop() {
+            Some(resolved) => Record::new(
+                self,
+                resolved.segment,
+                self.words(resolved, resolved.length)?,
+            ),
+            None => Ok(Record {
+                reader: self,
+                segment,
+                presence_table: PresenceTable::default(),
+                words: Words(&[]),
+            }),
+        }
+    }
+
+    /// The Array UInt8 the given pointer (stored in `segment`) points to.
+    pub fn bytes(&self, segment: usize, raw_ptr: u64) -> Result<Cow<'a, [u8]>, Problem> {
+        if raw_ptr == 0 {
+            return Ok(Cow::Borrowed(&[]));
+        }
+
+        let mut chunks = pointer::resolve(self, segment, pointer::decode(raw_ptr), Units::Bytes)?
+            .into_iter()
+            .map(|ptr| {
+                self.words(ptr, Units::Bytes.words(ptr.length))
+                    .map(|words| &words[..ptr.length])
+            })
+            .collect::<Result<Vec<&'a [u8]>, Problem>>()?;
+
+        if chunks.len() == 1 {
+            Ok(Cow::Borrowed(chunks.remove(0)))
+        } else {
+            Ok(Cow::Owned(chunks.concat()))
+        }
+    }
+
+    /// The UTF-8 string the given pointer (stored in `segment`) points to.
+    pub fn string(&self, segment: usize, raw_ptr: u64) -> Result<Cow<'a, str>, Problem> {
+        match self.bytes(segment, raw_ptr)? {
+            Cow::Borrowed(bytes) => str::from_utf8(bytes)
+                .map(Cow::Borrowed)
+                .map_err(|_| Problem::InvalidUtf8),
+            Cow::Owned(bytes) => String::from_utf8(bytes)
+                .map(Cow::Owned)
+                .map_err(|_| Problem::InvalidUtf8),
+        }
+    }
+
+    /// Like `string`, but without checking that the string is valid UTF-8.
+    /// Pointers are still bounds-checked.
+    ///
+    /// ## Safety
+    ///
+    /// The message must be trusted to only have valid UTF-8 strings, as in
+    /// `string::decode_string_unchecked`.
+    pub unsafe fn string_unchecked(
+        &self,
+        segment: usize,
+        raw_ptr: u64,
+    ) -> Result<Cow<'a, str>, Problem> {
+        Ok(match self.bytes(segment, raw_ptr)? {
+            Cow::Borrowed(bytes) => Cow::Borrowed(unsafe { str::from_utf8_unchecked(bytes) }),
+            Cow::Owned(bytes) => Cow::Owned(unsafe { String::from_utf8_unchecked(bytes) }),
+        })
+    }
+
+    /// The array of 64-bit values the given pointer (stored in `segment`)
+    /// points to.
+    pub fn array<T: FromWord>(
+        &self,
+        segment: usize,
+        raw_ptr: u64,
+    ) -> Result<Array<'a, T>, Problem> {
+        let mut chunks = vec![];
+
+        if raw_ptr != 0 {
+            for ptr in pointer::resolve(self, segment, pointer::decode(raw_ptr), Units::Words)? {
+                chunks.push(Words(self.words(ptr, ptr.length)?));
+            }
+        }
+
+        Ok(Array {
+            len: chunks.iter().map(Words::len).sum(),
+            chunks,
+            elem: PhantomData,
+        })
+    }
+}
+
+/// Contiguous Words, borrowed from the message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Words<'a>(&'a [u8]);
+
+impl<'a> Words<'a> {
+    pub fn len(&self) -> usize {
+        self.0.len() / 8
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<u64> {
+        self.0
+            .get(index * 8..index * 8 + 8)
+            .map(LittleEndian::read_u64)
+    }
+}
+
+/// The types which fill a whole Word in primitive arrays.
+pub trait FromWord {
+    fn from_word(word: u64) -> Self;
+}
+
+impl FromWord for u64 {
+    fn from_word(word: u64) -> u64 {
+        word
+    }
+}
+
+impl FromWord for i64 {
+    fn from_word(word: u64) -> i64 {
+        word as i64
+    }
+}
+
+impl FromWord for f64 {
+    fn from_word(word: u64) -> f64 {
+        f64::from_bits(word)
+    }
+}
+
+/// A primitive array, borrowed from the message one chunk at a time.
+pub struct Array<'a, T> {
+    chunks: Vec<Words<'a>>,
+    len: usize,
+    elem: PhantomData<T>,
+}
+
+impl<'a, T: FromWord> Array<'a, T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, mut index: usize) -> Option<T> {
+        for chunk in &self.chunks {
+            if index < chunk.len() {
+                return chunk.get(index).map(T::from_word);
+            }
+
+            index -= chunk.len();
+        }
+
+        None
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| (0..chunk.len()).filter_map(move |index| chunk.get(index)))
+            .map(T::from_word)
+    }
+}
+
+/// A record, read in place: its presence table, followed by its slots (see
+/// `record::encode_record`). Fields are accessed by the index of their slot,
+/// counting from the first Word after the presence table.
+pub struct Record<'r, 'a> {
+    reader: &'r Reader<'a>,
+
+    /// The segment the record is in, which its pointers are relative to.
+    segment: usize,
+    presence_table: PresenceTable<'a>,
+
+    /// The slots, after the presence table.
+    words: Words<'a>,
+}
+
+impl<'r, 'a> Record<'r, 'a> {
+    fn new(reader: &'r Reader<'a>, segment: usize, bytes: &'a [u8]) -> Result<Self, Problem> {
+        let presence_table = record::decode_presence_table(bytes)?;
+
+        Ok(Record {
+            reader,
+            segment,
+            presence_table,
+            words: Words(&bytes[presence_table.byte_len()..]),
+        })
+    }
+
+    /// Which fields the sender included. A null record pointer has none.
+    pub fn presence_table(&self) -> PresenceTable<'a> {
+        self.presence_table
+    }
+
+    /// How many slots the record has.
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn word(&self, slot: usize) -> Result<u64, Problem> {
+        self.words.get(slot).ok_or(Problem::MissingSlot(slot))
+    }
+
+    pub fn u64(&self, slot: usize) -> Result<u64, Problem> {
+        self.word(slot)
+    }
+
+    pub fn i64(&self, slot: usize) -> Result<i64, Problem> {
+        self.word(slot).map(i64::from_word)
+    }
+
+    pub fn f64(&self, slot: usize) -> Result<f64, Problem> {
+        self.word(slot).map(f64::from_word)
+    }
+
+    pub fn bool(&self, slot: usize) -> Result<bool, Problem> {
+        self.word(slot).map(|word| word != 0)
+    }
+
+    /// The Word in the slot, in the representation of the field's current
+    /// type. `sent_as` is the type the sender encoded it as (e.g. going by the
+    /// sender's schema version), which must be the current type or one of its
+    /// `formerly` types. See `widening::widen_word`.
+    pub fn widened(
+        &self,
+        slot: usize,
+        sent_as: &TypePrimitive,
+        tipe: &Type,
+    ) -> Result<u64, Problem> {
+        let word = self.word(slot)?;
+
+        if !widening::accepted_types(tipe).contains(&sent_as) {
+            return Err(Problem::NotAccepted(slot));
+        }
+
+        widening::widen_word(sent_as, &tipe.current, word).ok_or(Problem::NotAccepted(slot))
+    }
+
+    pub fn record(&self, slot: usize) -> Result<Record<'r, 'a>, Problem> {
+        self.reader.record(self.segment, self.word(slot)?)
+    }
+
+    pub fn bytes(&self, slot: usize) -> Result<Cow<'a, [u8]>, Problem> {
+        self.reader.bytes(self.segment, self.word(slot)?)
+    }
+
+    pub fn string(&self, slot: usize) -> Result<Cow<'a, str>, Problem> {
+        self.reader.string(self.segment, self.word(slot)?)
+    }
+
+    /// See `Reader::string_unchecked`.
+    ///
+    /// ## Safety
+    ///
+    /// The message must be trusted to only have valid UTF-8 strings.
+    pub unsafe fn string_unchecked(&self, slot: usize) -> Result<Cow<'a, str>, Problem> {
+        unsafe { self.reader.string_unchecked(self.segment, self.word(slot)?) }
+    }
+
+    pub fn array<T: FromWord>(&self, slot: usize) -> Result<Array<'a, T>, Problem> {
+        self.reader.array(self.segment, self.word(slot)?)
+    }
+}
@@ -1,8 +1,37 @@
 extern crate byteorder;
 
+use byteorder::{ByteOrder, LittleEndian};
+use std::fmt;
+
 // TODO should make this pub struct
 pub type FieldId = u32;
 
+/// How many bytes each page of a presence table takes up.
+pub const PAGE_BYTES: usize = 8;
+
+/// How many fields each page of a presence table covers. The 64th bit is
+/// taken by the flag saying whether there's another page.
+pub const FIELDS_PER_PAGE: usize = 63;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The bytes ended before the presence table did. `pages` is how many
+    /// pages the table claimed to have by then.
+    Truncated { pages: usize },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Truncated { pages } => write!(
+                formatter,
+                "the record ends partway through page {} of its presence table",
+                pages
+            ),
+        }
+    }
+}
+
 /// Read the field presence table from a record's data bytes.
 ///
 /// * The presence table is always at the beginning of a record's data bytes.
@@ -10,25 +39,145 @@ pub type FieldId = u32;
 /// * The 64th bit in a page is 1 iff there's another page after it.
 ///
 /// This function checks the 64th bit of consecutive pages until it hits a 0,
-/// at which point it returns the subslice of the original byte range that
-/// holds the properly sized presence table.
+/// at which point it returns the properly sized presence table. The record's
+/// fields start right after it, at `PresenceTable::byte_len`.
 ///
-/// ## Panics
+/// Bits go from the most significant bit of each byte to the least, so field
+/// 0 is the top bit of the first byte, and the 64th bit of a page is the
+/// bottom bit of its last byte. Page `n` covers the `FIELDS_PER_PAGE` fields
+/// starting at `n * FIELDS_PER_PAGE`.
+pub fn decode_presence_table(bytes: &[u8]) -> Result<PresenceTable<'_>, Problem> {
+    let mut pages = 1;
+
+    loop {
+        let end = pages * PAGE_BYTES;
+
+        if bytes.len() < end {
+            return Err(Problem::Truncated { pages });
+        }
+
+        // If the last bit in the (64-bit) presence table page is 1, then there
+        // is another page to this presence table! We need to keep reading.
+        if bytes[end - 1] & 0b1 == 0 {
+            return Ok(PresenceTable {
+                bytes: &bytes[0..end],
+            });
+        }
+
+        pages += 1;
+    }
+}
+
+/// A record's field presence table. See `decode_presence_table`. The default
+/// table has no pages, so no field is present in it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PresenceTable<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PresenceTable<'a> {
+    pub fn page_count(&self) -> usize {
+        self.bytes.len() / PAGE_BYTES
+    }
+
+    /// How many bytes the table takes up at the start of the record.
+    pub fn byte_len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether the field is present. Fields past the end of the table (e.g.
+    /// ones which are newer than the sender) aren't.
+    pub fn is_present(&self, field_id: FieldId) -> bool {
+        let field_id = field_id as usize;
+
+        self.pages()
+            .nth(field_id / FIELDS_PER_PAGE)
+            .is_some_and(|page| page.is_present(field_id % FIELDS_PER_PAGE))
+    }
+
+    pub fn pages(&self) -> impl Iterator<Item = Page<'a>> {
+        self.bytes
+            .chunks(PAGE_BYTES)
+            .enumerate()
+            .map(|(index, bytes)| Page { index, bytes })
+    }
+
+    /// The fields which are present, in order.
+    pub fn present_fields(&self) -> impl Iterator<Item = FieldId> + 'a {
+        self.pages().flat_map(|page| page.present_fields())
+    }
+}
+
+/// One page of a presence table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Page<'a> {
+    /// Which page of the table this is.
+    pub index: usize,
+    bytes: &'a [u8],
+}
+
+impl<'a> Page<'a> {
+    /// The first field this page covers.
+    pub fn first_field_id(&self) -> FieldId {
+        (self.index * FIELDS_PER_PAGE) as FieldId
+    }
+
+    /// Whether the field at the given index within the page is present.
+    pub fn is_present(&self, index: usize) -> bool {
+        index < FIELDS_PER_PAGE && self.bytes[index / 8] & (0b1000_0000 >> (index % 8)) != 0
+    }
+
+    /// Whether there's another page after this one.
+    pub fn has_next(&self) -> bool {
+        self.bytes[PAGE_BYTES - 1] & 0b1 != 0
+    }
+
+    pub fn present_fields(self) -> impl Iterator<Item = FieldId> + 'a {
+        let first = self.first_field_id();
+
+        (0..FIELDS_PER_PAGE)
+            .filter(move |index| self.is_present(*index))
+            .map(move |index| first + index as FieldId)
+    }
+}
+
+/// Encode a record as Words: its presence table, then the Word for each of
+/// the given fields (a scalar, or a pointer), in the order they're given -
+/// which should be slot order, not necessarily `FieldId` order.
+///
+/// The presence table has as many pages as it takes to cover the highest
+/// `FieldId`, with the 64th bit set on every page except the last. A record
+/// with no fields still has one (empty) page.
 ///
-/// This function does no bounds checking. So if the given byte range is fewer
-/// than 8 bytes in length, this will try to read outside its range and panic.
+/// ## Panics
 ///
-/// Similarly, if its 64th bit is 1, and there are not at least 8 bytes
-/// following that 64th bit, it will try to read outside its range and panic.
-#[inline]
-pub fn decode_presence_table(bytes: &[u8]) -> &[u8] {
-    let mut presence_table_length = 1;
+/// If the same `FieldId` is given more than once.
+pub fn encode_record(fields: &[(FieldId, u64)]) -> Vec<u64> {
+    let highest = fields.iter().map(|(field_id, _)| *field_id as usize).max();
+    let pages = highest.map_or(1, |highest| highest / FIELDS_PER_PAGE + 1);
+    let mut table = vec![0u8; pages * PAGE_BYTES];
 
-    // If the last bit in the (64-bit) presence table page is 1, then there is
-    // another page to this presence table! We need to keep reading.
-    while bytes[(presence_table_length * 8) - 1] & 0b1 != 0 {
-        presence_table_length += 1;
+    for page in 0..pages - 1 {
+        table[(page + 1) * PAGE_BYTES - 1] |= 0b1;
     }
 
-    &bytes[0..presence_table_length]
+    for (field_id, _) in fields {
+        let field_id = *field_id as usize;
+        let index = field_id % FIELDS_PER_PAGE;
+        let byte = &mut table[(field_id / FIELDS_PER_PAGE) * PAGE_BYTES + index / 8];
+        let bit = 0b1000_0000 >> (index % 8);
+
+        if *byte & bit != 0 {
+            panic!("Field {} was given more than once!", field_id);
+        }
+
+        *byte |= bit;
+    }
+
+    let mut words = vec![0u64; pages];
+
+    LittleEndian::read_u64_into(&table, &mut words);
+    words.extend(fields.iter().map(|(_, word)| *word));
+
+    words
 }
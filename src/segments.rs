@@ -1,3 +1,14 @@
+extern crate byteorder;
+
+use array;
+use byteorder::{LittleEndian, WriteBytesExt};
+use collection;
+use collection::Slot;
+use pointer;
+use pointer::Pointer;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
 /// The beginning of each segment is an u64 representing
 /// the length (in Words) of the segment.
 ///
@@ -9,3 +20,212 @@
 ///
 /// In other segments, the segment typically holds data
 /// for multiple values, not just a single record.
+///
+/// A message is its segments back to back, all the way to the end of the
+/// bytes. Word indexes in pointers don't count the length at the beginning
+/// of the segment.
+///
+/// Values are always added to the last segment, and a new segment is started
+/// once that one is full. A value which doesn't fit in an empty segment gets
+/// a segment of its own. Once there are more segments than a
+/// `segment_id_offset` can reach, the remainder of the message goes in the
+/// final segment.
+pub struct MessageBuilder {
+    segments: Vec<Vec<u64>>,
+
+    /// How many Words a segment can hold before we start a new one.
+    segment_words: usize,
+}
+
+/// The index of the segment holding the root record.
+pub const ROOT: usize = 0;
+
+/// 64KB per segment
+pub const DEFAULT_SEGMENT_WORDS: usize = 8 * 1024;
+
+/// Where some Words were allocated in a message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub segment: usize,
+    pub word_index: usize,
+}
+
+impl Default for MessageBuilder {
+    fn default() -> MessageBuilder {
+        MessageBuilder::new()
+    }
+}
+
+impl MessageBuilder {
+    pub fn new() -> MessageBuilder {
+        MessageBuilder::with_segment_words(DEFAULT_SEGMENT_WORDS)
+    }
+
+    pub fn with_segment_words(segment_words: usize) -> MessageBuilder {
+        MessageBuilder {
+            segments: vec![vec![]],
+            segment_words,
+        }
+    }
+
+    /// Set the root record's Words. Pointers in them are relative to `ROOT`.
+    pub fn set_root(&mut self, words: &[u64]) {
+        if words.len() > u32::MAX as usize {
+            panic!("A record can't be longer than u32::MAX Words!");
+        }
+
+        self.segments[ROOT] = words.to_vec();
+    }
+
+    /// Allocate zeroed Words for a value whose pointer will be stored in the
+    /// segment `from`. Fill them in with `words_mut`.
+    pub fn allocate(&mut self, from: usize, words: usize) -> Location {
+        let segment = self.segment_for(from, words);
+        let word_index = self.segments[segment].len();
+
+        self.segments[segment].resize(word_index + words, 0);
+
+        Location {
+            segment,
+            word_index,
+        }
+    }
+
+    pub fn words_mut(&mut self, location: Location, words: usize) -> &mut [u64] {
+        &mut self.segments[location.segment][location.word_index..location.word_index + words]
+    }
+
+    /// A pointer to Words allocated with `allocate`, to be stored in the
+    /// segment `from`. `length` is in whatever units suit the value's type,
+    /// and can be at most `pointer::MAX_LENGTH`, or this panics.
+    pub fn pointer(&self, from: usize, location: Location, length: usize) -> u64 {
+        assert!(
+            length <= pointer::MAX_LENGTH,
+            "A pointer's length can be at most {}, but it was {}",
+            pointer::MAX_LENGTH,
+            length
+        );
+
+        pointer::encode(Pointer {
+            word_index: location.word_index as u32,
+            segment_id_offset: segment_id_offset(from, location.segment),
+            length: length as u16,
+            is_composite: false,
+        })
+    }
+
+    /// Write an Array UInt64, returning a pointer to be stored in the segment
+    /// `from`. See `array::encode_u64_array`.
+    pub fn write_u64_array(&mut self, from: usize, arr: &[u64]) -> u64 {
+        self.write_with(from, arr.len(), |segment, offset| {
+            array::encode_u64_array(segment, offset, arr)
+        })
+    }
+
+    /// Write an Array UInt8, returning a pointer to be stored in the segment
+    /// `from`. See `array::encode_u8_array`.
+    pub fn write_u8_array(&mut self, from: usize, arr: &[u8]) -> u64 {
+        self.write_with(from, arr.len().div_ceil(8), |segment, offset| {
+            array::encode_u8_array(segment, offset, arr)
+        })
+    }
+
+    /// Write a String, returning a pointer to be stored in the segment `from`.
+    /// See `string::encode_string`.
+    pub fn write_string(&mut self, from: usize, string: &str) -> u64 {
+        self.write_u8_array(from, string.as_bytes())
+    }
+
+    /// Write a Set, returning a pointer to be stored in the segment `from`.
+    /// See `collection::encode_set`.
+    pub fn write_set<T: Slot + Ord>(&mut self, from: usize, set: &BTreeSet<T>) -> u64 {
+        self.write_with(from, set.len(), |segment, offset| {
+            collection::encode_set(segment, offset, set)
+        })
+    }
+
+    /// Write a Dict, returning a pointer to be stored in the segment `from`.
+    /// See `collection::encode_dict`.
+    pub fn write_dict<K: Slot + Ord, V: Slot>(
+        &mut self,
+        from: usize,
+        dict: &BTreeMap<K, V>,
+    ) -> u64 {
+        self.write_with(from, dict.len() * 2, |segment, offset| {
+            collection::encode_dict(segment, offset, dict)
+        })
+    }
+
+    /// Write a value with the given encoder, which appends it to a segment.
+    /// `words` is roughly how many Words it takes, for picking the segment;
+    /// Composite Pointers make it a little more.
+    fn write_with<F>(&mut self, from: usize, words: usize, encode: F) -> u64
+    where
+        F: FnOnce(&mut Vec<u64>, u16) -> u64,
+    {
+        if words == 0 {
+            // Empty values are the zero pointer; no need to allocate anything.
+            return encode(&mut vec![], 0);
+        }
+
+        let segment = self.segment_for(from, words);
+
+        encode(
+            &mut self.segments[segment],
+            segment_id_offset(from, segment),
+        )
+    }
+
+    /// The segment to put a value in, starting a new one if need be.
+    fn segment_for(&mut self, from: usize, words: usize) -> usize {
+        if from >= self.segments.len() {
+            panic!("Pointers can only be stored in segments which exist!");
+        }
+
+        let last = self.segments.len() - 1;
+        let out_of_reach = self.segments.len() > u16::MAX as usize;
+        let full = self.segments[last].len() + words > self.segment_words;
+
+        if last != ROOT && (out_of_reach || !full) {
+            last
+        } else {
+            self.segments.push(vec![]);
+
+            last + 1
+        }
+    }
+
+    /// The segments so far, e.g. for `array::decode_u64_array`.
+    pub fn segments(&self) -> &[Vec<u64>] {
+        &self.segments
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+
+        self.write_to(&mut bytes)
+            .expect("Writing to a Vec can't fail");
+
+        bytes
+    }
+
+    pub fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        for segment in &self.segments {
+            writer.write_u64::<LittleEndian>(segment.len() as u64)?;
+
+            for word in segment {
+                writer.write_u64::<LittleEndian>(*word)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn segment_id_offset(from: usize, to: usize) -> u16 {
+    if to < from || to - from > u16::MAX as usize {
+        panic!("Pointers can only point forward, at most u16::MAX segments!");
+    }
+
+    (to - from) as u16
+}
@@ -0,0 +1,74 @@
+//! Strings go over the wire as UTF-8 bytes, laid out like an Array UInt8
+//! (see `array::encode_u8_array`). So the Data Length is in bytes, the zero
+//! pointer is the empty string, and long strings get a Composite Pointer.
+//! Chunks may split a character in two; it's only whole once they've been
+//! concatenated, which is when decoding checks it's valid UTF-8.
+use array;
+use pointer;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    Pointer(pointer::Problem),
+
+    /// The bytes aren't valid UTF-8, starting at the given byte.
+    InvalidUtf8 {
+        valid_up_to: usize,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Pointer(problem) => write!(formatter, "{}", problem),
+            Problem::InvalidUtf8 { valid_up_to } => write!(
+                formatter,
+                "a string is not valid UTF-8, from byte {} on",
+                valid_up_to
+            ),
+        }
+    }
+}
+
+impl From<pointer::Problem> for Problem {
+    fn from(problem: pointer::Problem) -> Problem {
+        Problem::Pointer(problem)
+    }
+}
+
+/// Write a String to the end of the segment, and return a pointer to it.
+/// `segment_id_offset` is as in `array::encode_u64_array`.
+pub fn encode_string(segment: &mut Vec<u64>, segment_id_offset: u16, string: &str) -> u64 {
+    array::encode_u8_array(segment, segment_id_offset, string.as_bytes())
+}
+
+/// Read a String from the message's segments. `segment` is the index of the
+/// segment the pointer was read from.
+pub fn decode_string(
+    segments: &[Vec<u64>],
+    segment: usize,
+    raw_ptr: u64,
+) -> Result<String, Problem> {
+    String::from_utf8(array::decode_u8_array(segments, segment, raw_ptr)?).map_err(|error| {
+        Problem::InvalidUtf8 {
+            valid_up_to: error.utf8_error().valid_up_to(),
+        }
+    })
+}
+
+/// Read a String without checking that it's valid UTF-8, for input which is
+/// trusted, e.g. because it was just validated by the same process. Pointers
+/// are still bounds-checked.
+///
+/// ## Safety
+///
+/// The bytes must be valid UTF-8. Otherwise, the returned `String` breaks the
+/// invariant that makes `String` safe to use.
+pub unsafe fn decode_string_unchecked(
+    segments: &[Vec<u64>],
+    segment: usize,
+    raw_ptr: u64,
+) -> Result<String, pointer::Problem> {
+    array::decode_u8_array(segments, segment, raw_ptr)
+        .map(|bytes| unsafe { String::from_utf8_unchecked(bytes) })
+}
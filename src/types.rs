@@ -1,12 +1,29 @@
-#[derive(Debug)]
-#[allow(dead_code)]
+use std::fmt;
+
+/// A field's type, along with every type it has had in the past.
+///
+/// `formerly` lets us change a field's type (e.g. from `Int32` to `Int64`)
+/// without breaking anyone who is still sending the old representation.
+/// Decoders accept the `current` type as well as anything in `formerly`,
+/// as long as each former type can be safely widened into `current`.
+/// See the `widening` module for the rules, and `reader::Record::widened`
+/// for decoding a slot sent as a former type.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Type {
-    current: TypePrimitive,
-    formerly: Vec<TypePrimitive>,
+    pub current: TypePrimitive,
+    pub formerly: Vec<TypePrimitive>,
+}
+
+impl Type {
+    pub fn new(current: TypePrimitive) -> Type {
+        Type {
+            current,
+            formerly: vec![],
+        }
+    }
 }
 
-#[derive(Debug)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TypePrimitive {
     // scalars
     Bool,
@@ -30,67 +47,550 @@ pub enum TypePrimitive {
 
     // records
     Record(RecordInfo),
+
+    // references to other declarations
+    Named(TypeName, Vec<Type>),
+    Param(TypeParam),
+}
+
+impl fmt::Display for TypePrimitive {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypePrimitive::Bool => write!(formatter, "Bool"),
+            TypePrimitive::String => write!(formatter, "String"),
+            TypePrimitive::Int => write!(formatter, "Int"),
+            TypePrimitive::Int8 => write!(formatter, "Int8"),
+            TypePrimitive::Int16 => write!(formatter, "Int16"),
+            TypePrimitive::Int32 => write!(formatter, "Int32"),
+            TypePrimitive::Int64 => write!(formatter, "Int64"),
+            TypePrimitive::Float => write!(formatter, "Float"),
+            TypePrimitive::Float32 => write!(formatter, "Float32"),
+            TypePrimitive::Float64 => write!(formatter, "Float64"),
+            TypePrimitive::Array(elem) => write!(formatter, "Array {}", Arg(&elem.current)),
+            TypePrimitive::Dict(key, val) => write!(
+                formatter,
+                "Dict {} {}",
+                Arg(&key.current),
+                Arg(&val.current)
+            ),
+            TypePrimitive::Set(elem) => write!(formatter, "Set {}", Arg(&elem.current)),
+            TypePrimitive::Custom(info) => write!(formatter, "{}", info.name),
+            TypePrimitive::Record(info) => match info.name {
+                Some(ref name) => write!(formatter, "{}", name),
+                None => write!(formatter, "{{ ... }}"),
+            },
+            TypePrimitive::Named(name, args) => {
+                write!(formatter, "{}", name)?;
+
+                for arg in args {
+                    write!(formatter, " {}", Arg(&arg.current))?;
+                }
+
+                Ok(())
+            }
+            TypePrimitive::Param(TypeParam::TypeParam(param)) => write!(formatter, "{}", param),
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
-#[allow(dead_code)]
+/// Displays a type in argument position, wrapping it in parens if it has
+/// arguments of its own - e.g. the `(Array Int)` in `Array (Array Int)`.
+struct Arg<'a>(&'a TypePrimitive);
+
+impl<'a> fmt::Display for Arg<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            TypePrimitive::Array(_) | TypePrimitive::Dict(_, _) | TypePrimitive::Set(_) => {
+                write!(formatter, "({})", self.0)
+            }
+            TypePrimitive::Named(_, args) if !args.is_empty() => write!(formatter, "({})", self.0),
+            other => write!(formatter, "{}", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Optionality {
     Required,
     Optional,
 }
 
-#[derive(Debug, PartialEq)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TypeParam {
     TypeParam(String),
 }
 
-#[derive(Debug, PartialEq)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TypeName {
     TypeName(String),
 }
 
-#[derive(Debug)]
+impl fmt::Display for TypeName {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeName::TypeName(name) => write!(formatter, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct RecordInfo {
-    name: Option<TypeName>,
-    former_names: Vec<TypeName>,
-    fields: Vec<Field>,
-    params: Vec<TypeParam>,
+    pub name: Option<TypeName>,
+    pub former_names: Vec<TypeName>,
+    pub fields: Vec<Field>,
+    pub params: Vec<TypeParam>,
+
+    /// Records marked `[ packed ]` go over the wire in production as tuples
+    /// instead of objects when they're in an array. See the `codec` module.
+    pub packed: bool,
+
+    /// Where an inline record (or endpoint body) is in the schema, e.g.
+    /// "User.profile" for `* profile : { name : String }` in `struct User`,
+    /// or "sendDM.params". Inline records have no name of their own, so the
+    /// history keeps their field IDs under this one instead. Every inline
+    /// record in the same field shares it. See `Schema::inline_records`.
+    pub location: Option<String>,
 }
 
-#[derive(Debug)]
+impl RecordInfo {
+    /// The name the history keeps this record's field IDs under: its own
+    /// name, or its location if it's an inline record.
+    pub fn ledger_name(&self) -> Option<String> {
+        match self.name {
+            Some(ref name) => Some(name.to_string()),
+            None => self.location.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct CustomTypeInfo {
-    name: TypeName,
-    former_names: Vec<TypeName>,
-    variants: Vec<Variant>,
-    params: Vec<TypeParam>,
+    pub name: TypeName,
+    pub former_names: Vec<TypeName>,
+    pub variants: Vec<Variant>,
+    pub params: Vec<TypeParam>,
 }
 
 /// Use a 64-bit integer here for mmap alignment.
 /// In JSON it'll be represented as a string anyway!
 pub type FieldId = u64;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Field {
-    optionality: Optionality,
-    name: String,
-    tipe: Type,
-    field_id: FieldId,
-    fallback: bool,
-    former_names: Vec<String>,
+    pub optionality: Optionality,
+    pub name: String,
+    pub tipe: Type,
+    pub field_id: FieldId,
+    pub fallback: bool,
+    pub former_names: Vec<String>,
+
+    /// Deprecated fields are marked with `!` or `[ deprecated ]`
+    pub deprecated: bool,
+
+    /// The source of the field's `[ default ... ]` expression, if it has one,
+    /// e.g. `""` or `[]`
+    pub default: Option<String>,
+
+    /// For endpoint params sent as HTTP headers, e.g. `[ header "X-CSRF-Token" ]`
+    pub header: Option<String>,
 }
 
 /// Use a 64-bit integer here for mmap alignment.
 /// In JSON it'll be represented as a string anyway!
 pub type VariantId = u64;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Variant {
-    optionality: Optionality,
-    name: String,
-    tipe: Type,
-    variant_id: VariantId,
-    former_names: Vec<String>,
-    contents: Vec<Type>,
+    pub optionality: Optionality,
+    pub name: String,
+    pub tipe: Type,
+    pub variant_id: VariantId,
+    pub former_names: Vec<String>,
+    pub contents: Vec<Type>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verb {
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+impl fmt::Display for Verb {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verb::Get => write!(formatter, "get"),
+            Verb::Post => write!(formatter, "post"),
+            Verb::Put => write!(formatter, "put"),
+            Verb::Patch => write!(formatter, "patch"),
+            Verb::Delete => write!(formatter, "delete"),
+        }
+    }
+}
+
+/// An HTTP endpoint, e.g.
+///
+/// ```text
+/// post sendDM
+///     params:
+///         * userId : Int
+///
+///     response:
+///         * user : User Profile
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoint {
+    pub verb: Verb,
+    pub name: String,
+    pub params: Vec<Field>,
+    pub response: Vec<Field>,
+
+    /// Endpoints marked `[ packed ]` pack every record in an array in their
+    /// params and response, as if each record were marked `[ packed ]`.
+    pub packed: bool,
+}
+
+impl Endpoint {
+    /// The endpoint's params, as an inline record.
+    pub fn params_record(&self) -> RecordInfo {
+        self.body_record("params", &self.params)
+    }
+
+    /// The endpoint's response, as an inline record.
+    pub fn response_record(&self) -> RecordInfo {
+        self.body_record("response", &self.response)
+    }
+
+    fn body_record(&self, body: &str, fields: &[Field]) -> RecordInfo {
+        RecordInfo {
+            name: None,
+            former_names: vec![],
+            fields: fields.to_vec(),
+            params: vec![],
+            packed: self.packed,
+            location: Some(format!("{}.{}", self.name, body)),
+        }
+    }
+}
+
+/// e.g. `alias Email = String`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alias {
+    pub name: TypeName,
+    pub params: Vec<TypeParam>,
+    pub tipe: Type,
+}
+
+/// Everything declared in a set of `.wf` files.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Schema {
+    pub records: Vec<RecordInfo>,
+    pub custom_types: Vec<CustomTypeInfo>,
+    pub aliases: Vec<Alias>,
+    pub endpoints: Vec<Endpoint>,
+}
+
+impl Schema {
+    pub fn record(&self, name: &str) -> Option<&RecordInfo> {
+        self.records.iter().find(|record| {
+            record
+                .name
+                .as_ref()
+                .is_some_and(|record_name| record_name.to_string() == name)
+        })
+    }
+
+    pub fn custom_type(&self, name: &str) -> Option<&CustomTypeInfo> {
+        self.custom_types
+            .iter()
+            .find(|custom_type| custom_type.name.to_string() == name)
+    }
+
+    pub fn alias(&self, name: &str) -> Option<&Alias> {
+        self.aliases
+            .iter()
+            .find(|alias| alias.name.to_string() == name)
+    }
+
+    pub fn endpoint(&self, name: &str) -> Option<&Endpoint> {
+        self.endpoints.iter().find(|endpoint| endpoint.name == name)
+    }
+
+    /// The fields of every endpoint body and inline record in the schema,
+    /// along with the location the history keeps their IDs under (see
+    /// `RecordInfo::location`), in the order they appear.
+    pub fn inline_records(&self) -> Vec<(String, &[Field])> {
+        let mut found: Vec<(String, &[Field])> = vec![];
+
+        self.visit_inline_records(&mut |location, fields| {
+            found.push((location.to_string(), fields))
+        });
+
+        found
+    }
+
+    /// The names of the fields at each location in `inline_records`. Inline
+    /// records in the same field share a location, and so a ledger, so their
+    /// names are all listed together.
+    pub fn inline_field_names(&self) -> Vec<(String, Vec<String>)> {
+        let mut locations: Vec<(String, Vec<String>)> = vec![];
+
+        for (location, fields) in self.inline_records() {
+            let index = match locations.iter().position(|(other, _)| *other == location) {
+                Some(index) => index,
+                None => {
+                    locations.push((location, vec![]));
+                    locations.len() - 1
+                }
+            };
+            let names = &mut locations[index].1;
+
+            for field in fields.iter() {
+                if !names.contains(&field.name) {
+                    names.push(field.name.clone());
+                }
+            }
+        }
+
+        locations
+    }
+
+    /// Call `visit` with the location and fields of every endpoint body and
+    /// inline record in the schema, like `inline_records`.
+    pub fn visit_inline_records<'a, F>(&'a self, visit: &mut F)
+    where
+        F: FnMut(&str, &'a [Field]),
+    {
+        for record in self.records.iter() {
+            if let Some(ref name) = record.name {
+                find_in_fields(name.to_string().as_str(), &record.fields, visit);
+            }
+        }
+
+        for custom_type in self.custom_types.iter() {
+            for variant in custom_type.variants.iter() {
+                let location = format!("{}.{}", custom_type.name, variant.name);
+
+                for tipe in variant.contents.iter() {
+                    find_in_type(location.as_str(), tipe, visit);
+                }
+            }
+        }
+
+        for alias in self.aliases.iter() {
+            find_in_type(alias.name.to_string().as_str(), &alias.tipe, visit);
+        }
+
+        for endpoint in self.endpoints.iter() {
+            for (body, fields) in [
+                ("params", &endpoint.params),
+                ("response", &endpoint.response),
+            ] {
+                let location = format!("{}.{}", endpoint.name, body);
+
+                visit(location.as_str(), fields);
+                find_in_fields(location.as_str(), fields, visit);
+            }
+        }
+    }
+
+    /// Like `visit_inline_records`, but the fields can be changed, e.g. to
+    /// assign their IDs. This also sets every inline record's `location`.
+    pub fn visit_inline_records_mut<F>(&mut self, visit: &mut F)
+    where
+        F: FnMut(&str, &mut [Field]),
+    {
+        for record in self.records.iter_mut() {
+            if let Some(ref name) = record.name {
+                locate_in_fields(name.to_string().as_str(), &mut record.fields, visit);
+            }
+        }
+
+        for custom_type in self.custom_types.iter_mut() {
+            for variant in custom_type.variants.iter_mut() {
+                let location = format!("{}.{}", custom_type.name, variant.name);
+
+                for tipe in variant.contents.iter_mut() {
+                    locate_in_type(location.as_str(), tipe, visit);
+                }
+            }
+        }
+
+        for alias in self.aliases.iter_mut() {
+            locate_in_type(alias.name.to_string().as_str(), &mut alias.tipe, visit);
+        }
+
+        for endpoint in self.endpoints.iter_mut() {
+            for (body, fields) in [
+                ("params", &mut endpoint.params),
+                ("response", &mut endpoint.response),
+            ] {
+                let location = format!("{}.{}", endpoint.name, body);
+
+                visit(location.as_str(), fields);
+                locate_in_fields(location.as_str(), fields, visit);
+            }
+        }
+    }
+
+    /// Set the `location` of every inline record. `parse::parse` does this, so
+    /// it's only needed for schemas built some other way.
+    pub fn locate_inline_records(&mut self) {
+        self.visit_inline_records_mut(&mut |_, _| ());
+    }
+
+    /// Replace references to aliases with the types they alias, so that
+    /// types can be compared structurally.
+    pub fn expand_aliases(&self, tipe: &Type) -> Type {
+        self.expand_type(tipe, &mut vec![])
+    }
+
+    /// `expanding` holds the names of the aliases we're in the middle of
+    /// expanding, so that a cyclic alias gets left alone instead of
+    /// overflowing the stack.
+    fn expand_type(&self, tipe: &Type, expanding: &mut Vec<String>) -> Type {
+        Type {
+            current: self.expand_primitive(&tipe.current, expanding),
+            formerly: tipe
+                .formerly
+                .iter()
+                .map(|former| self.expand_primitive(former, expanding))
+                .collect(),
+        }
+    }
+
+    fn expand_primitive(
+        &self,
+        primitive: &TypePrimitive,
+        expanding: &mut Vec<String>,
+    ) -> TypePrimitive {
+        match primitive {
+            TypePrimitive::Named(name, args) => {
+                let name_string = name.to_string();
+
+                match self.alias(name_string.as_str()) {
+                    // Aliases with params are left alone; substituting them is
+                    // the code generators' job.
+                    Some(alias)
+                        if alias.params.is_empty()
+                            && args.is_empty()
+                            && !expanding.contains(&name_string) =>
+                    {
+                        expanding.push(name_string);
+
+                        let expanded = self.expand_primitive(&alias.tipe.current, expanding);
+
+                        expanding.pop();
+
+                        expanded
+                    }
+                    _ => TypePrimitive::Named(
+                        name.clone(),
+                        args.iter()
+                            .map(|arg| self.expand_type(arg, expanding))
+                            .collect(),
+                    ),
+                }
+            }
+            TypePrimitive::Array(elem) => {
+                TypePrimitive::Array(Box::new(self.expand_type(elem, expanding)))
+            }
+            TypePrimitive::Set(elem) => {
+                TypePrimitive::Set(Box::new(self.expand_type(elem, expanding)))
+            }
+            TypePrimitive::Dict(key, val) => TypePrimitive::Dict(
+                Box::new(self.expand_type(key, expanding)),
+                Box::new(self.expand_type(val, expanding)),
+            ),
+            other => other.clone(),
+        }
+    }
+}
+
+/// Inline records in the types of the given fields, which are at `location`.
+fn find_in_fields<'a, F>(location: &str, fields: &'a [Field], visit: &mut F)
+where
+    F: FnMut(&str, &'a [Field]),
+{
+    for field in fields.iter() {
+        find_in_type(
+            format!("{}.{}", location, field.name).as_str(),
+            &field.tipe,
+            visit,
+        );
+    }
+}
+
+fn find_in_type<'a, F>(location: &str, tipe: &'a Type, visit: &mut F)
+where
+    F: FnMut(&str, &'a [Field]),
+{
+    for primitive in Some(&tipe.current).into_iter().chain(tipe.formerly.iter()) {
+        match primitive {
+            TypePrimitive::Array(elem) | TypePrimitive::Set(elem) => {
+                find_in_type(location, elem, visit)
+            }
+            TypePrimitive::Dict(key, val) => {
+                find_in_type(location, key, visit);
+                find_in_type(location, val, visit);
+            }
+            TypePrimitive::Named(_, args) => {
+                for arg in args.iter() {
+                    find_in_type(location, arg, visit);
+                }
+            }
+            TypePrimitive::Record(info) if info.name.is_none() => {
+                visit(location, &info.fields);
+                find_in_fields(location, &info.fields, visit);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Like `find_in_fields`, but setting the `location` of each inline record.
+fn locate_in_fields<F>(location: &str, fields: &mut [Field], visit: &mut F)
+where
+    F: FnMut(&str, &mut [Field]),
+{
+    for field in fields.iter_mut() {
+        locate_in_type(
+            format!("{}.{}", location, field.name).as_str(),
+            &mut field.tipe,
+            visit,
+        );
+    }
+}
+
+fn locate_in_type<F>(location: &str, tipe: &mut Type, visit: &mut F)
+where
+    F: FnMut(&str, &mut [Field]),
+{
+    for primitive in Some(&mut tipe.current)
+        .into_iter()
+        .chain(tipe.formerly.iter_mut())
+    {
+        match primitive {
+            TypePrimitive::Array(elem) | TypePrimitive::Set(elem) => {
+                locate_in_type(location, elem, visit)
+            }
+            TypePrimitive::Dict(key, val) => {
+                locate_in_type(location, key, visit);
+                locate_in_type(location, val, visit);
+            }
+            TypePrimitive::Named(_, args) => {
+                for arg in args.iter_mut() {
+                    locate_in_type(location, arg, visit);
+                }
+            }
+            TypePrimitive::Record(info) if info.name.is_none() => {
+                info.location = Some(location.to_string());
+                visit(location, &mut info.fields);
+                locate_in_fields(location, &mut info.fields, visit);
+            }
+            _ => (),
+        }
+    }
 }
@@ -0,0 +1,213 @@
+use types::{Endpoint, Field, RecordInfo, Type, TypePrimitive};
+
+/// A field's type can change over time, as long as the change is a *widening*:
+/// every value of the old type must be representable in the new type.
+///
+/// For example, `Int32` can become `Int64`, and `Float32` can become `Float64`.
+/// An old client which still sends `Int32` values will be understood by a new
+/// server, because the new decoder accepts the former representation and
+/// widens it on the way in.
+///
+/// Going the other way (e.g. `Int64` to `Int32`) is a *narrowing*, which would
+/// silently mangle values sent by old clients, so it's rejected. So is any
+/// other change where the old and new types have nothing to do with each other,
+/// like `String` to `Bool`.
+///
+/// The rules:
+///
+/// * Any type can "widen" to itself.
+/// * `Int` and `Int64` have the same representation, as do `Float` and `Float64`.
+/// * Integers widen to wider integers: `Int8` -> `Int16` -> `Int32` -> `Int64`
+/// * Floats widen to wider floats: `Float32` -> `Float64`
+/// * Integers widen to floats whose mantissa can hold every value exactly:
+///   `Int8` and `Int16` to `Float32`, and `Int32` or narrower to `Float64`.
+/// * `Array`, `Dict`, and `Set` widen if their contents widen.
+/// * Records and custom types widen to types of the same name. (Their fields
+///   and variants have their own histories, which are checked separately.)
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    Narrowing {
+        field: String,
+        from: Box<TypePrimitive>,
+        to: Box<TypePrimitive>,
+    },
+    Incompatible {
+        field: String,
+        from: Box<TypePrimitive>,
+        to: Box<TypePrimitive>,
+    },
+}
+
+pub fn is_safe_widening(from: &TypePrimitive, to: &TypePrimitive) -> bool {
+    if let (Some(from_bits), Some(to_bits)) = (int_bits(from), int_bits(to)) {
+        return from_bits <= to_bits;
+    }
+
+    if let (Some(from_bits), Some(to_bits)) = (float_mantissa_bits(from), float_mantissa_bits(to)) {
+        return from_bits <= to_bits;
+    }
+
+    if let (Some(from_bits), Some(to_bits)) = (int_bits(from), float_mantissa_bits(to)) {
+        return from_bits <= to_bits;
+    }
+
+    match (from, to) {
+        (TypePrimitive::Bool, TypePrimitive::Bool) => true,
+        (TypePrimitive::String, TypePrimitive::String) => true,
+        (TypePrimitive::Array(from_elem), TypePrimitive::Array(to_elem)) => {
+            is_safe_widening(&from_elem.current, &to_elem.current)
+        }
+        (TypePrimitive::Set(from_elem), TypePrimitive::Set(to_elem)) => {
+            is_safe_widening(&from_elem.current, &to_elem.current)
+        }
+        (TypePrimitive::Dict(from_key, from_val), TypePrimitive::Dict(to_key, to_val)) => {
+            is_safe_widening(&from_key.current, &to_key.current)
+                && is_safe_widening(&from_val.current, &to_val.current)
+        }
+        (TypePrimitive::Custom(from_info), TypePrimitive::Custom(to_info)) => {
+            from_info.name == to_info.name
+        }
+        (TypePrimitive::Record(from_info), TypePrimitive::Record(to_info)) => {
+            from_info.name.is_some() && from_info.name == to_info.name
+        }
+        (TypePrimitive::Named(from_name, from_args), TypePrimitive::Named(to_name, to_args)) => {
+            from_name == to_name
+                && from_args.len() == to_args.len()
+                && from_args
+                    .iter()
+                    .zip(to_args.iter())
+                    .all(|(from_arg, to_arg)| is_safe_widening(&from_arg.current, &to_arg.current))
+        }
+        (TypePrimitive::Param(from_param), TypePrimitive::Param(to_param)) => {
+            from_param == to_param
+        }
+        _ => false,
+    }
+}
+
+/// Verify that every type in the field's `formerly` list safely widens to its
+/// current type.
+pub fn check(field_name: &str, tipe: &Type) -> Result<(), Problem> {
+    for former in tipe.formerly.iter() {
+        if !is_safe_widening(former, &tipe.current) {
+            let field = field_name.to_string();
+            let from = Box::new(former.clone());
+            let to = Box::new(tipe.current.clone());
+
+            return Err(if is_safe_widening(&to, &from) {
+                Problem::Narrowing { field, from, to }
+            } else {
+                Problem::Incompatible { field, from, to }
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the type history of every field in the record, returning all the
+/// problems found. Field names in problems are qualified by the record's name,
+/// e.g. "User.userId"
+pub fn check_record(record: &RecordInfo) -> Vec<Problem> {
+    let record_name = match record.name {
+        Some(ref name) => name.to_string(),
+        None => String::from("{ ... }"),
+    };
+
+    record
+        .fields
+        .iter()
+        .filter_map(|field: &Field| {
+            check(
+                format!("{}.{}", record_name, field.name).as_str(),
+                &field.tipe,
+            )
+            .err()
+        })
+        .collect()
+}
+
+/// Like `check_record`, but for an endpoint's params and response. Field names
+/// are qualified like "sendDM.params.userId"
+pub fn check_endpoint(endpoint: &Endpoint) -> Vec<Problem> {
+    let params = endpoint.params.iter().map(|field| ("params", field));
+    let response = endpoint.response.iter().map(|field| ("response", field));
+
+    params
+        .chain(response)
+        .filter_map(|(section, field)| {
+            check(
+                format!("{}.{}.{}", endpoint.name, section, field.name).as_str(),
+                &field.tipe,
+            )
+            .err()
+        })
+        .collect()
+}
+
+/// Every type a decoder for this type must accept: the current one first,
+/// followed by each former one.
+pub fn accepted_types(tipe: &Type) -> Vec<&TypePrimitive> {
+    let mut accepted = vec![&tipe.current];
+
+    accepted.extend(tipe.formerly.iter());
+
+    accepted
+}
+
+/// Convert a 64-bit slot holding a value of type `from` into the slot
+/// representation of type `to`. Decoders use this to accept values that were
+/// encoded using a field's former type.
+///
+/// Integers are stored sign-extended to 64 bits, so widening an integer to a
+/// wider integer leaves the word unchanged. A `Float32` is stored as its 32
+/// bits in the low half of the word.
+///
+/// Returns None if `from` cannot be safely widened to `to`.
+pub fn widen_word(from: &TypePrimitive, to: &TypePrimitive, word: u64) -> Option<u64> {
+    if !is_safe_widening(from, to) {
+        return None;
+    }
+
+    let widened = match (
+        int_bits(from),
+        float_mantissa_bits(from),
+        float_mantissa_bits(to),
+    ) {
+        // Int -> Float32
+        (Some(_), None, Some(FLOAT32_MANTISSA_BITS)) => u64::from(((word as i64) as f32).to_bits()),
+        // Int -> Float64
+        (Some(_), None, Some(_)) => ((word as i64) as f64).to_bits(),
+        // Float32 -> Float64
+        (None, Some(FLOAT32_MANTISSA_BITS), Some(FLOAT64_MANTISSA_BITS)) => {
+            f64::from(f32::from_bits(word as u32)).to_bits()
+        }
+        // Everything else has the same representation before and after.
+        _ => word,
+    };
+
+    Some(widened)
+}
+
+const FLOAT32_MANTISSA_BITS: u8 = 24;
+const FLOAT64_MANTISSA_BITS: u8 = 53;
+
+fn int_bits(primitive: &TypePrimitive) -> Option<u8> {
+    match primitive {
+        TypePrimitive::Int8 => Some(8),
+        TypePrimitive::Int16 => Some(16),
+        TypePrimitive::Int32 => Some(32),
+        TypePrimitive::Int64 | TypePrimitive::Int => Some(64),
+        _ => None,
+    }
+}
+
+/// Floats are compared by mantissa width (including the implicit leading bit),
+/// because that's what determines which integers they can represent exactly.
+fn float_mantissa_bits(primitive: &TypePrimitive) -> Option<u8> {
+    match primitive {
+        TypePrimitive::Float32 => Some(FLOAT32_MANTISSA_BITS),
+        TypePrimitive::Float64 | TypePrimitive::Float => Some(FLOAT64_MANTISSA_BITS),
+        _ => None,
+    }
+}
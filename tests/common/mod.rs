@@ -0,0 +1,18 @@
+use wf::allocator;
+use wf::code_gen;
+use wf::code_gen::MinificationTable;
+use wf::history::History;
+use wf::parse;
+use wf::types::Schema;
+
+/// Parse a schema, allocate it field ids on top of the given history, and
+/// make the minification table production code would use for it.
+pub fn productionize(source: &str, mut history: History) -> (Schema, MinificationTable) {
+    let mut schema = parse::parse(source).unwrap();
+
+    allocator::allocate(&mut history, &mut schema);
+
+    let table = code_gen::productionize(&history, &schema).unwrap();
+
+    (schema, table)
+}
@@ -0,0 +1,315 @@
+extern crate wf;
+
+use wf::allocator;
+use wf::allocator::Allocation;
+use wf::history;
+use wf::history::History;
+use wf::parse;
+use wf::types::{
+    CustomTypeInfo, Field, Optionality, RecordInfo, Schema, Type, TypeName, TypePrimitive, Variant,
+};
+
+fn field(name: &str) -> Field {
+    Field {
+        optionality: Optionality::Required,
+        name: name.to_string(),
+        tipe: Type::new(TypePrimitive::String),
+        field_id: 0,
+        fallback: false,
+        former_names: vec![],
+        deprecated: false,
+        default: None,
+        header: None,
+    }
+}
+
+fn variant(name: &str) -> Variant {
+    Variant {
+        optionality: Optionality::Required,
+        name: name.to_string(),
+        tipe: Type::new(TypePrimitive::String),
+        variant_id: 0,
+        former_names: vec![],
+        contents: vec![],
+    }
+}
+
+fn user(field_names: &[&str]) -> Schema {
+    Schema {
+        records: vec![RecordInfo {
+            name: Some(TypeName::TypeName(String::from("User"))),
+            former_names: vec![],
+            fields: field_names.iter().map(|name| field(name)).collect(),
+            params: vec![],
+            packed: false,
+            location: None,
+        }],
+        custom_types: vec![],
+        ..Schema::default()
+    }
+}
+
+fn field_ids(schema: &Schema) -> Vec<(&str, u64)> {
+    schema.records[0]
+        .fields
+        .iter()
+        .map(|field| (field.name.as_str(), field.field_id))
+        .collect()
+}
+
+fn strings(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn first_allocation_numbers_fields_in_order() {
+    let mut history = History::new();
+    let mut schema = user(&["userId", "email"]);
+
+    assert_eq!(
+        Allocation {
+            version: 1,
+            added: strings(&["User.userId", "User.email"]),
+            renamed: vec![],
+            retired: vec![],
+            retyped: vec![],
+        },
+        allocator::allocate(&mut history, &mut schema)
+    );
+    assert_eq!(vec![("userId", 0), ("email", 1)], field_ids(&schema));
+}
+
+#[test]
+fn reordering_keeps_ids_and_version() {
+    let mut history = History::new();
+
+    allocator::allocate(&mut history, &mut user(&["userId", "email"]));
+
+    let mut schema = user(&["email", "userId"]);
+    let allocation = allocator::allocate(&mut history, &mut schema);
+
+    assert_eq!(1, allocation.version);
+    assert!(allocation.added.is_empty());
+    assert_eq!(vec![("email", 1), ("userId", 0)], field_ids(&schema));
+}
+
+#[test]
+fn removed_fields_are_retired_and_their_ids_never_reused() {
+    let mut history = History::new();
+
+    allocator::allocate(&mut history, &mut user(&["userId", "email", "name"]));
+
+    let allocation = allocator::allocate(&mut history, &mut user(&["userId", "name"]));
+
+    assert_eq!(2, allocation.version);
+    assert_eq!(strings(&["User.email"]), allocation.retired);
+
+    let mut schema = user(&["userId", "name", "homepage"]);
+
+    allocator::allocate(&mut history, &mut schema);
+
+    assert_eq!(
+        vec![("userId", 0), ("name", 2), ("homepage", 3)],
+        field_ids(&schema)
+    );
+}
+
+#[test]
+fn reintroduced_name_gets_a_fresh_id() {
+    let mut history = History::new();
+
+    allocator::allocate(&mut history, &mut user(&["userId", "email"]));
+    allocator::allocate(&mut history, &mut user(&["userId"]));
+
+    let mut schema = user(&["userId", "email"]);
+
+    allocator::allocate(&mut history, &mut schema);
+
+    assert_eq!(vec![("userId", 0), ("email", 2)], field_ids(&schema));
+}
+
+#[test]
+fn removed_records_retire_all_their_fields() {
+    let mut history = History::new();
+
+    allocator::allocate(&mut history, &mut user(&["userId"]));
+
+    let allocation = allocator::allocate(&mut history, &mut Schema::default());
+
+    assert_eq!(strings(&["User.userId"]), allocation.retired);
+}
+
+#[test]
+fn variants_get_ids_per_custom_type() {
+    let mut history = History::new();
+    let mut schema = Schema {
+        records: vec![],
+        custom_types: vec![CustomTypeInfo {
+            name: TypeName::TypeName(String::from("Result")),
+            former_names: vec![],
+            variants: vec![variant("ok"), variant("err")],
+            params: vec![],
+        }],
+        ..Schema::default()
+    };
+
+    allocator::allocate(&mut history, &mut schema);
+
+    assert_eq!(1, schema.custom_types[0].variants[1].variant_id);
+}
+
+#[test]
+fn allocation_survives_a_round_trip_through_the_file_format() {
+    let mut history = History::new();
+
+    allocator::allocate(&mut history, &mut user(&["userId", "email"]));
+    allocator::allocate(&mut history, &mut user(&["userId"]));
+
+    let mut history = history::parse(history::to_string(&history).as_str()).unwrap();
+    let mut schema = user(&["userId", "name"]);
+
+    allocator::allocate(&mut history, &mut schema);
+
+    assert_eq!(vec![("userId", 0), ("name", 2)], field_ids(&schema));
+}
+
+#[test]
+fn renamed_fields_keep_their_ids() {
+    let mut history = History::new();
+
+    allocator::allocate(&mut history, &mut user(&["id", "email"]));
+
+    let mut schema = user(&["userId", "email"]);
+
+    schema.records[0].fields[0].former_names = strings(&["id"]);
+
+    let allocation = allocator::allocate(&mut history, &mut schema);
+
+    assert_eq!(
+        vec![(String::from("User.id"), String::from("User.userId"))],
+        allocation.renamed
+    );
+    assert!(allocation.added.is_empty());
+    assert!(allocation.retired.is_empty());
+    assert_eq!(2, allocation.version);
+    assert_eq!(vec![("userId", 0), ("email", 1)], field_ids(&schema));
+    assert_eq!(
+        vec!["userId", "id"],
+        history.record("User").unwrap().entries[0].accepted_names(2, 3)
+    );
+
+    // Once the rename is recorded, removing the fka changes nothing.
+    let mut schema = user(&["userId", "email"]);
+
+    assert!(allocator::allocate(&mut history, &mut schema)
+        .renamed
+        .is_empty());
+    assert_eq!(vec![("userId", 0), ("email", 1)], field_ids(&schema));
+}
+
+#[test]
+fn fka_is_ignored_while_the_former_name_is_still_in_use() {
+    let mut history = History::new();
+
+    allocator::allocate(&mut history, &mut user(&["id"]));
+
+    let mut schema = user(&["id", "userId"]);
+
+    schema.records[0].fields[1].former_names = strings(&["id"]);
+
+    let allocation = allocator::allocate(&mut history, &mut schema);
+
+    assert!(allocation.renamed.is_empty());
+    assert_eq!(vec![("id", 0), ("userId", 1)], field_ids(&schema));
+}
+
+#[test]
+fn renamed_records_keep_their_ledgers() {
+    let mut history = History::new();
+
+    allocator::allocate(&mut history, &mut user(&["userId", "email"]));
+
+    let mut schema = user(&["userId", "email"]);
+
+    schema.records[0].name = Some(TypeName::TypeName(String::from("Member")));
+    schema.records[0].former_names = vec![TypeName::TypeName(String::from("User"))];
+
+    let allocation = allocator::allocate(&mut history, &mut schema);
+
+    assert_eq!(
+        vec![(String::from("User"), String::from("Member"))],
+        allocation.renamed
+    );
+    assert!(allocation.retired.is_empty());
+    assert!(history.record("User").is_none());
+    assert_eq!(
+        vec!["Member", "User"],
+        history.record("Member").unwrap().accepted_names(2, 3)
+    );
+}
+
+#[test]
+fn renamed_records_take_their_inline_records_along() {
+    let mut history = History::new();
+
+    allocator::allocate(
+        &mut history,
+        &mut parse::parse("struct Person\n    * profile : { name : String, email : String }\n")
+            .unwrap(),
+    );
+
+    let mut schema = parse::parse(
+        "struct User [ fka \"Person\" ]\n    * profile : { email : String, name : String }\n",
+    )
+    .unwrap();
+    let allocation = allocator::allocate(&mut history, &mut schema);
+
+    assert_eq!(
+        vec![
+            (String::from("Person"), String::from("User")),
+            (String::from("Person.profile"), String::from("User.profile")),
+        ],
+        allocation.renamed
+    );
+    assert!(allocation.added.is_empty());
+    assert!(allocation.retired.is_empty());
+    assert!(history.record("Person.profile").is_none());
+    assert_eq!(
+        vec!["User.profile", "Person.profile"],
+        history.record("User.profile").unwrap().accepted_names(2, 3)
+    );
+}
+
+#[test]
+fn endpoint_bodies_and_inline_records_keep_their_ids() {
+    let mut history = History::new();
+    let mut schema = parse::parse(
+        "post sendDM\n    params:\n        * to : Int\n        * text : String\n\nstruct User\n    * profile : { name : String, email : String }\n",
+    )
+    .unwrap();
+
+    allocator::allocate(&mut history, &mut schema);
+
+    let mut schema = parse::parse(
+        "post sendDM\n    params:\n        * text : String\n        * urgent : Bool\n        * to : Int\n\nstruct User\n    * profile : { email : String }\n",
+    )
+    .unwrap();
+    let allocation = allocator::allocate(&mut history, &mut schema);
+    let params: Vec<(&str, u64)> = schema.endpoints[0]
+        .params
+        .iter()
+        .map(|field| (field.name.as_str(), field.field_id))
+        .collect();
+
+    assert_eq!(vec![("text", 1), ("urgent", 2), ("to", 0)], params);
+    assert_eq!(strings(&["sendDM.params.urgent"]), allocation.added);
+    assert_eq!(strings(&["User.profile.name"]), allocation.retired);
+
+    match schema.records[0].fields[0].tipe.current {
+        TypePrimitive::Record(ref profile) => {
+            assert_eq!(Some(String::from("User.profile")), profile.location);
+            assert_eq!(1, profile.fields[0].field_id);
+        }
+        ref other => panic!("Expected an inline record, got {:?}", other),
+    }
+}
@@ -2,11 +2,15 @@ extern crate byteorder;
 extern crate tempfile;
 extern crate wf;
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::File;
 use tempfile::TempDir;
+use wf::array;
 use wf::arrays;
+use wf::collection;
 use wf::pointer;
 use wf::pointer::Pointer;
+use wf::string;
 
 // For reference:
 //
@@ -15,6 +19,7 @@ use wf::pointer::Pointer;
 //
 
 #[test]
+#[allow(clippy::unusual_byte_groupings)]
 fn test_decode() {
     let word: u64 =
         0b0000_0000_0000_0000_0000_0000_0000_0011__0000_0000_0000_0101__000_0000_0000_0110__1;
@@ -30,6 +35,7 @@ fn test_decode() {
 }
 
 #[test]
+#[allow(clippy::unusual_byte_groupings)]
 fn test_segment_pointer_reflexive() {
     let expected: u64 =
         0b0000_0000_0000_0000_0000_0000_0000_0011__0000_0000_0000_0101__000_0000_0000_0110__1;
@@ -43,11 +49,22 @@ fn test_segment_pointer_reflexive() {
     assert_eq!(expected, actual);
 }
 
+#[test]
+#[should_panic(expected = "only has 15 bits")]
+fn test_pointer_length_too_long() {
+    pointer::encode(Pointer {
+        word_index: 0,
+        length: pointer::MAX_LENGTH as u16 + 1,
+        segment_id_offset: 0,
+        is_composite: false,
+    });
+}
+
 #[test]
 fn test_i64_arrays() {
     // Write several arrays to the same file, then read them back.
 
-    let file_path = TempDir::new().unwrap().into_path().join("deleteme.binary");
+    let file_path = TempDir::new().unwrap().keep().join("deleteme.binary");
     let mut file = File::create(&file_path).unwrap();
     let array_lengths = vec![1, 22, 333, 4444];
 
@@ -75,7 +92,306 @@ fn test_i64_arrays() {
         for num in 0..expected_array_length {
             let actual: Option<&i64> = result.get(num as usize);
 
-            assert_eq!(Some(&((num as i64) % 42)), actual);
+            assert_eq!(Some(&(num % 42)), actual);
         }
     }
 }
+
+#[test]
+fn test_composite_pointers() {
+    // Right at the limit, just past it (which used to overflow into the
+    // segment bits), and past u16::MAX (which used to panic).
+    for length in [pointer::MAX_LENGTH, pointer::MAX_LENGTH + 1, 70_000] {
+        let arr: Vec<u64> = (0..length as u64).collect();
+        let mut segment = vec![];
+        let raw_ptr = array::encode_u64_array(&mut segment, 2, &arr);
+        let ptr = pointer::decode(raw_ptr);
+
+        assert_eq!(ptr.is_composite, length > pointer::MAX_LENGTH);
+        assert_eq!(ptr.segment_id_offset, 2);
+
+        // The pointer is two segments before the array.
+        let segments = vec![vec![], vec![], segment];
+
+        assert_eq!(array::decode_u64_array(&segments, 0, raw_ptr), Ok(arr));
+    }
+
+    let bytes: Vec<u8> = (0..100_003).map(|num| (num % 251) as u8).collect();
+    let mut segment = vec![];
+    let raw_ptr = array::encode_u8_array(&mut segment, 0, &bytes);
+
+    assert!(pointer::decode(raw_ptr).is_composite);
+    assert_eq!(segment.len(), 4 + 100_003 / 8 + 1);
+    assert_eq!(array::encode_u64_array(&mut segment, 0, &[]), 0);
+
+    let segments = vec![segment];
+
+    assert_eq!(array::decode_u8_array(&segments, 0, raw_ptr), Ok(bytes));
+    assert_eq!(array::decode_u64_array(&segments, 0, 0), Ok(vec![]));
+}
+
+#[test]
+fn test_nested_composite_pointers() {
+    // Nesting only kicks in past MAX_LENGTH * MAX_LENGTH elements, so build
+    // a small nested one by hand: [1, 2] and [3] under one Composite Pointer,
+    // and that alongside [4, 5] under another.
+    let segment_ptr = |word_index, length| {
+        pointer::encode(Pointer {
+            word_index,
+            segment_id_offset: 0,
+            length,
+            is_composite: false,
+        })
+    };
+    let segment = vec![
+        1,
+        2,
+        3,
+        4,
+        5,
+        segment_ptr(0, 2),
+        segment_ptr(2, 1),
+        pointer::encode(Pointer {
+            word_index: 5,
+            segment_id_offset: 0,
+            length: 2,
+            is_composite: true,
+        }),
+        segment_ptr(3, 2),
+    ];
+    let raw_ptr = pointer::encode(Pointer {
+        word_index: 7,
+        segment_id_offset: 0,
+        length: 2,
+        is_composite: true,
+    });
+
+    assert_eq!(
+        array::decode_u64_array(&[segment], 0, raw_ptr),
+        Ok(vec![1, 2, 3, 4, 5])
+    );
+}
+
+fn composite(word_index: u32, segment_id_offset: u16, length: u16) -> u64 {
+    pointer::encode(Pointer {
+        word_index,
+        segment_id_offset,
+        length,
+        is_composite: true,
+    })
+}
+
+#[test]
+fn test_decode_across_segments() {
+    // The Composite Pointer is in segment 1, and its chunks are in segments 1
+    // and 2, relative to the segment holding the pointers.
+    let mut segments = vec![vec![], vec![7, 8], vec![9]];
+    let chunks = [
+        pointer::encode(Pointer {
+            word_index: 0,
+            segment_id_offset: 0,
+            length: 2,
+            is_composite: false,
+        }),
+        pointer::encode(Pointer {
+            word_index: 0,
+            segment_id_offset: 1,
+            length: 1,
+            is_composite: false,
+        }),
+    ];
+
+    segments[1].extend_from_slice(&chunks);
+
+    assert_eq!(
+        array::decode_u64_array(&segments, 0, composite(2, 1, 2)),
+        Ok(vec![7, 8, 9])
+    );
+}
+
+#[test]
+fn test_decode_bad_pointers() {
+    let segments = vec![vec![1, 2, composite(2, 0, 1)]];
+    let segment_ptr = |word_index, segment_id_offset, length| {
+        pointer::encode(Pointer {
+            word_index,
+            segment_id_offset,
+            length,
+            is_composite: false,
+        })
+    };
+
+    assert_eq!(
+        array::decode_u64_array(&segments, 0, segment_ptr(0, 1, 2)),
+        Err(pointer::Problem::MissingSegment(1))
+    );
+    assert_eq!(
+        array::decode_u64_array(&segments, 0, segment_ptr(1, 0, 5)),
+        Err(pointer::Problem::OutOfBounds {
+            segment: 0,
+            word_index: 1,
+            words: 5,
+        })
+    );
+    assert!(array::decode_u8_array(&segments, 0, segment_ptr(2, 0, 9)).is_err());
+
+    // A Composite Pointer which points to itself
+    assert_eq!(
+        array::decode_u64_array(&segments, 0, composite(2, 0, 1)),
+        Err(pointer::Problem::Overlapping)
+    );
+
+    // A Composite Pointer which points to the same chunk of data three times
+    let chunk = segment_ptr(0, 0, 2);
+
+    assert_eq!(
+        array::decode_u64_array(&[vec![7, 8, chunk, chunk, chunk]], 0, composite(2, 0, 3)),
+        Err(pointer::Problem::TooMuchData)
+    );
+
+    // A chain of Composite Pointers, each pointing to the next
+    let depth = pointer::MAX_COMPOSITE_DEPTH as u32 + 1;
+    let mut chain: Vec<u64> = (1..depth).map(|index| composite(index, 0, 1)).collect();
+
+    chain.push(segment_ptr(0, 0, 1));
+
+    assert_eq!(
+        array::decode_u64_array(&[chain], 0, composite(0, 0, 1)),
+        Err(pointer::Problem::TooDeep)
+    );
+}
+
+#[test]
+fn test_strings() {
+    // Long enough for a Composite Pointer, with chunks splitting characters
+    let long = "añ😀".repeat(10_000);
+    let mut segment = vec![];
+    let empty = string::encode_string(&mut segment, 0, "");
+    let short = string::encode_string(&mut segment, 0, "hi");
+    let long_ptr = string::encode_string(&mut segment, 0, &long);
+    let segments = vec![segment];
+
+    assert_eq!(empty, 0);
+    assert!(pointer::decode(long_ptr).is_composite);
+    assert_eq!(
+        string::decode_string(&segments, 0, empty),
+        Ok(String::new())
+    );
+    assert_eq!(
+        string::decode_string(&segments, 0, short),
+        Ok("hi".to_string())
+    );
+    assert_eq!(
+        string::decode_string(&segments, 0, long_ptr),
+        Ok(long.clone())
+    );
+    assert_eq!(
+        unsafe { string::decode_string_unchecked(&segments, 0, long_ptr) },
+        Ok(long)
+    );
+
+    let mut segment = vec![];
+    let invalid = array::encode_u8_array(&mut segment, 0, &[b'o', b'k', 0xc3]);
+
+    assert_eq!(
+        string::decode_string(&[segment], 0, invalid),
+        Err(string::Problem::InvalidUtf8 { valid_up_to: 2 })
+    );
+}
+
+#[test]
+fn test_sets_and_dicts() {
+    let numbers: BTreeSet<i64> = (-20_000..20_000).collect();
+    let names: BTreeSet<String> = ["b", "a", "c"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    let ages: BTreeMap<String, u64> = names.iter().cloned().zip(vec![30, 40, 50]).collect();
+    let mut segment = vec![];
+    let numbers_ptr = collection::encode_set(&mut segment, 0, &numbers);
+    let names_ptr = collection::encode_set(&mut segment, 0, &names);
+    let ages_ptr = collection::encode_dict(&mut segment, 0, &ages);
+    let empty_ptr = collection::encode_dict(&mut segment, 0, &BTreeMap::<u64, bool>::new());
+
+    // Sorted elements come out in order, whatever order they went in.
+    let names_start = pointer::decode(names_ptr).word_index as usize;
+    let segments = vec![segment];
+
+    assert!(pointer::decode(numbers_ptr).is_composite);
+    assert_eq!(
+        string::decode_string(&segments, 0, segments[0][names_start]),
+        Ok("a".to_string())
+    );
+    assert_eq!(
+        collection::decode_set(&segments, 0, numbers_ptr),
+        Ok(numbers)
+    );
+    assert_eq!(collection::decode_set(&segments, 0, names_ptr), Ok(names));
+    assert_eq!(collection::decode_dict(&segments, 0, ages_ptr), Ok(ages));
+    assert_eq!(
+        collection::decode_dict::<u64, bool>(&segments, 0, empty_ptr),
+        Ok(BTreeMap::new())
+    );
+
+    // True is all ones, but any other nonzero Word decodes as true too.
+    let flags: BTreeMap<u64, bool> = vec![(1, true), (2, false)].into_iter().collect();
+    let mut segment = vec![];
+    let flags_ptr = collection::encode_dict(&mut segment, 0, &flags);
+
+    assert_eq!(segment, vec![1, u64::MAX, 2, 0]);
+
+    segment[1] = 1;
+
+    assert_eq!(collection::decode_dict(&[segment], 0, flags_ptr), Ok(flags));
+}
+
+#[test]
+fn test_invalid_sets_and_dicts() {
+    let segment_ptr = |word_index, length| {
+        pointer::encode(Pointer {
+            word_index,
+            segment_id_offset: 0,
+            length,
+            is_composite: false,
+        })
+    };
+    // [1, 2] and [2, 3] as two chunks of a Set, then [3, 1], and a Dict with
+    // the entries 1 => 10 and 1 => 11
+    let segments = vec![vec![
+        1,
+        2,
+        2,
+        3,
+        segment_ptr(0, 2),
+        segment_ptr(2, 2),
+        3,
+        1,
+        1,
+        10,
+        1,
+        11,
+    ]];
+
+    assert_eq!(
+        collection::decode_set::<u64>(&segments, 0, composite(4, 0, 2)),
+        Err(collection::Problem::Duplicate)
+    );
+    assert_eq!(
+        collection::decode_set::<u64>(&segments, 0, segment_ptr(6, 2)),
+        Err(collection::Problem::Unsorted)
+    );
+    assert_eq!(
+        collection::decode_dict::<u64, u64>(&segments, 0, segment_ptr(8, 2)),
+        Err(collection::Problem::Duplicate)
+    );
+    assert_eq!(
+        collection::decode_set::<u64>(&segments, 0, segment_ptr(10, 5)),
+        Err(collection::Problem::Pointer(
+            pointer::Problem::OutOfBounds {
+                segment: 0,
+                word_index: 10,
+                words: 5,
+            }
+        ))
+    );
+}
@@ -0,0 +1,162 @@
+extern crate wf;
+
+use wf::allocator;
+use wf::changelog;
+use wf::changelog::{Note, Section, SectionKind};
+use wf::history::History;
+use wf::parse;
+use wf::types::Schema;
+
+/// Allocate IDs for each source in turn, returning the history along with the
+/// schema as of each version.
+fn versions(sources: &[&str]) -> (History, Vec<Schema>) {
+    let mut history = History::new();
+    let mut schemas: Vec<Schema> = vec![Schema::default()];
+
+    for source in sources.iter() {
+        let mut schema = parse::parse(source).unwrap();
+
+        allocator::allocate(&mut history, &mut schema);
+        schemas.push(schema);
+    }
+
+    (history, schemas)
+}
+
+fn user(notes: Vec<Note>) -> Section {
+    Section {
+        kind: SectionKind::Record,
+        name: String::from("User"),
+        notes,
+    }
+}
+
+fn added(name: &str) -> Note {
+    Note::Added(name.to_string())
+}
+
+const V1: &str = "record User\n    * id : Int\n    * email : String\n    * age : Int32\n";
+const V2: &str = "record User\n    * userId : Int [ fka \"id\" ]\n    * email : String\n    * age : Int64 [ formerly Int32 ]\n";
+const V3: &str = "record User\n    * userId : Int [ fka \"id\" ]\n    ! email : String\n    * age : Int64 [ formerly Int32 ]\n    ~ name : String\n";
+
+#[test]
+fn renames_and_widenings() {
+    let (history, schemas) = versions(&[V1, V2]);
+    let log = changelog::between(&history, 1, 2, Some((&schemas[1], &schemas[2])));
+
+    assert_eq!(
+        log.sections,
+        vec![user(vec![
+            Note::Renamed {
+                from: String::from("id"),
+                to: String::from("userId"),
+            },
+            Note::TypeChanged {
+                name: String::from("age"),
+                from: String::from("Int32"),
+                to: String::from("Int64"),
+                widened: true,
+            },
+        ])]
+    );
+    assert!(log.complete);
+}
+
+#[test]
+fn additions_and_deprecations() {
+    let (history, schemas) = versions(&[V1, V2, V3]);
+    let log = changelog::between(&history, 2, 3, Some((&schemas[2], &schemas[3])));
+
+    assert_eq!(
+        log.sections,
+        vec![user(vec![
+            added("name"),
+            Note::Other(String::from("User.email became optional.")),
+            Note::Deprecated(String::from("email")),
+        ])]
+    );
+}
+
+#[test]
+fn retirements() {
+    let (history, _) = versions(&[V1, "record User\n    * id : Int\n    * email : String\n"]);
+    let log = changelog::between(&history, 1, 2, None);
+
+    assert_eq!(
+        log.sections,
+        vec![user(vec![Note::Retired(String::from("age"))])]
+    );
+    assert!(!log.complete);
+}
+
+#[test]
+fn spanning_several_versions() {
+    let (history, schemas) = versions(&[V1, V2, V3]);
+    let log = changelog::between(&history, 1, 3, Some((&schemas[1], &schemas[3])));
+
+    assert_eq!(log.sections.len(), 1);
+    assert_eq!(log.sections[0].notes.len(), 5);
+
+    // Without the schemas, only what the history knows is listed.
+    assert_eq!(
+        changelog::between(&history, 1, 3, None).sections,
+        vec![user(vec![
+            Note::Renamed {
+                from: String::from("id"),
+                to: String::from("userId"),
+            },
+            added("name"),
+        ])]
+    );
+}
+
+#[test]
+fn added_from_the_start() {
+    let (history, schemas) = versions(&[V1]);
+    let log = changelog::between(&history, 0, 1, Some((&schemas[0], &schemas[1])));
+
+    assert_eq!(
+        log.sections,
+        vec![user(vec![
+            added("User"),
+            added("id"),
+            added("email"),
+            added("age"),
+        ])]
+    );
+}
+
+#[test]
+fn endpoints() {
+    let old = "post sendDM\n    params:\n        * userId : Int\n";
+    let new = "post sendDM\n    params:\n        * userId : Int\n        ~ text : String\n\nget ping\n    response:\n        * ok : Bool\n";
+    let (history, schemas) = versions(&[old, new]);
+    let log = changelog::between(&history, 1, 2, Some((&schemas[1], &schemas[2])));
+
+    assert_eq!(
+        log.sections,
+        vec![
+            Section {
+                kind: SectionKind::Endpoint,
+                name: String::from("sendDM"),
+                notes: vec![added("params.text")],
+            },
+            Section {
+                kind: SectionKind::Endpoint,
+                name: String::from("ping"),
+                notes: vec![added("ping")],
+            },
+        ]
+    );
+}
+
+#[test]
+fn markdown() {
+    let (history, schemas) = versions(&[V1, V2]);
+    let log = changelog::between(&history, 1, 2, Some((&schemas[1], &schemas[2])));
+
+    assert_eq!(
+        changelog::to_markdown(&log),
+        "## Schema changes from version 1 to 2\n\n### `User` record\n\n- Renamed `id` to `userId`\n- Changed the type of `age` from `Int32` to `Int64` (old values are still accepted)\n"
+    );
+}
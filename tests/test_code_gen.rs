@@ -0,0 +1,237 @@
+extern crate wf;
+
+mod common;
+
+use wf::allocator;
+use wf::code_gen;
+use wf::code_gen::{MinificationTable, Mode, Problem};
+use wf::history;
+use wf::history::History;
+use wf::parse;
+use wf::types::Schema;
+
+const HISTORY: &str = "wirefunc history 1
+version 3
+
+record User
+    a userId since 1
+    b email since 1 retired 2
+    c id since 1 retired 3
+    d id since 3
+
+type Reply
+    a Text since 1
+    b Pigeon since 1 retired 2
+";
+
+fn check(source: &str) -> Result<(), Problem> {
+    let history = history::parse(HISTORY).unwrap();
+
+    code_gen::check_for_retired_names(&history, &parse::parse(source).unwrap())
+}
+
+#[test]
+fn current_names_are_fine() {
+    assert!(check("struct User\n    * userId : Int\n    * id : String\n").is_ok());
+}
+
+#[test]
+fn retired_names_are_rejected() {
+    match check(
+        "struct User\n    * userId : Int\n    ~ email : String\n\ntype Reply\n    | Text String\n    | Pigeon\n",
+    ) {
+        Err(Problem::RetiredNamesUsed(names)) => {
+            assert_eq!(vec!["User.email", "Reply.Pigeon"], names)
+        }
+        other => panic!("Expected RetiredNamesUsed, got {:?}", other),
+    }
+}
+
+#[test]
+fn retired_names_only_apply_to_their_own_record() {
+    assert!(check("struct Profile\n    * email : String\n").is_ok());
+}
+
+#[test]
+fn names_reintroduced_before_retirement_was_enforced_are_grandfathered() {
+    let history = history::parse(HISTORY).unwrap();
+
+    assert!(!history
+        .record("User")
+        .unwrap()
+        .retired_names()
+        .contains("id"));
+    assert!(check("struct User\n    * id : String\n").is_ok());
+}
+
+#[test]
+fn productionize_minifies_names_by_id() {
+    let history = history::parse(HISTORY).unwrap();
+    let schema = parse::parse(
+        "struct User\n    * userId : Int\n    * id : String\n\ntype Reply\n    | Text String\n",
+    )
+    .unwrap();
+    let table = code_gen::productionize(&history, &schema).unwrap();
+
+    assert_eq!(Some("a"), table.field("User", "userId"));
+    assert_eq!(Some("d"), table.field("User", "id"));
+    assert_eq!(Some("a"), table.variant("Reply", "Text"));
+    assert_eq!(None, table.field("User", "email"));
+
+    // Sorted by name, regardless of the order in the schema.
+    assert_eq!(
+        vec![
+            (String::from("id"), String::from("d")),
+            (String::from("userId"), String::from("a")),
+        ],
+        table.records[0].names
+    );
+}
+
+#[test]
+fn productionize_rejects_retired_names() {
+    let history = history::parse(HISTORY).unwrap();
+    let schema = parse::parse("struct User\n    * userId : Int\n    * email : String\n").unwrap();
+
+    match code_gen::productionize(&history, &schema) {
+        Err(Problem::RetiredNamesUsed(names)) => assert_eq!(vec!["User.email"], names),
+        other => panic!("Expected RetiredNamesUsed, got {:?}", other),
+    }
+}
+
+#[test]
+fn productionize_requires_ids() {
+    let history = history::parse(HISTORY).unwrap();
+    let schema = parse::parse("struct User\n    * userId : Int\n    * name : String\n").unwrap();
+
+    match code_gen::productionize(&history, &schema) {
+        Err(Problem::MissingIds(names)) => assert_eq!(vec!["User.name"], names),
+        other => panic!("Expected MissingIds, got {:?}", other),
+    }
+}
+
+fn packed_schema() -> (Schema, MinificationTable) {
+    // tags took the slot after two retired fields.
+    let history = history::parse(
+        "wirefunc history 1\nversion 2\n\nrecord User\n    a userId since 1\n    b name since 1 retired 2\n    c email since 1 retired 2\n    d tags since 2\n\nrecord Tag\n    a label since 1\n\nrecord feed.response\n    a users since 1\n",
+    )
+    .unwrap();
+
+    common::productionize(
+        "get feed [ packed ]\n    response:\n        * users : Array User\n\nstruct User\n    * userId : Int\n    * tags : Array Tag\n\nstruct Tag [ packed ]\n    * label : String\n",
+        history,
+    )
+}
+
+#[test]
+fn packing_js() {
+    let (schema, table) = packed_schema();
+
+    assert!(code_gen::uses_packing(&schema));
+
+    let js = code_gen::packing_js(&schema, &table).unwrap();
+
+    assert!(js.contains("var wf$slots$User = [\"a\", null, null, \"d\"];"));
+    assert!(js.contains(
+        "return inArray && packAll && fields != null ? wf$toTuple(fields, wf$slots$User) : fields;"
+    ));
+    assert!(js
+        .contains("return inArray && fields != null ? wf$toTuple(fields, wf$slots$Tag) : fields;"));
+    assert!(js.contains("fields.d = wf$mapArray(fields.d, function (elem) { return wf$pack$Tag(elem, true, packAll); });"));
+    assert!(js.contains("function wf$unpackResponse$feed(value) {\n  var packAll = true;"));
+}
+
+#[test]
+fn formats_js() {
+    let js = code_gen::formats_js(Mode::Production);
+
+    assert!(js.contains("var wf$format = \"application/vnd.wirefunc.min+json\";"));
+    assert!(js.contains("var wf$accept = \"application/vnd.wirefunc.min+json\";"));
+    assert!(code_gen::formats_js(Mode::Development).contains("application/vnd.wirefunc.dev+json"));
+}
+
+#[test]
+fn packing_elm() {
+    let (schema, table) = packed_schema();
+    let elm = code_gen::packing_elm(&schema, &table).unwrap();
+
+    assert!(elm.starts_with("module WfPacking exposing (..)\n"));
+    assert!(elm.contains("slotsUser =\n    [ Just \"a\", Nothing, Nothing, Just \"d\" ]\n"));
+    assert!(elm.contains("packUser : Bool -> Bool -> Decoder Value\npackUser inArray packAll =\n    let\n        fields =\n            update [ ( \"d\", mapArray (Decode.lazy (\\_ -> packTag True packAll)) ) ]\n    in\n    if inArray && packAll then\n        Decode.andThen (convertWith (toTuple slotsUser)) fields\n"));
+    assert!(elm.contains(
+        "    if inArray then\n        Decode.andThen (convertWith fields) (fromTuple slotsTag)\n"
+    ));
+    assert!(elm.contains("unpackResponse_feed =\n    let\n        packAll =\n            True\n    in\n    update [ ( \"a\", mapArray (Decode.lazy (\\_ -> unpackUser True packAll)) ) ]\n"));
+}
+
+#[test]
+fn packing_js_requires_the_schemas_table() {
+    let schema = parse::parse("struct User [ packed ]\n    * userId : Int\n").unwrap();
+
+    match code_gen::packing_js(&schema, &code_gen::MinificationTable::default()) {
+        Err(Problem::MissingIds(names)) => assert_eq!(vec!["User.userId"], names),
+        other => panic!("Expected MissingIds, got {:?}", other),
+    }
+}
+
+#[test]
+fn wire_js() {
+    let (schema, table) = packed_schema();
+    let development = code_gen::wire_js(&schema, &table, Mode::Development, None).unwrap();
+    let production = code_gen::wire_js(&schema, &table, Mode::Production, None).unwrap();
+
+    assert!(development.contains("var wf$production = false;"));
+    assert!(production.contains("var wf$production = true;"));
+
+    // Both modes know both names, so each can tell when it's been sent the
+    // other mode's JSON.
+    for js in [&development, &production] {
+        assert!(js.contains("function wf$encode$User(value) {\n  return wf$recordToWire(value, \"User\", [[\"userId\", \"a\", null, true], [\"tags\", \"d\", function (value) { return wf$convertElems(value, wf$encode$Tag); }, true]]);\n}"));
+    }
+
+    assert!(development.contains("function wf$decodeResponse$feed(value) {\n  return wf$recordFromWire(value, null, [[\"users\", \"a\", function (value) { return wf$convertElems(value, wf$decode$User); }, true]]);\n}"));
+    assert!(production.contains("function wf$decodeResponse$feed(value) {\n  return wf$recordFromWire(wf$unpackResponse$feed(value), null, "));
+    assert!(production.contains("function wf$encodeResponse$feed(value) {\n  return wf$packResponse$feed(wf$recordToWire(value, null, "));
+}
+
+#[test]
+fn wire_elm() {
+    let (schema, table) = common::productionize(
+        "struct Page a\n    * items : Array a\n\ntype Reply\n    | Empty\n    | Text String\n    | Pages (Page Reply)\n",
+        History::new(),
+    );
+    let elm = code_gen::wire_elm(&schema, &table, Mode::Development, None).unwrap();
+
+    assert!(elm.starts_with("module WfWire exposing (..)\n"));
+    assert!(!elm.contains("import WfPacking"));
+    assert!(elm.contains("production : Bool\nproduction =\n    False\n"));
+    assert!(elm.contains("encodePage : Decoder Value -> Decoder Value\nencodePage param_a =\n    recordToWire (Just \"Page\") [ Field \"items\" \"a\" [] (convertElems param_a) True ]\n"));
+    assert!(elm.contains("decodeReply =\n    variantFromWire \"Reply\" [] [ Variant \"Empty\" 0 [] [], Variant \"Text\" 1 [] [ Decode.value ], Variant \"Pages\" 2 [] [ Decode.lazy (\\_ -> decodePage (Decode.lazy (\\_ -> decodeReply))) ] ]\n"));
+}
+
+#[test]
+fn wire_former_names() {
+    let mut history = History::new();
+    let mut schema = parse::parse("struct Profile\n    * name : String\n").unwrap();
+
+    allocator::allocate(&mut history, &mut schema);
+
+    let mut schema = parse::parse(
+        "struct Person [ fka \"Profile\" ]\n    * fullName : String [ fka \"name\" ]\n",
+    )
+    .unwrap();
+
+    allocator::allocate(&mut history, &mut schema);
+
+    let table = code_gen::productionize(&history, &schema).unwrap();
+    let js =
+        |mode, grace| code_gen::wire_js(&schema, &table, mode, Some((&history, grace))).unwrap();
+    let elm = code_gen::wire_elm(&schema, &table, Mode::Development, Some((&history, 3))).unwrap();
+
+    // Only decoders accept the former names, and only in development.
+    assert!(js(Mode::Development, 3).contains("function wf$decode$Person(value) {\n  return wf$recordFromWire(value, \"Person\", [[\"fullName\", \"a\", null, true, [\"name\"]]], [\"Profile\"]);\n}"));
+    assert!(js(Mode::Development, 3).contains("function wf$encode$Person(value) {\n  return wf$recordToWire(value, \"Person\", [[\"fullName\", \"a\", null, true]]);\n}"));
+    assert!(js(Mode::Development, 0).contains("function wf$decode$Person(value) {\n  return wf$recordFromWire(value, \"Person\", [[\"fullName\", \"a\", null, true]]);\n}"));
+    assert!(!js(Mode::Production, 3).contains("[\"name\"]"));
+    assert!(elm.contains("decodePerson =\n    recordFromWire (Just \"Person\") [ \"Profile\" ] [ Field \"fullName\" \"a\" [ \"name\" ] Decode.value True ]\n"));
+}
@@ -0,0 +1,487 @@
+extern crate wf;
+
+mod common;
+
+use wf::allocator;
+use wf::code_gen;
+use wf::code_gen::{MinificationTable, Mode};
+use wf::codec;
+use wf::codec::{Codec, Names, Problem, ProblemKind};
+use wf::history::History;
+use wf::json;
+use wf::json::Value;
+use wf::parse;
+use wf::types::Schema;
+
+const SCHEMA: &str = "
+post sendDM
+    params:
+        * user : User
+        ~ text : String [ default \"\" ]
+
+    response:
+        * replies : Array Reply
+
+struct User
+    * userId : Int
+    * profile : Profile
+    ~ tags : Array String [ default [] ]
+
+struct Profile
+    * name : String
+    ~ email : Email
+    ~ age : Int8
+
+struct Page a
+    * items : Array a
+    ~ counts : Dict Int Int
+
+type Reply
+    | Empty
+    | Text String
+    | Pair Int User
+
+alias Email = String
+";
+
+fn schema() -> (Schema, MinificationTable) {
+    common::productionize(SCHEMA, History::new())
+}
+
+fn value(source: &str) -> Value {
+    json::parse(source).unwrap()
+}
+
+fn decode(type_name: &str, minified: &str) -> Result<String, Problem> {
+    let (schema, table) = schema();
+
+    Codec::new(&schema, &table)
+        .decode(&codec::named(type_name), &value(minified))
+        .map(|value| value.to_string())
+}
+
+fn encode(type_name: &str, full: &str) -> Result<String, Problem> {
+    let (schema, table) = schema();
+
+    Codec::new(&schema, &table)
+        .encode(&codec::named(type_name), &value(full))
+        .map(|value| value.to_string())
+}
+
+fn development<'a>(schema: &'a Schema, table: &'a MinificationTable) -> Codec<'a> {
+    Codec::with_mode(schema, table, Mode::Development)
+}
+
+fn problem(path: &str, kind: ProblemKind) -> Problem {
+    Problem {
+        path: path.to_string(),
+        kind,
+    }
+}
+
+#[test]
+fn decode_record() {
+    assert_eq!(
+        decode("User", r#"{"a":1,"b":{"a":"Sam","b":"sam@example.com"}}"#),
+        Ok(String::from(
+            r#"{"userId":1,"profile":{"name":"Sam","email":"sam@example.com"},"tags":[]}"#
+        ))
+    );
+}
+
+#[test]
+fn encode_record() {
+    assert_eq!(
+        encode("User", r#"{"profile":{"name":"Sam","age":30},"userId":1}"#),
+        Ok(String::from(r#"{"a":1,"b":{"a":"Sam","c":30}}"#))
+    );
+}
+
+#[test]
+fn unknown_fields() {
+    // Someone running a newer schema may send fields we don't know about.
+    assert!(decode("Profile", r#"{"a":"Sam","z":1}"#).is_ok());
+
+    assert_eq!(
+        encode("Profile", r#"{"name":"Sam","emial":"sam@example.com"}"#),
+        Err(problem(
+            "",
+            ProblemKind::UnknownField(String::from("emial"))
+        ))
+    );
+}
+
+#[test]
+fn missing_required_fields() {
+    assert_eq!(
+        decode("User", r#"{"a":1,"b":{"b":"sam@example.com"}}"#),
+        Err(problem("profile.name", ProblemKind::Missing))
+    );
+    assert_eq!(
+        decode("User", r#"{"a":1,"b":null}"#),
+        Err(problem("profile", ProblemKind::Missing))
+    );
+}
+
+#[test]
+fn endpoints() {
+    let (schema, table) = schema();
+    let endpoint = schema.endpoint("sendDM").unwrap();
+    let codec = Codec::new(&schema, &table);
+
+    assert_eq!(
+        codec
+            .decode(
+                &codec::params(endpoint),
+                &value(r#"{"a":{"a":1,"b":{"a":"Sam"}}}"#)
+            )
+            .map(|value| value.to_string()),
+        Ok(String::from(
+            r#"{"user":{"userId":1,"profile":{"name":"Sam"},"tags":[]},"text":""}"#
+        ))
+    );
+    assert_eq!(
+        codec.decode(
+            &codec::params(endpoint),
+            &value(r#"{"a":{"a":1,"b":{"a":"Sam","b":5}}}"#)
+        ),
+        Err(problem(
+            "user.profile.email",
+            ProblemKind::Expected {
+                expected: String::from("a String"),
+                found: "an integer",
+            }
+        ))
+    );
+}
+
+#[test]
+fn custom_types() {
+    let (schema, table) = schema();
+    let endpoint = schema.endpoint("sendDM").unwrap();
+    let codec = Codec::new(&schema, &table);
+    let minified = value(r#"{"a":[[0],[1,"hi"],[2,7,{"a":1,"b":{"a":"Sam"}}]]}"#);
+    let full = codec.decode(&codec::response(endpoint), &minified).unwrap();
+
+    assert_eq!(
+        full.to_string(),
+        r#"{"replies":[{"variant":"Empty"},{"variant":"Text","contents":["hi"]},{"variant":"Pair","contents":[7,{"userId":1,"profile":{"name":"Sam"},"tags":[]}]}]}"#
+    );
+    assert_eq!(
+        codec
+            .encode(&codec::response(endpoint), &full)
+            .unwrap()
+            .to_string(),
+        r#"{"a":[[0],[1,"hi"],[2,7,{"a":1,"b":{"a":"Sam"},"c":[]}]]}"#
+    );
+    assert_eq!(
+        codec.decode(
+            &codec::response(endpoint),
+            &value(r#"{"a":[[1,"hi"],[9]]}"#)
+        ),
+        Err(problem(
+            "replies[1]",
+            ProblemKind::UnknownVariant(String::from("9"))
+        ))
+    );
+    assert_eq!(
+        codec
+            .decode(
+                &codec::response(endpoint),
+                &value(r#"{"a":[[2,7,{"a":1}]]}"#)
+            )
+            .unwrap_err()
+            .to_string(),
+        "replies[0].Pair[1].profile: this required field is missing"
+    );
+}
+
+#[test]
+fn type_params_and_dicts() {
+    let (schema, table) = schema();
+    let codec = Codec::new(&schema, &table);
+    let tipe = parse::parse("struct Wrapper\n    * page : Page Profile\n")
+        .unwrap()
+        .records[0]
+        .fields[0]
+        .tipe
+        .clone();
+
+    assert_eq!(
+        codec
+            .decode(&tipe, &value(r#"{"a":[{"a":"Sam"}],"b":[[1,2]]}"#))
+            .map(|value| value.to_string()),
+        Ok(String::from(
+            r#"{"items":[{"name":"Sam"}],"counts":[[1,2]]}"#
+        ))
+    );
+    assert_eq!(
+        codec
+            .decode(&tipe, &value(r#"{"a":[{"a":"Sam"}],"b":{"1":2}}"#))
+            .unwrap_err()
+            .path,
+        "counts"
+    );
+}
+
+#[test]
+fn integer_ranges() {
+    assert_eq!(
+        decode("Profile", r#"{"a":"Sam","c":300}"#),
+        Err(problem(
+            "age",
+            ProblemKind::Expected {
+                expected: String::from("an Int8"),
+                found: "an integer",
+            }
+        ))
+    );
+}
+
+#[test]
+fn former_types() {
+    let mut schema =
+        parse::parse("struct Score\n    * value : Float64 [ formerly String ]\n").unwrap();
+    let mut history = History::new();
+
+    allocator::allocate(&mut history, &mut schema);
+
+    let table = code_gen::productionize(&history, &schema).unwrap();
+    let codec = Codec::new(&schema, &table);
+    let tipe = codec::named("Score");
+
+    assert!(codec.decode(&tipe, &value(r#"{"a":1.5}"#)).is_ok());
+    assert!(codec.decode(&tipe, &value(r#"{"a":"1.5"}"#)).is_ok());
+    assert!(codec.decode(&tipe, &value(r#"{"a":true}"#)).is_err());
+}
+
+#[test]
+fn full_to_full() {
+    let (schema, table) = schema();
+
+    assert_eq!(
+        Codec::new(&schema, &table)
+            .convert(
+                &codec::named("User"),
+                &value(r#"{"userId":1,"profile":{"name":"Sam"}}"#),
+                Names::Full,
+                Names::Full
+            )
+            .map(|value| value.to_string()),
+        Ok(String::from(
+            r#"{"userId":1,"profile":{"name":"Sam"},"tags":[]}"#
+        ))
+    );
+}
+
+const PACKED_SCHEMA: &str = "
+get feed [ packed ]
+    response:
+        * users : Array User
+
+get tags
+    response:
+        * tags : Array Tag
+        * byName : Dict String Tag
+
+struct User
+    * userId : Int
+    ~ email : String
+    ~ tags : Array Tag
+
+struct Tag [ packed ]
+    * label : String
+    ~ color : String
+";
+
+fn packed_schema() -> (Schema, MinificationTable) {
+    let mut schema = parse::parse(PACKED_SCHEMA).unwrap();
+    let mut history = History::new();
+
+    allocator::allocate(&mut history, &mut schema);
+
+    let table = code_gen::productionize(&history, &schema).unwrap();
+
+    (schema, table)
+}
+
+fn encode_packed(type_name: &str, full: &str) -> Result<String, Problem> {
+    let (schema, table) = packed_schema();
+
+    Codec::new(&schema, &table)
+        .encode(&codec::named(type_name), &value(full))
+        .map(|value| value.to_string())
+}
+
+#[test]
+fn packed_records() {
+    let (schema, table) = packed_schema();
+    let codec = Codec::new(&schema, &table);
+    let tags = codec::response(schema.endpoint("tags").unwrap());
+    let full = value(
+        r#"{"tags":[{"label":"a"},{"label":"b","color":"red"}],"byName":{"a":{"label":"a"}}}"#,
+    );
+    let packed = codec.encode(&tags, &full).unwrap();
+
+    // Only records in arrays are packed, and trailing nulls are left off.
+    assert_eq!(
+        packed.to_string(),
+        r#"{"a":[["a"],["b","red"]],"b":{"a":{"a":"a"}}}"#
+    );
+    assert_eq!(codec.decode(&tags, &packed), Ok(full.clone()));
+
+    // Production expects tuples, so an object is an error.
+    assert_eq!(
+        codec
+            .decode(&tags, &value(r#"{"a":[{"a":"a"}],"b":{}}"#))
+            .unwrap_err()
+            .path,
+        "tags[0]"
+    );
+
+    // Minified names alone leave every record as an object.
+    assert_eq!(
+        codec
+            .convert(&tags, &full, Names::Full, Names::Minified)
+            .unwrap()
+            .to_string(),
+        r#"{"a":[{"a":"a"},{"a":"b","b":"red"}],"b":{"a":{"a":"a"}}}"#
+    );
+}
+
+#[test]
+fn packed_endpoints() {
+    let (schema, table) = packed_schema();
+    let codec = Codec::new(&schema, &table);
+    let feed = codec::response(schema.endpoint("feed").unwrap());
+    let packed = value(r#"{"a":[[1,null,[["x"]]],[2,"sam@example.com"]]}"#);
+
+    assert_eq!(
+        codec.decode(&feed, &packed).map(|value| value.to_string()),
+        Ok(String::from(
+            r#"{"users":[{"userId":1,"tags":[{"label":"x"}]},{"userId":2,"email":"sam@example.com"}]}"#
+        ))
+    );
+    assert_eq!(
+        codec.encode(&feed, &codec.decode(&feed, &packed).unwrap()),
+        Ok(packed)
+    );
+    assert_eq!(
+        codec.decode(&feed, &value(r#"{"a":[[null,"sam@example.com"]]}"#)),
+        Err(problem("users[0].userId", ProblemKind::Missing))
+    );
+
+    // A User outside of the packed endpoint is still an object.
+    assert_eq!(
+        encode_packed("User", r#"{"userId":1,"tags":[{"label":"x"}]}"#),
+        Ok(String::from(r#"{"a":1,"c":[["x"]]}"#))
+    );
+}
+
+#[test]
+fn development_format() {
+    let (schema, table) = schema();
+    let endpoint = schema.endpoint("sendDM").unwrap();
+    let full = value(
+        r#"{"replies":[{"variant":"Text","contents":["hi"]},{"variant":"Pair","contents":[7,{"userId":1,"profile":{"name":"Sam"}}]}]}"#,
+    );
+    let wire = development(&schema, &table)
+        .encode(&codec::response(endpoint), &full)
+        .unwrap();
+
+    assert_eq!(
+        wire.to_string(),
+        r#"{"replies":[{"$type":"Reply","variant":"Text","contents":["hi"]},{"$type":"Reply","variant":"Pair","contents":[7,{"$type":"User","userId":1,"profile":{"$type":"Profile","name":"Sam"}}]}]}"#
+    );
+    assert_eq!(
+        development(&schema, &table)
+            .decode(&codec::response(endpoint), &wire)
+            .map(|value| value.to_string()),
+        Ok(String::from(
+            r#"{"replies":[{"variant":"Text","contents":["hi"]},{"variant":"Pair","contents":[7,{"userId":1,"profile":{"name":"Sam"},"tags":[]}]}]}"#
+        ))
+    );
+}
+
+#[test]
+fn mixed_formats() {
+    let (schema, table) = schema();
+    let production = Codec::new(&schema, &table);
+    let user = codec::named("User");
+    let mixed = |expected| ProblemKind::MixedFormats { expected };
+
+    assert_eq!(
+        development(&schema, &table)
+            .decode(&user, &value(r#"{"a":1,"b":{"a":"Sam"}}"#))
+            .map_err(|problem| problem.kind),
+        Err(mixed(Mode::Development))
+    );
+    assert_eq!(
+        production
+            .decode(
+                &user,
+                &value(r#"{"$type":"User","userId":1,"profile":{"$type":"Profile","name":"Sam"}}"#)
+            )
+            .map_err(|problem| problem.kind),
+        Err(mixed(Mode::Production))
+    );
+    assert_eq!(
+        production
+            .decode(&codec::named("Reply"), &value(r#"{"variant":"Empty"}"#))
+            .map_err(|problem| problem.kind),
+        Err(mixed(Mode::Production))
+    );
+
+    // Endpoint bodies have no annotation, but their fields give them away.
+    assert_eq!(
+        production
+            .decode(
+                &codec::params(schema.endpoint("sendDM").unwrap()),
+                &value(r#"{"user":{}}"#)
+            )
+            .unwrap_err(),
+        problem("user", mixed(Mode::Production))
+    );
+}
+
+#[test]
+fn former_names_in_development() {
+    let mut history = History::new();
+    let mut schema = parse::parse("struct Profile\n    * name : String\n").unwrap();
+
+    allocator::allocate(&mut history, &mut schema);
+
+    let mut schema =
+        parse::parse("struct Profile\n    * fullName : String [ fka \"name\" ]\n").unwrap();
+
+    allocator::allocate(&mut history, &mut schema);
+
+    let table = code_gen::productionize(&history, &schema).unwrap();
+    let old = value(r#"{"$type":"Profile","name":"Sam"}"#);
+    let tipe = codec::named("Profile");
+
+    assert_eq!(
+        development(&schema, &table)
+            .accepting_former_names(&history, 3)
+            .decode(&tipe, &old)
+            .map(|value| value.to_string()),
+        Ok(String::from(r#"{"fullName":"Sam"}"#))
+    );
+    assert_eq!(
+        development(&schema, &table)
+            .accepting_former_names(&history, 0)
+            .decode(&tipe, &old),
+        Err(problem("fullName", ProblemKind::Missing))
+    );
+}
+
+#[test]
+fn minified_names_come_from_the_table() {
+    let (schema, _) = schema();
+    let table = MinificationTable::default();
+
+    assert_eq!(
+        Codec::new(&schema, &table).decode(&codec::named("User"), &value(r#"{"a":1}"#)),
+        Err(problem("userId", ProblemKind::NotMinified))
+    );
+}
@@ -0,0 +1,317 @@
+extern crate wf;
+
+use wf::compat;
+use wf::compat::{Change, ChangeKind, Impact, Incompatibility, Side};
+use wf::parse;
+use wf::types::{Optionality, TypePrimitive};
+
+fn compare(old: &str, new: &str) -> Vec<Change> {
+    compat::compare(&parse::parse(old).unwrap(), &parse::parse(new).unwrap())
+}
+
+fn impacts(changes: &[Change]) -> Vec<(&str, Impact)> {
+    changes
+        .iter()
+        .map(|change| (change.path.as_str(), change.impact))
+        .collect()
+}
+
+const USER: &str = "struct User\n    * userId : Int\n    ~ email : String\n";
+
+#[test]
+fn identical_schemas_have_no_changes() {
+    assert_eq!(Vec::<Change>::new(), compare(USER, USER));
+}
+
+#[test]
+fn reordering_fields_is_not_a_change() {
+    assert!(compare(
+        USER,
+        "struct User\n    ~ email : String\n    * userId : Int\n"
+    )
+    .is_empty());
+}
+
+#[test]
+fn added_fields() {
+    let changes = compare(
+        USER,
+        "struct User\n    * userId : Int\n    ~ email : String\n    * name : String\n    ~ bio : String\n",
+    );
+
+    assert_eq!(
+        vec![
+            ("User.name", Impact::BreaksOldSenders),
+            ("User.bio", Impact::Safe),
+        ],
+        impacts(&changes)
+    );
+    assert_eq!(
+        ChangeKind::FieldAdded(Optionality::Required),
+        changes[0].kind
+    );
+}
+
+#[test]
+fn removed_fields() {
+    let changes = compare(USER, "struct User\n");
+
+    assert_eq!(
+        vec![
+            ("User.userId", Impact::BreaksOldReceivers),
+            ("User.email", Impact::Safe),
+        ],
+        impacts(&changes)
+    );
+}
+
+#[test]
+fn optionality_changes() {
+    let changes = compare(
+        USER,
+        "struct User\n    ~ userId : Int\n    * email : String\n",
+    );
+
+    assert_eq!(
+        vec![
+            ("User.userId", Impact::BreaksOldReceivers),
+            ("User.email", Impact::BreaksOldSenders),
+        ],
+        impacts(&changes)
+    );
+}
+
+#[test]
+fn undeclared_type_change_breaks_everyone() {
+    let changes = compare(
+        USER,
+        "struct User\n    * userId : Int\n    ~ email : Bool\n",
+    );
+
+    assert_eq!(vec![("User.email", Impact::BreaksBoth)], impacts(&changes));
+    assert_eq!(
+        ChangeKind::TypeChanged {
+            from: Box::new(TypePrimitive::String),
+            to: Box::new(TypePrimitive::Bool),
+        },
+        changes[0].kind
+    );
+}
+
+#[test]
+fn declared_widening_only_breaks_old_receivers() {
+    let changes = compare(
+        "struct Score\n    * value : Float32\n",
+        "struct Score\n    * value : Float64 [ formerly Float32 ]\n",
+    );
+
+    assert_eq!(
+        vec![("Score.value", Impact::BreaksOldReceivers)],
+        impacts(&changes)
+    );
+}
+
+#[test]
+fn changes_inside_inline_records() {
+    let changes = compare(
+        "get feed\n    response:\n        * user : { name : String }\n        * posts : Array { text : String }\n        * byTag : Dict String { count : Int }\n",
+        "get feed\n    response:\n        * user : { name : String, bio : String }\n        * posts : Array { text : String, likes : Int }\n        * byTag : Dict String { count : String }\n",
+    );
+
+    assert_eq!(
+        vec![
+            ("feed.response.user.bio", Impact::BreaksOldSenders),
+            ("feed.response.posts.likes", Impact::BreaksOldSenders),
+            ("feed.response.byTag.count", Impact::BreaksBoth),
+        ],
+        impacts(&changes)
+    );
+    assert!(changes
+        .iter()
+        .all(|change| change.flow == compat::Flow::ServerToClient));
+
+    // Other changes to the element type are still a change to the whole type.
+    let changes = compare(
+        "struct User\n    * tags : Array { label : String }\n",
+        "struct User\n    * tags : Set { label : String }\n",
+    );
+
+    assert_eq!(vec![("User.tags", Impact::BreaksBoth)], impacts(&changes));
+}
+
+#[test]
+fn aliases_are_expanded_before_comparing() {
+    let changes = compare(
+        "struct User\n    * email : String\n",
+        "struct User\n    * email : Email\n\nalias Email = String\n",
+    );
+
+    assert!(changes.is_empty());
+}
+
+#[test]
+fn variants() {
+    let changes = compare(
+        "type Reply\n    | Email String\n    | Text String\n",
+        "type Reply\n    | Email Int\n    | Pigeon\n",
+    );
+
+    assert_eq!(
+        vec![
+            ("Reply.Email", Impact::BreaksBoth),
+            ("Reply.Text", Impact::BreaksOldSenders),
+            ("Reply.Pigeon", Impact::BreaksOldReceivers),
+        ],
+        impacts(&changes)
+    );
+}
+
+#[test]
+fn endpoints() {
+    let changes = compare(
+        "post sendDM\n    params:\n        * userId : Int\n\npost sendReply\n    params:\n        * userId : Int\n",
+        "post sendDM\n    params:\n        * userId : Int\n    response:\n        * ok : Bool\n\nget fetchReplies\n    params:\n        * userId : Int\n",
+    );
+
+    assert_eq!(
+        vec![
+            ("sendDM.response.ok", Impact::BreaksOldSenders),
+            ("sendReply", Impact::BreaksOldSenders),
+            ("fetchReplies", Impact::BreaksOldReceivers),
+        ],
+        impacts(&changes)
+    );
+}
+
+#[test]
+fn acknowledged_changes() {
+    let changes = compare(USER, "struct User\n    ~ email : String\n");
+    let acknowledged = vec![String::from("User.userId")];
+
+    assert!(compat::is_acknowledged(&changes[0], &acknowledged));
+    assert!(!compat::is_acknowledged(&changes[0], &[]));
+}
+
+#[test]
+fn renames_are_safe() {
+    let changes = compare(
+        "struct Person\n    * id : Int\n\ntype Reply\n    | Mail String\n",
+        "struct User [ fka \"Person\" ]\n    * userId : Int [ fka \"id\" ]\n\ntype Reply\n    | Email String [ fka \"Mail\" ]\n",
+    );
+
+    assert_eq!(
+        vec![
+            ("User", Impact::Safe),
+            ("User.userId", Impact::Safe),
+            ("Reply.Email", Impact::Safe),
+        ],
+        impacts(&changes)
+    );
+    assert_eq!(
+        ChangeKind::Renamed(String::from("User.id")),
+        changes[1].kind
+    );
+}
+
+#[test]
+fn renamed_fields_are_still_compared() {
+    let changes = compare(
+        USER,
+        "struct User\n    * id : String [ fka \"userId\" ]\n    ~ email : String\n",
+    );
+
+    assert_eq!(
+        vec![("User.id", Impact::Safe), ("User.id", Impact::BreaksBoth),],
+        impacts(&changes)
+    );
+}
+
+#[test]
+fn fka_is_ignored_while_the_former_name_is_still_in_use() {
+    let changes = compare(
+        USER,
+        "struct User\n    * userId : Int\n    ~ email : String\n    ~ mail : String [ fka \"email\" ]\n",
+    );
+
+    assert_eq!(vec![("User.mail", Impact::Safe)], impacts(&changes));
+    assert_eq!(
+        ChangeKind::FieldAdded(Optionality::Optional),
+        changes[0].kind
+    );
+}
+
+fn paths(changes: &[Change]) -> Vec<&str> {
+    changes.iter().map(|change| change.path.as_str()).collect()
+}
+
+#[test]
+fn params_and_responses_break_in_opposite_directions() {
+    let changes = compare(
+        "post sendDM\n    params:\n        * userId : Int\n    response:\n        * ok : Bool\n",
+        "post sendDM\n    params:\n        * userId : Int\n        * body : String\n    response:\n        * ok : Bool\n        * sent : Bool\n",
+    );
+
+    // Old clients don't send the new required param, but old servers don't
+    // care about it.
+    assert!(changes[0].breaks(Side::Client));
+    assert!(!changes[0].breaks(Side::Server));
+
+    // Old servers don't send the new required response field, but old clients
+    // don't care about it.
+    assert!(!changes[1].breaks(Side::Client));
+    assert!(changes[1].breaks(Side::Server));
+}
+
+#[test]
+fn new_endpoints_break_old_servers() {
+    let changes = compare(
+        "",
+        "get fetchReplies\n    params:\n        * userId : Int\n",
+    );
+
+    assert_eq!(Impact::BreaksOldReceivers, changes[0].impact);
+    assert!(!changes[0].breaks(Side::Client));
+    assert!(changes[0].breaks(Side::Server));
+}
+
+#[test]
+fn check_window_finds_every_incompatible_pair() {
+    let released = vec![
+        (
+            1,
+            parse::parse("struct User\n    * userId : Int\n    * email : String\n").unwrap(),
+        ),
+        (
+            2,
+            parse::parse("struct User\n    * userId : Int\n    ~ email : String\n").unwrap(),
+        ),
+        (
+            3,
+            parse::parse("struct User\n    * userId : Int\n").unwrap(),
+        ),
+    ];
+    let current = parse::parse("struct User\n    * userId : Int\n").unwrap();
+    let incompatibilities = compat::check_window(&released, &current, &[]);
+
+    // Only version 1 had email as a required field, which the current schema
+    // no longer sends.
+    assert_eq!(
+        vec![(Side::Client, 1), (Side::Server, 1)],
+        incompatibilities
+            .iter()
+            .map(|incompatibility| (incompatibility.old_side, incompatibility.old_version))
+            .collect::<Vec<(Side, u32)>>()
+    );
+    assert_eq!(vec!["User.email"], paths(&incompatibilities[0].changes));
+}
+
+#[test]
+fn check_window_ignores_acknowledged_changes() {
+    let released = vec![(1, parse::parse(USER).unwrap())];
+    let current = parse::parse("struct User\n    ~ email : String\n").unwrap();
+
+    assert_eq!(
+        Vec::<Incompatibility>::new(),
+        compat::check_window(&released, &current, &[String::from("User.userId")])
+    );
+}
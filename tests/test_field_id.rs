@@ -31,7 +31,7 @@ fn assert_invalid_char(num: u32) {
         Some(ch) => {
             assert_eq!(None, wf::field_id::from_string(ch.to_string().as_str()));
         }
-        None => panic!(format!("Somehow, {} is an invalid char!", num)),
+        None => panic!("Somehow, {} is an invalid char!", num),
     }
 }
 
@@ -0,0 +1,140 @@
+extern crate tempfile;
+extern crate wf;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+use wf::git;
+
+fn git(directory: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .current_dir(directory)
+        .args(["-c", "user.name=wf", "-c", "user.email=wf@example.com"])
+        .args(args)
+        .output()
+        .unwrap()
+        .status;
+
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+fn commit(directory: &Path, files: &[(&str, &str)], message: &str) {
+    for (path, contents) in files.iter() {
+        let path = directory.join(path);
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    git(directory, &["add", "-A"]);
+    git(directory, &["commit", "-q", "-m", message]);
+}
+
+fn repository() -> TempDir {
+    let directory = TempDir::new().unwrap();
+
+    git(directory.path(), &["init", "-q"]);
+    commit(
+        directory.path(),
+        &[
+            ("schema/user.wf", "record User\n    email : String\n"),
+            ("schema/notes.txt", "not a schema"),
+            ("README", "hello"),
+        ],
+        "First",
+    );
+    git(directory.path(), &["tag", "v1"]);
+    commit(
+        directory.path(),
+        &[
+            (
+                "schema/user.wf",
+                "record User\n    email : String\n    name : String\n",
+            ),
+            ("schema/post.wf", "record Post\n    body : String\n"),
+        ],
+        "Second",
+    );
+
+    directory
+}
+
+#[test]
+fn read_sources_at_head() {
+    let directory = repository();
+    let sources = git::read_sources(directory.path(), "HEAD", &[PathBuf::from(".")]).unwrap();
+    let paths: Vec<&Path> = sources.iter().map(|(path, _)| path.as_path()).collect();
+
+    assert_eq!(
+        paths,
+        vec![Path::new("schema/post.wf"), Path::new("schema/user.wf")]
+    );
+    assert!(sources[1].1.contains("name : String"));
+}
+
+#[test]
+fn read_sources_at_tag() {
+    let directory = repository();
+    let sources = git::read_sources(directory.path(), "v1", &[PathBuf::from("schema")]).unwrap();
+
+    assert_eq!(
+        sources,
+        vec![(
+            PathBuf::from("schema/user.wf"),
+            String::from("record User\n    email : String\n")
+        )]
+    );
+}
+
+#[test]
+fn read_sources_relative_to_subdirectory() {
+    let directory = repository();
+    let sources = git::read_sources(
+        &directory.path().join("schema"),
+        "v1",
+        &[PathBuf::from(".")],
+    )
+    .unwrap();
+
+    assert_eq!(sources.len(), 1);
+    assert_eq!(sources[0].0, PathBuf::from("user.wf"));
+}
+
+#[test]
+fn read_explicitly_listed_file() {
+    let directory = repository();
+    let sources = git::read_sources(directory.path(), "HEAD", &[PathBuf::from("README")]).unwrap();
+
+    assert_eq!(
+        sources,
+        vec![(PathBuf::from("README"), String::from("hello"))]
+    );
+}
+
+#[test]
+fn read_file_missing_at_revision() {
+    let directory = repository();
+
+    assert_eq!(
+        git::read_file(directory.path(), "v1", Path::new("schema/post.wf")).unwrap(),
+        None
+    );
+    assert_eq!(
+        git::read_file(directory.path(), "HEAD", Path::new("schema/post.wf")).unwrap(),
+        Some(String::from("record Post\n    body : String\n"))
+    );
+}
+
+#[test]
+fn invalid_revision() {
+    let directory = repository();
+
+    match git::read_sources(directory.path(), "no-such-tag", &[PathBuf::from(".")]) {
+        Err(git::Problem::Failed { args, stderr }) => {
+            assert!(args.contains(&String::from("no-such-tag")));
+            assert!(!stderr.is_empty());
+        }
+        other => panic!("Expected a failure, got {:?}", other),
+    }
+}
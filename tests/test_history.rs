@@ -0,0 +1,414 @@
+extern crate tempfile;
+extern crate wf;
+
+use tempfile::TempDir;
+use wf::history;
+use wf::history::{Entry, FormerName, History, Ledger, Problem};
+use wf::types::{
+    CustomTypeInfo, Field, Optionality, RecordInfo, Schema, Type, TypeName, TypePrimitive, Variant,
+};
+
+const SOURCE: &str = "wirefunc history 1
+version 3
+
+record User
+    a userId since 1
+    b profile since 1 retyped 2
+    c aliases since 2
+    d id since 1 retired 3
+
+type Result
+    a ok since 1
+    b err since 1
+";
+
+fn entry(name: &str, id: u64, since: u32) -> Entry {
+    Entry {
+        name: name.to_string(),
+        id,
+        since,
+        former_names: vec![],
+        retyped: vec![],
+        retired: None,
+    }
+}
+
+fn field(name: &str) -> Field {
+    Field {
+        optionality: Optionality::Required,
+        name: name.to_string(),
+        tipe: Type::new(TypePrimitive::String),
+        field_id: 0,
+        fallback: false,
+        former_names: vec![],
+        deprecated: false,
+        default: None,
+        header: None,
+    }
+}
+
+fn variant(name: &str) -> Variant {
+    Variant {
+        optionality: Optionality::Required,
+        name: name.to_string(),
+        tipe: Type::new(TypePrimitive::String),
+        variant_id: 0,
+        former_names: vec![],
+        contents: vec![],
+    }
+}
+
+fn schema() -> Schema {
+    Schema {
+        records: vec![RecordInfo {
+            name: Some(TypeName::TypeName(String::from("User"))),
+            former_names: vec![],
+            // Deliberately in a different order than the history.
+            fields: vec![field("aliases"), field("email"), field("userId")],
+            params: vec![],
+            packed: false,
+            location: None,
+        }],
+        custom_types: vec![CustomTypeInfo {
+            name: TypeName::TypeName(String::from("Result")),
+            former_names: vec![],
+            variants: vec![variant("err"), variant("ok")],
+            params: vec![],
+        }],
+        ..Schema::default()
+    }
+}
+
+#[test]
+fn parse_source() {
+    let history = history::parse(SOURCE).unwrap();
+
+    assert_eq!(3, history.version);
+    assert_eq!(
+        vec![Ledger {
+            name: String::from("User"),
+            former_names: vec![],
+            entries: vec![
+                entry("userId", 0, 1),
+                Entry {
+                    retyped: vec![2],
+                    ..entry("profile", 1, 1)
+                },
+                entry("aliases", 2, 2),
+                Entry {
+                    retired: Some(3),
+                    ..entry("id", 3, 1)
+                },
+            ],
+        }],
+        history.records
+    );
+    assert_eq!(
+        Some(&entry("err", 1, 1)),
+        history.custom_type("Result").unwrap().entry("err")
+    );
+}
+
+#[test]
+fn to_string_and_parse_are_reflexive() {
+    let history = history::parse(SOURCE).unwrap();
+
+    assert_eq!(
+        history,
+        history::parse(history::to_string(&history).as_str()).unwrap()
+    );
+}
+
+#[test]
+fn to_string_sorts_sections_and_entries() {
+    let history = History {
+        version: 2,
+        records: vec![
+            Ledger {
+                name: String::from("Profile"),
+                former_names: vec![],
+                entries: vec![entry("email", 27, 2), entry("name", 0, 1)],
+            },
+            Ledger {
+                name: String::from("Comment"),
+                former_names: vec![],
+                entries: vec![entry("body", 0, 1)],
+            },
+        ],
+        custom_types: vec![],
+        snapshots: vec![],
+    };
+
+    assert_eq!(
+        "# This file is generated by wf. Commit it to version control!
+wirefunc history 1
+version 2
+
+record Comment
+    a body since 1
+
+record Profile
+    a name since 1
+    ab email since 2
+",
+        history::to_string(&history)
+    );
+}
+
+#[test]
+fn pin_ids_uses_history_regardless_of_order() {
+    let history = history::parse(SOURCE).unwrap();
+    let mut schema = schema();
+    let unpinned = history.pin_ids(&mut schema);
+
+    assert_eq!(vec![String::from("User.email")], unpinned);
+
+    let ids: Vec<(&str, u64)> = schema.records[0]
+        .fields
+        .iter()
+        .map(|field| (field.name.as_str(), field.field_id))
+        .collect();
+
+    assert_eq!(vec![("aliases", 2), ("email", 0), ("userId", 0)], ids);
+    assert_eq!(1, schema.custom_types[0].variants[0].variant_id);
+}
+
+#[test]
+fn missing_from() {
+    let older = history::parse(SOURCE).unwrap();
+
+    // Renames and retirements carry on from the older history.
+    let newer = history::parse(
+        "wirefunc history 1
+version 4
+
+record Person fka User 4
+    a personId since 1 fka userId 4
+    b profile since 1 retyped 2
+    c aliases since 2 retired 4
+    d id since 1 retired 3
+    e email since 4
+
+type Result
+    a ok since 1
+    b err since 1
+",
+    )
+    .unwrap();
+
+    assert!(older.missing_from(&newer).is_empty());
+
+    // A history from another branch, which gave the same ID to something else.
+    let diverged = history::parse(
+        "wirefunc history 1
+version 4
+
+record User
+    a userId since 1
+    b profile since 1 retyped 2
+    c email since 4
+    d id since 1 retired 3
+
+type Result
+    a ok since 1
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec![String::from("User.aliases"), String::from("Result.err")],
+        older.missing_from(&diverged)
+    );
+}
+
+#[test]
+fn write_to_and_read_from_are_reflexive() {
+    let path = TempDir::new().unwrap().keep().join("schema.wfhistory");
+    let history = history::parse(SOURCE).unwrap();
+
+    history::write_to(&path, &history).unwrap();
+
+    assert_eq!(history, history::read_from(&path).unwrap());
+}
+
+#[test]
+fn missing_header() {
+    match history::parse("version 1\n") {
+        Err(Problem::MissingHeader) => (),
+        other => panic!("Expected MissingHeader, got {:?}", other),
+    }
+}
+
+#[test]
+fn unsupported_format() {
+    match history::parse("wirefunc history 99\n") {
+        Err(Problem::UnsupportedFormat(header)) => assert_eq!("wirefunc history 99", header),
+        other => panic!("Expected UnsupportedFormat, got {:?}", other),
+    }
+}
+
+#[test]
+fn invalid_id() {
+    match history::parse("wirefunc history 1\nrecord User\n    A userId since 1\n") {
+        Err(Problem::InvalidId { line_number, id }) => {
+            assert_eq!(3, line_number);
+            assert_eq!("A", id);
+        }
+        other => panic!("Expected InvalidId, got {:?}", other),
+    }
+}
+
+#[test]
+fn duplicate_id() {
+    match history::parse(
+        "wirefunc history 1\nrecord User\n    a userId since 1\n    a email since 2\n",
+    ) {
+        Err(Problem::DuplicateEntry { line_number, name }) => {
+            assert_eq!(4, line_number);
+            assert_eq!("email", name);
+        }
+        other => panic!("Expected DuplicateEntry, got {:?}", other),
+    }
+}
+
+#[test]
+fn entry_outside_section() {
+    match history::parse("wirefunc history 1\n    a userId since 1\n") {
+        Err(Problem::InvalidLine { line_number, .. }) => assert_eq!(2, line_number),
+        other => panic!("Expected InvalidLine, got {:?}", other),
+    }
+}
+
+#[test]
+fn retired_entry_may_share_a_name_with_an_active_one() {
+    let history = history::parse(
+        "wirefunc history 1\nrecord User\n    a email since 1 retired 2\n    b email since 2\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        Some(&entry("email", 1, 2)),
+        history.record("User").unwrap().entry("email")
+    );
+}
+
+#[test]
+fn next_id_skips_retired_ids() {
+    let history = history::parse(SOURCE).unwrap();
+
+    assert_eq!(4, history.record("User").unwrap().next_id());
+    assert_eq!(0, Ledger::new("Empty").next_id());
+}
+
+#[test]
+fn snapshot_round_trip() {
+    let mut history = history::parse(SOURCE).unwrap();
+    let source = "struct User\n    * userId : Int\n\n    * email : String\n";
+
+    assert_eq!(3, history.record_snapshot(source.to_string(), 5));
+
+    let printed = history::to_string(&history);
+
+    assert!(printed.ends_with(
+        "\nsnapshot 3\n    struct User\n        * userId : Int\n\n        * email : String\n"
+    ));
+    assert_eq!(history, history::parse(printed.as_str()).unwrap());
+}
+
+#[test]
+fn record_snapshot_only_bumps_the_version_when_the_source_changes() {
+    let mut history = History::new();
+
+    assert_eq!(1, history.record_snapshot(String::from("struct A\n"), 5));
+    assert_eq!(1, history.record_snapshot(String::from("struct A\n"), 5));
+    assert_eq!(2, history.record_snapshot(String::from("struct B\n"), 5));
+}
+
+#[test]
+fn renames_round_trip() {
+    let source = "wirefunc history 1
+version 4
+
+record Member fka Person 2 fka User 4
+    a userId since 1 fka id 3
+    b email since 1 fka mail 2 fka eMail 3 retired 4
+";
+    let history = history::parse(source).unwrap();
+    let ledger = history.record("Member").unwrap();
+
+    assert_eq!(
+        vec![
+            FormerName {
+                name: String::from("Person"),
+                renamed: 2,
+            },
+            FormerName {
+                name: String::from("User"),
+                renamed: 4,
+            },
+        ],
+        ledger.former_names
+    );
+    assert_eq!(Some(4), ledger.entries[1].retired);
+    assert_eq!(2, ledger.entries[1].former_names.len());
+    assert_eq!(
+        history,
+        history::parse(history::to_string(&history).as_str()).unwrap()
+    );
+}
+
+#[test]
+fn former_names_are_accepted_during_the_grace_period() {
+    let history = history::parse(
+        "wirefunc history 1\nrecord User\n    a userId since 1 fka uid 2 fka id 3\n",
+    )
+    .unwrap();
+    let entry = history.record("User").unwrap().entry("userId").unwrap();
+
+    assert_eq!(vec!["userId", "id", "uid"], entry.accepted_names(3, 2));
+    assert_eq!(vec!["userId", "id"], entry.accepted_names(4, 2));
+    assert_eq!(vec!["userId"], entry.accepted_names(5, 2));
+    assert_eq!(vec!["userId"], entry.accepted_names(3, 0));
+}
+
+#[test]
+fn former_names_are_reserved() {
+    let history =
+        history::parse("wirefunc history 1\nrecord User\n    a userId since 1 fka id 2\n").unwrap();
+
+    assert!(history
+        .record("User")
+        .unwrap()
+        .retired_names()
+        .contains("id"));
+}
+
+#[test]
+fn invalid_rename() {
+    match history::parse("wirefunc history 1\nrecord User\n    a userId since 1 fka id\n") {
+        Err(Problem::InvalidLine { line_number, .. }) => assert_eq!(3, line_number),
+        other => panic!("Expected InvalidLine, got {:?}", other),
+    }
+}
+
+#[test]
+fn only_the_most_recent_snapshots_are_kept() {
+    let mut history = History::new();
+
+    for name in ["A", "B", "C", "D"].iter() {
+        history.record_snapshot(format!("struct {}\n", name), 3);
+    }
+
+    let versions: Vec<u32> = history
+        .snapshots
+        .iter()
+        .map(|snapshot| snapshot.version)
+        .collect();
+
+    assert_eq!(vec![2, 3, 4], versions);
+    assert_eq!("struct D\n", history.latest_snapshot().unwrap().source);
+    assert_eq!(
+        history,
+        history::parse(history::to_string(&history).as_str()).unwrap()
+    );
+}
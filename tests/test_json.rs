@@ -0,0 +1,71 @@
+extern crate wf;
+
+use wf::json;
+use wf::json::Value;
+
+fn round_trip(source: &str) -> String {
+    json::parse(source).unwrap().to_string()
+}
+
+#[test]
+fn scalars() {
+    assert_eq!(json::parse("null"), Ok(Value::Null));
+    assert_eq!(json::parse(" true "), Ok(Value::Bool(true)));
+    assert_eq!(json::parse("-42"), Ok(Value::Int(-42)));
+    assert_eq!(json::parse("1.5e2"), Ok(Value::Float(150.0)));
+    assert_eq!(json::parse("9223372036854775807"), Ok(Value::Int(i64::MAX)));
+}
+
+#[test]
+fn strings() {
+    assert_eq!(
+        json::parse(r#""a\"b\\c\ndé😀""#),
+        Ok(Value::String(String::from("a\"b\\c\nd\u{e9}\u{1F600}")))
+    );
+    assert_eq!(round_trip(r#""tab\there""#), r#""tab\there""#);
+    assert_eq!(round_trip(r#""\u0001""#), r#""\u0001""#);
+}
+
+#[test]
+fn nesting() {
+    assert_eq!(
+        round_trip(r#" { "a" : [1, 2.5, {"b": null}], "c": {} } "#),
+        r#"{"a":[1,2.5,{"b":null}],"c":{}}"#
+    );
+    assert_eq!(round_trip("[3.0]"), "[3.0]");
+}
+
+#[test]
+fn invalid() {
+    assert!(json::parse("").is_err());
+    assert!(json::parse("[1,]").is_err());
+    assert!(json::parse("{\"a\" 1}").is_err());
+    assert!(json::parse("\"unterminated").is_err());
+    assert!(json::parse("01x").is_err());
+    assert!(json::parse(r#""\ud83d""#).is_err());
+    assert!(json::parse(r#""\u+123""#).is_err());
+    assert_eq!(
+        json::parse("[1] 2").unwrap_err(),
+        json::Problem {
+            offset: 4,
+            expected: "the end of the input",
+        }
+    );
+}
+
+#[test]
+fn too_deep() {
+    let source = "[".repeat(json::MAX_DEPTH + 2);
+
+    assert!(json::parse(source.as_str()).is_err());
+}
+
+#[test]
+fn pretty() {
+    let value = json::parse(r#"{"a":[1,{"b":null}],"c":[],"d":{}}"#).unwrap();
+
+    assert_eq!(
+        json::to_pretty_string(&value),
+        "{\n  \"a\": [\n    1,\n    {\n      \"b\": null\n    }\n  ],\n  \"c\": [],\n  \"d\": {}\n}\n"
+    );
+}
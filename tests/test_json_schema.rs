@@ -0,0 +1,117 @@
+extern crate wf;
+
+mod common;
+
+use wf::code_gen::MinificationTable;
+use wf::codec::Names;
+use wf::history::History;
+use wf::json;
+use wf::json::Value;
+use wf::json_schema;
+use wf::parse;
+
+const SCHEMA: &str = "
+get feed [ packed ]
+    response:
+        * users : Array User
+
+struct User
+    * userId : Int
+    ~ nickname : String [ default \"\" ]
+    ! email : String
+    ~ age : Int8
+
+type Reply
+    | Empty
+    | Text String
+";
+
+fn export(names: Names) -> Value {
+    let (schema, table) = common::productionize(SCHEMA, History::new());
+
+    json_schema::export(&schema, &table, names).unwrap()
+}
+
+fn def(exported: &Value, name: &str) -> String {
+    exported
+        .get("$defs")
+        .and_then(|defs| defs.get(name))
+        .map(|def| def.to_string())
+        .unwrap_or_default()
+}
+
+#[test]
+fn full_names() {
+    let exported = export(Names::Full);
+
+    assert_eq!(
+        exported.get("$schema"),
+        Some(&Value::String(String::from(json_schema::DIALECT)))
+    );
+    assert_eq!(
+        def(&exported, "User"),
+        r#"{"type":"object","title":"User","properties":{"userId":{"type":"integer"},"nickname":{"type":"string","default":""},"email":{"type":"string","deprecated":true},"age":{"type":"integer","minimum":-128,"maximum":127}},"required":["userId"],"additionalProperties":false}"#
+    );
+    assert_eq!(
+        def(&exported, "Reply"),
+        r#"{"title":"Reply","oneOf":[{"type":"object","properties":{"variant":{"const":"Empty"}},"required":["variant"]},{"type":"object","properties":{"variant":{"const":"Text"},"contents":{"type":"array","prefixItems":[{"type":"string"}],"minItems":1,"maxItems":1}},"required":["variant","contents"]}]}"#
+    );
+    assert_eq!(
+        def(&exported, "feed.response"),
+        r##"{"type":"object","properties":{"users":{"type":"array","items":{"$ref":"#/$defs/User"}}},"required":["users"],"additionalProperties":false}"##
+    );
+}
+
+#[test]
+fn development_names() {
+    let exported = export(Names::Development);
+
+    // Unknown fields are refused here too, but the annotation is expected.
+    assert_eq!(
+        def(&exported, "User"),
+        r#"{"type":"object","title":"User","properties":{"$type":{"const":"User"},"userId":{"type":"integer"},"nickname":{"type":"string","default":""},"email":{"type":"string","deprecated":true},"age":{"type":"integer","minimum":-128,"maximum":127}},"required":["$type","userId"],"additionalProperties":false}"#
+    );
+}
+
+#[test]
+fn minified_names() {
+    let exported = export(Names::Packed);
+
+    assert_eq!(
+        def(&exported, "Reply"),
+        r#"{"title":"Reply","oneOf":[{"type":"array","title":"Empty","prefixItems":[{"const":0}],"minItems":1,"maxItems":1},{"type":"array","title":"Text","prefixItems":[{"const":1},{"type":"string"}],"minItems":2,"maxItems":2}]}"#
+    );
+    assert_eq!(
+        def(&exported, "feed.response"),
+        r##"{"type":"object","properties":{"a":{"type":"array","items":{"$ref":"#/$defs/User.packed.inPackedEndpoint"},"title":"users"}},"required":["a"]}"##
+    );
+
+    // Packed into a tuple, where missing optional fields are null.
+    assert_eq!(
+        def(&exported, "User.packed.inPackedEndpoint"),
+        r#"{"type":"array","title":"User","prefixItems":[{"type":"integer","title":"userId"},{"anyOf":[{"type":"string","title":"nickname","default":""},{"type":"null"}]},{"anyOf":[{"type":"string","title":"email","deprecated":true},{"type":"null"}]},{"anyOf":[{"type":"integer","minimum":-128,"maximum":127,"title":"age"},{"type":"null"}]}],"minItems":1}"#
+    );
+}
+
+#[test]
+fn generic_declarations() {
+    let schema = parse::parse(
+        "struct Page a\n    * items : Array a\n\nstruct Feed\n    * page : Page String\n",
+    )
+    .unwrap();
+    // Full names don't need a minification table.
+    let exported =
+        json_schema::export(&schema, &MinificationTable::default(), Names::Full).unwrap();
+
+    assert_eq!(def(&exported, "Page"), "");
+    assert_eq!(
+        json::parse(def(&exported, "Feed").as_str())
+            .unwrap()
+            .get("properties")
+            .and_then(|properties| properties.get("page"))
+            .map(|page| page.to_string()),
+        Some(String::from(
+            r#"{"type":"object","title":"Page","properties":{"items":{"type":"array","items":{"type":"string"}}},"required":["items"],"additionalProperties":false}"#
+        ))
+    );
+}
@@ -0,0 +1,213 @@
+extern crate wf;
+
+mod common;
+
+use wf::code_gen::MinificationTable;
+use wf::codec;
+use wf::codec::{Codec, ProblemKind};
+use wf::history::History;
+use wf::json;
+use wf::json_stream;
+use wf::json_stream::Problem;
+use wf::types::Schema;
+
+const SCHEMA: &str = "
+get feed [ packed ]
+    response:
+        ~ note : String
+        * page : Page
+        ~ pinned : Array User
+
+struct Page
+    * users : Array User
+    ~ cursor : String
+
+struct Tag [ packed ]
+    * label : String
+    ~ color : String
+
+struct User
+    * userId : Int
+    ~ tags : Array Tag [ default [] ]
+";
+
+fn schema() -> (Schema, MinificationTable) {
+    common::productionize(SCHEMA, History::new())
+}
+
+fn read(type_name: &str, source: &str) -> Vec<Result<String, Problem>> {
+    let (schema, table) = schema();
+    let codec = Codec::new(&schema, &table);
+    let elem = codec::named(type_name);
+
+    json_stream::read_array_from(source.as_bytes(), &codec, &elem)
+        .map(|elem| elem.map(|value| value.to_string()))
+        .collect()
+}
+
+fn json_problem(result: &Result<String, Problem>) -> (usize, &'static str) {
+    match result {
+        Err(Problem::Json(problem)) => (problem.offset, problem.expected),
+        other => panic!("expected a JSON problem, but got {:?}", other),
+    }
+}
+
+#[test]
+fn elements() {
+    let elems = read(
+        "User",
+        r#" [ {"a":1,"b":[["x]\"{"],["y","red"]]} , {"a":2} ]
+"#,
+    );
+
+    assert_eq!(
+        elems
+            .into_iter()
+            .collect::<Result<Vec<String>, Problem>>()
+            .unwrap(),
+        vec![
+            r#"{"userId":1,"tags":[{"label":"x]\"{"},{"label":"y","color":"red"}]}"#,
+            r#"{"userId":2,"tags":[]}"#,
+        ]
+    );
+
+    // Records packed into tuples at the top level too, since they're in an array.
+    assert_eq!(
+        read("Tag", r#"[["a"],["b","blue"]]"#)[1].as_ref().unwrap(),
+        r#"{"label":"b","color":"blue"}"#
+    );
+    assert!(read("User", "[]").is_empty());
+}
+
+#[test]
+fn invalid_elements() {
+    let elems = read("User", r#"[{"a":1},{"a":"two"},{"a":3}]"#);
+
+    // A problem with one element's contents doesn't stop the rest.
+    assert_eq!(elems.len(), 3);
+    assert!(elems[0].is_ok());
+    assert!(elems[2].is_ok());
+
+    match elems[1] {
+        Err(Problem::Codec(ref problem)) => {
+            assert_eq!(problem.path, "[1].userId");
+            assert!(matches!(problem.kind, ProblemKind::Expected { .. }));
+        }
+        ref other => panic!("expected a codec problem, but got {:?}", other),
+    }
+}
+
+#[test]
+fn invalid_json() {
+    // Broken JSON ends the stream, with offsets from the start of the input.
+    let elems = read("User", r#"[{"a":1}, {"a":}, {"a":3}]"#);
+
+    assert_eq!(elems.len(), 2);
+    assert_eq!(json_problem(&elems[1]), (15, "a value"));
+
+    let elems = read("User", r#"[{"a":1},]"#);
+
+    assert_eq!(json_problem(&elems[1]), (9, "a value"));
+    assert_eq!(json_problem(&read("User", "{}")[0]), (0, "an array"));
+    assert_eq!(
+        json_problem(&read("User", r#"[{"a":1} {"a":2}]"#)[1]),
+        (9, "a `,` or `]`")
+    );
+    assert_eq!(
+        json_problem(&read("User", "[] []")[0]),
+        (3, "the end of the input")
+    );
+
+    let deep = format!("[{}", "[".repeat(wf::json::MAX_DEPTH + 1));
+
+    assert!(json_problem(&read("User", &deep)[0]).1.contains("nested"));
+}
+
+fn read_feed(field_path: &str, full: &str) -> Result<Vec<Result<String, Problem>>, Problem> {
+    let (schema, table) = schema();
+    let codec = Codec::new(&schema, &table);
+    let feed = schema.endpoint("feed").unwrap();
+    let wire = codec
+        .encode(&codec::response(feed), &json::parse(full).unwrap())
+        .unwrap()
+        .to_string();
+
+    Ok(
+        json_stream::read_response_field_from(wire.as_bytes(), &codec, feed, field_path)?
+            .map(|elem| elem.map(|value| value.to_string()))
+            .collect(),
+    )
+}
+
+#[test]
+fn response_fields() {
+    let users = read_feed(
+        "page.users",
+        r#"{"note":"hi","page":{"users":[{"userId":1},{"userId":2,"tags":[{"label":"a"}]}],"cursor":"x"}}"#,
+    )
+    .unwrap();
+
+    // Users are packed into tuples on the wire, since the endpoint is.
+    assert_eq!(
+        users
+            .into_iter()
+            .collect::<Result<Vec<String>, Problem>>()
+            .unwrap(),
+        vec![
+            r#"{"userId":1,"tags":[]}"#,
+            r#"{"userId":2,"tags":[{"label":"a"}]}"#,
+        ]
+    );
+
+    // An optional field that's missing has no elements, but a required one is
+    // a problem.
+    assert!(read_feed("pinned", r#"{"page":{"users":[]}}"#)
+        .unwrap()
+        .is_empty());
+
+    let (schema, table) = schema();
+    let codec = Codec::new(&schema, &table);
+    let feed = schema.endpoint("feed").unwrap();
+    let missing: Vec<_> =
+        json_stream::read_response_field_from(&b"{}"[..], &codec, feed, "page.users")
+            .unwrap()
+            .collect();
+
+    match missing[0] {
+        Err(Problem::Codec(ref problem)) => {
+            assert_eq!(problem.path, "page");
+            assert_eq!(problem.kind, ProblemKind::Missing);
+        }
+        ref other => panic!("expected a codec problem, but got {:?}", other),
+    }
+}
+
+#[test]
+fn invalid_response_fields() {
+    let problem = |field_path| match read_feed(field_path, r#"{"page":{"users":[]}}"#) {
+        Err(Problem::Codec(problem)) => problem,
+        other => panic!("expected a codec problem, but got {:?}", other),
+    };
+
+    assert_eq!(
+        problem("page.posts").kind,
+        ProblemKind::UnknownField(String::from("posts"))
+    );
+    assert_eq!(problem("page.cursor").kind, ProblemKind::NotStreamable);
+    assert_eq!(problem("note.users").kind, ProblemKind::NotStreamable);
+
+    // Problems with elements have their path in the response.
+    let (schema, table) = schema();
+    let codec = Codec::new(&schema, &table);
+    let feed = schema.endpoint("feed").unwrap();
+    let array = codec.response_array(feed, "page.users").unwrap();
+    let wire = json::parse(r#"["two"]"#).unwrap();
+
+    assert_eq!(
+        codec
+            .decode_response_element(&array, 3, &wire)
+            .unwrap_err()
+            .path,
+        "page.users[3].userId"
+    );
+}
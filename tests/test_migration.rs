@@ -0,0 +1,255 @@
+extern crate wf;
+
+use std::io::Cursor;
+use wf::allocator;
+use wf::history::History;
+use wf::migration;
+use wf::migration::{Decision, Decisions, Question};
+use wf::parse;
+use wf::types::Schema;
+
+const USER: &str = "struct User\n    * id : Int\n    * score : Int\n";
+
+/// A history which has allocated IDs for the given source, and snapshotted it.
+fn history(source: &str) -> (History, Schema) {
+    let mut history = History::new();
+    let mut schema = parse::parse(source).unwrap();
+
+    allocator::allocate(&mut history, &mut schema);
+    history.record_snapshot(source.to_string(), 5);
+
+    (history, schema)
+}
+
+fn questions(old: &str, new: &str) -> Vec<Question> {
+    let (history, baseline) = history(old);
+
+    migration::questions(
+        &history,
+        Some((history.version, &baseline)),
+        &parse::parse(new).unwrap(),
+    )
+}
+
+fn removed(name: &str, candidates: &[&str]) -> Question {
+    Question::Removed {
+        parent: String::from("User"),
+        name: name.to_string(),
+        candidates: candidates.iter().map(|name| name.to_string()).collect(),
+    }
+}
+
+#[test]
+fn removal_alongside_an_addition_is_ambiguous() {
+    assert_eq!(
+        vec![removed("id", &["userId"])],
+        questions(USER, "struct User\n    * userId : Int\n    * score : Int\n")
+    );
+}
+
+#[test]
+fn unambiguous_changes_need_no_questions() {
+    // Just a removal
+    assert!(questions(USER, "struct User\n    * score : Int\n").is_empty());
+
+    // Just an addition
+    assert!(questions(USER, &format!("{}    * name : String\n", USER)).is_empty());
+
+    // A rename which says so
+    assert!(questions(
+        USER,
+        "struct User\n    * userId : Int [ fka \"id\" ]\n    * score : Int\n"
+    )
+    .is_empty());
+
+    // A declared widening
+    assert!(questions(
+        "struct User\n    * score : Int32\n",
+        "struct User\n    * score : Int64 [ formerly Int32 ]\n"
+    )
+    .is_empty());
+}
+
+#[test]
+fn undeclared_type_changes_are_ambiguous() {
+    assert_eq!(
+        vec![Question::Retyped {
+            parent: String::from("User"),
+            name: String::from("score"),
+        }],
+        questions(USER, "struct User\n    * id : Int\n    * score : String\n")
+    );
+}
+
+#[test]
+fn endpoint_bodies_and_inline_records_are_asked_about() {
+    assert_eq!(
+        vec![Question::Retyped {
+            parent: String::from("sendDM.params"),
+            name: String::from("userId"),
+        }],
+        questions(
+            "post sendDM\n    params:\n        * userId : Int\n",
+            "post sendDM\n    params:\n        * userId : String\n"
+        )
+    );
+
+    let (mut history, _) = history("struct User\n    * profile : { name : String }\n");
+    let mut schema = parse::parse("struct User\n    * profile : { fullName : String }\n").unwrap();
+    let renamed = Question::Removed {
+        parent: String::from("User.profile"),
+        name: String::from("name"),
+        candidates: vec![String::from("fullName")],
+    };
+
+    assert_eq!(
+        vec![renamed.clone()],
+        migration::removal_questions(&history, &schema)
+    );
+
+    // Renaming a field of an inline record keeps its ID.
+    let allocation = migration::apply(
+        &mut history,
+        &mut schema,
+        &[(renamed, Decision::Rename(String::from("fullName")))],
+    );
+
+    assert_eq!(
+        vec![(
+            String::from("User.profile.name"),
+            String::from("User.profile.fullName")
+        )],
+        allocation.renamed
+    );
+    assert_eq!(
+        0,
+        history
+            .record("User.profile")
+            .unwrap()
+            .entry("fullName")
+            .unwrap()
+            .id
+    );
+}
+
+#[test]
+fn decisions_from_flags() {
+    let decisions = Decisions {
+        renames: vec![(String::from("User.id"), String::from("userId"))],
+        retire: vec![String::from("User.email")],
+        ..Decisions::default()
+    };
+
+    assert_eq!(
+        Some(Decision::Rename(String::from("userId"))),
+        decisions.decide(&removed("id", &["userId", "name"]))
+    );
+    assert_eq!(
+        Some(Decision::Retire),
+        decisions.decide(&removed("email", &["userId"]))
+    );
+    assert_eq!(None, decisions.decide(&removed("score", &["userId"])));
+
+    // Renaming to something which wasn't added doesn't answer the question.
+    assert_eq!(None, decisions.decide(&removed("id", &["name"])));
+}
+
+#[test]
+fn ask_retries_until_it_gets_a_valid_choice() {
+    let mut output: Vec<u8> = vec![];
+    let decision = migration::ask(
+        &removed("id", &["userId"]),
+        &mut Cursor::new("nope\n3\n2\n"),
+        &mut output,
+    )
+    .unwrap();
+
+    assert_eq!(Some(Decision::Retire), decision);
+    assert!(String::from_utf8(output)
+        .unwrap()
+        .contains("1. It was renamed to userId"));
+}
+
+#[test]
+fn ask_gives_up_when_input_runs_out() {
+    let decision = migration::ask(
+        &removed("id", &["userId"]),
+        &mut Cursor::new(""),
+        &mut vec![],
+    )
+    .unwrap();
+
+    assert_eq!(None, decision);
+}
+
+#[test]
+fn apply_records_renames_and_new_ids() {
+    let (mut history, _) = history(USER);
+    let mut schema =
+        parse::parse("struct User\n    * userId : Int\n    * score : String\n").unwrap();
+    let retyped = Question::Retyped {
+        parent: String::from("User"),
+        name: String::from("score"),
+    };
+    let allocation = migration::apply(
+        &mut history,
+        &mut schema,
+        &[
+            (
+                removed("id", &["userId"]),
+                Decision::Rename(String::from("userId")),
+            ),
+            (retyped, Decision::NewId),
+        ],
+    );
+
+    assert_eq!(2, allocation.version);
+    assert_eq!(
+        vec![(String::from("User.id"), String::from("User.userId"))],
+        allocation.renamed
+    );
+    assert_eq!(vec![String::from("User.score")], allocation.retired);
+
+    let ledger = history.record("User").unwrap();
+
+    assert_eq!(0, ledger.entry("userId").unwrap().id);
+    assert_eq!(2, ledger.entry("score").unwrap().id);
+
+    // Now that score has a new ID, it's no longer a question.
+    let baseline = parse::parse(USER).unwrap();
+
+    assert!(migration::questions(&history, Some((1, &baseline)), &schema).is_empty());
+}
+
+#[test]
+fn apply_records_kept_ids() {
+    let (mut history, _) = history(USER);
+    let mut schema = parse::parse("struct User\n    * id : Int\n    * score : String\n").unwrap();
+    let retyped = Question::Retyped {
+        parent: String::from("User"),
+        name: String::from("score"),
+    };
+    let allocation = migration::apply(&mut history, &mut schema, &[(retyped, Decision::KeepId)]);
+
+    // Nothing was allocated, but the decision still makes a new version.
+    assert_eq!(2, allocation.version);
+    assert_eq!(vec![String::from("User.score")], allocation.retyped);
+
+    let entry = history.record("User").unwrap().entry("score").unwrap();
+
+    assert_eq!(1, entry.id);
+    assert_eq!(vec![2], entry.retyped);
+
+    let baseline = parse::parse(USER).unwrap();
+
+    assert!(migration::questions(&history, Some((1, &baseline)), &schema).is_empty());
+
+    // Once a version with the new type is deployed, changing it again is a
+    // new question.
+    let changed_again = parse::parse("struct User\n    * id : Int\n    * score : Bool\n").unwrap();
+
+    assert_eq!(
+        1,
+        migration::questions(&history, Some((2, &schema)), &changed_again).len()
+    );
+}
@@ -0,0 +1,108 @@
+extern crate wf;
+
+use wf::code_gen::Mode;
+use wf::negotiation;
+use wf::negotiation::{Format, Problem};
+
+fn respond(accept: &str, mode: Mode) -> Result<Format, Problem> {
+    negotiation::response_format(Some(accept), mode)
+}
+
+#[test]
+fn media_types() {
+    for format in [Format::Development, Format::Minified, Format::Binary] {
+        assert_eq!(Format::from_media_type(format.media_type()), Some(format));
+    }
+
+    assert_eq!(
+        Format::from_media_type("Application/VND.wirefunc.min+json; charset=utf-8"),
+        Some(Format::Minified)
+    );
+    assert_eq!(Format::from_media_type("text/html"), None);
+}
+
+#[test]
+fn response_formats() {
+    let binary = "application/vnd.wirefunc.bin, application/vnd.wirefunc.min+json;q=0.9";
+
+    assert_eq!(respond(binary, Mode::Production), Ok(Format::Binary));
+    assert_eq!(
+        respond(
+            "application/vnd.wirefunc.bin;q=0.5, application/json",
+            Mode::Production
+        ),
+        Ok(Format::Minified)
+    );
+    assert_eq!(respond("*/*", Mode::Production), Ok(Format::Minified));
+    assert_eq!(
+        respond("application/json", Mode::Development),
+        Ok(Format::Development)
+    );
+    assert_eq!(
+        negotiation::response_format(None, Mode::Development),
+        Ok(Format::Development)
+    );
+
+    // The most specific range decides, even if a broader one allows it.
+    assert_eq!(
+        respond(
+            "application/*, application/vnd.wirefunc.min+json;q=0",
+            Mode::Production
+        ),
+        Ok(Format::Binary)
+    );
+
+    let problem = respond(binary, Mode::Development).unwrap_err();
+
+    assert_eq!(problem, Problem::NotAcceptable(binary.to_string()));
+    assert_eq!(problem.status(), 406);
+}
+
+#[test]
+fn request_formats() {
+    assert_eq!(
+        negotiation::request_format(Some("application/vnd.wirefunc.bin"), Mode::Production),
+        Ok(Format::Binary)
+    );
+    assert_eq!(
+        negotiation::request_format(Some("application/json; charset=utf-8"), Mode::Production),
+        Ok(Format::Minified)
+    );
+    assert_eq!(
+        negotiation::request_format(None, Mode::Development),
+        Ok(Format::Development)
+    );
+
+    let mixed =
+        negotiation::request_format(Some("application/vnd.wirefunc.min+json"), Mode::Development)
+            .unwrap_err();
+
+    assert_eq!(
+        mixed,
+        Problem::MixedFormats {
+            expected: Mode::Development,
+            found: Format::Minified,
+        }
+    );
+    assert_eq!(mixed.status(), 415);
+    assert_eq!(
+        negotiation::request_format(Some("text/plain"), Mode::Production),
+        Err(Problem::UnsupportedMediaType(String::from("text/plain")))
+    );
+}
+
+#[test]
+fn accept_headers() {
+    assert_eq!(
+        negotiation::accept_header(Mode::Production, true),
+        "application/vnd.wirefunc.bin, application/vnd.wirefunc.min+json;q=0.9"
+    );
+    assert_eq!(
+        negotiation::accept_header(Mode::Production, false),
+        "application/vnd.wirefunc.min+json"
+    );
+    assert_eq!(
+        negotiation::accept_header(Mode::Development, true),
+        "application/vnd.wirefunc.dev+json"
+    );
+}
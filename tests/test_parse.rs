@@ -0,0 +1,153 @@
+extern crate wf;
+
+use std::fs;
+use wf::parse;
+use wf::types::{Optionality, Type, TypeName, TypeParam, TypePrimitive, Verb};
+
+fn named(name: &str, args: Vec<TypePrimitive>) -> TypePrimitive {
+    TypePrimitive::Named(
+        TypeName::TypeName(name.to_string()),
+        args.into_iter().map(Type::new).collect(),
+    )
+}
+
+#[test]
+fn parse_newsfeed_example() {
+    let source = fs::read_to_string("examples/javascript/newsfeed.wf").unwrap();
+    let schema = parse::parse(source.as_str()).unwrap();
+
+    assert_eq!(2, schema.records.len());
+    assert_eq!(1, schema.aliases.len());
+    assert_eq!(1, schema.endpoints.len());
+
+    let user = schema.record("User").unwrap();
+
+    assert_eq!(vec![TypeParam::TypeParam(String::from("a"))], user.params);
+    assert_eq!(vec![String::from("id")], user.fields[0].former_names);
+    assert_eq!(
+        TypePrimitive::Param(TypeParam::TypeParam(String::from("a"))),
+        user.fields[1].tipe.current
+    );
+    assert_eq!(Optionality::Optional, user.fields[2].optionality);
+    assert_eq!(Some(String::from("[]")), user.fields[2].default);
+
+    let profile = schema.record("Profile").unwrap();
+
+    assert_eq!(
+        Some(String::from("\"{{adminEmailUsername}}@{{domain}}\"")),
+        profile.fields[1].default
+    );
+    assert_eq!(named("Email", vec![]), profile.fields[1].tipe.current);
+    assert_eq!(
+        Some(String::from("DEFAULT_HOMEPAGE")),
+        profile.fields[2].default
+    );
+    assert!(profile.fields[3].deprecated);
+
+    let send_dm = schema.endpoint("sendDM").unwrap();
+
+    assert_eq!(Verb::Post, send_dm.verb);
+    assert_eq!(4, send_dm.params.len());
+    assert_eq!(Some(String::from("X-CSRF-Token")), send_dm.params[3].header);
+    assert_eq!(
+        "Result { ... }",
+        send_dm.response[0].tipe.current.to_string()
+    );
+}
+
+#[test]
+fn parse_custom_types() {
+    let schema = parse::parse(
+        "type Reply a [ fka \"Response\" ]\n    | Email EmailReply\n    | Text (Array a) [ fka \"Sms\" ]\n    | Pigeon\n",
+    )
+    .unwrap();
+    let reply = schema.custom_type("Reply").unwrap();
+
+    assert_eq!(
+        vec![TypeName::TypeName(String::from("Response"))],
+        reply.former_names
+    );
+    assert_eq!(3, reply.variants.len());
+    assert_eq!("Array a", reply.variants[1].contents[0].current.to_string());
+    assert_eq!(vec![String::from("Sms")], reply.variants[1].former_names);
+    assert!(reply.variants[2].contents.is_empty());
+}
+
+#[test]
+fn parse_formerly() {
+    let schema = parse::parse(
+        "struct Score\n    * value : Float64 [ formerly Float32 ] [ formerly Int16 ]\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        Type {
+            current: TypePrimitive::Float64,
+            formerly: vec![TypePrimitive::Float32, TypePrimitive::Int16],
+        },
+        schema.records[0].fields[0].tipe
+    );
+}
+
+#[test]
+fn parse_nested_collections() {
+    let schema =
+        parse::parse("struct Index\n    * byName : Dict String (Set (Array Int))\n").unwrap();
+
+    assert_eq!(
+        "Dict String (Set (Array Int))",
+        schema.records[0].fields[0].tipe.current.to_string()
+    );
+}
+
+#[test]
+fn wrong_arity() {
+    let problem = parse::parse("struct Index\n    * ids : Array\n").unwrap_err();
+
+    assert_eq!(2, problem.line_number);
+    assert_eq!(
+        "exactly one type argument, like `Array Int`",
+        problem.expected
+    );
+}
+
+#[test]
+fn field_without_marker() {
+    let problem = parse::parse("struct User\n    userId : Int\n").unwrap_err();
+
+    assert_eq!(2, problem.line_number);
+    assert_eq!("a field like `* userId : Int`", problem.expected);
+}
+
+#[test]
+fn unknown_attribute() {
+    let problem = parse::parse("struct User\n    * userId : Int [ shiny ]\n").unwrap_err();
+
+    assert_eq!(2, problem.line_number);
+}
+
+#[test]
+fn indented_line_without_declaration() {
+    let problem = parse::parse("-- a comment\n    * userId : Int\n").unwrap_err();
+
+    assert_eq!(2, problem.line_number);
+}
+
+#[test]
+fn unterminated_string() {
+    let problem = parse::parse("struct User\n    * userId : Int [ fka \"id ]\n").unwrap_err();
+
+    assert_eq!("a closing quotation mark", problem.expected);
+}
+
+#[test]
+fn parse_packed() {
+    let schema = parse::parse(
+        "get feed [ packed ]\n    response:\n        * users : Array User\n\nstruct User [ packed ]\n    * userId : Int\n\nstruct Tag\n    * label : String\n",
+    )
+    .unwrap();
+
+    assert!(schema.endpoints[0].packed);
+    assert!(schema.records[0].packed);
+    assert!(!schema.records[1].packed);
+}
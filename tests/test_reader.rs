@@ -0,0 +1,177 @@
+extern crate wf;
+
+use wf::pointer;
+use wf::reader::{Problem, Reader};
+use wf::record;
+use wf::segments::{MessageBuilder, ROOT};
+use wf::types::{Type, TypePrimitive};
+
+/// A root record with an Int, a nested record, a string, and a Float64 array,
+/// where the nested record has a long UInt64 array.
+fn message() -> Vec<u8> {
+    let mut builder = MessageBuilder::with_segment_words(16);
+    let nested = builder.allocate(ROOT, 3);
+    let long: Vec<u64> = (0..40_000).collect();
+    let numbers = builder.write_u64_array(nested.segment, &long);
+
+    builder
+        .words_mut(nested, 3)
+        .copy_from_slice(&record::encode_record(&[(0, 1), (1, numbers)]));
+
+    let nested_ptr = builder.pointer(ROOT, nested, 3);
+    let name = builder.write_string(ROOT, "Zoë");
+    let floats: Vec<u64> = [1.5f64, -2.0].iter().map(|float| float.to_bits()).collect();
+    let floats = builder.write_u64_array(ROOT, &floats);
+
+    builder.set_root(&record::encode_record(&[
+        (0, (-7i64) as u64),
+        (1, nested_ptr),
+        (2, name),
+        (3, floats),
+        (4, 0),
+    ]));
+    builder.to_bytes()
+}
+
+#[test]
+fn views() {
+    let bytes = message();
+    let reader = Reader::new(&bytes).unwrap();
+    let root = reader.root().unwrap();
+
+    assert_eq!(root.len(), 5);
+    assert_eq!(root.i64(0), Ok(-7));
+    assert_eq!(root.string(2).unwrap(), "Zoë");
+    assert_eq!(root.string(4).unwrap(), "");
+    assert_eq!(unsafe { root.string_unchecked(2) }.unwrap(), "Zoë");
+    assert_eq!(
+        root.array::<f64>(3).unwrap().iter().collect::<Vec<f64>>(),
+        vec![1.5, -2.0]
+    );
+
+    let nested = root.record(1).unwrap();
+    let long = nested.array::<u64>(1).unwrap();
+
+    // Long arrays are borrowed a chunk at a time.
+    assert!(nested.bool(0).unwrap());
+    assert_eq!(long.len(), 40_000);
+    assert_eq!(long.get(39_999), Some(39_999));
+    assert_eq!(long.get(40_000), None);
+    assert_eq!(long.iter().sum::<u64>(), (0..40_000).sum());
+}
+
+#[test]
+fn hostile_messages() {
+    let bytes = message();
+
+    assert_eq!(
+        Reader::new(&bytes[..bytes.len() - 1]).err(),
+        Some(Problem::Truncated(3))
+    );
+    assert_eq!(Reader::new(&[]).err(), Some(Problem::Empty));
+    assert_eq!(Reader::new(&[255; 8]).err(), Some(Problem::Truncated(0)));
+
+    let mut builder = MessageBuilder::new();
+    let not_utf8 = builder.write_u8_array(ROOT, &[0xff, 0xfe]);
+    let past_the_end = pointer::encode(pointer::Pointer {
+        word_index: 100,
+        segment_id_offset: 1,
+        length: 3,
+        is_composite: false,
+    });
+
+    builder.set_root(&record::encode_record(&[(0, not_utf8), (1, past_the_end)]));
+
+    let bytes = builder.to_bytes();
+    let reader = Reader::new(&bytes).unwrap();
+    let root = reader.root().unwrap();
+
+    assert_eq!(root.string(0).err(), Some(Problem::InvalidUtf8));
+    assert_eq!(
+        root.array::<u64>(1).err(),
+        Some(Problem::Pointer(pointer::Problem::OutOfBounds {
+            segment: 1,
+            word_index: 100,
+            words: 3,
+        }))
+    );
+    assert!(root.record(1).is_err());
+    assert_eq!(root.u64(2).err(), Some(Problem::MissingSlot(2)));
+
+    // A presence table which says it has another page, but doesn't.
+    let mut builder = MessageBuilder::new();
+
+    builder.set_root(&[1 << 56]);
+
+    let bytes = builder.to_bytes();
+
+    assert_eq!(
+        Reader::new(&bytes).unwrap().root().err(),
+        Some(Problem::PresenceTable(record::Problem::Truncated {
+            pages: 2
+        }))
+    );
+}
+
+#[test]
+fn records_from_the_encoder() {
+    let mut builder = MessageBuilder::new();
+    let name = builder.write_string(ROOT, "Sam");
+
+    // Field 70 needs a second page of the presence table, so the slots start
+    // two Words in.
+    builder.set_root(&record::encode_record(&[(0, 5), (2, name), (70, 1)]));
+
+    let bytes = builder.to_bytes();
+    let reader = Reader::new(&bytes).unwrap();
+    let root = reader.root().unwrap();
+    let presence_table = root.presence_table();
+
+    assert_eq!(presence_table.page_count(), 2);
+    assert_eq!(
+        presence_table.present_fields().collect::<Vec<_>>(),
+        vec![0, 2, 70]
+    );
+    assert!(!presence_table.is_present(1));
+    assert_eq!(root.len(), 3);
+    assert_eq!(root.u64(0), Ok(5));
+    assert_eq!(root.string(1).unwrap(), "Sam");
+    assert!(root.bool(2).unwrap());
+}
+
+#[test]
+fn widened_slots() {
+    let mut builder = MessageBuilder::new();
+
+    builder.set_root(&record::encode_record(&[
+        (0, (-3i64) as u64),
+        (1, u64::from(1.5f32.to_bits())),
+    ]));
+
+    let bytes = builder.to_bytes();
+    let reader = Reader::new(&bytes).unwrap();
+    let root = reader.root().unwrap();
+    let score = Type {
+        current: TypePrimitive::Float64,
+        formerly: vec![TypePrimitive::Int32, TypePrimitive::Float32],
+    };
+
+    assert_eq!(
+        root.widened(0, &TypePrimitive::Int32, &score),
+        Ok((-3f64).to_bits())
+    );
+    assert_eq!(
+        root.widened(1, &TypePrimitive::Float32, &score),
+        Ok(1.5f64.to_bits())
+    );
+    assert_eq!(
+        root.widened(1, &TypePrimitive::Float64, &score),
+        root.word(1)
+    );
+
+    // Int16 would widen fine, but it was never one of the field's types.
+    assert_eq!(
+        root.widened(0, &TypePrimitive::Int16, &score),
+        Err(Problem::NotAccepted(0))
+    );
+}
@@ -1,11 +1,10 @@
 extern crate byteorder;
 extern crate wf;
 
-use std::f64;
-use std::slice;
-use std::u64;
+use byteorder::{ByteOrder, LittleEndian};
 use wf::pointer;
 use wf::pointer::Pointer;
+use wf::record;
 use wf::record::FieldId;
 
 // TODO test the case where FieldId order is wildly different from slot
@@ -44,8 +43,8 @@ mod primitive_fields_test {
         f32: f32,
     }
 
-    fn decode<'a>(buffer: &'a [u8]) -> Record {
-        let presence_table: &[u8] = record::decode_presence_table(buffer);
+    fn decode(buffer: &[u8]) -> Record {
+        let _presence_table = record::decode_presence_table(buffer).unwrap();
         let presence_table_page = 0b1111_1110; // TODO presence_table[0];
 
         let i64 = if presence_table_page & 0b1000_0000 != 0 {
@@ -85,16 +84,16 @@ mod primitive_fields_test {
         };
 
         Record {
-            i64: i64,
-            u64: u64,
-            bool_true: bool_true,
-            bool_false: bool_false,
-            f64: f64,
-            f32: f32,
+            i64,
+            u64,
+            bool_true,
+            bool_false,
+            f64,
+            f32,
         }
     }
 
-    fn encode(record: &Record, buffer: &mut [u8]) -> () {
+    fn encode(record: &Record, buffer: &mut [u8]) {
         LittleEndian::write_i64(&mut buffer[0..8], record.i64);
         LittleEndian::write_u64(&mut buffer[8..16], record.u64);
         buffer[23] = record.bool_true as u8;
@@ -121,66 +120,88 @@ mod primitive_fields_test {
     }
 }
 
-fn encode_fields<'a>(fields: Vec<(FieldId, Value)>, highest_field_id: FieldId) -> &'a [u8] {
-    let num_fields: usize = fields.len();
+fn encode_fields(fields: Vec<(FieldId, Value)>) -> Vec<u8> {
+    let fields: Vec<(FieldId, u64)> = fields
+        .into_iter()
+        .map(|(field_id, field)| (field_id, encode_field(field)))
+        .collect();
+    let words = record::encode_record(&fields);
+    let mut bytes = vec![0; words.len() * 8];
 
-    if num_fields == 0 {
-        let empty: Vec<u8> = vec![];
+    LittleEndian::write_u64_into(&words, &mut bytes);
 
-        // TODO: Figure out a non-unsafe way to return a fresh, empty slice.
-        unsafe {
-            return slice::from_raw_parts(empty.as_ptr() as *const _, 0);
-        }
-    }
-
-    // Each Word can store presence for 64 fields. Round up to the nearest Word.
-    let presence_table_words: usize = f64::ceil(((highest_field_id + 1) as f64) / 64.0) as usize;
-    let total_words = num_fields + presence_table_words;
-
-    let mut result: Vec<u64> = vec![0; total_words];
-    let mut index: usize = 0;
-    let mut presence_table_index: usize = 0;
-
-    // These have already been sorted by word index, so we can iterate through
-    // them directly.
-    for (field_id, field) in fields {
-        let field_id_usize = field_id as usize;
-
-        // Write the value to the appropriate place in the result words.
-        result[index] = encode_field(field);
-        index += 1;
-
-        // Advance presence_table_index to the current field_id.
-        // We'll leave everything else as zeroes.
-        while (presence_table_index + 1) * 64 <= field_id_usize {
-            presence_table_index += 1;
-        }
-
-        // TODO If we need multiple presence table pages, write a 1 at the end
-        // of each of them.
-
-        // TODO write the presence table at the start of the segment, not the end!
-
-        // Set a bit to 1 in the presence table for this field_id.
-        let presence_table_index_in_result = total_words - presence_table_index - 1;
-        let presence_table = result[presence_table_index_in_result];
-        // TODO this math is wrong because it doesn't account for the 64th bit
-        // in the presence table being responsible for telling if there are more pages
-        let presence_table_bit_index: i64 = 64 * presence_table_index as i64;
-        let field_id_offset: i64 = field_id_usize as i64 - presence_table_bit_index;
+    bytes
+}
 
-        result[presence_table_index_in_result] = presence_table | (1 << field_id_offset);
-    }
+#[test]
+fn test_presence_table() {
+    let bytes: Vec<u8> = vec![
+        // Page 0: fields 0 and 9 are present, and there's another page.
+        0b1000_0000,
+        0b0100_0000,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0b0000_0001,
+        // Page 1: field 63 + 62 = 125 is present, and this is the last page.
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0b0000_0010,
+        // The first field
+        0xff,
+    ];
+    let table = record::decode_presence_table(&bytes).unwrap();
+
+    // The table's length is in bytes, not pages.
+    assert_eq!(table.page_count(), 2);
+    assert_eq!(table.byte_len(), 16);
+    assert_eq!(table.present_fields().collect::<Vec<_>>(), vec![0, 9, 125]);
+    assert!(table.is_present(9));
+    assert!(!table.is_present(63));
+    assert!(!table.is_present(1_000));
+    assert_eq!(
+        table
+            .pages()
+            .map(|page| (page.first_field_id(), page.has_next()))
+            .collect::<Vec<_>>(),
+        vec![(0, true), (63, false)]
+    );
+}
 
-    // Now that we're done working in terms of u64, cast the vector to &[u8].
-    unsafe { slice::from_raw_parts(result.as_mut_ptr() as *const _, result.len() * 8) }
+#[test]
+fn test_truncated_presence_table() {
+    assert_eq!(
+        record::decode_presence_table(&[]),
+        Err(record::Problem::Truncated { pages: 1 })
+    );
+    assert_eq!(
+        record::decode_presence_table(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0]),
+        Err(record::Problem::Truncated { pages: 2 })
+    );
 }
 
 #[test]
 fn test_encode_record() {
     // A record with a single boolean field @0
-    let actual = encode_fields(vec![(0, Value::Bool(true))], 0);
+    let actual = encode_fields(vec![(0, Value::Bool(true))]);
     let expected: &[u8] = &vec![
+        // The presence table - only field 0 is present, and there are no
+        // more pages
+        0b1000_0000,
+        0b0000_0000,
+        0b0000_0000,
+        0b0000_0000,
+        0b0000_0000,
+        0b0000_0000,
+        0b0000_0000,
+        0b0000_0000,
         // The boolean field - all 1s, indicating True
         0b1111_1111,
         0b1111_1111,
@@ -190,18 +211,38 @@ fn test_encode_record() {
         0b1111_1111,
         0b1111_1111,
         0b1111_1111,
-        // The presence table - only the final bit is set
-        0b0000_0000,
-        0b0000_0000,
-        0b0000_0000,
-        0b0000_0000,
-        0b0000_0000,
-        0b0000_0000,
-        0b0000_0000,
-        0b0000_0001,
     ];
 
-    assert_eq!(expected, actual);
+    assert_eq!(expected, actual.as_slice());
+}
+
+#[test]
+fn test_encode_multi_page_record() {
+    // Slot order differs from FieldId order, and field 130 needs a third page.
+    let actual = encode_fields(vec![
+        (130, Value::Int(-1)),
+        (2, Value::UInt16(7)),
+        (64, Value::Float(2.5)),
+    ]);
+    let table = record::decode_presence_table(&actual).unwrap();
+
+    // Every page but the last has the 64th bit set.
+    assert_eq!(
+        table
+            .pages()
+            .map(|page| page.has_next())
+            .collect::<Vec<_>>(),
+        vec![true, true, false]
+    );
+    assert_eq!(table.present_fields().collect::<Vec<_>>(), vec![2, 64, 130]);
+
+    // The fields come right after the table, in slot order.
+    let fields = &actual[table.byte_len()..];
+
+    assert_eq!(LittleEndian::read_i64(&fields[0..8]), -1);
+    assert_eq!(LittleEndian::read_u64(&fields[8..16]), 7);
+    assert_eq!(LittleEndian::read_f64(&fields[16..24]), 2.5);
+    assert_eq!(record::encode_record(&[]), vec![0]);
 }
 
 #[derive(PartialEq, Debug)]
@@ -227,15 +268,21 @@ pub enum Value {
 fn encode_field(field: Value) -> u64 {
     match field {
         Value::Int(inner) => inner as u64,
-        Value::UInt64(inner) => inner as u64,
+        Value::UInt64(inner) => inner,
         Value::Int32(inner) => inner as u64,
         Value::UInt32(inner) => inner as u64,
         Value::Int16(inner) => inner as u64,
         Value::UInt16(inner) => inner as u64,
         Value::Byte(inner) => inner as u64,
-        Value::Float(inner) => inner as u64,
-        Value::Float32(inner) => inner as u64,
-        Value::Bool(inner) => inner as u64,
+        Value::Float(inner) => inner.to_bits(),
+        Value::Float32(inner) => inner.to_bits() as u64,
+        Value::Bool(inner) => {
+            if inner {
+                u64::MAX
+            } else {
+                0
+            }
+        }
         Value::String(ptr) => pointer::encode(ptr),
         Value::Array(ptr) => pointer::encode(ptr),
         Value::Dict(ptr) => pointer::encode(ptr),
@@ -0,0 +1,108 @@
+extern crate byteorder;
+extern crate wf;
+
+use byteorder::{ByteOrder, LittleEndian};
+use std::collections::{BTreeMap, BTreeSet};
+use wf::array;
+use wf::collection;
+use wf::pointer;
+use wf::segments::{Location, MessageBuilder, ROOT};
+
+#[test]
+fn values_across_segments() {
+    let mut builder = MessageBuilder::with_segment_words(4);
+
+    // A nested record, and an array it points to
+    let record = builder.allocate(ROOT, 2);
+    let numbers = builder.write_u64_array(record.segment, &[1, 2, 3]);
+
+    builder.words_mut(record, 2).copy_from_slice(&[42, numbers]);
+
+    let record_ptr = builder.pointer(ROOT, record, 2);
+    let bytes = builder.write_u8_array(ROOT, b"hello, segments");
+    let empty = builder.write_u64_array(ROOT, &[]);
+
+    builder.set_root(&[record_ptr, bytes, empty]);
+
+    // The root is alone in the first segment, and the rest spill over.
+    assert_eq!(
+        builder.segments().iter().map(Vec::len).collect::<Vec<_>>(),
+        vec![3, 2, 3, 2]
+    );
+    assert_eq!(
+        pointer::decode(record_ptr).word_index,
+        record.word_index as u32
+    );
+    assert_eq!(pointer::decode(bytes).segment_id_offset, 3);
+    assert_eq!(empty, 0);
+
+    // Pointers are relative to the segment they're in.
+    let segments = builder.segments();
+
+    assert_eq!(
+        record,
+        Location {
+            segment: 1,
+            word_index: 0,
+        }
+    );
+    assert_eq!(pointer::decode(numbers).segment_id_offset, 1);
+    assert_eq!(
+        array::decode_u64_array(segments, record.segment, segments[1][1]),
+        Ok(vec![1, 2, 3])
+    );
+    assert_eq!(
+        array::decode_u8_array(segments, ROOT, segments[ROOT][1]),
+        Ok(b"hello, segments".to_vec())
+    );
+}
+
+#[test]
+fn serialization() {
+    let mut builder = MessageBuilder::new();
+    let numbers = builder.write_u64_array(ROOT, &[7, 8]);
+
+    builder.set_root(&[numbers]);
+
+    let bytes = builder.to_bytes();
+    let mut words = vec![0; bytes.len() / 8];
+
+    LittleEndian::read_u64_into(&bytes, &mut words);
+
+    // Each segment starts with its length in Words.
+    assert_eq!(words, vec![1, numbers, 2, 7, 8]);
+
+    let mut written = vec![];
+
+    builder.write_to(&mut written).unwrap();
+
+    assert_eq!(written, bytes);
+}
+
+#[test]
+fn collections() {
+    let mut builder = MessageBuilder::new();
+    let tags: BTreeSet<String> = ["new", "sale"].iter().map(|tag| tag.to_string()).collect();
+    let counts: BTreeMap<i64, u64> = vec![(-1, 3), (4, 1)].into_iter().collect();
+    let tags_ptr = builder.write_set(ROOT, &tags);
+    let counts_ptr = builder.write_dict(ROOT, &counts);
+
+    builder.set_root(&[tags_ptr, counts_ptr]);
+
+    let segments = builder.segments();
+
+    assert_eq!(collection::decode_set(segments, ROOT, tags_ptr), Ok(tags));
+    assert_eq!(
+        collection::decode_dict(segments, ROOT, counts_ptr),
+        Ok(counts)
+    );
+}
+
+#[test]
+#[should_panic(expected = "can be at most")]
+fn pointer_length_too_long() {
+    let mut builder = MessageBuilder::new();
+    let location = builder.allocate(ROOT, 1);
+
+    builder.pointer(ROOT, location, pointer::MAX_LENGTH + 1);
+}
@@ -0,0 +1,226 @@
+extern crate wf;
+
+use wf::types::{Field, Optionality, RecordInfo, Type, TypeName, TypePrimitive};
+use wf::widening;
+use wf::widening::Problem;
+
+fn formerly(current: TypePrimitive, formerly: Vec<TypePrimitive>) -> Type {
+    Type { current, formerly }
+}
+
+fn array_of(elem: TypePrimitive) -> TypePrimitive {
+    TypePrimitive::Array(Box::new(Type::new(elem)))
+}
+
+#[test]
+fn integers_widen_to_wider_integers() {
+    assert!(widening::is_safe_widening(
+        &TypePrimitive::Int8,
+        &TypePrimitive::Int16
+    ));
+    assert!(widening::is_safe_widening(
+        &TypePrimitive::Int16,
+        &TypePrimitive::Int32
+    ));
+    assert!(widening::is_safe_widening(
+        &TypePrimitive::Int32,
+        &TypePrimitive::Int64
+    ));
+    assert!(widening::is_safe_widening(
+        &TypePrimitive::Int32,
+        &TypePrimitive::Int
+    ));
+    assert!(widening::is_safe_widening(
+        &TypePrimitive::Int,
+        &TypePrimitive::Int64
+    ));
+    assert!(widening::is_safe_widening(
+        &TypePrimitive::Int64,
+        &TypePrimitive::Int
+    ));
+}
+
+#[test]
+fn integers_do_not_narrow() {
+    assert!(!widening::is_safe_widening(
+        &TypePrimitive::Int64,
+        &TypePrimitive::Int32
+    ));
+    assert!(!widening::is_safe_widening(
+        &TypePrimitive::Int16,
+        &TypePrimitive::Int8
+    ));
+}
+
+#[test]
+fn floats_widen() {
+    assert!(widening::is_safe_widening(
+        &TypePrimitive::Float32,
+        &TypePrimitive::Float64
+    ));
+    assert!(widening::is_safe_widening(
+        &TypePrimitive::Float32,
+        &TypePrimitive::Float
+    ));
+    assert!(!widening::is_safe_widening(
+        &TypePrimitive::Float64,
+        &TypePrimitive::Float32
+    ));
+}
+
+#[test]
+fn integers_widen_to_floats_only_when_exact() {
+    assert!(widening::is_safe_widening(
+        &TypePrimitive::Int16,
+        &TypePrimitive::Float32
+    ));
+    assert!(widening::is_safe_widening(
+        &TypePrimitive::Int32,
+        &TypePrimitive::Float64
+    ));
+    assert!(!widening::is_safe_widening(
+        &TypePrimitive::Int32,
+        &TypePrimitive::Float32
+    ));
+    assert!(!widening::is_safe_widening(
+        &TypePrimitive::Int64,
+        &TypePrimitive::Float64
+    ));
+    assert!(!widening::is_safe_widening(
+        &TypePrimitive::Float32,
+        &TypePrimitive::Int64
+    ));
+}
+
+#[test]
+fn collections_widen_when_contents_widen() {
+    assert!(widening::is_safe_widening(
+        &array_of(TypePrimitive::Int32),
+        &array_of(TypePrimitive::Int64)
+    ));
+    assert!(!widening::is_safe_widening(
+        &array_of(TypePrimitive::Int64),
+        &array_of(TypePrimitive::Int32)
+    ));
+    assert!(!widening::is_safe_widening(
+        &array_of(TypePrimitive::Int32),
+        &TypePrimitive::Int64
+    ));
+}
+
+#[test]
+fn check_accepts_widenings() {
+    let tipe = formerly(
+        TypePrimitive::Int64,
+        vec![TypePrimitive::Int16, TypePrimitive::Int32],
+    );
+
+    assert_eq!(Ok(()), widening::check("User.userId", &tipe));
+}
+
+#[test]
+fn check_rejects_narrowing() {
+    let tipe = formerly(TypePrimitive::Int32, vec![TypePrimitive::Int64]);
+
+    assert_eq!(
+        Err(Problem::Narrowing {
+            field: String::from("User.userId"),
+            from: Box::new(TypePrimitive::Int64),
+            to: Box::new(TypePrimitive::Int32),
+        }),
+        widening::check("User.userId", &tipe)
+    );
+}
+
+#[test]
+fn check_rejects_incompatible_changes() {
+    let tipe = formerly(TypePrimitive::Bool, vec![TypePrimitive::String]);
+
+    assert_eq!(
+        Err(Problem::Incompatible {
+            field: String::from("User.active"),
+            from: Box::new(TypePrimitive::String),
+            to: Box::new(TypePrimitive::Bool),
+        }),
+        widening::check("User.active", &tipe)
+    );
+}
+
+#[test]
+fn check_record_qualifies_field_names() {
+    let record = RecordInfo {
+        name: Some(TypeName::TypeName(String::from("User"))),
+        former_names: vec![],
+        fields: vec![Field {
+            optionality: Optionality::Required,
+            name: String::from("score"),
+            tipe: formerly(TypePrimitive::Float32, vec![TypePrimitive::Float64]),
+            field_id: 0,
+            fallback: false,
+            former_names: vec![],
+            deprecated: false,
+            default: None,
+            header: None,
+        }],
+        params: vec![],
+        packed: false,
+        location: None,
+    };
+
+    assert_eq!(
+        vec![Problem::Narrowing {
+            field: String::from("User.score"),
+            from: Box::new(TypePrimitive::Float64),
+            to: Box::new(TypePrimitive::Float32),
+        }],
+        widening::check_record(&record)
+    );
+}
+
+#[test]
+fn accepted_types_lists_current_first() {
+    let tipe = formerly(TypePrimitive::Int64, vec![TypePrimitive::Int32]);
+
+    assert_eq!(
+        vec![&TypePrimitive::Int64, &TypePrimitive::Int32],
+        widening::accepted_types(&tipe)
+    );
+}
+
+#[test]
+fn widen_word_sign_extended_ints_are_unchanged() {
+    let word = -42i32 as i64 as u64;
+
+    assert_eq!(
+        Some(word),
+        widening::widen_word(&TypePrimitive::Int32, &TypePrimitive::Int64, word)
+    );
+}
+
+#[test]
+fn widen_word_float32_to_float64() {
+    let word = u64::from(1.5f32.to_bits());
+
+    assert_eq!(
+        Some(1.5f64.to_bits()),
+        widening::widen_word(&TypePrimitive::Float32, &TypePrimitive::Float64, word)
+    );
+}
+
+#[test]
+fn widen_word_int_to_float64() {
+    let word = -7i32 as i64 as u64;
+
+    assert_eq!(
+        Some((-7f64).to_bits()),
+        widening::widen_word(&TypePrimitive::Int32, &TypePrimitive::Float64, word)
+    );
+}
+
+#[test]
+fn widen_word_rejects_narrowing() {
+    assert_eq!(
+        None,
+        widening::widen_word(&TypePrimitive::Int64, &TypePrimitive::Int32, 0)
+    );
+}